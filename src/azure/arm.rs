@@ -0,0 +1,842 @@
+use std::collections::BTreeMap;
+
+use log::warn;
+use serde_json::{Map, Value};
+
+use crate::azure::LOG_TARGET;
+use crate::error::DeserializeError;
+use crate::pulumi;
+use crate::serializer::{
+    snippet_at, BuildOptions, ContainerAppBluePrint, ContainerAppConfiguration,
+};
+
+const CONTAINER_APP_TYPE: &str = "Microsoft.App/containerApps";
+
+// Variables referencing each other deeper than this are considered cyclic
+const MAX_VARIABLE_DEPTH: usize = 32;
+
+fn filter_by_type(val: &&Value) -> bool {
+    match val.get("type").and_then(|x| x.as_str()) {
+        // ARM resource types are case insensitive
+        Some(x) => x.eq_ignore_ascii_case(CONTAINER_APP_TYPE),
+        None => false,
+    }
+}
+
+fn get_resources(value: &Value) -> Vec<&Value> {
+    match value.get("resources").and_then(|x| x.as_array()) {
+        Some(resources) => resources.iter().collect(),
+        // A single resource, as given by `az containerapp show`
+        None => vec![value],
+    }
+}
+
+/***
+ * Template expressions (eg: [concat(parameters('registry'), '/api:v1')]) as JSON values,
+ * none when they cannot be evaluated statically (eg: [resourceGroup().location])
+ */
+struct Expression<'a> {
+    chars: Vec<char>,
+    position: usize,
+    template: &'a Value,
+    parameters: &'a BTreeMap<String, Value>,
+    depth: usize,
+    /// Parameters without value, neither from the parameters file nor by default
+    unresolved: Vec<String>,
+}
+
+impl<'a> Expression<'a> {
+    fn new(
+        input: &str,
+        template: &'a Value,
+        parameters: &'a BTreeMap<String, Value>,
+        depth: usize,
+    ) -> Expression<'a> {
+        Expression {
+            chars: input.chars().collect(),
+            position: 0,
+            template,
+            parameters,
+            depth,
+            unresolved: vec![],
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.position).copied()
+    }
+
+    fn skip_blanks(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.position += 1;
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        self.skip_blanks();
+
+        match self.peek() {
+            Some(c) if c == expected => {
+                self.position += 1;
+                Ok(())
+            }
+            _ => Err(format!("Expected `{}` at {}", expected, self.position)),
+        }
+    }
+
+    fn parse_identifier(&mut self) -> String {
+        let mut identifier = String::new();
+
+        while let Some(c) = self.peek().filter(|c| c.is_alphanumeric() || *c == '_') {
+            identifier.push(c);
+            self.position += 1;
+        }
+
+        identifier
+    }
+
+    fn parse(&mut self) -> Result<Option<Value>, String> {
+        self.skip_blanks();
+
+        let value = match self.peek() {
+            Some('\'') => Some(Value::String(self.parse_string()?)),
+            Some(c) if c.is_ascii_digit() || c == '-' => {
+                let mut number = String::new();
+                while let Some(c) = self
+                    .peek()
+                    .filter(|c| c.is_ascii_digit() || (*c == '-' && number.is_empty()))
+                {
+                    number.push(c);
+                    self.position += 1;
+                }
+                let number = number
+                    .parse::<i64>()
+                    .map_err(|_| format!("Invalid number `{}`", number))?;
+
+                Some(Value::Number(number.into()))
+            }
+            Some(c) if c.is_alphabetic() => {
+                let name = self.parse_identifier();
+                let mut arguments = vec![];
+                self.expect('(')?;
+
+                loop {
+                    self.skip_blanks();
+                    match self.peek() {
+                        Some(')') => {
+                            self.position += 1;
+                            break;
+                        }
+                        Some(',') => self.position += 1,
+                        Some(_) => arguments.push(self.parse()?),
+                        None => return Err("Expected `)`".to_string()),
+                    }
+                }
+
+                self.call(&name, arguments)
+            }
+            _ => return Err(format!("Unexpected token at {}", self.position)),
+        };
+
+        self.parse_accessors(value)
+    }
+
+    /// Single quoted, a quote is escaped by another one (eg: 'it''s')
+    fn parse_string(&mut self) -> Result<String, String> {
+        let mut output = String::new();
+        self.expect('\'')?;
+
+        loop {
+            match self.peek() {
+                None => return Err("Unterminated string".to_string()),
+                Some('\'') if self.chars.get(self.position + 1) == Some(&'\'') => {
+                    output.push('\'');
+                    self.position += 2;
+                }
+                Some('\'') => {
+                    self.position += 1;
+                    return Ok(output);
+                }
+                Some(c) => {
+                    output.push(c);
+                    self.position += 1;
+                }
+            }
+        }
+    }
+
+    /// Properties (eg: parameters('settings').port) and indexes (eg: variables('names')[0])
+    fn parse_accessors(&mut self, mut value: Option<Value>) -> Result<Option<Value>, String> {
+        loop {
+            self.skip_blanks();
+
+            match self.peek() {
+                Some('.') => {
+                    self.position += 1;
+                    let key = self.parse_identifier();
+                    value = value.and_then(|value| value.get(&key).cloned());
+                }
+                Some('[') => {
+                    self.position += 1;
+                    let index = self.parse()?;
+                    self.expect(']')?;
+                    value = match (value, index) {
+                        (Some(value), Some(Value::Number(index))) => index
+                            .as_u64()
+                            .and_then(|index| value.get(index as usize).cloned()),
+                        (Some(value), Some(Value::String(key))) => value.get(&key).cloned(),
+                        _ => None,
+                    };
+                }
+                _ => return Ok(value),
+            }
+        }
+    }
+
+    fn parameter(&mut self, name: &str) -> Option<Value> {
+        if let Some(value) = self.parameters.get(name) {
+            return Some(value.clone());
+        }
+
+        let default_value = self
+            .template
+            .get("parameters")
+            .and_then(|x| x.get(name))
+            .and_then(|x| x.get("defaultValue"));
+
+        match default_value {
+            // The default value can be an expression itself (eg: [resourceGroup().location])
+            Some(value) => self.resolve(value),
+            None => {
+                self.unresolved.push(name.to_string());
+                None
+            }
+        }
+    }
+
+    fn variable(&mut self, name: &str) -> Option<Value> {
+        let variable = self.template.get("variables")?.get(name)?;
+
+        self.resolve(variable)
+    }
+
+    /// Nested expressions of a default value or a variable, none when one of them is unknown
+    fn resolve(&mut self, value: &Value) -> Option<Value> {
+        if self.depth >= MAX_VARIABLE_DEPTH {
+            return None;
+        }
+
+        let mut unresolved = vec![];
+        let value = resolve_expressions(
+            value.clone(),
+            "",
+            self.template,
+            self.parameters,
+            self.depth + 1,
+            &mut unresolved,
+        );
+        let is_known = unresolved.is_empty() && !value.as_str().is_some_and(is_expression);
+        self.unresolved
+            .extend(unresolved.into_iter().map(|(name, _)| name));
+
+        is_known.then_some(value)
+    }
+
+    /// Functions of the template language whose result is known from their arguments,
+    /// function names are case insensitive
+    fn call(&mut self, name: &str, arguments: Vec<Option<Value>>) -> Option<Value> {
+        let name = name.to_lowercase();
+        let string = |value: &Option<Value>| match value {
+            Some(Value::String(value)) => Some(value.clone()),
+            Some(Value::Number(value)) => Some(value.to_string()),
+            Some(Value::Bool(value)) => Some(value.to_string()),
+            _ => None,
+        };
+
+        match (name.as_str(), arguments.as_slice()) {
+            ("parameters", [Some(Value::String(name))]) => self.parameter(name),
+            ("variables", [Some(Value::String(name))]) => self.variable(name),
+            ("true", []) => Some(Value::Bool(true)),
+            ("false", []) => Some(Value::Bool(false)),
+            ("null", []) => Some(Value::Null),
+            ("concat", [Some(Value::Array(_)), ..]) => arguments
+                .iter()
+                .map(|argument| argument.as_ref()?.as_array().cloned())
+                .collect::<Option<Vec<_>>>()
+                .map(|arrays| Value::Array(arrays.concat())),
+            ("concat", arguments) => arguments
+                .iter()
+                .map(string)
+                .collect::<Option<Vec<_>>>()
+                .map(|strings| Value::String(strings.concat())),
+            // Placeholders are indexes of the other arguments (eg: format('{0}-app', name))
+            ("format", [format, values @ ..]) => {
+                let format = string(format)?;
+                let values = values.iter().map(string).collect::<Option<Vec<_>>>()?;
+
+                Some(Value::String(
+                    values
+                        .iter()
+                        .enumerate()
+                        .fold(format, |output, (index, value)| {
+                            output.replace(&format!("{{{}}}", index), value)
+                        }),
+                ))
+            }
+            ("string", [value]) => string(value).map(Value::String),
+            ("int", [value]) => string(value)?
+                .trim()
+                .parse::<i64>()
+                .ok()
+                .map(|number| Value::Number(number.into())),
+            ("json", [value]) => serde_json::from_str(&string(value)?).ok(),
+            ("tolower", [value]) => string(value).map(|value| Value::String(value.to_lowercase())),
+            ("toupper", [value]) => string(value).map(|value| Value::String(value.to_uppercase())),
+            ("equals", [Some(left), Some(right)]) => Some(Value::Bool(left == right)),
+            ("if", [Some(Value::Bool(condition)), when_true, when_false]) => match condition {
+                true => when_true.clone(),
+                false => when_false.clone(),
+            },
+            _ => None,
+        }
+    }
+}
+
+fn is_expression(input: &str) -> bool {
+    input.starts_with('[') && !input.starts_with("[[") && input.ends_with(']')
+}
+
+/***
+ * Replace the template expressions of the value, the unknown ones are kept
+ * and the parameters without value are returned with their property path
+ */
+fn resolve_expressions(
+    value: Value,
+    path: &str,
+    template: &Value,
+    parameters: &BTreeMap<String, Value>,
+    depth: usize,
+    unresolved: &mut Vec<(String, String)>,
+) -> Value {
+    let at = |key: String| match path.is_empty() {
+        true => key,
+        false => format!("{}.{}", path, key),
+    };
+
+    match value {
+        Value::Object(fields) => Value::Object(
+            fields
+                .into_iter()
+                .map(|(key, value)| {
+                    let path = at(key.clone());
+                    let value =
+                        resolve_expressions(value, &path, template, parameters, depth, unresolved);
+                    (key, value)
+                })
+                .collect::<Map<String, Value>>(),
+        ),
+        Value::Array(items) => Value::Array(
+            items
+                .into_iter()
+                .enumerate()
+                .map(|(index, value)| {
+                    let path = format!("{}[{}]", path, index);
+                    resolve_expressions(value, &path, template, parameters, depth, unresolved)
+                })
+                .collect(),
+        ),
+        // Two opening brackets escape a literal string (eg: [[not an expression])
+        Value::String(input) if input.starts_with("[[") => Value::String(input[1..].to_string()),
+        Value::String(input) if is_expression(&input) => {
+            let mut expression =
+                Expression::new(&input[1..input.len() - 1], template, parameters, depth);
+            let value = expression.parse();
+
+            unresolved.extend(
+                expression
+                    .unresolved
+                    .into_iter()
+                    .map(|name| (name, path.to_string())),
+            );
+
+            match value {
+                Ok(Some(value)) => value,
+                _ => Value::String(input),
+            }
+        }
+        value => value,
+    }
+}
+
+// Resource with its expressions resolved and its parameters without value by property path
+type AppResource = (Value, Vec<(String, String)>);
+
+fn get_app_resources(value: &Value, options: &BuildOptions) -> Vec<AppResource> {
+    get_resources(value)
+        .into_iter()
+        .filter(filter_by_type)
+        .map(|resource| {
+            let mut unresolved = vec![];
+            let resource = resolve_expressions(
+                resource.to_owned(),
+                "",
+                value,
+                &options.parameters,
+                0,
+                &mut unresolved,
+            );
+
+            (resource, unresolved)
+        })
+        .collect()
+}
+
+fn get_app_properties(value: &Value, options: &BuildOptions) -> Vec<Value> {
+    get_app_resources(value, options)
+        .into_iter()
+        .map(|(resource, _)| resource.get("properties").cloned().unwrap_or_default())
+        .collect()
+}
+
+fn get_apps(
+    value: &Value,
+    options: &BuildOptions,
+) -> Result<Vec<ContainerAppBluePrint>, DeserializeError> {
+    get_app_resources(value, options)
+        .into_iter()
+        .map(|(container, _)| {
+            let mut app: ContainerAppBluePrint =
+                serde_json::from_value(container.get("properties").cloned().unwrap_or_default())
+                    .map_err(|e| DeserializeError::Parse(e.to_string()))?;
+            app.name = container
+                .get("name")
+                .and_then(|x| x.as_str())
+                .map(|x| x.to_string());
+            // The location is a property of the resource, not of the app
+            app.location = container
+                .get("location")
+                .and_then(|x| x.as_str())
+                .filter(|x| !x.starts_with('['))
+                .map(|x| x.to_string());
+            // As the pinned API versions of the Pulumi types (eg: 2023-05-01 for v20230501)
+            app.api_version = container
+                .get("apiVersion")
+                .and_then(|x| x.as_str())
+                .map(|x| format!("v{}", x.replace('-', "")));
+            pulumi::normalize_scale(&mut app);
+
+            Ok(app)
+        })
+        .collect()
+}
+
+/***
+ * Parameters without value and fields which are not translated, the input is expected to be valid
+ */
+pub fn collect_warnings(input: &str, options: &BuildOptions) -> Vec<String> {
+    let Ok(value) = serde_json::from_str::<Value>(input) else {
+        return vec![];
+    };
+    let mut warnings = vec![];
+
+    for (resource, unresolved) in get_app_resources(&value, options) {
+        let name = resource
+            .get("name")
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+            .to_string();
+
+        warnings.extend(unresolved.into_iter().map(|(parameter, path)| {
+            format!("unresolved parameter {} at {} in {}", parameter, path, name)
+        }));
+    }
+
+    let apps = get_apps(&value, options).unwrap_or_default();
+    warnings.extend(pulumi::collect_api_version_warnings(&apps));
+    warnings.extend(pulumi::collect_secret_warnings(&apps));
+    warnings.extend(pulumi::collect_resource_warnings(&apps));
+    warnings.extend(pulumi::collect_scale_warnings(&apps));
+
+    for (resource, _) in get_app_resources(&value, options) {
+        let name = resource
+            .get("name")
+            .and_then(Value::as_str)
+            .unwrap_or_default();
+        let properties = resource
+            .get("properties")
+            .and_then(|x| serde_yaml::to_value(x).ok())
+            .unwrap_or_default();
+
+        warnings.extend(
+            pulumi::collect_ignored_fields(&properties)
+                .unwrap_or_default()
+                .into_iter()
+                .map(|path| format!("ignored {} in {}", path, name)),
+        );
+    }
+
+    warnings
+}
+
+pub fn deserialize(
+    input: &str,
+    options: &BuildOptions,
+) -> Result<Vec<ContainerAppConfiguration>, DeserializeError> {
+    deserialize_per_app(input, options).map(pulumi::flatten_apps)
+}
+
+/***
+ * Services of each app, see `pulumi::build_per_app`
+ */
+pub fn deserialize_per_app(
+    input: &str,
+    options: &BuildOptions,
+) -> Result<Vec<(String, Vec<ContainerAppConfiguration>)>, DeserializeError> {
+    match serde_json::from_str::<Value>(input) {
+        Ok(v) => {
+            if options.strict {
+                for properties in get_app_properties(&v, options) {
+                    let properties = serde_json::from_value(properties)
+                        .map_err(|e| DeserializeError::Parse(e.to_string()))?;
+
+                    pulumi::validate_known_fields(&properties)?;
+                }
+            }
+
+            let apps = get_apps(&v, options)?;
+
+            // ARM templates have no image resources, images are always remote
+            pulumi::build_per_app(apps, vec![], options.include.as_deref(), options)
+        }
+        Err(e) => {
+            warn!(target: LOG_TARGET, "{} near `{}`", e, snippet_at(input, e.line()));
+            Err(DeserializeError::Parse(e.to_string()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::serializer::{
+        ConfigurationBluePrint, ContainerBluePrint, DaprBluePrint, IngressBluePrint,
+        TemplateBluePrint,
+    };
+
+    use super::*;
+
+    const TEMPLATE: &str = r#"{
+        "$schema": "https://schema.management.azure.com/schemas/2019-04-01/deploymentTemplate.json#",
+        "contentVersion": "1.0.0.0",
+        "resources": [
+            {
+                "type": "Microsoft.App/managedEnvironments",
+                "apiVersion": "2022-03-01",
+                "name": "env",
+                "properties": {}
+            },
+            {
+                "type": "Microsoft.App/containerApps",
+                "apiVersion": "2022-03-01",
+                "name": "myapp",
+                "properties": {
+                    "managedEnvironmentId": "[resourceId('Microsoft.App/managedEnvironments', 'env')]",
+                    "configuration": {
+                        "ingress": {
+                            "external": true,
+                            "targetPort": 80
+                        }
+                    },
+                    "template": {
+                        "containers": [
+                            {
+                                "image": "node-12",
+                                "name": "myapp"
+                            }
+                        ]
+                    }
+                }
+            }
+        ]
+    }"#;
+
+    #[test]
+    fn test_get_apps() {
+        let value: Value = serde_json::from_str(TEMPLATE).unwrap();
+
+        let output = get_apps(&value, &BuildOptions::default()).unwrap();
+
+        let expected = vec![ContainerAppBluePrint {
+            name: Some("myapp".to_string()),
+            configuration: Some(ConfigurationBluePrint {
+                ingress: Some(IngressBluePrint {
+                    external: Some(true),
+                    target_port: Some(80),
+                    cors_policy: None,
+                    custom_domains: None,
+                    additional_port_mappings: None,
+                    transport: None,
+                    client_certificate_mode: None,
+                }),
+                ..ConfigurationBluePrint::default()
+            }),
+            template: Some(TemplateBluePrint::new(vec![ContainerBluePrint::new(
+                "myapp", "node-12",
+            )])),
+            api_version: Some("v20220301".to_string()),
+            ..ContainerAppBluePrint::default()
+        }];
+
+        assert_eq!(expected, output);
+
+        // Single resource, as given by `az containerapp show`
+        let value: Value = serde_json::from_str(
+            r#"{
+                "type": "Microsoft.App/containerApps",
+                "name": "myapp",
+                "properties": {
+                    "configuration": {
+                        "dapr": { "enabled": true, "appPort": 3000, "appId": "myapp" }
+                    },
+                    "template": {
+                        "containers": [{ "image": "node-12", "name": "myapp" }]
+                    }
+                }
+            }"#,
+        )
+        .unwrap();
+
+        let output = get_apps(&value, &BuildOptions::default()).unwrap();
+
+        let expected = vec![ContainerAppBluePrint {
+            name: Some("myapp".to_string()),
+            configuration: Some(ConfigurationBluePrint {
+                dapr: Some(DaprBluePrint {
+                    app_port: Some(3000),
+                    enabled: Some(true),
+                    app_id: Some("myapp".to_string()),
+                    command: None,
+                    sidecar_image: None,
+                    max_concurrency: None,
+                    http_max_request_size: None,
+                    components: None,
+                }),
+                ..ConfigurationBluePrint::default()
+            }),
+            template: Some(TemplateBluePrint::new(vec![ContainerBluePrint::new(
+                "myapp", "node-12",
+            )])),
+            ..ContainerAppBluePrint::default()
+        }];
+
+        assert_eq!(expected, output);
+    }
+
+    #[test]
+    fn test_deserialize() {
+        let output = deserialize(TEMPLATE, &BuildOptions::default());
+
+        let expected = vec![ContainerAppConfiguration {
+            image: Some("node-12".to_string()),
+            ports: Some(vec!["80:80".to_string()]),
+            restart: Some("unless-stopped".to_string()),
+            ..ContainerAppConfiguration::new("myapp")
+        }];
+
+        assert_eq!(Ok(expected), output);
+    }
+
+    const PARAMETERIZED_TEMPLATE: &str = r#"{
+        "$schema": "https://schema.management.azure.com/schemas/2019-04-01/deploymentTemplate.json#",
+        "contentVersion": "1.0.0.0",
+        "parameters": {
+            "registry": { "type": "string" },
+            "tag": { "type": "string", "defaultValue": "v1" },
+            "location": { "type": "string", "defaultValue": "[resourceGroup().location]" },
+            "appPort": { "type": "int", "defaultValue": 3000 }
+        },
+        "variables": {
+            "appName": "[concat('shop-', 'api')]",
+            "image": "[format('{0}/api:{1}', parameters('registry'), parameters('tag'))]"
+        },
+        "resources": [
+            {
+                "type": "Microsoft.App/containerApps",
+                "apiVersion": "2023-05-01",
+                "name": "[variables('appName')]",
+                "location": "[parameters('location')]",
+                "properties": {
+                    "managedEnvironmentId": "[resourceId('Microsoft.App/managedEnvironments', 'env')]",
+                    "configuration": {
+                        "dapr": {
+                            "enabled": true,
+                            "appId": "[variables('appName')]",
+                            "appPort": "[parameters('appPort')]"
+                        }
+                    },
+                    "template": {
+                        "containers": [
+                            { "name": "api", "image": "[variables('image')]" }
+                        ]
+                    }
+                }
+            }
+        ]
+    }"#;
+
+    const PARAMETERS: &str = r#"{
+        "$schema": "https://schema.management.azure.com/schemas/2019-04-01/deploymentParameters.json#",
+        "contentVersion": "1.0.0.0",
+        "parameters": {
+            "registry": { "value": "myregistry.azurecr.io" },
+            "password": {
+                "reference": {
+                    "keyVault": { "id": "/subscriptions/id/resourceGroups/rg/providers/Microsoft.KeyVault/vaults/vault" },
+                    "secretName": "password"
+                }
+            }
+        }
+    }"#;
+
+    #[test]
+    fn test_parse_parameters() {
+        let output = crate::azure::parse_parameters(PARAMETERS).unwrap();
+
+        let expected = BTreeMap::from([(
+            "registry".to_string(),
+            Value::String("myregistry.azurecr.io".to_string()),
+        )]);
+
+        assert_eq!(expected, output);
+    }
+
+    #[test]
+    fn test_resolve_expressions() {
+        let template: Value = serde_json::from_str(PARAMETERIZED_TEMPLATE).unwrap();
+        let parameters = BTreeMap::from([(
+            "registry".to_string(),
+            Value::String("myregistry.azurecr.io".to_string()),
+        )]);
+        let resolve = |input: &str| {
+            let mut unresolved = vec![];
+            let value = resolve_expressions(
+                Value::String(input.to_string()),
+                "image",
+                &template,
+                &parameters,
+                0,
+                &mut unresolved,
+            );
+            (value, unresolved)
+        };
+
+        assert_eq!(
+            (
+                Value::String("myregistry.azurecr.io/api:v1".to_string()),
+                vec![]
+            ),
+            resolve("[variables('image')]")
+        );
+        assert_eq!(
+            (Value::Number(3000.into()), vec![]),
+            resolve("[parameters('appPort')]")
+        );
+        assert_eq!(
+            (Value::String("it's".to_string()), vec![]),
+            resolve("[concat('it''s')]")
+        );
+        assert_eq!(
+            (Value::String("[literal]".to_string()), vec![]),
+            resolve("[[literal]")
+        );
+        assert_eq!(
+            (Value::String("v1".to_string()), vec![]),
+            resolve("[if(equals(parameters('appPort'), 3000), parameters('tag'), 'latest')]")
+        );
+        // Unknown before the deployment, the expression is kept
+        assert_eq!(
+            (
+                Value::String("[parameters('location')]".to_string()),
+                vec![]
+            ),
+            resolve("[parameters('location')]")
+        );
+        assert_eq!(
+            (
+                Value::String("[toLower(parameters('missing'))]".to_string()),
+                vec![("missing".to_string(), "image".to_string())]
+            ),
+            resolve("[toLower(parameters('missing'))]")
+        );
+    }
+
+    #[test]
+    fn test_collect_warnings() {
+        let output = collect_warnings(PARAMETERIZED_TEMPLATE, &BuildOptions::default());
+
+        let expected = vec![
+            "unresolved parameter registry at properties.template.containers[0].image in shop-api",
+        ];
+
+        assert_eq!(expected, output);
+
+        let options = BuildOptions {
+            parameters: crate::azure::parse_parameters(PARAMETERS).unwrap(),
+            ..BuildOptions::default()
+        };
+
+        assert_eq!(
+            Vec::<String>::new(),
+            collect_warnings(PARAMETERIZED_TEMPLATE, &options)
+        );
+    }
+
+    #[cfg(feature = "yaml")]
+    #[test]
+    fn test_deserialize_as_pulumi_yaml() {
+        let program = r#"
+name: shop
+runtime: yaml
+resources:
+  shop-api:
+    type: azure-native:app:ContainerApp
+    properties:
+      managedEnvironmentId: ${env.id}
+      configuration:
+        dapr:
+          enabled: true
+          appId: shop-api
+          appPort: 3000
+      template:
+        containers:
+          - name: api
+            image: myregistry.azurecr.io/api:v1
+"#;
+        let options = BuildOptions {
+            parameters: crate::azure::parse_parameters(PARAMETERS).unwrap(),
+            ..BuildOptions::default()
+        };
+
+        let output = deserialize(PARAMETERIZED_TEMPLATE, &options).unwrap();
+
+        let expected = pulumi::yaml::deserialize(program, &BuildOptions::default()).unwrap();
+
+        assert_eq!(expected, output);
+        assert_eq!(
+            Some("myregistry.azurecr.io/api:v1".to_string()),
+            output[0].image
+        );
+    }
+
+    #[test]
+    fn test_deserialize_dapr_without_enabled() {
+        let template = TEMPLATE.replace(
+            r#""ingress": {"#,
+            r#""dapr": { "appId": "myapp", "appPort": 80 }, "ingress": {"#,
+        );
+        let output = deserialize(&template, &BuildOptions::default()).unwrap();
+
+        let names: Vec<&str> = output.iter().map(|service| service.name.as_str()).collect();
+        // Dapr is disabled by default, as in Container Apps
+        assert_eq!(vec!["myapp"], names);
+    }
+}
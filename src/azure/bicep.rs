@@ -0,0 +1,1027 @@
+use std::collections::BTreeMap;
+
+use regex::Regex;
+use serde_json::{Map, Value};
+
+use crate::error::DeserializeError;
+use crate::pulumi;
+use crate::serializer::{BuildOptions, ContainerAppBluePrint, ContainerAppConfiguration};
+
+const CONTAINER_APP_TYPE: &str = "Microsoft.App/containerApps";
+
+/***
+ * Bicep expressions as JSON values.
+ * Parameters with a default value and variables are substituted, references to other
+ * resources are `${...}` tokens and expressions which cannot be evaluated statically
+ * (eg: resourceGroup().location) are null
+ */
+struct Parser<'a> {
+    chars: Vec<char>,
+    position: usize,
+    constants: &'a Map<String, Value>,
+}
+
+impl<'a> Parser<'a> {
+    fn new(input: &str, position: usize, constants: &'a Map<String, Value>) -> Parser<'a> {
+        Parser {
+            chars: input.chars().collect(),
+            position,
+            constants,
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.position).copied()
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<char> {
+        self.chars.get(self.position + offset).copied()
+    }
+
+    fn line(&self) -> usize {
+        self.chars[..self.position.min(self.chars.len())]
+            .iter()
+            .filter(|c| **c == '\n')
+            .count()
+            + 1
+    }
+
+    fn error(&self, message: &str) -> String {
+        format!("{} at line {}", message, self.line())
+    }
+
+    fn skip_blanks(&mut self) {
+        loop {
+            match (self.peek(), self.peek_at(1)) {
+                (Some('/'), Some('/')) => {
+                    while !matches!(self.peek(), Some('\n') | None) {
+                        self.position += 1;
+                    }
+                }
+                (Some('/'), Some('*')) => {
+                    self.position += 2;
+                    while self.peek().is_some()
+                        && !(self.peek() == Some('*') && self.peek_at(1) == Some('/'))
+                    {
+                        self.position += 1;
+                    }
+                    self.position += 2;
+                }
+                (Some(c), _) if c.is_whitespace() => self.position += 1,
+                _ => break,
+            }
+        }
+    }
+
+    /// Operators continue an expression on its line only, a new line starts the next property
+    fn skip_inline_blanks(&mut self) {
+        while matches!(self.peek(), Some(' ' | '\t')) {
+            self.position += 1;
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        self.skip_blanks();
+
+        match self.peek() {
+            Some(c) if c == expected => {
+                self.position += 1;
+                Ok(())
+            }
+            Some(c) => Err(self.error(&format!("Expected `{}`, found `{}`", expected, c))),
+            None => Err(self.error(&format!("Expected `{}`", expected))),
+        }
+    }
+
+    fn parse_identifier(&mut self) -> String {
+        let mut identifier = String::new();
+
+        while let Some(c) = self.peek().filter(|c| c.is_alphanumeric() || *c == '_') {
+            identifier.push(c);
+            self.position += 1;
+        }
+
+        identifier
+    }
+
+    fn parse_value(&mut self) -> Result<Value, String> {
+        self.skip_blanks();
+
+        let value = match self.peek() {
+            Some('{') => self.parse_object()?,
+            Some('[') => self.parse_array()?,
+            Some('(') => {
+                self.position += 1;
+                let value = self.parse_value()?;
+                self.expect(')')?;
+                value
+            }
+            Some('\'') => Value::String(self.parse_string()?),
+            Some('!') => {
+                self.position += 1;
+                match self.parse_value()? {
+                    Value::Bool(value) => Value::Bool(!value),
+                    _ => Value::Null,
+                }
+            }
+            Some(c) if c.is_ascii_digit() || c == '-' => self.parse_number()?,
+            Some(c) if c.is_alphabetic() || c == '_' => self.parse_name()?,
+            Some(c) => return Err(self.error(&format!("Unexpected `{}`", c))),
+            None => return Err(self.error("Unexpected end of input")),
+        };
+
+        self.parse_operators(value)
+    }
+
+    /// Conditions are evaluated when known, other operations are null
+    fn parse_operators(&mut self, value: Value) -> Result<Value, String> {
+        self.skip_inline_blanks();
+
+        match (self.peek(), self.peek_at(1)) {
+            (Some('?'), Some('?')) => {
+                self.position += 2;
+                let fallback = self.parse_value()?;
+
+                Ok(match value {
+                    Value::Null => fallback,
+                    value => value,
+                })
+            }
+            (Some('?'), _) => {
+                self.position += 1;
+                let when_true = self.parse_value()?;
+                self.expect(':')?;
+                let when_false = self.parse_value()?;
+
+                Ok(match value {
+                    Value::Bool(true) => when_true,
+                    Value::Bool(false) => when_false,
+                    _ => Value::Null,
+                })
+            }
+            (Some('=' | '!'), Some('='))
+            | (Some('&'), Some('&'))
+            | (Some('|'), Some('|'))
+            | (Some('<' | '>'), Some('=')) => {
+                let operator: String = self.chars[self.position..self.position + 2]
+                    .iter()
+                    .collect();
+                self.position += 2;
+                let right = self.parse_value()?;
+                let is_known = |value: &Value| {
+                    !(value.is_null() || value.as_str().is_some_and(|value| value.contains("${")))
+                };
+
+                Ok(match (operator.as_str(), &value, &right) {
+                    ("==", left, right) if is_known(left) && is_known(right) => {
+                        Value::Bool(left == right)
+                    }
+                    ("!=", left, right) if is_known(left) && is_known(right) => {
+                        Value::Bool(left != right)
+                    }
+                    ("&&", Value::Bool(left), Value::Bool(right)) => Value::Bool(*left && *right),
+                    ("||", Value::Bool(left), Value::Bool(right)) => Value::Bool(*left || *right),
+                    _ => Value::Null,
+                })
+            }
+            (Some('<' | '>' | '+' | '-' | '*' | '/' | '%'), _) => {
+                self.position += 1;
+                self.parse_value()?;
+
+                Ok(Value::Null)
+            }
+            _ => Ok(value),
+        }
+    }
+
+    /// Properties are separated by new lines or commas
+    fn parse_object(&mut self) -> Result<Value, String> {
+        let mut fields = Map::new();
+        self.expect('{')?;
+
+        loop {
+            self.skip_blanks();
+            match self.peek() {
+                Some('}') => {
+                    self.position += 1;
+                    return Ok(Value::Object(fields));
+                }
+                Some(',') => {
+                    self.position += 1;
+                    continue;
+                }
+                _ => {}
+            }
+
+            let key = match self.peek() {
+                Some('\'') => self.parse_string()?,
+                _ => self.parse_identifier(),
+            };
+            if key.is_empty() {
+                return Err(self.error("Expected a property name"));
+            }
+
+            self.expect(':')?;
+            let value = self.parse_value()?;
+            fields.insert(key, value);
+        }
+    }
+
+    /// Items are separated by new lines or commas, loops (eg: [for name in names: {...}])
+    /// are not evaluated
+    fn parse_array(&mut self) -> Result<Value, String> {
+        let start = self.position;
+        let mut items = vec![];
+        self.expect('[')?;
+
+        loop {
+            self.skip_blanks();
+            match self.peek() {
+                Some(']') => {
+                    self.position += 1;
+                    return Ok(Value::Array(items));
+                }
+                Some(',') => {
+                    self.position += 1;
+                    continue;
+                }
+                _ => {}
+            }
+
+            if items.is_empty() && self.is_loop() {
+                self.position = start;
+                self.skip_balanced('[', ']')?;
+
+                return Ok(Value::Null);
+            }
+
+            items.push(self.parse_value()?);
+        }
+    }
+
+    fn is_loop(&self) -> bool {
+        self.chars[self.position..].starts_with(&['f', 'o', 'r'])
+            && self.peek_at(3).is_some_and(char::is_whitespace)
+    }
+
+    /// Single quoted strings, interpolations (eg: '${name}-app') are substituted when known
+    /// and kept as `${...}` tokens otherwise
+    fn parse_string(&mut self) -> Result<String, String> {
+        let mut output = String::new();
+
+        // Multi-line strings are verbatim
+        if self.chars[self.position..].starts_with(&['\'', '\'', '\'']) {
+            self.position += 3;
+
+            while !self.chars[self.position..].starts_with(&['\'', '\'', '\'']) {
+                output.push(
+                    self.peek()
+                        .ok_or_else(|| self.error("Unterminated string"))?,
+                );
+                self.position += 1;
+            }
+            self.position += 3;
+
+            return Ok(output);
+        }
+
+        self.expect('\'')?;
+
+        loop {
+            match self.peek() {
+                None => return Err(self.error("Unterminated string")),
+                Some('\'') => {
+                    self.position += 1;
+                    return Ok(output);
+                }
+                Some('\\') => {
+                    match self.peek_at(1) {
+                        Some('n') => output.push('\n'),
+                        Some('r') => output.push('\r'),
+                        Some('t') => output.push('\t'),
+                        Some(c) => output.push(c),
+                        None => return Err(self.error("Unterminated string")),
+                    }
+                    self.position += 2;
+                }
+                Some('$') if self.peek_at(1) == Some('{') => {
+                    self.position += 2;
+                    self.skip_blanks();
+                    let start = self.position;
+                    let value = self.parse_value()?;
+                    let expression: String = self.chars[start..self.position].iter().collect();
+                    self.expect('}')?;
+
+                    match value {
+                        Value::String(value) => output.push_str(&value),
+                        Value::Number(value) => output.push_str(&value.to_string()),
+                        Value::Bool(value) => output.push_str(&value.to_string()),
+                        _ => output.push_str(&format!("${{{}}}", expression.trim())),
+                    }
+                }
+                Some(c) => {
+                    output.push(c);
+                    self.position += 1;
+                }
+            }
+        }
+    }
+
+    fn parse_number(&mut self) -> Result<Value, String> {
+        let mut number = String::new();
+
+        while let Some(c) = self
+            .peek()
+            .filter(|c| c.is_ascii_digit() || (*c == '-' && number.is_empty()))
+        {
+            number.push(c);
+            self.position += 1;
+        }
+
+        number
+            .parse::<i64>()
+            .map(|number| Value::Number(number.into()))
+            .map_err(|_| self.error(&format!("Invalid number `{}`", number)))
+    }
+
+    fn parse_arguments(&mut self) -> Result<Vec<Value>, String> {
+        let mut arguments = vec![];
+        self.expect('(')?;
+
+        loop {
+            self.skip_blanks();
+            match self.peek() {
+                Some(')') => {
+                    self.position += 1;
+                    return Ok(arguments);
+                }
+                Some(',') => self.position += 1,
+                _ => arguments.push(self.parse_value()?),
+            }
+        }
+    }
+
+    fn skip_balanced(&mut self, open: char, close: char) -> Result<(), String> {
+        let mut depth = 0;
+
+        while let Some(c) = self.peek() {
+            match c {
+                '\'' => {
+                    self.parse_string()?;
+                    continue;
+                }
+                c if c == open => depth += 1,
+                c if c == close => {
+                    depth -= 1;
+                    if depth == 0 {
+                        self.position += 1;
+                        return Ok(());
+                    }
+                }
+                _ => {}
+            }
+            self.position += 1;
+        }
+
+        Err(self.error(&format!("Expected `{}`", close)))
+    }
+
+    /// Accessors of a call result (eg: resourceGroup().location), they cannot be evaluated
+    fn skip_chain(&mut self) -> Result<bool, String> {
+        let mut is_chained = false;
+
+        loop {
+            match (self.peek(), self.peek_at(1)) {
+                (Some('.'), _) | (Some('?' | '!'), Some('.')) => {
+                    while matches!(self.peek(), Some('.' | '?' | '!')) {
+                        self.position += 1;
+                    }
+                    self.parse_identifier();
+                }
+                (Some('('), _) => self.skip_balanced('(', ')')?,
+                (Some('['), _) => self.skip_balanced('[', ']')?,
+                _ => return Ok(is_chained),
+            }
+            is_chained = true;
+        }
+    }
+
+    /// Functions of the Bicep standard library whose result is known from their arguments
+    fn call(&self, name: &str, arguments: &[Value]) -> Value {
+        let strings: Option<Vec<String>> = arguments
+            .iter()
+            .map(|argument| match argument {
+                Value::String(value) if !value.contains("${") => Some(value.clone()),
+                Value::Number(value) => Some(value.to_string()),
+                Value::Bool(value) => Some(value.to_string()),
+                _ => None,
+            })
+            .collect();
+        let name = name.strip_prefix("sys.").unwrap_or(name);
+
+        match (name, strings) {
+            ("json", Some(strings)) if strings.len() == 1 => {
+                serde_json::from_str(&strings[0]).unwrap_or_default()
+            }
+            ("string", Some(strings)) if strings.len() == 1 => Value::String(strings[0].clone()),
+            ("int", Some(strings)) if strings.len() == 1 => strings[0]
+                .trim()
+                .parse::<i64>()
+                .map(|number| Value::Number(number.into()))
+                .unwrap_or_default(),
+            ("concat", Some(strings)) => Value::String(strings.concat()),
+            ("toLower", Some(strings)) if strings.len() == 1 => {
+                Value::String(strings[0].to_lowercase())
+            }
+            ("toUpper", Some(strings)) if strings.len() == 1 => {
+                Value::String(strings[0].to_uppercase())
+            }
+            // Placeholders are indexes of the other arguments (eg: format('{0}-app', name))
+            ("format", Some(strings)) if !strings.is_empty() => Value::String(
+                strings[1..]
+                    .iter()
+                    .enumerate()
+                    .fold(strings[0].clone(), |output, (index, value)| {
+                        output.replace(&format!("{{{}}}", index), value)
+                    }),
+            ),
+            _ => Value::Null,
+        }
+    }
+
+    fn parse_name(&mut self) -> Result<Value, String> {
+        let mut name = self.parse_identifier();
+
+        while self.peek() == Some('.')
+            && self
+                .peek_at(1)
+                .is_some_and(|c| c.is_alphabetic() || c == '_')
+        {
+            self.position += 1;
+            name.push('.');
+            name.push_str(&self.parse_identifier());
+        }
+
+        match name.as_str() {
+            "true" => return Ok(Value::Bool(true)),
+            "false" => return Ok(Value::Bool(false)),
+            "null" => return Ok(Value::Null),
+            _ => {}
+        }
+
+        if self.peek() == Some('(') {
+            let arguments = self.parse_arguments()?;
+            let value = self.call(&name, &arguments);
+
+            return match self.skip_chain()? {
+                true => Ok(Value::Null),
+                false => Ok(value),
+            };
+        }
+
+        // Indexers are kept in the tokens (eg: storage.listKeys().keys[0].value)
+        if self.peek() == Some('[') {
+            let start = self.position;
+            self.skip_chain()?;
+            let accessors: String = self.chars[start..self.position].iter().collect();
+
+            return Ok(Value::String(format!("${{{}{}}}", name, accessors)));
+        }
+
+        // Properties of the variables are known (eg: settings.port), other names are references
+        let mut path = name.split('.');
+        let constant = path
+            .next()
+            .and_then(|root| self.constants.get(root))
+            .and_then(|constant| path.try_fold(constant, |value, key| value.get(key)));
+
+        Ok(constant
+            .cloned()
+            .unwrap_or_else(|| Value::String(format!("${{{}}}", name))))
+    }
+}
+
+fn char_position(input: &str, byte_position: usize) -> usize {
+    input[..byte_position].chars().count()
+}
+
+/***
+ * Top level declaration (eg: resource app 'Microsoft.App/containerApps@2023-05-01' = {...})
+ */
+struct Statement {
+    keyword: String,
+    name: String,
+    /// Type of a resource or path of a module
+    target: Option<String>,
+    is_existing: bool,
+    /// Position of the value, after `=`
+    position: Option<usize>,
+}
+
+fn get_statements(input: &str) -> Vec<Statement> {
+    let re = Regex::new(
+        r"(?m)^[ \t]*(?P<keyword>param|var|resource|module)[ \t]+(?P<name>\w+)(?:[ \t]+'(?P<target>[^'\n]*)')?(?P<rest>[^=\n]*)(?P<assign>=)?",
+    )
+    .unwrap();
+
+    re.captures_iter(input)
+        .map(|captures| Statement {
+            keyword: captures["keyword"].to_string(),
+            name: captures["name"].to_string(),
+            target: captures.name("target").map(|x| x.as_str().to_string()),
+            is_existing: captures["rest"].split_whitespace().any(|x| x == "existing"),
+            position: captures
+                .name("assign")
+                .map(|assign| char_position(input, assign.end())),
+        })
+        .collect()
+}
+
+/***
+ * Parameters, from the parameters file or by default, and variables in declaration order
+ * so that variables can use the previous ones
+ */
+fn get_constants(
+    input: &str,
+    statements: &[Statement],
+    parameters: &BTreeMap<String, Value>,
+) -> Map<String, Value> {
+    let mut constants = Map::new();
+
+    for statement in statements
+        .iter()
+        .filter(|statement| matches!(statement.keyword.as_str(), "param" | "var"))
+    {
+        if let Some(value) = parameters
+            .get(&statement.name)
+            .filter(|_| statement.keyword == "param")
+        {
+            constants.insert(statement.name.clone(), value.clone());
+            continue;
+        }
+
+        let Some(position) = statement.position else {
+            continue;
+        };
+        let value = Parser::new(input, position, &constants).parse_value();
+
+        if let Some(value) = value.ok().filter(|value| !value.is_null()) {
+            constants.insert(statement.name.clone(), value);
+        }
+    }
+
+    constants
+}
+
+fn is_container_app(statement: &Statement) -> bool {
+    statement.keyword == "resource"
+        && !statement.is_existing
+        && statement.target.as_ref().is_some_and(|target| {
+            // Resource types are case insensitive
+            let resource_type = target.split('@').next().unwrap_or_default();
+            resource_type.eq_ignore_ascii_case(CONTAINER_APP_TYPE)
+        })
+}
+
+// Resource statement and its body, none for resources created in a loop
+type Declaration<'a> = (&'a Statement, Option<Value>);
+
+/***
+ * Bodies of the container app resources, conditions (eg: = if (deployApi) {...}) are ignored
+ */
+fn get_declarations<'a>(
+    input: &str,
+    statements: &'a [Statement],
+    options: &BuildOptions,
+) -> Result<Vec<Declaration<'a>>, DeserializeError> {
+    let constants = get_constants(input, statements, &options.parameters);
+
+    statements
+        .iter()
+        .filter(|statement| is_container_app(statement))
+        .map(|statement| {
+            let mut parser = Parser::new(input, statement.position.unwrap_or_default(), &constants);
+            parser.skip_blanks();
+
+            if parser.chars[parser.position..].starts_with(&['i', 'f']) {
+                parser.position += 2;
+                parser.skip_blanks();
+                parser
+                    .skip_balanced('(', ')')
+                    .map_err(DeserializeError::Parse)?;
+            }
+
+            let body = parser.parse_value().map_err(DeserializeError::Parse)?;
+
+            Ok((statement, body.is_object().then_some(body)))
+        })
+        .collect()
+}
+
+fn get_app_properties(
+    input: &str,
+    options: &BuildOptions,
+) -> Result<Vec<(String, Value)>, DeserializeError> {
+    let statements = get_statements(input);
+
+    Ok(get_declarations(input, &statements, options)?
+        .into_iter()
+        .filter_map(|(statement, body)| {
+            let body = body?;
+            // The resource name, or its symbolic name when it is not known
+            let name = body
+                .get("name")
+                .and_then(Value::as_str)
+                .filter(|name| !name.contains("${"))
+                .unwrap_or(&statement.name)
+                .to_string();
+
+            Some((name, body))
+        })
+        .collect())
+}
+
+fn get_apps(
+    input: &str,
+    options: &BuildOptions,
+) -> Result<Vec<ContainerAppBluePrint>, DeserializeError> {
+    let statements = get_statements(input);
+
+    get_declarations(input, &statements, options)?
+        .into_iter()
+        .filter_map(|(statement, body)| Some((statement, body?)))
+        .map(|(statement, body)| {
+            let properties = body.get("properties").cloned().unwrap_or_default();
+            let mut app: ContainerAppBluePrint = serde_json::from_value(properties)
+                .map_err(|e| DeserializeError::Parse(e.to_string()))?;
+            app.name = body
+                .get("name")
+                .and_then(Value::as_str)
+                .filter(|name| !name.contains("${"))
+                .or(Some(&statement.name))
+                .map(str::to_string);
+            // The location is a property of the resource, not of the app
+            app.location = body
+                .get("location")
+                .and_then(Value::as_str)
+                .filter(|location| !location.contains("${"))
+                .map(str::to_string);
+            // As the pinned API versions of the Pulumi types (eg: 2023-05-01 for v20230501)
+            app.api_version = statement
+                .target
+                .as_ref()
+                .and_then(|target| target.split_once('@'))
+                .map(|(_, version)| format!("v{}", version.replace('-', "")));
+            pulumi::normalize_scale(&mut app);
+
+            Ok(app)
+        })
+        .collect()
+}
+
+fn as_yaml_properties(properties: &Value) -> Result<serde_yaml::Value, DeserializeError> {
+    serde_yaml::to_value(properties).map_err(|e| DeserializeError::Parse(e.to_string()))
+}
+
+/***
+ * Declarations which are not translated (eg: modules), the input is expected to be valid
+ */
+pub fn collect_warnings(input: &str, options: &BuildOptions) -> Vec<String> {
+    let statements = get_statements(input);
+    let mut warnings: Vec<String> = statements
+        .iter()
+        .filter(|statement| statement.keyword == "module")
+        .map(|statement| {
+            format!(
+                "ignored module {} ({}), modules are not supported",
+                statement.name,
+                statement.target.as_deref().unwrap_or_default()
+            )
+        })
+        .collect();
+
+    for (statement, body) in get_declarations(input, &statements, options).unwrap_or_default() {
+        if body.is_none() {
+            warnings.push(format!(
+                "ignored resource {}, resources created in a loop are not supported",
+                statement.name
+            ));
+        }
+    }
+
+    let apps = get_apps(input, options).unwrap_or_default();
+    warnings.extend(pulumi::collect_api_version_warnings(&apps));
+    warnings.extend(pulumi::collect_secret_warnings(&apps));
+    warnings.extend(pulumi::collect_resource_warnings(&apps));
+    warnings.extend(pulumi::collect_scale_warnings(&apps));
+
+    let ignored_fields = get_app_properties(input, options)
+        .unwrap_or_default()
+        .into_iter()
+        .flat_map(|(name, body)| {
+            let properties = as_yaml_properties(&body["properties"]).unwrap_or_default();
+
+            pulumi::collect_ignored_fields(&properties)
+                .unwrap_or_default()
+                .into_iter()
+                .map(move |path| format!("ignored {} in {}", path, name))
+        });
+
+    warnings.extend(ignored_fields);
+    warnings
+}
+
+pub fn deserialize(
+    input: &str,
+    options: &BuildOptions,
+) -> Result<Vec<ContainerAppConfiguration>, DeserializeError> {
+    deserialize_per_app(input, options).map(pulumi::flatten_apps)
+}
+
+/***
+ * Services of each app, see `pulumi::build_per_app`
+ */
+pub fn deserialize_per_app(
+    input: &str,
+    options: &BuildOptions,
+) -> Result<Vec<(String, Vec<ContainerAppConfiguration>)>, DeserializeError> {
+    if options.strict {
+        for (_, body) in get_app_properties(input, options)? {
+            pulumi::validate_known_fields(&as_yaml_properties(&body["properties"])?)?;
+        }
+    }
+
+    let apps = get_apps(input, options)?;
+
+    // Bicep files build no images, images are always remote
+    pulumi::build_per_app(apps, vec![], options.include.as_deref(), options)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEMPLATE: &str = r#"
+targetScope = 'resourceGroup'
+
+@description('Registry of the images')
+param registry string = 'myregistry.azurecr.io'
+param location string = resourceGroup().location
+param tag string = 'v1'
+@secure()
+param registryPassword string
+
+var prefix = 'shop'
+var apiImage = '${registry}/${prefix}-api:${tag}'
+var settings = {
+  port: 3000
+  replicas: 2
+}
+
+resource env 'Microsoft.App/managedEnvironments@2023-05-01' = {
+  name: '${prefix}-env'
+  location: location
+  properties: {}
+}
+
+/* Dapr sidecar on the settings port */
+resource api 'Microsoft.App/containerApps@2023-05-01' = {
+  name: '${prefix}-api'
+  location: 'westeurope'
+  properties: {
+    managedEnvironmentId: env.id
+    configuration: {
+      dapr: {
+        enabled: true
+        appId: 'api'
+        appPort: settings.port
+      }
+      secrets: [
+        {
+          name: 'registry-password'
+          value: registryPassword
+        }
+      ]
+    }
+    template: {
+      containers: [
+        {
+          name: 'api'
+          image: apiImage
+          resources: {
+            cpu: json('0.5')
+            memory: '1Gi'
+          }
+        }
+      ]
+      scale: {
+        minReplicas: settings.replicas
+        maxReplicas: 5
+      }
+    }
+  }
+}
+
+resource frontend 'Microsoft.App/containerApps@2023-05-01' = {
+  name: 'frontend'
+  location: location
+  properties: {
+    managedEnvironmentId: env.id
+    configuration: {
+      ingress: {
+        external: true
+        targetPort: 80
+      }
+    }
+    template: {
+      containers: [
+        {
+          name: 'frontend'
+          image: 'nginx:latest'
+          env: [
+            { name: 'API_URL', value: 'http://${api.properties.configuration.ingress.fqdn}' }
+          ]
+        }
+      ]
+    }
+  }
+}
+
+module monitoring './monitoring.bicep' = {
+  name: 'monitoring'
+}
+
+output url string = frontend.properties.configuration.ingress.fqdn
+"#;
+
+    #[test]
+    fn test_get_constants() {
+        let statements = get_statements(TEMPLATE);
+
+        let output = get_constants(TEMPLATE, &statements, &BTreeMap::new());
+
+        // The location is only known at deployment and the password has no default value
+        assert_eq!(None, output.get("location"));
+        assert_eq!(None, output.get("registryPassword"));
+        assert_eq!(
+            Some(&Value::String(
+                "myregistry.azurecr.io/shop-api:v1".to_string()
+            )),
+            output.get("apiImage")
+        );
+        assert_eq!(
+            Some(&serde_json::json!({ "port": 3000, "replicas": 2 })),
+            output.get("settings")
+        );
+
+        // The parameters file takes precedence over the default values
+        let parameters = BTreeMap::from([
+            ("tag".to_string(), Value::String("v2".to_string())),
+            (
+                "registryPassword".to_string(),
+                Value::String("secret".to_string()),
+            ),
+        ]);
+
+        let output = get_constants(TEMPLATE, &statements, &parameters);
+
+        assert_eq!(
+            Some(&Value::String(
+                "myregistry.azurecr.io/shop-api:v2".to_string()
+            )),
+            output.get("apiImage")
+        );
+        assert_eq!(
+            Some(&Value::String("secret".to_string())),
+            output.get("registryPassword")
+        );
+    }
+
+    #[test]
+    fn test_get_apps() {
+        let output = get_apps(TEMPLATE, &BuildOptions::default()).unwrap();
+
+        assert_eq!(2, output.len());
+
+        let api = &output[0];
+        let dapr = api.configuration.as_ref().unwrap().dapr.as_ref().unwrap();
+        let container = &api.template.as_ref().unwrap().containers.as_ref().unwrap()[0];
+        assert_eq!(Some("shop-api".to_string()), api.name);
+        assert_eq!(Some("westeurope".to_string()), api.location);
+        assert_eq!(Some("v20230501".to_string()), api.api_version);
+        assert_eq!((Some(true), Some(3000)), (dapr.enabled, dapr.app_port));
+        assert_eq!("myregistry.azurecr.io/shop-api:v1", container.image);
+
+        // The location parameter has no static value
+        let frontend = &output[1];
+        let ingress = frontend
+            .configuration
+            .as_ref()
+            .unwrap()
+            .ingress
+            .as_ref()
+            .unwrap();
+        assert_eq!(Some("frontend".to_string()), frontend.name);
+        assert_eq!(None, frontend.location);
+        assert_eq!(
+            (Some(true), Some(80)),
+            (ingress.external, ingress.target_port)
+        );
+    }
+
+    #[test]
+    fn test_get_apps_with_conditions_and_loops() {
+        let input = r#"
+param deployApi bool = true
+param names array = [
+  'first'
+  'second'
+]
+
+resource api 'microsoft.app/containerapps@2024-03-01' = if (deployApi) {
+  name: deployApi ? 'api' : 'disabled'
+  properties: {
+    template: {
+      containers: [{ name: 'api', image: 'node:20' }]
+    }
+  }
+}
+
+resource workers 'Microsoft.App/containerApps@2023-05-01' = [for name in names: {
+  name: name
+  properties: {}
+}]
+
+resource existingApp 'Microsoft.App/containerApps@2023-05-01' existing = {
+  name: 'legacy'
+}
+"#;
+
+        let output = get_apps(input, &BuildOptions::default()).unwrap();
+
+        assert_eq!(1, output.len());
+        assert_eq!(Some("api".to_string()), output[0].name);
+
+        assert_eq!(
+            vec!["ignored resource workers, resources created in a loop are not supported"],
+            collect_warnings(input, &BuildOptions::default())
+        );
+    }
+
+    #[test]
+    fn test_parser_errors() {
+        let input = "resource api 'Microsoft.App/containerApps@2023-05-01' = {\n  name: 'api\n}";
+
+        assert_eq!(
+            Err(DeserializeError::Parse(
+                "Unterminated string at line 3".to_string()
+            )),
+            get_apps(input, &BuildOptions::default())
+        );
+    }
+
+    #[test]
+    fn test_collect_warnings() {
+        let output = collect_warnings(TEMPLATE, &BuildOptions::default());
+
+        let expected = vec![
+            "ignored module monitoring (./monitoring.bicep), modules are not supported",
+            "2 replicas of shop-api are not run, its Dapr sidecar cannot be replicated",
+        ];
+
+        assert_eq!(expected, output);
+    }
+
+    #[test]
+    fn test_deserialize() {
+        let output = deserialize(TEMPLATE, &BuildOptions::default()).unwrap();
+
+        let services: Vec<(&str, Option<&str>)> = output
+            .iter()
+            .map(|service| (service.name.as_str(), service.image.as_deref()))
+            .collect();
+
+        assert_eq!(
+            vec![
+                ("api", Some("myregistry.azurecr.io/shop-api:v1")),
+                ("api_dapr", Some("daprio/daprd:edge")),
+                ("frontend", Some("nginx:latest")),
+            ],
+            services
+        );
+        assert_eq!(Some(vec!["80:80".to_string()]), output[2].ports);
+    }
+
+    #[test]
+    fn test_deserialize_dapr_without_enabled() {
+        let template = TEMPLATE.replace(
+            "        enabled: true\n        appId: 'api'",
+            "        appId: 'api'",
+        );
+        let output = deserialize(&template, &BuildOptions::default()).unwrap();
+
+        let names: Vec<&str> = output.iter().map(|service| service.name.as_str()).collect();
+        // Dapr is disabled by default, as in Container Apps
+        assert_eq!(vec!["api", "frontend"], names);
+    }
+}
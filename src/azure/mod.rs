@@ -0,0 +1,101 @@
+#[cfg(feature = "arm")]
+pub mod arm;
+#[cfg(feature = "bicep")]
+pub mod bicep;
+use crate::error::DeserializeError;
+use crate::serializer::{BuildOptions, ContainerAppConfiguration, Language, Serializer};
+use log::error;
+use std::collections::BTreeMap;
+
+// Logs of the Azure parsers, whatever their language
+pub(crate) const LOG_TARGET: &str = "capp_s::azure";
+
+pub struct Azure {
+    language: Language,
+    options: BuildOptions,
+    pub resources: Option<Vec<ContainerAppConfiguration>>,
+    apps: Vec<(String, Vec<ContainerAppConfiguration>)>,
+    warnings: Vec<String>,
+}
+
+impl Azure {
+    pub fn new(language: Language) -> Option<Azure> {
+        let is_supported = (cfg!(feature = "arm") && matches!(language, Language::Arm))
+            || (cfg!(feature = "bicep") && matches!(language, Language::Bicep));
+
+        is_supported.then(|| Azure {
+            language,
+            options: BuildOptions::default(),
+            resources: None,
+            apps: vec![],
+            warnings: vec![],
+        })
+    }
+
+    pub fn with_options(mut self, options: BuildOptions) -> Azure {
+        self.options = options;
+        self
+    }
+
+    /// Services of the last deserialized input by app, for one compose file per app
+    pub fn apps(&self) -> &[(String, Vec<ContainerAppConfiguration>)] {
+        &self.apps
+    }
+
+    /// Parameters without value and declarations of the last deserialized input which are not translated
+    pub fn warnings(&self) -> &[String] {
+        &self.warnings
+    }
+}
+
+/***
+ * Values of a deployment parameters file (eg: azuredeploy.parameters.json), by parameter name.
+ * The Key Vault references are secrets, they stay unresolved
+ */
+pub fn parse_parameters(
+    input: &str,
+) -> Result<BTreeMap<String, serde_json::Value>, DeserializeError> {
+    let value: serde_json::Value =
+        serde_json::from_str(input).map_err(|e| DeserializeError::Parse(e.to_string()))?;
+
+    Ok(value
+        .get("parameters")
+        .and_then(serde_json::Value::as_object)
+        .into_iter()
+        .flatten()
+        .filter_map(|(name, parameter)| Some((name.clone(), parameter.get("value")?.clone())))
+        .collect())
+}
+
+impl Serializer for Azure {
+    type Output = Azure;
+    #[cfg_attr(not(any(feature = "arm", feature = "bicep")), allow(unused_variables))]
+    fn deserialize_value(&mut self, input: &str) -> Result<&Self, DeserializeError> {
+        match self.language {
+            #[cfg(feature = "arm")]
+            Language::Arm => match arm::deserialize_per_app(input, &self.options) {
+                Ok(value) => {
+                    self.resources = Some(crate::pulumi::flatten_apps(value.clone()));
+                    self.apps = value;
+                    self.warnings = arm::collect_warnings(input, &self.options);
+                    Ok(self)
+                }
+                Err(err) => Err(err),
+            },
+            #[cfg(feature = "bicep")]
+            Language::Bicep => match bicep::deserialize_per_app(input, &self.options) {
+                Ok(value) => {
+                    self.resources = Some(crate::pulumi::flatten_apps(value.clone()));
+                    self.apps = value;
+                    self.warnings = bicep::collect_warnings(input, &self.options);
+                    Ok(self)
+                }
+                Err(err) => Err(err),
+            },
+            _ => {
+                error!(target: LOG_TARGET, "Language not supported");
+                Err(DeserializeError::UnsupportedLanguage)
+            }
+        }
+    }
+}
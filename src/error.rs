@@ -0,0 +1,151 @@
+use std::fmt;
+
+pub type Result<T> = std::result::Result<T, DeserializeError>;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DeserializeError {
+    /// The input could not be parsed in the given language
+    Parse(String),
+    /// The language is not handled by the provider
+    UnsupportedLanguage,
+    /// No container could be extracted from the input
+    NoContainer,
+    /// A shell-form command could not be split into arguments
+    InvalidCommand(String),
+    /// Several containers share a service name once sanitized, with their original names
+    DuplicateContainer {
+        name: String,
+        containers: Vec<String>,
+    },
+    /// Two images share a reference name, the reference is ambiguous
+    DuplicateImageReference(String),
+    /// A key of the input is not known, only raised in strict mode
+    UnknownField(String),
+    /// Several services bind the same host port
+    PortConflict { port: String, services: Vec<String> },
+    /// A Dapr option expecting a positive integer got another value
+    InvalidDaprOption(String),
+    /// The image of a container references no known image resource
+    UnresolvedImage {
+        container: String,
+        reference: String,
+    },
+    /// A network, volume or `.env` key is declared twice with different definitions
+    ConflictingDeclaration(String),
+    /// A port is out of the 1-65535 range
+    InvalidPort { field: String, value: u32 },
+    /// A Dapr-enabled app has no `appPort` for its sidecar
+    MissingDaprAppPort(String),
+    /// A service sets both `network_mode` and `networks`, compose rejects it
+    NetworkModeConflict(String),
+    /// The input declares no container app resource (eg: a stack export of another stack)
+    NoContainerApp,
+    /// A file of the input (eg: an imported module) does not exist or cannot be read
+    MissingFile(String),
+    /// Two inputs declare a resource of the same name, by position of the inputs
+    DuplicateResource {
+        name: String,
+        inputs: (usize, usize),
+    },
+    /// The language of an input is neither given nor detected, with the hints which were tried
+    UndetectedLanguage(Vec<String>),
+}
+
+impl fmt::Display for DeserializeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DeserializeError::Parse(message) => write!(f, "{}", message),
+            DeserializeError::UnsupportedLanguage => write!(f, "Language not supported"),
+            DeserializeError::NoContainer => write!(f, "No container to deserialize"),
+            DeserializeError::InvalidCommand(command) => {
+                write!(f, "Malformed quoting in command: {}", command)
+            }
+            DeserializeError::DuplicateContainer { name, containers } => write!(
+                f,
+                "Container name {} is declared more than once: {}",
+                name,
+                containers.join(", ")
+            ),
+            DeserializeError::DuplicateImageReference(name) => {
+                write!(f, "Image reference is declared more than once: {}", name)
+            }
+            DeserializeError::UnknownField(path) => write!(f, "Unknown field: {}", path),
+            DeserializeError::PortConflict { port, services } => write!(
+                f,
+                "Host port {} is bound by several services: {}",
+                port,
+                services.join(", ")
+            ),
+            DeserializeError::InvalidDaprOption(option) => {
+                write!(f, "Dapr option must be a positive integer: {}", option)
+            }
+            DeserializeError::UnresolvedImage {
+                container,
+                reference,
+            } => write!(
+                f,
+                "Image {} of container {} could not be resolved",
+                reference, container
+            ),
+            DeserializeError::ConflictingDeclaration(name) => {
+                write!(f, "Declaration has conflicting definitions: {}", name)
+            }
+            DeserializeError::InvalidPort { field, value } => {
+                write!(f, "Port {} must be between 1 and 65535: {}", field, value)
+            }
+            DeserializeError::MissingDaprAppPort(name) => {
+                write!(f, "Dapr is enabled without appPort for app: {}", name)
+            }
+            DeserializeError::NetworkModeConflict(name) => {
+                write!(f, "Service sets both network_mode and networks: {}", name)
+            }
+            DeserializeError::NoContainerApp => {
+                write!(f, "No container app resource in the input")
+            }
+            DeserializeError::MissingFile(path) => write!(f, "File not found: {}", path),
+            DeserializeError::DuplicateResource { name, inputs } => write!(
+                f,
+                "Resource {} is declared by the inputs {} and {}",
+                name, inputs.0, inputs.1
+            ),
+            DeserializeError::UndetectedLanguage(hints) => {
+                write!(f, "Language cannot be detected, tried {}", hints.join(", "))
+            }
+        }
+    }
+}
+
+impl std::error::Error for DeserializeError {}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ResolveError {
+    /// The image is neither a literal image nor a valid reference
+    UnparseableImage(String),
+    /// The image references no known image resource
+    ReferenceNotFound(String),
+}
+
+impl fmt::Display for ResolveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ResolveError::UnparseableImage(image) => write!(f, "Image cannot be parsed: {}", image),
+            ResolveError::ReferenceNotFound(image) => {
+                write!(f, "Image reference not found: {}", image)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ResolveError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_boxed_error() {
+        let boxed: Box<dyn std::error::Error> = Box::new(DeserializeError::NoContainer);
+
+        assert_eq!("No container to deserialize", boxed.to_string());
+    }
+}
@@ -0,0 +1,7 @@
+pub mod azure;
+pub mod error;
+pub mod pulumi;
+pub mod serializer;
+pub mod terraform;
+
+pub use error::{DeserializeError, ResolveError, Result};
@@ -1,14 +1,28 @@
-pub mod pulumi;
-pub mod serializer;
-
 use clap::{Parser, ValueEnum};
 
-use log::{error, info};
-use pulumi::Pulumi;
-use serializer::{Language, Serializer};
-use std::{fs, path::Path};
+use capp_s::azure::{parse_parameters, Azure};
+use capp_s::pulumi::{find_project_entry, parse_stack_config, parse_stack_outputs, Pulumi};
+use capp_s::serializer::{
+    to_app_compose_yaml_with_options, to_compose_yaml_with_options, to_env_example, to_json,
+    to_k8s, to_shared_compose_yaml_with_options, AzureFilesMode, BuildOptions, CommandStyle,
+    ContainerAppConfiguration, DaprRuntimeOptions, Language, SecretsMode, SerializeOptions,
+    Serializer,
+};
+use capp_s::terraform::Terraform;
+use flate2::read::GzDecoder;
+use log::{error, info, warn};
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::{self, Read},
+    path::Path,
+    thread,
+    time::{Duration, SystemTime},
+};
 
-const FILENAME: &str = "docker-compose.yml";
+const GZIP_MAGIC_BYTES: [u8; 2] = [0x1f, 0x8b];
+// Input read from the standard input (eg: pulumi stack export | capp_s pulumi -l json -i - -o .)
+const STDIN: &str = "-";
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Args {
@@ -16,12 +30,146 @@ struct Args {
     #[arg(value_enum)]
     provider: Provider,
 
-    /// input file to convert
-    #[arg(short, long)]
+    /// input file to convert, `-` reads the standard input
+    #[arg(short, long, required_unless_present = "project", default_value = "")]
     input: String,
+    /// Language of the input, detected from its extension when not given (eg: yaml, ts, json)
+    #[arg(short, long)]
+    language: Option<String>,
+    /// Pulumi project folder, the input is the entry point of its Pulumi.yaml (eg: ./infra)
+    #[arg(long, conflicts_with = "input")]
+    project: Option<String>,
     // Output folder
     #[arg(short, long)]
     output: String,
+    /// Dapr sidecar image used when an app does not pin one (eg: daprio/daprd:1.13.0)
+    #[arg(long)]
+    dapr_image: Option<String>,
+    /// Folder prefixed to the resolved build contexts (eg: the folder of the input file)
+    #[arg(long)]
+    base_path: Option<String>,
+    /// Fail on unknown fields instead of ignoring them
+    #[arg(long)]
+    strict: bool,
+    /// Only serialize the given apps (eg: --include frontend,backend)
+    #[arg(long, value_delimiter = ',')]
+    include: Option<Vec<String>>,
+    /// Restart policy of the app services (eg: always), defaults to unless-stopped
+    #[arg(long)]
+    restart: Option<String>,
+    /// Stack name used to resolve ${pulumi.stack} tokens
+    #[arg(long)]
+    stack: Option<String>,
+    /// Stack config file resolving the config variables, defaults to the Pulumi.<stack>.yaml
+    /// file next to the input or in the project folder
+    #[arg(long)]
+    config: Option<String>,
+    /// Parameters file resolving the parameters of ARM templates and Bicep files
+    /// (eg: azuredeploy.parameters.json)
+    #[arg(long)]
+    parameters: Option<String>,
+    /// Outputs file of the referenced stacks resolving their outputs
+    /// (eg: pulumi stack output --json > outputs.json)
+    #[arg(long)]
+    stack_outputs: Option<String>,
+    /// Serialize the apps without their Dapr sidecars
+    #[arg(long)]
+    disable_dapr: bool,
+    /// Re-run the conversion whenever the input file changes
+    #[arg(long)]
+    watch: bool,
+    /// Polling interval in milliseconds used by --watch
+    #[arg(long, default_value_t = 500)]
+    poll_interval: u64,
+    /// Output format
+    #[arg(long, value_enum, default_value_t = Format::Compose)]
+    format: Format,
+    /// Write one compose file per app (eg: docker-compose.frontend.yml) along the shared one
+    #[arg(long)]
+    split: bool,
+    /// How the services read the secrets of their app
+    #[arg(long, value_enum, default_value_t = Secrets::Env)]
+    secrets: Secrets,
+    /// How the Azure Files shares are mounted in the services
+    #[arg(long, value_enum, default_value_t = AzureFiles::Volume)]
+    azure_files: AzureFiles,
+    /// How the commands of the compose services are written
+    #[arg(long, value_enum, default_value_t = Commands::Auto)]
+    command_style: Commands,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug, ValueEnum)]
+enum Format {
+    /// Docker compose file
+    Compose,
+    /// Compose configuration as JSON
+    Json,
+    /// Kubernetes manifests
+    K8s,
+}
+
+impl Format {
+    fn filename(&self) -> &'static str {
+        match self {
+            Format::Compose => "docker-compose.yml",
+            Format::Json => "docker-compose.json",
+            Format::K8s => "k8s.yml",
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug, ValueEnum)]
+enum Secrets {
+    /// Variables of the host, listed in a generated .env.example file
+    Env,
+    /// Compose secrets read from the ./secrets/<app>/<secret> files
+    Files,
+}
+
+impl From<Secrets> for SecretsMode {
+    fn from(secrets: Secrets) -> Self {
+        match secrets {
+            Secrets::Env => SecretsMode::Env,
+            Secrets::Files => SecretsMode::Files,
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug, ValueEnum)]
+enum AzureFiles {
+    /// Named volumes of the storage, shared by the apps
+    Volume,
+    /// Folders of the host (eg: ./volumes/<storage>)
+    Bind,
+}
+
+impl From<AzureFiles> for AzureFilesMode {
+    fn from(azure_files: AzureFiles) -> Self {
+        match azure_files {
+            AzureFiles::Volume => AzureFilesMode::Volume,
+            AzureFiles::Bind => AzureFilesMode::Bind,
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug, ValueEnum)]
+enum Commands {
+    /// On one line up to 3 arguments, one argument per line for the longer ones
+    Auto,
+    /// One argument per line
+    Block,
+    /// On one line (eg: command: ["npm", "start"])
+    Flow,
+}
+
+impl From<Commands> for CommandStyle {
+    fn from(commands: Commands) -> Self {
+        match commands {
+            Commands::Auto => CommandStyle::Auto,
+            Commands::Block => CommandStyle::Block,
+            Commands::Flow => CommandStyle::Flow,
+        }
+    }
 }
 
 #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug, ValueEnum)]
@@ -35,69 +183,398 @@ enum Provider {
 }
 
 fn parse_language(filename: &str) -> Language {
-    let language = Path::new(filename).extension().and_then(|val| val.to_str());
+    // Compressed inputs are detected from the extension before `.gz`
+    let filename = filename.strip_suffix(".gz").unwrap_or(filename);
+
+    parse_extension(Path::new(filename).extension().and_then(|val| val.to_str()))
+}
 
+fn parse_extension(language: Option<&str>) -> Language {
     match language {
         Some("yml" | "yaml") => Language::Yaml,
         Some("ts") => Language::Typescript,
+        Some("py") => Language::Python,
+        Some("cs") => Language::CSharp,
+        Some("go") => Language::Go,
         Some("bicep") => Language::Bicep,
-        Some("json") => Language::Json,
+        Some("json") => Language::Arm,
+        Some("tf") => Language::Hcl,
         _ => Language::NotSupported,
     }
 }
 
-fn main() {
-    simple_logger::init().unwrap();
-    let args = Args::parse();
+fn write_output_file(output: &str, path: &str, content: Vec<u8>) {
+    if Path::new(path).exists() {
+        let old_file = fs::read_to_string(Path::new(path));
+        // eg: docker-compose.yml is dumped to docker-compose.old.yml
+        let old_file_name = match Path::new(path).file_name().and_then(|x| x.to_str()) {
+            Some(name) => match name.rsplit_once('.') {
+                Some((stem, extension)) => format!("{}.old.{}", stem, extension),
+                None => format!("{}.old", name),
+            },
+            None => "output.old".to_string(),
+        };
+        let old_file_path = format!("{}/{}", output, old_file_name);
 
-    info!("Starting...");
+        match fs::write(old_file_path, old_file.unwrap()) {
+            Ok(_r) => {
+                info!("Previous output file dumped to >> {}", old_file_name)
+            }
+            Err(e) => error!("{}", e),
+        };
+    }
+
+    fs::write(path, content).unwrap();
+
+    info!("Completed!")
+}
+
+/***
+ * Serialize the services to the selected format
+ */
+fn serialize(
+    format: Format,
+    services: &[ContainerAppConfiguration],
+    options: &SerializeOptions,
+) -> Result<Vec<u8>, String> {
+    match format {
+        Format::Compose => to_compose_yaml_with_options(services, options)
+            .map(String::into_bytes)
+            .map_err(|e| e.to_string()),
+        Format::Json => to_json(services)
+            .map(String::into_bytes)
+            .map_err(|e| e.to_string()),
+        Format::K8s => to_k8s(services)
+            .map(String::into_bytes)
+            .map_err(|e| e.to_string()),
+    }
+}
+
+/***
+ * Serialize each app to its own compose file, the placement service
+ * and the Dapr network go to the default one
+ */
+fn serialize_per_app(
+    format: Format,
+    apps: &[(String, Vec<ContainerAppConfiguration>)],
+    options: &SerializeOptions,
+) -> Result<Vec<(String, Vec<u8>)>, String> {
+    if format != Format::Compose {
+        return Err("--split is only supported by the compose format".to_string());
+    }
+
+    let mut files = vec![(
+        format.filename().to_string(),
+        to_shared_compose_yaml_with_options(options)
+            .map_err(|e| e.to_string())?
+            .into_bytes(),
+    )];
+
+    for (name, services) in apps {
+        files.push((
+            format!("docker-compose.{}.yml", name),
+            to_app_compose_yaml_with_options(services, options)
+                .map_err(|e| e.to_string())?
+                .into_bytes(),
+        ));
+    }
 
-    let file = fs::read_to_string(&args.input);
-    let path = format!("{}/{}", args.output, FILENAME);
+    Ok(files)
+}
+
+/***
+ * Output files of the services, with the `.env.example` of the variables
+ * they read from the host (eg: the secrets with `--secrets env`)
+ */
+fn serialize_files(
+    args: &Args,
+    apps: &[(String, Vec<ContainerAppConfiguration>)],
+    services: &[ContainerAppConfiguration],
+) -> Result<Vec<(String, Vec<u8>)>, String> {
+    let options = SerializeOptions {
+        command_style: args.command_style.into(),
+    };
+    let mut files = match args.split {
+        true => serialize_per_app(args.format, apps, &options)?,
+        false => vec![(
+            args.format.filename().to_string(),
+            serialize(args.format, services, &options)?,
+        )],
+    };
+    let env_example = to_env_example(services);
+
+    // Kubernetes manifests do not read the host variables
+    if args.format != Format::K8s && !env_example.is_empty() {
+        files.push((".env.example".to_string(), env_example.into_bytes()));
+    }
+
+    Ok(files)
+}
+
+/***
+ * Values of the stack config file, none when the file is not given nor found
+ */
+fn read_stack_config(args: &Args) -> Result<BTreeMap<String, serde_yaml::Value>, String> {
+    let path = args.config.clone().or_else(|| {
+        let file_name = format!("Pulumi.{}.yaml", args.stack.as_ref()?);
+        // Stack files are next to the project file, the entry point may be in a sub folder
+        let path = match &args.project {
+            Some(project) => Path::new(project).join(file_name),
+            None => Path::new(&args.input).with_file_name(file_name),
+        };
+        path.is_file().then(|| path.display().to_string())
+    });
+
+    match path {
+        Some(path) => {
+            let input = fs::read_to_string(&path).map_err(|e| format!("{}: {}", path, e))?;
+            parse_stack_config(&input).map_err(|e| e.to_string())
+        }
+        None => Ok(BTreeMap::new()),
+    }
+}
+
+/***
+ * Values of the parameters file of the ARM template or Bicep file, none when not given
+ */
+fn read_parameters(args: &Args) -> Result<BTreeMap<String, serde_json::Value>, String> {
+    match &args.parameters {
+        Some(path) => {
+            let input = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+            parse_parameters(&input).map_err(|e| e.to_string())
+        }
+        None => Ok(BTreeMap::new()),
+    }
+}
+
+/***
+ * Values of the stack outputs file, none when not given
+ */
+fn read_stack_outputs(args: &Args) -> Result<BTreeMap<String, String>, String> {
+    match &args.stack_outputs {
+        Some(path) => {
+            let input = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+            parse_stack_outputs(&input).map_err(|e| format!("{}: {}", path, e))
+        }
+        None => Ok(BTreeMap::new()),
+    }
+}
+
+/***
+ * Parse the input with the selected provider and serialize it to the selected format,
+ * returns the content of each output file by file name
+ */
+fn parse(args: &Args, input: &str) -> Result<Vec<(String, Vec<u8>)>, String> {
+    let language = match &args.language {
+        Some(language) => parse_extension(Some(language)),
+        None => parse_language(&args.input),
+    };
+    let is_stdin = args.input == STDIN;
+    let options = BuildOptions {
+        dapr: DaprRuntimeOptions {
+            sidecar_image: args.dapr_image.clone(),
+        },
+        base_path: args.base_path.clone(),
+        strict: args.strict,
+        include: args.include.clone(),
+        restart: args.restart.clone(),
+        tokens: args
+            .stack
+            .iter()
+            .map(|stack| ("pulumi.stack".to_string(), stack.clone()))
+            .collect(),
+        stack_outputs: read_stack_outputs(args)?,
+        config: read_stack_config(args)?,
+        parameters: read_parameters(args)?,
+        env_from: Default::default(),
+        disable_dapr: args.disable_dapr,
+        secrets: args.secrets.into(),
+        azure_files: args.azure_files.into(),
+        token_resolver: None,
+    };
+
+    match args.provider {
+        Provider::Pulumi => {
+            // JSON inputs of Pulumi are stack states (eg: pulumi stack export or pulumi preview --json)
+            // Other extensions are detected from the project file and the content of the input
+            let language = match language {
+                Language::Arm => Language::StackExport,
+                Language::NotSupported => Language::Auto,
+                language => language,
+            };
+            let mut provider = Pulumi::new(language)
+                .ok_or("Language is not supported for this provider")?
+                .with_options(options);
+
+            // The standard input has no path, nor files next to it
+            if !is_stdin {
+                provider = provider.with_path(Path::new(&args.input));
+            }
+
+            // Programs may be split in several files, compressed inputs are a single file
+            let value = match language {
+                Language::Typescript | Language::Javascript | Language::Auto
+                    if !args.input.ends_with(".gz") && !is_stdin =>
+                {
+                    provider.deserialize_project(Path::new(&args.input))
+                }
+                _ => provider.deserialize_value(input),
+            }
+            .map_err(|e| e.to_string())?;
+
+            for warning in value.warnings() {
+                warn!("{}", warning);
+            }
+
+            serialize_files(args, value.apps(), value.resources.as_ref().unwrap())
+        }
+        Provider::Azure => {
+            let mut provider = Azure::new(language)
+                .ok_or("Language is not supported for this provider")?
+                .with_options(options);
+
+            let value = provider
+                .deserialize_value(input)
+                .map_err(|e| e.to_string())?;
+
+            for warning in value.warnings() {
+                warn!("{}", warning);
+            }
+
+            serialize_files(args, value.apps(), value.resources.as_ref().unwrap())
+        }
+        Provider::Terraform => {
+            let mut provider = Terraform::new(language)
+                .ok_or("Language is not supported for this provider")?
+                .with_options(options);
+
+            let value = provider
+                .deserialize_value(input)
+                .map_err(|e| e.to_string())?;
+
+            for warning in value.warnings() {
+                warn!("{}", warning);
+            }
+
+            serialize_files(args, value.apps(), value.resources.as_ref().unwrap())
+        }
+    }
+}
+
+/***
+ * Read the input file or the standard input, decompressing it when gzipped,
+ * without its byte order mark
+ */
+fn read_input(path: &str) -> io::Result<String> {
+    let content = match path {
+        STDIN => {
+            let mut content = vec![];
+            io::stdin().read_to_end(&mut content)?;
+            content
+        }
+        path => fs::read(path)?,
+    };
+
+    let content = match content.starts_with(&GZIP_MAGIC_BYTES) {
+        true => {
+            let mut decompressed = String::new();
+            GzDecoder::new(content.as_slice()).read_to_string(&mut decompressed)?;
+            decompressed
+        }
+        false => {
+            String::from_utf8(content).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?
+        }
+    };
+
+    Ok(match content.strip_prefix('\u{feff}') {
+        Some(content) => content.to_string(),
+        None => content,
+    })
+}
+
+fn run(args: &Args) {
+    let file = read_input(&args.input).map_err(|e| match args.input.as_str() {
+        STDIN => format!("stdin: {}", e),
+        path => format!("{}: {}", path, e),
+    });
 
     match file {
-        Ok(file) => {
-            let language = parse_language(&args.input);
-
-            match args.provider {
-                Provider::Pulumi => {
-                    let mut provider =
-                        Pulumi::new(language).expect("Language is not supported for this provider");
-
-                    let value = provider
-                        .deserialize_value(&file)
-                        .expect("Deserialiazed value is defined");
-
-                    match value.serialize_value(&value.resources.as_ref().unwrap()) {
-                        Ok(v) => {
-                            if Path::new(&path).exists() {
-                                let old_file = fs::read_to_string(Path::new(&path));
-                                let old_file_path =
-                                    format!("{}/{}", args.output, "docker-compose.old.yml");
-
-                                match fs::write(old_file_path, old_file.unwrap()) {
-                                    Ok(_r) => {
-                                        info!("Previous compose file dumped to >> docker-compose.old.yml")
-                                    }
-                                    Err(e) => error!("{}", e),
-                                };
-                            }
-
-                            fs::write(&path, v).unwrap();
-
-                            info!("Completed!")
-                        }
-                        Err(e) => error!("{}", e),
+        Ok(file) => match parse(args, &file) {
+            Ok(files) => {
+                for (name, content) in files {
+                    if args.watch {
+                        println!("{}", String::from_utf8_lossy(&content));
                     }
+
+                    let path = format!("{}/{}", args.output, name);
+                    write_output_file(&args.output, &path, content)
                 }
-                Provider::Azure => todo!(),
-                Provider::Terraform => todo!(),
             }
-        }
+            Err(e) => error!("{}", e),
+        },
         Err(e) => error!("{}", e),
     }
 }
 
+fn get_file_state(path: &str) -> Option<(SystemTime, u64)> {
+    let metadata = fs::metadata(path).ok()?;
+
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+fn watch(args: &Args) {
+    let interval = Duration::from_millis(args.poll_interval);
+    let mut last_state = None;
+
+    info!("Watching {} for changes...", args.input);
+
+    loop {
+        let mut state = get_file_state(&args.input);
+
+        if state.is_some() && state != last_state {
+            // Debounce successive writes, wait for the file to be stable
+            loop {
+                thread::sleep(interval);
+                let next_state = get_file_state(&args.input);
+
+                if next_state == state {
+                    break;
+                }
+                state = next_state;
+            }
+
+            last_state = state;
+            run(args);
+        }
+
+        thread::sleep(interval);
+    }
+}
+
+fn main() {
+    simple_logger::init().unwrap();
+    let mut args = Args::parse();
+
+    info!("Starting...");
+
+    if let Some(project) = &args.project {
+        match find_project_entry(Path::new(project)) {
+            Ok((_, entry)) => args.input = entry.display().to_string(),
+            Err(e) => return error!("{}", e),
+        }
+    }
+
+    if args.watch && args.input == STDIN {
+        return error!("--watch cannot watch the standard input");
+    }
+
+    if args.watch {
+        watch(&args);
+    } else {
+        run(&args);
+    }
+}
+
 #[cfg(test)]
 mod tests {
     #[warn(dead_code)]
@@ -0,0 +1,906 @@
+use regex::Regex;
+use serde_json::{Map, Number, Value};
+
+use crate::error::DeserializeError;
+use crate::pulumi;
+use crate::serializer::{
+    BuildOptions, ContainerAppBluePrint, ContainerAppConfiguration, ContainerImageBluePrint,
+};
+
+/***
+ * Properties are PascalCase, the blueprint ones camelCase (eg: TargetPort)
+ */
+fn to_camel_case(name: &str) -> String {
+    let mut chars = name.chars();
+
+    match chars.next() {
+        Some(first) => first.to_lowercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+/***
+ * C# expressions of the resource arguments as JSON values.
+ * Object initializers are objects, collection initializers arrays (or objects for
+ * dictionaries), property accesses are `${...}` references and calls which cannot
+ * be evaluated (eg: output.Apply(name => ...)) are null
+ */
+struct Parser<'a> {
+    chars: Vec<char>,
+    position: usize,
+    constants: &'a Map<String, Value>,
+}
+
+impl<'a> Parser<'a> {
+    fn new(input: &str, position: usize, constants: &'a Map<String, Value>) -> Parser<'a> {
+        Parser {
+            chars: input.chars().collect(),
+            position,
+            constants,
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.position).copied()
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<char> {
+        self.chars.get(self.position + offset).copied()
+    }
+
+    fn line(&self) -> usize {
+        self.chars[..self.position.min(self.chars.len())]
+            .iter()
+            .filter(|c| **c == '\n')
+            .count()
+            + 1
+    }
+
+    fn error(&self, message: &str) -> String {
+        format!("{} at line {}", message, self.line())
+    }
+
+    fn skip_blanks(&mut self) {
+        while let Some(c) = self.peek() {
+            match (c, self.peek_at(1)) {
+                ('/', Some('/')) => {
+                    while !matches!(self.peek(), Some('\n') | None) {
+                        self.position += 1;
+                    }
+                }
+                ('/', Some('*')) => {
+                    self.position += 2;
+                    while self.peek().is_some()
+                        && !(self.peek() == Some('*') && self.peek_at(1) == Some('/'))
+                    {
+                        self.position += 1;
+                    }
+                    self.position += 2;
+                }
+                (c, _) if c.is_whitespace() => self.position += 1,
+                _ => break,
+            }
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        self.skip_blanks();
+
+        match self.peek() {
+            Some(c) if c == expected => {
+                self.position += 1;
+                Ok(())
+            }
+            Some(c) => Err(self.error(&format!("Expected `{}`, found `{}`", expected, c))),
+            None => Err(self.error(&format!("Expected `{}`", expected))),
+        }
+    }
+
+    fn parse_identifier(&mut self) -> String {
+        let mut identifier = String::new();
+
+        while let Some(c) = self.peek().filter(|c| c.is_alphanumeric() || *c == '_') {
+            identifier.push(c);
+            self.position += 1;
+        }
+
+        identifier
+    }
+
+    fn parse_value(&mut self) -> Result<Value, String> {
+        self.skip_blanks();
+
+        let value = match (self.peek(), self.peek_at(1)) {
+            (Some('{'), _) => self.parse_initializer()?,
+            (Some('('), _) => {
+                self.position += 1;
+                let value = self.parse_value()?;
+                self.expect(')')?;
+
+                value
+            }
+            (Some('"'), _) => Value::String(self.parse_string(false, false)?),
+            (Some('@'), Some('"')) => {
+                self.position += 1;
+                Value::String(self.parse_string(false, true)?)
+            }
+            (Some('$'), _) => {
+                self.position += 1;
+                let is_verbatim = self.peek() == Some('@');
+                if is_verbatim {
+                    self.position += 1;
+                }
+                Value::String(self.parse_string(true, is_verbatim)?)
+            }
+            (Some(c), _) if c.is_ascii_digit() || c == '-' => self.parse_number()?,
+            (Some(c), _) if c.is_alphabetic() || c == '_' => self.parse_name()?,
+            (Some(c), _) => return Err(self.error(&format!("Unexpected `{}`", c))),
+            (None, _) => return Err(self.error("Unexpected end of input")),
+        };
+
+        // String concatenations (eg: prefix + "-app")
+        self.skip_blanks();
+        if self.peek() == Some('+') {
+            self.position += 1;
+
+            return match (value, self.parse_value()?) {
+                (Value::String(left), Value::String(right)) => Ok(Value::String(left + &right)),
+                _ => Ok(Value::Null),
+            };
+        }
+
+        Ok(value)
+    }
+
+    /// Members (eg: { External = true }), items (eg: { new ContainerArgs { ... } })
+    /// or dictionary entries (eg: { { "key", "value" } } or { ["key"] = "value" })
+    fn parse_initializer(&mut self) -> Result<Value, String> {
+        let mut members = Map::new();
+        let mut items = vec![];
+        self.expect('{')?;
+
+        loop {
+            self.skip_blanks();
+            match self.peek() {
+                Some('}') => {
+                    self.position += 1;
+                    break;
+                }
+                Some('[') => {
+                    self.position += 1;
+                    let key = self.parse_value()?;
+                    self.expect(']')?;
+                    self.expect('=')?;
+                    let value = self.parse_value()?;
+
+                    members.insert(key.as_str().map_or(key.to_string(), String::from), value);
+                }
+                _ => {
+                    let start = self.position;
+                    let identifier = self.parse_identifier();
+                    self.skip_blanks();
+
+                    if !identifier.is_empty()
+                        && self.peek() == Some('=')
+                        && !matches!(self.peek_at(1), Some('=' | '>'))
+                    {
+                        self.position += 1;
+                        let value = self.parse_value()?;
+                        members.insert(to_camel_case(&identifier), value);
+                    } else {
+                        self.position = start;
+                        items.push(self.parse_value()?);
+                    }
+                }
+            }
+
+            self.skip_blanks();
+            match self.peek() {
+                Some(',') => self.position += 1,
+                Some('}') => {}
+                _ => return Err(self.error("Expected `,` or `}`")),
+            }
+        }
+
+        let is_dictionary = !items.is_empty()
+            && items.iter().all(|item| match item.as_array() {
+                Some(entry) => entry.len() == 2 && entry[0].is_string(),
+                None => false,
+            });
+
+        if is_dictionary {
+            for item in items.drain(..) {
+                if let Value::Array(mut entry) = item {
+                    let value = entry.pop().unwrap_or_default();
+                    let key = entry.pop().unwrap_or_default();
+
+                    members.insert(key.as_str().unwrap_or_default().to_string(), value);
+                }
+            }
+        }
+
+        // An empty initializer sets nothing
+        Ok(match (members.is_empty(), items.is_empty()) {
+            (true, true) => Value::Null,
+            (false, _) => Value::Object(members),
+            (true, false) => Value::Array(items),
+        })
+    }
+
+    fn parse_arguments(&mut self) -> Result<Vec<Value>, String> {
+        let mut arguments = vec![];
+        self.expect('(')?;
+
+        loop {
+            self.skip_blanks();
+            if self.peek() == Some(')') {
+                self.position += 1;
+                return Ok(arguments);
+            }
+
+            // Named arguments (eg: opts: new CustomResourceOptions { ... })
+            let start = self.position;
+            let identifier = self.parse_identifier();
+            self.skip_blanks();
+            if identifier.is_empty() || self.peek() != Some(':') {
+                self.position = start;
+            } else {
+                self.position += 1;
+            }
+
+            arguments.push(self.parse_value()?);
+
+            self.skip_blanks();
+            match self.peek() {
+                Some(',') => self.position += 1,
+                Some(')') => {}
+                _ => return Err(self.error("Expected `,` or `)`")),
+            }
+        }
+    }
+
+    /// Quoted strings, interpolated strings placeholders become `${...}` tokens
+    fn parse_string(&mut self, is_interpolated: bool, is_verbatim: bool) -> Result<String, String> {
+        self.expect('"')?;
+        let mut output = String::new();
+
+        loop {
+            match self.peek() {
+                None => return Err(self.error("Unterminated string")),
+                Some('"') if is_verbatim && self.peek_at(1) == Some('"') => {
+                    output.push('"');
+                    self.position += 2;
+                }
+                Some('"') => {
+                    self.position += 1;
+                    return Ok(output);
+                }
+                Some('\\') if !is_verbatim => {
+                    match self.peek_at(1) {
+                        Some('n') => output.push('\n'),
+                        Some('t') => output.push('\t'),
+                        Some(c) => output.push(c),
+                        None => return Err(self.error("Unterminated string")),
+                    }
+                    self.position += 2;
+                }
+                Some('{') if is_interpolated && self.peek_at(1) == Some('{') => {
+                    output.push('{');
+                    self.position += 2;
+                }
+                Some('}') if is_interpolated && self.peek_at(1) == Some('}') => {
+                    output.push('}');
+                    self.position += 2;
+                }
+                Some('{') if is_interpolated => {
+                    let mut expression = String::new();
+                    self.position += 1;
+
+                    while let Some(c) = self.peek().filter(|c| *c != '}') {
+                        expression.push(c);
+                        self.position += 1;
+                    }
+                    self.position += 1;
+
+                    // Format specs are dropped (eg: {port:D})
+                    let expression = expression.split(':').next().unwrap_or_default().trim();
+                    output.push_str(&format!("${{{}}}", expression));
+                }
+                Some(c) => {
+                    output.push(c);
+                    self.position += 1;
+                }
+            }
+        }
+    }
+
+    fn parse_number(&mut self) -> Result<Value, String> {
+        let mut number = String::new();
+
+        while let Some(c) = self
+            .peek()
+            .filter(|c| c.is_ascii_digit() || matches!(c, '-' | '.' | '_'))
+        {
+            number.push(c);
+            self.position += 1;
+        }
+
+        // Literal suffixes (eg: 0.5m, 1.0f, 3000L)
+        while self.peek().is_some_and(|c| "dDfFmMlLuU".contains(c)) {
+            self.position += 1;
+        }
+
+        let number = number.replace('_', "");
+
+        match number.parse::<i64>() {
+            Ok(number) => Ok(Value::Number(number.into())),
+            Err(_) => number
+                .parse::<f64>()
+                .ok()
+                .and_then(Number::from_f64)
+                .map(Value::Number)
+                .ok_or_else(|| self.error(&format!("Invalid number `{}`", number))),
+        }
+    }
+
+    fn skip_balanced(&mut self, open: char, close: char) -> Result<(), String> {
+        let mut depth = 0;
+
+        while let Some(c) = self.peek() {
+            match c {
+                '"' => {
+                    self.parse_string(false, false)?;
+                    continue;
+                }
+                c if c == open => depth += 1,
+                c if c == close => {
+                    depth -= 1;
+                    if depth == 0 {
+                        self.position += 1;
+                        return Ok(());
+                    }
+                }
+                _ => {}
+            }
+            self.position += 1;
+        }
+
+        Err(self.error(&format!("Expected `{}`", close)))
+    }
+
+    /// Members and calls chained to a skipped call (eg: .Apply(name => name))
+    fn skip_chain(&mut self) -> Result<(), String> {
+        loop {
+            match self.peek() {
+                Some('.') => {
+                    self.position += 1;
+                    self.parse_identifier();
+                }
+                Some('(') => self.skip_balanced('(', ')')?,
+                Some('[') => self.skip_balanced('[', ']')?,
+                _ => return Ok(()),
+            }
+        }
+    }
+
+    fn parse_dotted_name(&mut self) -> String {
+        let mut name = self.parse_identifier();
+
+        while self.peek() == Some('.')
+            && self
+                .peek_at(1)
+                .is_some_and(|c| c.is_alphabetic() || c == '_')
+        {
+            self.position += 1;
+            name.push('.');
+            name.push_str(&self.parse_identifier());
+        }
+
+        name
+    }
+
+    /// `new Type(...) { ... }`, `new[] { ... }` and `new() { ... }`
+    fn parse_new(&mut self) -> Result<Value, String> {
+        self.skip_blanks();
+
+        if self.peek() == Some('[') {
+            self.skip_balanced('[', ']')?;
+        } else {
+            self.parse_dotted_name();
+            self.skip_blanks();
+
+            // Generic arguments (eg: InputList<ContainerArgs>)
+            if self.peek() == Some('<') {
+                self.skip_balanced('<', '>')?;
+            }
+        }
+
+        self.skip_blanks();
+        if self.peek() == Some('(') {
+            self.skip_balanced('(', ')')?;
+        }
+
+        self.skip_blanks();
+        match self.peek() {
+            Some('{') => self.parse_initializer(),
+            // Constructors arguments are not evaluated
+            _ => Ok(Value::Null),
+        }
+    }
+
+    fn parse_name(&mut self) -> Result<Value, String> {
+        let name = self.parse_dotted_name();
+
+        match name.as_str() {
+            "true" => return Ok(Value::Bool(true)),
+            "false" => return Ok(Value::Bool(false)),
+            "null" => return Ok(Value::Null),
+            "new" => return self.parse_new(),
+            _ => {}
+        }
+
+        self.skip_blanks();
+        match self.peek() {
+            // Formatted outputs are their format (eg: Output.Format($"{server}/app"))
+            Some('(') if matches!(name.as_str(), "Output.Format" | "Output.Create") => {
+                let arguments = self.parse_arguments()?;
+                self.skip_chain()?;
+
+                Ok(arguments.into_iter().next().unwrap_or_default())
+            }
+            // The image reference of the outputs, as for pulumi.all in Typescript
+            Some('(') if name == "Output.Tuple" || name == "Output.All" => {
+                let arguments = self.parse_arguments()?;
+                self.skip_chain()?;
+
+                let references: Vec<&str> = arguments.iter().filter_map(|x| x.as_str()).collect();
+
+                Ok(references
+                    .iter()
+                    .find(|reference| reference.ends_with(".ImageName}"))
+                    .or(references.first())
+                    .map(|reference| Value::String(reference.to_string()))
+                    .unwrap_or_default())
+            }
+            // Applied outputs keep the reference of the output (eg: image.ImageName.Apply(...))
+            Some('(') if name.contains('.') && name.ends_with(".Apply") => {
+                self.skip_chain()?;
+
+                Ok(Value::String(format!(
+                    "${{{}}}",
+                    name.trim_end_matches(".Apply")
+                )))
+            }
+            Some('(') => {
+                self.skip_chain()?;
+
+                Ok(Value::Null)
+            }
+            // Indexers are kept as tokens (eg: stackRef.Outputs["imageName"])
+            Some('[') => {
+                let start = self.position;
+                self.skip_balanced('[', ']')?;
+                let indexer: String = self.chars[start..self.position].iter().collect();
+
+                Ok(Value::String(format!("${{{}{}}}", name, indexer)))
+            }
+            // Enum members are their name (eg: ManagedServiceIdentityType.SystemAssigned)
+            _ if name.contains('.') && name.starts_with(char::is_uppercase) => Ok(Value::String(
+                name.rsplit('.').next().unwrap_or_default().to_string(),
+            )),
+            _ if name.contains('.') => Ok(Value::String(format!("${{{}}}", name))),
+            _ => Ok(self
+                .constants
+                .get(&name)
+                .cloned()
+                .unwrap_or_else(|| Value::String(format!("${{{}}}", name)))),
+        }
+    }
+}
+
+fn char_position(input: &str, byte_position: usize) -> usize {
+    input[..byte_position].chars().count()
+}
+
+/***
+ * Local literals (eg: var appPort = 3000;), substituted where they are used
+ */
+fn get_constants(input: &str) -> Map<String, Value> {
+    let empty = Map::new();
+    let mut constants = Map::new();
+
+    for captures in Regex::new(
+        r"\b(?:var|const\s+\w+|int|string|bool|double)\s+(?P<name>[A-Za-z_]\w*)\s*=\s*[^=\s]",
+    )
+    .unwrap()
+    .captures_iter(input)
+    {
+        let start = char_position(input, captures.get(0).unwrap().end() - 1);
+        let value = Parser::new(input, start, &empty).parse_value();
+
+        match value {
+            Ok(Value::String(value)) if !value.contains("${") => {
+                constants.insert(captures["name"].to_string(), Value::String(value));
+            }
+            Ok(value @ (Value::Number(_) | Value::Bool(_))) => {
+                constants.insert(captures["name"].to_string(), value);
+            }
+            _ => {}
+        }
+    }
+
+    constants
+}
+
+// Variable, resource name and arguments of a resource
+type Declaration = (Option<String>, String, Value);
+
+/***
+ * Resources created with the given constructor (eg: new ContainerApp("myapp", new ContainerAppArgs { ... }))
+ */
+fn get_declarations(input: &str, constructor: &str) -> Result<Vec<Declaration>, DeserializeError> {
+    let constants = get_constants(input);
+    let re = Regex::new(&format!(
+        r"(?:(?P<variable>[A-Za-z_]\w*)\s*=\s*)?new\s+(?:[A-Za-z_]\w*\.)*{}\s*\(",
+        constructor
+    ))
+    .unwrap();
+
+    re.captures_iter(input)
+        .map(|captures| {
+            let start = char_position(input, captures.get(0).unwrap().end() - 1);
+            let mut parser = Parser::new(input, start, &constants);
+            let mut arguments = parser
+                .parse_arguments()
+                .map_err(DeserializeError::Parse)?
+                .into_iter();
+            let variable = captures.name("variable").map(|x| x.as_str().to_string());
+            let name = arguments
+                .next()
+                .and_then(|name| name.as_str().map(|name| name.to_string()))
+                .or(variable.clone())
+                .unwrap_or_default();
+
+            Ok((variable, name, arguments.next().unwrap_or_default()))
+        })
+        .collect()
+}
+
+fn get_images(input: &str) -> Result<Vec<ContainerImageBluePrint>, DeserializeError> {
+    let images = get_declarations(input, "Image")?
+        .into_iter()
+        .filter_map(|(variable, name, mut arguments)| {
+            let properties = arguments.as_object_mut()?;
+
+            if let Some(name) = properties.remove("imageName") {
+                properties.insert("name".to_string(), name);
+            }
+
+            // Images without build context are remote ones, they are not referenced
+            let mut image: ContainerImageBluePrint = serde_json::from_value(arguments).ok()?;
+            // An unassigned image is referenced by its resource name
+            image.reference_name = variable
+                .clone()
+                .or(Some(name).filter(|name| !name.is_empty()));
+            image.name = image.name.or(variable);
+
+            Some(image)
+        })
+        .collect();
+
+    Ok(images)
+}
+
+fn get_app_properties(input: &str) -> Result<Vec<(String, Value)>, DeserializeError> {
+    Ok(get_declarations(input, "ContainerApp")?
+        .into_iter()
+        .map(|(_, name, arguments)| (name, arguments))
+        .collect())
+}
+
+fn get_apps(input: &str) -> Result<Vec<ContainerAppBluePrint>, DeserializeError> {
+    get_app_properties(input)?
+        .into_iter()
+        .map(|(name, properties)| {
+            let mut app: ContainerAppBluePrint = serde_json::from_value(properties)
+                .map_err(|e| DeserializeError::Parse(e.to_string()))?;
+            app.name = Some(name);
+            pulumi::normalize_scale(&mut app);
+
+            Ok(app)
+        })
+        .collect()
+}
+
+fn as_yaml_properties(properties: &Value) -> Result<serde_yaml::Value, DeserializeError> {
+    serde_yaml::to_value(properties).map_err(|e| DeserializeError::Parse(e.to_string()))
+}
+
+/***
+ * Fields of the apps which are not translated, the input is expected to be valid
+ */
+pub fn collect_warnings(input: &str) -> Vec<String> {
+    get_app_properties(input)
+        .unwrap_or_default()
+        .into_iter()
+        .flat_map(|(name, properties)| {
+            let properties = as_yaml_properties(&properties).unwrap_or_default();
+
+            pulumi::collect_ignored_fields(&properties)
+                .unwrap_or_default()
+                .into_iter()
+                .map(move |path| format!("ignored {} in {}", path, name))
+        })
+        .collect()
+}
+
+pub fn deserialize(
+    input: &str,
+    options: &BuildOptions,
+) -> Result<Vec<ContainerAppConfiguration>, DeserializeError> {
+    deserialize_per_app(input, options).map(pulumi::flatten_apps)
+}
+
+/***
+ * Services of each app, see `pulumi::build_per_app`
+ */
+pub fn deserialize_per_app(
+    input: &str,
+    options: &BuildOptions,
+) -> Result<Vec<(String, Vec<ContainerAppConfiguration>)>, DeserializeError> {
+    let (apps, images) = deserialize_blueprints(input, options)?;
+
+    pulumi::build_per_app(apps, images, options.include.as_deref(), options)
+}
+
+/***
+ * Apps and images of the input, their references are not resolved yet
+ */
+pub fn deserialize_blueprints(
+    input: &str,
+    options: &BuildOptions,
+) -> Result<pulumi::BluePrints, DeserializeError> {
+    if options.strict {
+        for (_, properties) in get_app_properties(input)? {
+            pulumi::validate_known_fields(&as_yaml_properties(&properties)?)?;
+        }
+    }
+
+    let images = get_images(input)?;
+    let apps = get_apps(input)?;
+
+    Ok((apps, images))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::serializer::BuildContextBluePrint;
+
+    use super::*;
+
+    const PROGRAM: &str = r#"
+using System.Collections.Generic;
+using Pulumi;
+using Pulumi.AzureNative.App;
+using Pulumi.AzureNative.App.Inputs;
+using Pulumi.Docker;
+using Pulumi.Docker.Inputs;
+
+return await Deployment.RunAsync(() =>
+{
+    const int backendPort = 4000;
+
+    var resourceGroup = new Pulumi.AzureNative.Resources.ResourceGroup("rg");
+
+    var nodeImage = new Image("node-app", new ImageArgs
+    {
+        ImageName = Output.Format($"{registry.LoginServer}/node-app:v1.0.0"),
+        Build = new DockerBuildArgs { Context = "./node-app" },
+    });
+
+    var frontend = new ContainerApp("frontend", new ContainerAppArgs
+    {
+        ResourceGroupName = resourceGroup.Name,
+        ManagedEnvironmentId = managedEnv.Id,
+        Configuration = new ConfigurationArgs
+        {
+            Ingress = new IngressArgs { External = true, TargetPort = 3000 },
+        },
+        Template = new TemplateArgs
+        {
+            Containers =
+            {
+                new ContainerArgs
+                {
+                    Name = "frontend",
+                    Image = nodeImage.ImageName,
+                    // Not translated, but must not fail the parse
+                    Env = { new EnvironmentVarArgs { Name = "PORT", Value = "3000" } },
+                },
+            },
+        },
+    });
+
+    /* The backend is only reachable through Dapr */
+    var backend = new ContainerApp("backend", new ContainerAppArgs
+    {
+        ResourceGroupName = resourceGroup.Name,
+        Configuration = new ConfigurationArgs
+        {
+            Dapr = new DaprArgs { Enabled = true, AppId = "backend", AppPort = backendPort },
+        },
+        Identity = new ManagedServiceIdentityArgs
+        {
+            Type = ManagedServiceIdentityType.SystemAssigned,
+        },
+        Template = new TemplateArgs
+        {
+            Containers = new[]
+            {
+                new ContainerArgs { Name = "backend", Image = "node-12" },
+            },
+        },
+    });
+
+    return new Dictionary<string, object?>
+    {
+        ["url"] = frontend.LatestRevisionFqdn.Apply(fqdn => $"https://{fqdn}"),
+    };
+});
+"#;
+
+    #[test]
+    fn test_to_camel_case() {
+        assert_eq!("targetPort", to_camel_case("TargetPort"));
+        assert_eq!("appId", to_camel_case("AppId"));
+    }
+
+    #[test]
+    fn test_parse_value() {
+        let constants = Map::new();
+        let parse = |input: &str| Parser::new(input, 0, &constants).parse_value();
+
+        assert_eq!(
+            Ok(serde_json::json!({ "external": true, "targetPort": 3000 })),
+            parse("new IngressArgs { External = true, TargetPort = 3000 }")
+        );
+        assert_eq!(
+            Ok(serde_json::json!([{ "name": "myapp" }])),
+            parse("new InputList<ContainerArgs> { new ContainerArgs { Name = \"myapp\" } }")
+        );
+        assert_eq!(
+            Ok(serde_json::json!({ "redisHost": "redis:6379" })),
+            parse(r#"{ { "redisHost", "redis:6379" } }"#)
+        );
+        assert_eq!(
+            Ok(Value::String("${registry.LoginServer}/app:v1".to_string())),
+            parse(r#"$"{registry.LoginServer}/app:v1""#)
+        );
+        assert_eq!(
+            Ok(Value::String("${nodeImage.ImageName}".to_string())),
+            parse("nodeImage.ImageName.Apply(name => $\"{name}\")")
+        );
+        assert_eq!(Ok(serde_json::json!(0.5)), parse("0.5m"));
+
+        assert!(parse("new IngressArgs { External = true").is_err());
+    }
+
+    #[test]
+    fn test_get_images() {
+        let output = get_images(PROGRAM).unwrap();
+
+        let expected = vec![ContainerImageBluePrint {
+            name: Some("${registry.LoginServer}/node-app:v1.0.0".to_string()),
+            build: BuildContextBluePrint::new("./node-app"),
+            reference_name: Some("nodeImage".to_string()),
+        }];
+        assert_eq!(expected, output);
+    }
+
+    #[test]
+    fn test_get_images_without_variable() {
+        let input = PROGRAM.replace(
+            r#"var nodeImage = new Image("node-app""#,
+            r#"new Image("node-app""#,
+        );
+
+        let output = get_images(&input).unwrap();
+        assert_eq!(Some("node-app".to_string()), output[0].reference_name);
+
+        // The container referencing the former variable is reported instead of panicking
+        let output = deserialize(&input, &BuildOptions::default());
+        assert_eq!(
+            Err(DeserializeError::UnresolvedImage {
+                container: "frontend".to_string(),
+                reference: "${nodeImage.ImageName}".to_string(),
+            }),
+            output
+        );
+    }
+
+    #[test]
+    fn test_deserialize() {
+        let output = deserialize(PROGRAM, &BuildOptions::default()).unwrap();
+
+        let names: Vec<&str> = output.iter().map(|service| service.name.as_str()).collect();
+        assert_eq!(vec!["frontend", "backend", "backend_dapr"], names);
+
+        assert_eq!(None, output[0].image);
+        assert_eq!(
+            Some("./node-app".to_string()),
+            output[0].build.as_ref().map(|build| build.context.clone())
+        );
+        assert_eq!(Some(vec!["3000:3000".to_string()]), output[0].ports);
+
+        assert_eq!(Some("node-12".to_string()), output[1].image);
+        let command = output[2].command.clone().unwrap();
+        assert!(command.contains(&"4000".to_string()));
+        assert_eq!(
+            Some(&"SystemAssigned".to_string()),
+            output[1]
+                .labels
+                .as_ref()
+                .and_then(|labels| labels.get("capp.identity.type"))
+        );
+    }
+
+    #[cfg(feature = "yaml")]
+    #[test]
+    fn test_deserialize_as_yaml() {
+        let yaml = r#"
+      resources:
+        nodeImage:
+          type: docker:RegistryImage
+          properties:
+            name: ${registry.loginServer}/node-app:v1.0.0
+            build:
+              context: ./node-app
+        frontend:
+          type: azure-native:app:ContainerApp
+          properties:
+            resourceGroupName: ${resourceGroup.Name}
+            managedEnvironmentId: ${managedEnv.Id}
+            configuration:
+              ingress:
+                external: true
+                targetPort: 3000
+            template:
+              containers:
+                - name: frontend
+                  image: ${nodeImage.name}
+                  env:
+                    - name: PORT
+                      value: "3000"
+        backend:
+          type: azure-native:app:ContainerApp
+          properties:
+            resourceGroupName: ${resourceGroup.Name}
+            configuration:
+              dapr:
+                enabled: true
+                appId: backend
+                appPort: 4000
+            identity:
+              type: SystemAssigned
+            template:
+              containers:
+                - name: backend
+                  image: node-12
+      "#;
+
+        let output = deserialize(PROGRAM, &BuildOptions::default()).unwrap();
+        let expected = pulumi::yaml::deserialize(yaml, &BuildOptions::default()).unwrap();
+
+        assert_eq!(expected, output);
+    }
+
+    #[test]
+    fn test_deserialize_dapr_without_enabled() {
+        let program = PROGRAM.replace("Enabled = true, ", "");
+        let output = deserialize(&program, &BuildOptions::default()).unwrap();
+
+        let names: Vec<&str> = output.iter().map(|service| service.name.as_str()).collect();
+        // Dapr is disabled by default, as in Container Apps
+        assert_eq!(vec!["frontend", "backend"], names);
+    }
+}
@@ -0,0 +1,898 @@
+use regex::Regex;
+use serde_json::{Map, Number, Value};
+
+use crate::error::DeserializeError;
+use crate::pulumi;
+use crate::serializer::{
+    BuildOptions, ContainerAppBluePrint, ContainerAppConfiguration, ContainerImageBluePrint,
+};
+
+/***
+ * Struct fields are exported (PascalCase), the blueprint properties camelCase (eg: TargetPort)
+ */
+fn to_camel_case(name: &str) -> String {
+    let mut chars = name.chars();
+
+    match chars.next() {
+        Some(first) => first.to_lowercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+/***
+ * Go expressions of the resource arguments as JSON values.
+ * Struct literals are objects, slices arrays and maps objects, the input wrappers are
+ * their value (eg: pulumi.Int(3000)), property accesses are `${...}` references and
+ * calls which cannot be evaluated (eg: output.ApplyT(...)) are null
+ */
+struct Parser<'a> {
+    chars: Vec<char>,
+    position: usize,
+    constants: &'a Map<String, Value>,
+}
+
+impl<'a> Parser<'a> {
+    fn new(input: &str, position: usize, constants: &'a Map<String, Value>) -> Parser<'a> {
+        Parser {
+            chars: input.chars().collect(),
+            position,
+            constants,
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.position).copied()
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<char> {
+        self.chars.get(self.position + offset).copied()
+    }
+
+    fn line(&self) -> usize {
+        self.chars[..self.position.min(self.chars.len())]
+            .iter()
+            .filter(|c| **c == '\n')
+            .count()
+            + 1
+    }
+
+    fn error(&self, message: &str) -> String {
+        format!("{} at line {}", message, self.line())
+    }
+
+    fn skip_blanks(&mut self) {
+        while let Some(c) = self.peek() {
+            match (c, self.peek_at(1)) {
+                ('/', Some('/')) => {
+                    while !matches!(self.peek(), Some('\n') | None) {
+                        self.position += 1;
+                    }
+                }
+                ('/', Some('*')) => {
+                    self.position += 2;
+                    while self.peek().is_some()
+                        && !(self.peek() == Some('*') && self.peek_at(1) == Some('/'))
+                    {
+                        self.position += 1;
+                    }
+                    self.position += 2;
+                }
+                (c, _) if c.is_whitespace() => self.position += 1,
+                _ => break,
+            }
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        self.skip_blanks();
+
+        match self.peek() {
+            Some(c) if c == expected => {
+                self.position += 1;
+                Ok(())
+            }
+            Some(c) => Err(self.error(&format!("Expected `{}`, found `{}`", expected, c))),
+            None => Err(self.error(&format!("Expected `{}`", expected))),
+        }
+    }
+
+    fn parse_identifier(&mut self) -> String {
+        let mut identifier = String::new();
+
+        while let Some(c) = self.peek().filter(|c| c.is_alphanumeric() || *c == '_') {
+            identifier.push(c);
+            self.position += 1;
+        }
+
+        identifier
+    }
+
+    fn parse_value(&mut self) -> Result<Value, String> {
+        self.skip_blanks();
+
+        let value = match self.peek() {
+            // Pointers to struct literals (eg: &app.IngressArgs{ ... })
+            Some('&') => {
+                self.position += 1;
+                self.parse_value()?
+            }
+            // Slice literals (eg: []string{ ... })
+            Some('[') => {
+                self.skip_balanced('[', ']')?;
+                self.skip_blanks();
+                self.parse_type();
+                self.parse_composite()?
+            }
+            Some('{') => self.parse_composite()?,
+            Some('(') => {
+                self.position += 1;
+                let value = self.parse_value()?;
+                self.expect(')')?;
+
+                value
+            }
+            Some('"') => Value::String(self.parse_string()?),
+            Some('`') => Value::String(self.parse_raw_string()?),
+            Some(c) if c.is_ascii_digit() || c == '-' => self.parse_number()?,
+            Some(c) if c.is_alphabetic() || c == '_' => self.parse_name()?,
+            Some(c) => return Err(self.error(&format!("Unexpected `{}`", c))),
+            None => return Err(self.error("Unexpected end of input")),
+        };
+
+        // String concatenations (eg: prefix + "-app")
+        self.skip_blanks();
+        if self.peek() == Some('+') {
+            self.position += 1;
+
+            return match (value, self.parse_value()?) {
+                (Value::String(left), Value::String(right)) => Ok(Value::String(left + &right)),
+                _ => Ok(Value::Null),
+            };
+        }
+
+        Ok(value)
+    }
+
+    /// Type of a composite literal (eg: app.ContainerArray or map[string]string)
+    fn parse_type(&mut self) -> String {
+        let name = self.parse_dotted_name();
+
+        if name == "map" && self.peek() == Some('[') {
+            // The key type is always a string for the resource arguments
+            let _ = self.skip_balanced('[', ']');
+            self.parse_type();
+        }
+
+        name
+    }
+
+    /// Keyed elements (eg: { External: pulumi.Bool(true) } or { "key": "value" })
+    /// or positional ones (eg: { &app.ContainerArgs{ ... } })
+    fn parse_composite(&mut self) -> Result<Value, String> {
+        let mut fields = Map::new();
+        let mut items = vec![];
+        self.expect('{')?;
+
+        loop {
+            self.skip_blanks();
+            if self.peek() == Some('}') {
+                self.position += 1;
+                break;
+            }
+
+            let start = self.position;
+            let identifier = self.parse_identifier();
+            self.skip_blanks();
+
+            if !identifier.is_empty() && self.peek() == Some(':') && self.peek_at(1) != Some('=') {
+                self.position += 1;
+                fields.insert(to_camel_case(&identifier), self.parse_value()?);
+            } else {
+                self.position = start;
+                let item = self.parse_value()?;
+
+                self.skip_blanks();
+                if self.peek() == Some(':') {
+                    self.position += 1;
+                    let key = item.as_str().map_or(item.to_string(), String::from);
+
+                    fields.insert(key, self.parse_value()?);
+                } else {
+                    items.push(item);
+                }
+            }
+
+            self.skip_blanks();
+            match self.peek() {
+                Some(',') => self.position += 1,
+                Some('}') => {}
+                _ => return Err(self.error("Expected `,` or `}`")),
+            }
+        }
+
+        // An empty literal sets nothing
+        Ok(match (fields.is_empty(), items.is_empty()) {
+            (true, true) => Value::Null,
+            (false, _) => Value::Object(fields),
+            (true, false) => Value::Array(items),
+        })
+    }
+
+    fn parse_arguments(&mut self) -> Result<Vec<Value>, String> {
+        let mut arguments = vec![];
+        self.expect('(')?;
+
+        loop {
+            self.skip_blanks();
+            if self.peek() == Some(')') {
+                self.position += 1;
+                return Ok(arguments);
+            }
+
+            arguments.push(self.parse_value()?);
+
+            self.skip_blanks();
+            // Variadic arguments (eg: opts...)
+            while self.peek() == Some('.') {
+                self.position += 1;
+            }
+
+            self.skip_blanks();
+            match self.peek() {
+                Some(',') => self.position += 1,
+                Some(')') => {}
+                _ => return Err(self.error("Expected `,` or `)`")),
+            }
+        }
+    }
+
+    fn parse_string(&mut self) -> Result<String, String> {
+        self.expect('"')?;
+        let mut output = String::new();
+
+        loop {
+            match self.peek() {
+                None | Some('\n') => return Err(self.error("Unterminated string")),
+                Some('"') => {
+                    self.position += 1;
+                    return Ok(output);
+                }
+                Some('\\') => {
+                    match self.peek_at(1) {
+                        Some('n') => output.push('\n'),
+                        Some('t') => output.push('\t'),
+                        Some(c) => output.push(c),
+                        None => return Err(self.error("Unterminated string")),
+                    }
+                    self.position += 2;
+                }
+                Some(c) => {
+                    output.push(c);
+                    self.position += 1;
+                }
+            }
+        }
+    }
+
+    fn parse_raw_string(&mut self) -> Result<String, String> {
+        self.expect('`')?;
+        let mut output = String::new();
+
+        loop {
+            match self.peek() {
+                None => return Err(self.error("Unterminated string")),
+                Some('`') => {
+                    self.position += 1;
+                    return Ok(output);
+                }
+                Some(c) => {
+                    output.push(c);
+                    self.position += 1;
+                }
+            }
+        }
+    }
+
+    fn parse_number(&mut self) -> Result<Value, String> {
+        let mut number = String::new();
+
+        while let Some(c) = self
+            .peek()
+            .filter(|c| c.is_ascii_digit() || matches!(c, '-' | '.' | '_'))
+        {
+            number.push(c);
+            self.position += 1;
+        }
+
+        let number = number.replace('_', "");
+
+        match number.parse::<i64>() {
+            Ok(number) => Ok(Value::Number(number.into())),
+            Err(_) => number
+                .parse::<f64>()
+                .ok()
+                .and_then(Number::from_f64)
+                .map(Value::Number)
+                .ok_or_else(|| self.error(&format!("Invalid number `{}`", number))),
+        }
+    }
+
+    fn skip_balanced(&mut self, open: char, close: char) -> Result<(), String> {
+        let mut depth = 0;
+
+        while let Some(c) = self.peek() {
+            match c {
+                '"' => {
+                    self.parse_string()?;
+                    continue;
+                }
+                '`' => {
+                    self.parse_raw_string()?;
+                    continue;
+                }
+                c if c == open => depth += 1,
+                c if c == close => {
+                    depth -= 1;
+                    if depth == 0 {
+                        self.position += 1;
+                        return Ok(());
+                    }
+                }
+                _ => {}
+            }
+            self.position += 1;
+        }
+
+        Err(self.error(&format!("Expected `{}`", close)))
+    }
+
+    /// Selectors and calls chained to a skipped call (eg: .ApplyT(...).(pulumi.StringOutput))
+    fn skip_chain(&mut self) -> Result<(), String> {
+        loop {
+            match self.peek() {
+                Some('.') if self.peek_at(1) == Some('(') => {
+                    self.position += 1;
+                    self.skip_balanced('(', ')')?;
+                }
+                Some('.') => {
+                    self.position += 1;
+                    self.parse_identifier();
+                }
+                Some('(') => self.skip_balanced('(', ')')?,
+                Some('[') => self.skip_balanced('[', ']')?,
+                _ => return Ok(()),
+            }
+        }
+    }
+
+    fn parse_dotted_name(&mut self) -> String {
+        let mut name = self.parse_identifier();
+
+        while self.peek() == Some('.')
+            && self
+                .peek_at(1)
+                .is_some_and(|c| c.is_alphabetic() || c == '_')
+        {
+            self.position += 1;
+            name.push('.');
+            name.push_str(&self.parse_identifier());
+        }
+
+        name
+    }
+
+    /// Formatted outputs with their arguments (eg: pulumi.Sprintf("%s/app", server))
+    fn format(arguments: Vec<Value>) -> Value {
+        let mut arguments = arguments.into_iter();
+        let format = match arguments.next() {
+            Some(Value::String(format)) => format,
+            _ => return Value::Null,
+        };
+        let re = Regex::new(r"%[-+# 0-9.]*[a-zA-Z%]").unwrap();
+
+        let output = re.replace_all(&format, |captures: &regex::Captures| {
+            if &captures[0] == "%%" {
+                return "%".to_string();
+            }
+
+            match arguments.next() {
+                Some(Value::String(value)) => value,
+                Some(value) => value.to_string(),
+                None => String::new(),
+            }
+        });
+
+        Value::String(output.to_string())
+    }
+
+    fn parse_name(&mut self) -> Result<Value, String> {
+        let name = self.parse_dotted_name();
+
+        match name.as_str() {
+            "true" => return Ok(Value::Bool(true)),
+            "false" => return Ok(Value::Bool(false)),
+            "nil" => return Ok(Value::Null),
+            "map" => {
+                self.skip_balanced('[', ']')?;
+                self.parse_type();
+                return self.parse_composite();
+            }
+            _ => {}
+        }
+
+        self.skip_blanks();
+        match self.peek() {
+            // Composite literals (eg: app.ContainerArray{ ... })
+            Some('{') => self.parse_composite(),
+            Some('(') if matches!(name.as_str(), "pulumi.Sprintf" | "fmt.Sprintf") => {
+                let arguments = self.parse_arguments()?;
+                self.skip_chain()?;
+
+                Ok(Self::format(arguments))
+            }
+            // The image reference of the outputs, as for pulumi.all in Typescript
+            Some('(') if name == "pulumi.All" => {
+                let arguments = self.parse_arguments()?;
+                self.skip_chain()?;
+
+                let references: Vec<&str> = arguments.iter().filter_map(|x| x.as_str()).collect();
+
+                Ok(references
+                    .iter()
+                    .find(|reference| reference.ends_with(".ImageName}"))
+                    .or(references.first())
+                    .map(|reference| Value::String(reference.to_string()))
+                    .unwrap_or_default())
+            }
+            // Applied outputs keep the reference of the output (eg: image.ImageName.ApplyT(...))
+            Some('(') if name.contains('.') && name.ends_with(".ApplyT") => {
+                self.skip_chain()?;
+
+                Ok(Value::String(format!(
+                    "${{{}}}",
+                    name.trim_end_matches(".ApplyT")
+                )))
+            }
+            // Resource ids (eg: env.ID())
+            Some('(') if name.contains('.') && name.ends_with(".ID") => {
+                self.skip_chain()?;
+
+                Ok(Value::String(format!("${{{}}}", name)))
+            }
+            // Input wrappers and conversions are their value
+            // (eg: pulumi.String("app"), pulumi.IntPtr(3000) or app.ActiveRevisionsMode("Single"))
+            Some('(') if name.contains('.') => {
+                let mut arguments = self.parse_arguments()?;
+                self.skip_chain()?;
+
+                Ok(match arguments.len() {
+                    1 => arguments.remove(0),
+                    _ => Value::Null,
+                })
+            }
+            Some('(') => {
+                self.skip_chain()?;
+
+                Ok(Value::Null)
+            }
+            // Indexers are kept as tokens (eg: outputs["imageName"])
+            Some('[') => {
+                let start = self.position;
+                self.skip_balanced('[', ']')?;
+                let indexer: String = self.chars[start..self.position].iter().collect();
+
+                Ok(Value::String(format!("${{{}{}}}", name, indexer)))
+            }
+            _ if name.contains('.') => Ok(Value::String(format!("${{{}}}", name))),
+            _ => Ok(self
+                .constants
+                .get(&name)
+                .cloned()
+                .unwrap_or_else(|| Value::String(format!("${{{}}}", name)))),
+        }
+    }
+}
+
+fn char_position(input: &str, byte_position: usize) -> usize {
+    input[..byte_position].chars().count()
+}
+
+/***
+ * Literal constants and variables (eg: const appPort = 3000), substituted where they are used
+ */
+fn get_constants(input: &str) -> Map<String, Value> {
+    let empty = Map::new();
+    let mut constants = Map::new();
+
+    for captures in Regex::new(
+        r"(?:\b(?:const|var)\s+(?P<declared>[A-Za-z_]\w*)(?:\s+\w+)?\s*=|\b(?P<short>[A-Za-z_]\w*)\s*:=)\s*[^=\s]",
+    )
+    .unwrap()
+    .captures_iter(input)
+    {
+        let name = captures
+            .name("declared")
+            .or(captures.name("short"))
+            .unwrap()
+            .as_str();
+        let start = char_position(input, captures.get(0).unwrap().end() - 1);
+        let value = Parser::new(input, start, &empty).parse_value();
+
+        match value {
+            Ok(Value::String(value)) if !value.contains("${") => {
+                constants.insert(name.to_string(), Value::String(value));
+            }
+            Ok(value @ (Value::Number(_) | Value::Bool(_))) => {
+                constants.insert(name.to_string(), value);
+            }
+            _ => {}
+        }
+    }
+
+    constants
+}
+
+// Variable, resource name and arguments of a resource
+type Declaration = (Option<String>, String, Value);
+
+/***
+ * Resources created with the given constructor (eg: app.NewContainerApp(ctx, "myapp", &app.ContainerAppArgs{ ... }))
+ */
+fn get_declarations(input: &str, constructor: &str) -> Result<Vec<Declaration>, DeserializeError> {
+    let constants = get_constants(input);
+    let re = Regex::new(&format!(
+        r"(?:(?P<variable>[A-Za-z_]\w*)\s*(?:,\s*\w+\s*)?:?=\s*)?[A-Za-z_]\w*\.New{}\s*\(",
+        constructor
+    ))
+    .unwrap();
+
+    re.captures_iter(input)
+        .map(|captures| {
+            let start = char_position(input, captures.get(0).unwrap().end() - 1);
+            let mut parser = Parser::new(input, start, &constants);
+            // The context is the first argument
+            let mut arguments = parser
+                .parse_arguments()
+                .map_err(DeserializeError::Parse)?
+                .into_iter()
+                .skip(1);
+            let variable = captures
+                .name("variable")
+                .map(|x| x.as_str().to_string())
+                .filter(|variable| variable != "_");
+            let name = arguments
+                .next()
+                .and_then(|name| name.as_str().map(|name| name.to_string()))
+                .or(variable.clone())
+                .unwrap_or_default();
+
+            Ok((variable, name, arguments.next().unwrap_or_default()))
+        })
+        .collect()
+}
+
+fn get_images(input: &str) -> Result<Vec<ContainerImageBluePrint>, DeserializeError> {
+    let images = get_declarations(input, "Image")?
+        .into_iter()
+        .filter_map(|(variable, name, mut arguments)| {
+            let properties = arguments.as_object_mut()?;
+
+            if let Some(name) = properties.remove("imageName") {
+                properties.insert("name".to_string(), name);
+            }
+
+            // Images without build context are remote ones, they are not referenced
+            let mut image: ContainerImageBluePrint = serde_json::from_value(arguments).ok()?;
+            // An unassigned image is referenced by its resource name
+            image.reference_name = variable
+                .clone()
+                .or(Some(name).filter(|name| !name.is_empty()));
+            image.name = image.name.or(variable);
+
+            Some(image)
+        })
+        .collect();
+
+    Ok(images)
+}
+
+fn get_app_properties(input: &str) -> Result<Vec<(String, Value)>, DeserializeError> {
+    Ok(get_declarations(input, "ContainerApp")?
+        .into_iter()
+        .map(|(_, name, arguments)| (name, arguments))
+        .collect())
+}
+
+fn get_apps(input: &str) -> Result<Vec<ContainerAppBluePrint>, DeserializeError> {
+    get_app_properties(input)?
+        .into_iter()
+        .map(|(name, properties)| {
+            let mut app: ContainerAppBluePrint = serde_json::from_value(properties)
+                .map_err(|e| DeserializeError::Parse(e.to_string()))?;
+            app.name = Some(name);
+            pulumi::normalize_scale(&mut app);
+
+            Ok(app)
+        })
+        .collect()
+}
+
+fn as_yaml_properties(properties: &Value) -> Result<serde_yaml::Value, DeserializeError> {
+    serde_yaml::to_value(properties).map_err(|e| DeserializeError::Parse(e.to_string()))
+}
+
+/***
+ * Fields of the apps which are not translated, the input is expected to be valid
+ */
+pub fn collect_warnings(input: &str) -> Vec<String> {
+    get_app_properties(input)
+        .unwrap_or_default()
+        .into_iter()
+        .flat_map(|(name, properties)| {
+            let properties = as_yaml_properties(&properties).unwrap_or_default();
+
+            pulumi::collect_ignored_fields(&properties)
+                .unwrap_or_default()
+                .into_iter()
+                .map(move |path| format!("ignored {} in {}", path, name))
+        })
+        .collect()
+}
+
+pub fn deserialize(
+    input: &str,
+    options: &BuildOptions,
+) -> Result<Vec<ContainerAppConfiguration>, DeserializeError> {
+    deserialize_per_app(input, options).map(pulumi::flatten_apps)
+}
+
+/***
+ * Services of each app, see `pulumi::build_per_app`
+ */
+pub fn deserialize_per_app(
+    input: &str,
+    options: &BuildOptions,
+) -> Result<Vec<(String, Vec<ContainerAppConfiguration>)>, DeserializeError> {
+    let (apps, images) = deserialize_blueprints(input, options)?;
+
+    pulumi::build_per_app(apps, images, options.include.as_deref(), options)
+}
+
+/***
+ * Apps and images of the input, their references are not resolved yet
+ */
+pub fn deserialize_blueprints(
+    input: &str,
+    options: &BuildOptions,
+) -> Result<pulumi::BluePrints, DeserializeError> {
+    if options.strict {
+        for (_, properties) in get_app_properties(input)? {
+            pulumi::validate_known_fields(&as_yaml_properties(&properties)?)?;
+        }
+    }
+
+    let images = get_images(input)?;
+    let apps = get_apps(input)?;
+
+    Ok((apps, images))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::serializer::BuildContextBluePrint;
+
+    use super::*;
+
+    const PROGRAM: &str = r#"
+package main
+
+import (
+	app "github.com/pulumi/pulumi-azure-native-sdk/app/v2"
+	"github.com/pulumi/pulumi-docker/sdk/v4/go/docker"
+	"github.com/pulumi/pulumi/sdk/v3/go/pulumi"
+)
+
+const backendPort = 4000
+
+func main() {
+	pulumi.Run(func(ctx *pulumi.Context) error {
+		image, err := docker.NewImage(ctx, "node-app", &docker.ImageArgs{
+			ImageName: pulumi.Sprintf("%s/node-app:v1.0.0", registry.LoginServer),
+			Build: &docker.DockerBuildArgs{
+				Context: pulumi.String("./node-app"),
+			},
+		})
+		if err != nil {
+			return err
+		}
+
+		frontend, err := app.NewContainerApp(ctx, "frontend", &app.ContainerAppArgs{
+			ResourceGroupName:    resourceGroup.Name,
+			ManagedEnvironmentId: managedEnv.ID(),
+			Configuration: &app.ConfigurationArgs{
+				Ingress: &app.IngressArgs{
+					External:   pulumi.Bool(true),
+					TargetPort: pulumi.Int(3000),
+				},
+			},
+			Template: &app.TemplateArgs{
+				Containers: app.ContainerArray{
+					&app.ContainerArgs{
+						Name:  pulumi.String("frontend"),
+						Image: image.ImageName,
+					},
+				},
+			},
+		})
+		if err != nil {
+			return err
+		}
+
+		// The backend is only reachable through Dapr
+		_, err = app.NewContainerApp(ctx, "backend", &app.ContainerAppArgs{
+			ResourceGroupName: resourceGroup.Name,
+			Configuration: &app.ConfigurationArgs{
+				Dapr: &app.DaprArgs{
+					Enabled: pulumi.Bool(true),
+					AppId:   pulumi.String("backend"),
+					AppPort: pulumi.Int(backendPort),
+				},
+			},
+			Template: &app.TemplateArgs{
+				Containers: app.ContainerArray{
+					&app.ContainerArgs{
+						Name:  pulumi.String("backend"),
+						Image: pulumi.String("node-12"),
+					},
+				},
+			},
+		})
+		if err != nil {
+			return err
+		}
+
+		ctx.Export("url", frontend.LatestRevisionFqdn.ApplyT(func(fqdn string) string {
+			return "https://" + fqdn
+		}).(pulumi.StringOutput))
+		return nil
+	})
+}
+"#;
+
+    #[test]
+    fn test_parse_value() {
+        let constants = Map::new();
+        let parse = |input: &str| Parser::new(input, 0, &constants).parse_value();
+
+        assert_eq!(
+            Ok(serde_json::json!({ "external": true, "targetPort": 3000 })),
+            parse("&app.IngressArgs{ External: pulumi.Bool(true), TargetPort: pulumi.Int(3000) }")
+        );
+        assert_eq!(
+            Ok(serde_json::json!(["a", "b"])),
+            parse(r#"pulumi.StringArray{ pulumi.String("a"), pulumi.String("b") }"#)
+        );
+        assert_eq!(
+            Ok(serde_json::json!({ "redisHost": "redis:6379" })),
+            parse(r#"pulumi.StringMap{ "redisHost": pulumi.String("redis:6379") }"#)
+        );
+        assert_eq!(
+            Ok(Value::String("${registry.LoginServer}/app:v1".to_string())),
+            parse(r#"pulumi.Sprintf("%s/app:v1", registry.LoginServer)"#)
+        );
+        assert_eq!(
+            Ok(Value::String("${image.ImageName}".to_string())),
+            parse("image.ImageName.ApplyT(func(name string) string { return name }).(pulumi.StringOutput)")
+        );
+
+        assert!(parse("&app.IngressArgs{ External: pulumi.Bool(true)").is_err());
+    }
+
+    #[test]
+    fn test_get_images() {
+        let output = get_images(PROGRAM).unwrap();
+
+        let expected = vec![ContainerImageBluePrint {
+            name: Some("${registry.LoginServer}/node-app:v1.0.0".to_string()),
+            build: BuildContextBluePrint::new("./node-app"),
+            reference_name: Some("image".to_string()),
+        }];
+        assert_eq!(expected, output);
+    }
+
+    #[test]
+    fn test_get_images_without_variable() {
+        let input = PROGRAM.replace(
+            r#"image, err := docker.NewImage(ctx, "node-app""#,
+            r#"_, err := docker.NewImage(ctx, "node-app""#,
+        );
+
+        let output = get_images(&input).unwrap();
+        assert_eq!(Some("node-app".to_string()), output[0].reference_name);
+
+        // The container referencing the former variable is reported instead of panicking
+        let output = deserialize(&input, &BuildOptions::default());
+        assert_eq!(
+            Err(DeserializeError::UnresolvedImage {
+                container: "frontend".to_string(),
+                reference: "${image.ImageName}".to_string(),
+            }),
+            output
+        );
+    }
+
+    #[test]
+    fn test_deserialize() {
+        let output = deserialize(PROGRAM, &BuildOptions::default()).unwrap();
+
+        let names: Vec<&str> = output.iter().map(|service| service.name.as_str()).collect();
+        assert_eq!(vec!["frontend", "backend", "backend_dapr"], names);
+
+        assert_eq!(None, output[0].image);
+        assert_eq!(
+            Some("./node-app".to_string()),
+            output[0].build.as_ref().map(|build| build.context.clone())
+        );
+        assert_eq!(Some(vec!["3000:3000".to_string()]), output[0].ports);
+        assert_eq!(Some("node-12".to_string()), output[1].image);
+        assert!(output[2]
+            .command
+            .as_ref()
+            .is_some_and(|command| command.contains(&"4000".to_string())));
+    }
+
+    #[cfg(feature = "yaml")]
+    #[test]
+    fn test_deserialize_as_yaml() {
+        let yaml = r#"
+      resources:
+        image:
+          type: docker:RegistryImage
+          properties:
+            name: ${registry.loginServer}/node-app:v1.0.0
+            build:
+              context: ./node-app
+        frontend:
+          type: azure-native:app:ContainerApp
+          properties:
+            resourceGroupName: ${resourceGroup.Name}
+            managedEnvironmentId: ${managedEnv.ID}
+            configuration:
+              ingress:
+                external: true
+                targetPort: 3000
+            template:
+              containers:
+                - name: frontend
+                  image: ${image.name}
+        backend:
+          type: azure-native:app:ContainerApp
+          properties:
+            resourceGroupName: ${resourceGroup.Name}
+            configuration:
+              dapr:
+                enabled: true
+                appId: backend
+                appPort: 4000
+            template:
+              containers:
+                - name: backend
+                  image: node-12
+      "#;
+
+        let output = deserialize(PROGRAM, &BuildOptions::default()).unwrap();
+        let expected = pulumi::yaml::deserialize(yaml, &BuildOptions::default()).unwrap();
+
+        assert_eq!(expected, output);
+    }
+
+    #[test]
+    fn test_deserialize_dapr_without_enabled() {
+        let program = PROGRAM.replace("\t\t\t\t\tEnabled: pulumi.Bool(true),\n", "");
+        let output = deserialize(&program, &BuildOptions::default()).unwrap();
+
+        let names: Vec<&str> = output.iter().map(|service| service.name.as_str()).collect();
+        // Dapr is disabled by default, as in Container Apps
+        assert_eq!(vec!["frontend", "backend"], names);
+    }
+}
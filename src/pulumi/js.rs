@@ -1,212 +1,1273 @@
-use crate::pulumi;
+//! Pulumi Typescript / Javascript programs, parsed by swc.
+//!
+//! The resources are the `new` expressions of their constructors and their arguments are
+//! evaluated as JSON values: literals, objects, arrays, template literals, property accesses
+//! kept as tokens, `+` concatenations, `??` / `||` fallbacks, spreads and Typescript assertions.
+//!
+//! The other expressions are not evaluated, their value is null (eg: port * 2, a === b ? x : y,
+//! await getImage()). Syntax errors are reported with their line and column.
 use regex::Regex;
+use serde_json::{Map, Number, Value};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use swc_common::sync::Lrc;
+use swc_common::{FileName, SourceFile, SourceMap, Span, Spanned};
+use swc_ecma_ast::{
+    ArrayLit, ArrowExpr, ArrowFunctionBody, BinExpr, BinaryOp, CallExpr, Callee, Expr, ForHead,
+    ForOfStmt, Lit, MemberProp, NewExpr, ObjectLit, ObjectPatProp, OptChainBase, Pat, Program,
+    Prop, PropName, PropOrSpread, Tpl, UnaryOp, VarDeclarator,
+};
+use swc_ecma_parser::{Parser, StringInput, Syntax, TsSyntax};
+use swc_ecma_visit::{Visit, VisitWith};
 
+use crate::error::DeserializeError;
+use crate::pulumi;
 use crate::serializer::{
-    BuildContextBluePrint, ContainerAppBluePrint, ContainerAppConfiguration,
-    ContainerImageBluePrint,
+    BuildOptions, ContainerAppBluePrint, ContainerAppConfiguration, ContainerImageBluePrint,
+    JobBluePrint,
 };
 
-fn parse_line(line: &str) -> String {
-    let a = line.replace(" ", "");
-    let re = Regex::new(r####"([a-zA-Z"]+)(:)([a-zA-Z0-9-:.`'/"\{}\[\]]+)?"####).unwrap();
-
-    let captures = re.captures(&a);
-    let computed = match captures {
-        Some(c) => {
-            let key = c.get(1).unwrap().as_str();
-            let value = if c.get(3).is_some() {
-                let computed = c.get(3).unwrap().as_str();
-                let tokens = ["{", "[{"];
-                let has_token = tokens.contains(&computed);
-
-                if has_token {
-                    computed.to_string()
-                } else {
-                    let with_quotes = format!("\"{}\",", computed);
-                    // Check if it's a number or a boolean
-                    let re = Regex::new(r"^[0-9]+").unwrap().is_match(computed);
-                    // Need to cleanup this part
-                    if re || computed == "true" || computed == "false" {
-                        format!("{},", computed.to_string())
-                    } else {
-                        with_quotes.to_string()
+/***
+ * Variables bound to the items of a loop (eg: name, { name, port } or [name, port])
+ */
+#[derive(Debug, Clone, PartialEq)]
+enum Binding {
+    Name(String),
+    Object(Vec<(String, String)>),
+    Array(Vec<String>),
+}
+
+impl Binding {
+    /// Variables of a declaration or a parameter, none for the other patterns (eg: ...rest)
+    fn from_pattern(pattern: &Pat) -> Option<Binding> {
+        match pattern {
+            Pat::Object(object) => object
+                .props
+                .iter()
+                .map(|property| match property {
+                    ObjectPatProp::KeyValue(property) => {
+                        Some((get_key_name(&property.key)?, get_name(&property.value)?))
+                    }
+                    ObjectPatProp::Assign(property) => {
+                        Some((property.key.sym.to_string(), property.key.sym.to_string()))
+                    }
+                    ObjectPatProp::Rest(_) => None,
+                })
+                .collect::<Option<Vec<_>>>()
+                .map(Binding::Object),
+            Pat::Array(array) => array
+                .elems
+                .iter()
+                .map(|element| get_name(element.as_ref()?))
+                .collect::<Option<Vec<_>>>()
+                .map(Binding::Array),
+            pattern => get_name(pattern).map(Binding::Name),
+        }
+    }
+
+    fn bind(&self, item: &Value, constants: &mut Map<String, Value>) {
+        let mut insert = |name: &str, value: Option<&Value>| {
+            if let Some(value) = value.filter(|value| !value.is_null()) {
+                constants.insert(name.to_string(), value.clone());
+            }
+        };
+
+        match self {
+            Binding::Name(name) => insert(name, Some(item)),
+            Binding::Object(properties) => {
+                for (key, name) in properties {
+                    insert(name, item.get(key));
+                }
+            }
+            Binding::Array(names) => {
+                for (index, name) in names.iter().enumerate() {
+                    insert(name, item.get(index));
+                }
+            }
+        }
+    }
+}
+
+/// Name of a variable, with its default value skipped (eg: name = "api")
+fn get_name(pattern: &Pat) -> Option<String> {
+    match pattern {
+        Pat::Ident(name) => Some(name.id.sym.to_string()),
+        Pat::Assign(pattern) => get_name(&pattern.left),
+        _ => None,
+    }
+}
+
+fn get_key_name(key: &PropName) -> Option<String> {
+    match key {
+        PropName::Ident(key) => Some(key.sym.to_string()),
+        PropName::Str(key) => Some(key.value.to_atom_lossy().to_string()),
+        _ => None,
+    }
+}
+
+/// Name of a variable or of a property of it (eg: app.ContainerApp or config?.image)
+fn get_dotted_name(expression: &Expr) -> Option<String> {
+    let (object, property) = match expression {
+        Expr::Ident(name) => return Some(name.sym.to_string()),
+        Expr::Member(member) => (&member.obj, &member.prop),
+        Expr::OptChain(chain) => match &*chain.base {
+            OptChainBase::Member(member) => (&member.obj, &member.prop),
+            OptChainBase::Call(_) => return None,
+        },
+        _ => return None,
+    };
+
+    match property {
+        MemberProp::Ident(property) => {
+            Some(format!("{}.{}", get_dotted_name(object)?, property.sym))
+        }
+        _ => None,
+    }
+}
+
+/// Variables and their properties, with indexers (eg: outputs["imageName"])
+fn is_reference(expression: &Expr) -> bool {
+    match expression {
+        Expr::Ident(_) => true,
+        Expr::Member(member) => is_reference(&member.obj),
+        Expr::OptChain(chain) => match &*chain.base {
+            OptChainBase::Member(member) => is_reference(&member.obj),
+            OptChainBase::Call(_) => false,
+        },
+        _ => false,
+    }
+}
+
+fn as_number(value: f64) -> Value {
+    match value.fract() == 0.0 && value.abs() < 2f64.powi(53) {
+        true => Value::Number((value as i64).into()),
+        false => Number::from_f64(value)
+            .map(Value::Number)
+            .unwrap_or_default(),
+    }
+}
+
+/***
+ * A `for...of` loop or a `map` / `forEach` callback around a `new` expression,
+ * the resource is created once per item
+ */
+#[derive(Debug, Clone, PartialEq)]
+struct Loop {
+    binding: Binding,
+    /// Position of the item in the array, for map callbacks (eg: (name, index) => ...)
+    index: Option<String>,
+    /// None when the array is not known (eg: config.requireObject("apps"))
+    items: Option<Vec<Value>>,
+    /// The iterated expression, reported when it is not known
+    iterable: String,
+}
+
+/***
+ * A `new` expression of a resource, see `NewExpressionFinder`
+ */
+struct NewExpression {
+    variable: Option<String>,
+    constructor: String,
+    arguments: Vec<Expr>,
+    loops: Vec<Loop>,
+}
+
+/***
+ * Program parsed by swc, with its file for the positions and the text of the expressions
+ */
+struct Source {
+    map: Lrc<SourceMap>,
+    file: Lrc<SourceFile>,
+    program: Program,
+}
+
+impl Source {
+    /// Programs are parsed as Typescript, which Javascript programs are a subset of
+    fn parse(input: &str) -> Result<Source, DeserializeError> {
+        let map: Lrc<SourceMap> = Default::default();
+        let file = map.new_source_file(Lrc::new(FileName::Anon), input.to_string());
+
+        let program = {
+            let mut parser = Parser::new(
+                Syntax::Typescript(TsSyntax::default()),
+                StringInput::from(&*file),
+                None,
+            );
+
+            // The recovered errors fail the parsing too, the program is expected to compile
+            parser.parse_program().and_then(|program| {
+                match parser.take_errors().into_iter().next() {
+                    Some(error) => Err(error),
+                    None => Ok(program),
+                }
+            })
+        };
+        let program = program.map_err(|error| {
+            DeserializeError::Parse(format_error(&map, &error.kind().msg(), error.span()))
+        })?;
+
+        Ok(Source { map, file, program })
+    }
+
+    fn text(&self, span: Span) -> String {
+        let start = span.lo.0.saturating_sub(self.file.start_pos.0) as usize;
+        let end = span.hi.0.saturating_sub(self.file.start_pos.0) as usize;
+
+        self.file
+            .src
+            .get(start..end)
+            .unwrap_or_default()
+            .to_string()
+    }
+
+    fn error(&self, message: &str, span: Span) -> String {
+        format_error(&self.map, message, span)
+    }
+}
+
+fn format_error(map: &SourceMap, message: &str, span: Span) -> String {
+    let position = map.lookup_char_pos(span.lo);
+
+    format!(
+        "{} at line {}, column {}",
+        message,
+        position.line,
+        position.col_display + 1
+    )
+}
+
+/***
+ * Javascript / Typescript expressions of the resource arguments as JSON values.
+ * Property accesses are `${...}` references, template literals keep their
+ * placeholders and expressions which cannot be evaluated (eg: timestamp()) are null
+ */
+struct Evaluator<'a> {
+    source: &'a Source,
+    constants: &'a Map<String, Value>,
+}
+
+impl<'a> Evaluator<'a> {
+    fn new(source: &'a Source, constants: &'a Map<String, Value>) -> Evaluator<'a> {
+        Evaluator { source, constants }
+    }
+
+    fn evaluate(&self, expression: &Expr) -> Result<Value, String> {
+        let value = match expression {
+            Expr::Lit(Lit::Str(value)) => Value::String(value.value.to_atom_lossy().to_string()),
+            Expr::Lit(Lit::Num(number)) => as_number(number.value),
+            Expr::Lit(Lit::Bool(value)) => Value::Bool(value.value),
+            Expr::Object(object) => self.evaluate_object(object)?,
+            Expr::Array(array) => self.evaluate_array(array)?,
+            Expr::Tpl(template) => Value::String(self.evaluate_template(template)?),
+            // Tagged templates are their template (eg: pulumi.interpolate`${server}/app`)
+            Expr::TaggedTpl(template) => Value::String(self.evaluate_template(&template.tpl)?),
+            Expr::Paren(expression) => self.evaluate(&expression.expr)?,
+            // Typescript assertions have no value (eg: "Single" as const, config.image!)
+            Expr::TsAs(expression) => self.evaluate(&expression.expr)?,
+            Expr::TsSatisfies(expression) => self.evaluate(&expression.expr)?,
+            Expr::TsNonNull(expression) => self.evaluate(&expression.expr)?,
+            Expr::TsConstAssertion(expression) => self.evaluate(&expression.expr)?,
+            Expr::TsTypeAssertion(expression) => self.evaluate(&expression.expr)?,
+            Expr::Unary(unary) => match (unary.op, self.evaluate(&unary.arg)?) {
+                (UnaryOp::Bang, Value::Bool(value)) => Value::Bool(!value),
+                (UnaryOp::Minus, Value::Number(number)) => {
+                    as_number(-number.as_f64().unwrap_or_default())
+                }
+                _ => Value::Null,
+            },
+            Expr::Bin(binary) => self.evaluate_binary(binary)?,
+            Expr::Call(call) => self.evaluate_call(call)?,
+            Expr::Ident(name) if &*name.sym == "undefined" => Value::Null,
+            expression if is_reference(expression) => self.evaluate_reference(expression),
+            _ => Value::Null,
+        };
+
+        Ok(value)
+    }
+
+    /// Value of a variable or of a property of it (eg: app.port)
+    fn resolve_constant(&self, name: &str) -> Option<Value> {
+        let mut segments = name.split('.');
+        let mut value = self.constants.get(segments.next()?)?;
+
+        for segment in segments {
+            value = value.get(segment)?;
+        }
+
+        Some(value.clone())
+    }
+
+    /// Unknown variables are tokens (eg: ${registry.loginServer}), like their indexers
+    fn evaluate_reference(&self, expression: &Expr) -> Value {
+        match get_dotted_name(expression) {
+            Some(name) => self
+                .resolve_constant(&name)
+                .unwrap_or_else(|| Value::String(format!("${{{}}}", name))),
+            None => Value::String(format!("${{{}}}", self.source.text(expression.span()))),
+        }
+    }
+
+    /// Empty objects set nothing (eg: configuration: {})
+    fn evaluate_object(&self, object: &ObjectLit) -> Result<Value, String> {
+        let mut properties = Map::new();
+
+        for property in &object.props {
+            let property = match property {
+                PropOrSpread::Prop(property) => property,
+                // Spread objects are merged when they are known (eg: ...defaults)
+                PropOrSpread::Spread(spread) => {
+                    if let Value::Object(spread) = self.evaluate(&spread.expr)? {
+                        properties.extend(spread);
                     }
+                    continue;
+                }
+            };
+
+            match &**property {
+                Prop::KeyValue(property) => {
+                    let key = match &property.key {
+                        // Computed keys (eg: ["targetPort"]: 80 or [portKey]: 80)
+                        PropName::Computed(key) => match self.evaluate(&key.expr)? {
+                            Value::String(key) => key,
+                            key => key.to_string(),
+                        },
+                        PropName::Num(key) => as_number(key.value).to_string(),
+                        key => get_key_name(key).unwrap_or_default(),
+                    };
+                    properties.insert(key, self.evaluate(&property.value)?);
+                }
+                // Shorthand properties are the value of the variable (eg: { name })
+                Prop::Shorthand(name) => {
+                    properties.insert(
+                        name.sym.to_string(),
+                        self.evaluate_reference(&Expr::Ident(name.clone())),
+                    );
                 }
-            } else {
-                "".to_string()
+                // Methods, getters and setters cannot be evaluated (eg: toString() { ... })
+                _ => {}
+            }
+        }
+
+        if properties.is_empty() {
+            return Ok(Value::Null);
+        }
+
+        Ok(Value::Object(properties))
+    }
+
+    fn evaluate_array(&self, array: &ArrayLit) -> Result<Value, String> {
+        let mut items = vec![];
+
+        for item in array.elems.iter().flatten() {
+            match (item.spread, self.evaluate(&item.expr)?) {
+                // Spread arrays are appended when they are known (eg: ...sidecars)
+                (Some(_), Value::Array(spread)) => items.extend(spread),
+                (Some(_), _) => {}
+                (None, value) => items.push(value),
+            }
+        }
+
+        Ok(Value::Array(items))
+    }
+
+    /// Template literals keep their unknown placeholders as tokens (eg: `${server}/app:v1`)
+    fn evaluate_template(&self, template: &Tpl) -> Result<String, String> {
+        let mut output = String::new();
+
+        for (index, quasi) in template.quasis.iter().enumerate() {
+            match &quasi.cooked {
+                Some(cooked) => output.push_str(&cooked.to_atom_lossy()),
+                None => output.push_str(&quasi.raw),
+            }
+
+            let Some(expression) = template.exprs.get(index) else {
+                continue;
             };
+            // Known variables are substituted (eg: `${name}-app` in a loop)
+            match self.evaluate(expression)? {
+                Value::String(value) => output.push_str(&value),
+                value @ (Value::Number(_) | Value::Bool(_)) => output.push_str(&value.to_string()),
+                _ => output.push_str(&format!("${{{}}}", self.source.text(expression.span()))),
+            }
+        }
 
-            let key = format!("\"{}\"", key).replace("\"\"", "\"");
-            let value = value
-                .replace("\"\"", "\"")
-                .replace("`", "")
-                .replace("'", "");
+        Ok(output)
+    }
 
-            let mut computed = format!("{key}:{value}");
+    fn evaluate_binary(&self, binary: &BinExpr) -> Result<Value, String> {
+        let (left, right) = (self.evaluate(&binary.left)?, self.evaluate(&binary.right)?);
 
-            // TODO: clean that
-            if value.contains("{}") {
-                computed = "".to_string();
+        Ok(match (binary.op, left, right) {
+            // String concatenations (eg: prefix + "-app")
+            (BinaryOp::Add, Value::String(left), Value::String(right)) => {
+                Value::String(left + &right)
             }
+            // Fallbacks are the first known value (eg: config.get("image") ?? "node-12")
+            (BinaryOp::NullishCoalescing | BinaryOp::LogicalOr, Value::Null, fallback) => fallback,
+            (BinaryOp::NullishCoalescing | BinaryOp::LogicalOr, value, _) => value,
+            _ => Value::Null,
+        })
+    }
 
-            computed
+    fn evaluate_call(&self, call: &CallExpr) -> Result<Value, String> {
+        let Callee::Expr(callee) = &call.callee else {
+            return Ok(Value::Null);
+        };
+        let argument = |index: usize| {
+            call.args
+                .get(index)
+                .map(|argument| self.evaluate(&argument.expr))
+                .transpose()
+        };
+
+        // Applied outputs keep the reference of the output (eg: image.imageName.apply(...))
+        if let Expr::Member(member) = &**callee {
+            if member.prop.is_ident_with("apply") {
+                return match (&*member.obj, get_dotted_name(&member.obj)) {
+                    (Expr::Call(all), _)
+                        if get_dotted_name_of_callee(all).as_deref() == Some("pulumi.all") =>
+                    {
+                        self.evaluate_pulumi_all(all, call)
+                    }
+                    (_, Some(name)) => Ok(Value::String(format!("${{{}}}", name))),
+                    (output, None) => self.evaluate(output),
+                };
+            }
         }
-        None => a.to_string(),
-    };
 
-    computed
+        let name = get_dotted_name(callee).unwrap_or_default();
+        let value = match name.as_str() {
+            "pulumi.all" => return Err(self.unsupported_pulumi_all(call.span)),
+            // Wrapped outputs are their value (eg: pulumi.output("node-12"))
+            "pulumi.output" => argument(0)?.unwrap_or_default(),
+            // Stack config values are tokens resolved with the config (eg: config.require("root"))
+            name if name.ends_with(".require") => match argument(0)? {
+                Some(Value::String(key)) => Value::String(format!("${{config.{}}}", key)),
+                _ => Value::Null,
+            },
+            // Outputs of other stacks are stack output tokens (eg: infra.getOutput("apiImage"))
+            name if name.ends_with(".getOutput") || name.ends_with(".requireOutput") => {
+                let (variable, _) = name.rsplit_once('.').unwrap_or_default();
+
+                match argument(0)? {
+                    Some(Value::String(key)) => {
+                        Value::String(format!("${{{}.outputs[\"{}\"]}}", variable, key))
+                    }
+                    _ => Value::Null,
+                }
+            }
+            _ => Value::Null,
+        };
+
+        Ok(value)
+    }
+
+    fn unsupported_pulumi_all(&self, span: Span) -> String {
+        self.source.error(
+            &format!(
+                "Unsupported pulumi.all expression: {}",
+                self.source.text(span)
+            ),
+            span,
+        )
+    }
+
+    /// `pulumi.all([...]).apply(([a, b]) => ...)` is the image reference of the array,
+    /// the value of the function when no image is referenced
+    fn evaluate_pulumi_all(&self, all: &CallExpr, apply: &CallExpr) -> Result<Value, String> {
+        let items = match all.args.first() {
+            Some(argument) => self.evaluate(&argument.expr)?,
+            None => Value::Null,
+        };
+        let value = match (items.as_array(), apply.args.first().map(|x| &*x.expr)) {
+            (Some(items), Some(Expr::Arrow(function))) => {
+                self.evaluate_applied_function(function, items)
+            }
+            _ => None,
+        }
+        .ok_or_else(|| self.unsupported_pulumi_all(apply.span))?;
+        let image = items
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(Value::as_str)
+            .find(|reference| reference.ends_with(".imageName}"));
+
+        Ok(match (image, value) {
+            (Some(reference), _) => Value::String(reference.to_string()),
+            (None, value @ Value::String(_)) => value,
+            // The function is kept as a token, it is reported once unresolved (eg: path.join(root, "api"))
+            (None, _) => Value::String("${pulumi.all}".to_string()),
+        })
+    }
+
+    /// The value of the arrow function of `.apply(...)` for the given items (eg: the
+    /// concatenation of ([root]) => root + "/api"), null when its body is a block
+    fn evaluate_applied_function(&self, function: &ArrowExpr, items: &[Value]) -> Option<Value> {
+        let binding = Binding::from_pattern(function.params.first()?)?;
+
+        match &*function.body {
+            ArrowFunctionBody::Expr(body) => {
+                let mut constants = self.constants.clone();
+                binding.bind(&Value::Array(items.to_vec()), &mut constants);
+
+                Evaluator::new(self.source, &constants).evaluate(body).ok()
+            }
+            ArrowFunctionBody::FunctionBody(_) => Some(Value::Null),
+        }
+    }
+}
+
+fn get_dotted_name_of_callee(call: &CallExpr) -> Option<String> {
+    match &call.callee {
+        Callee::Expr(callee) => get_dotted_name(callee),
+        _ => None,
+    }
 }
 
-fn prune_output(output: String) -> String {
-    //  s = s.replace("})", "}").replace(",}", "}");
-    output.replace("})", "}").replace(",}", "}")
+/***
+ * Values of the variables (eg: const appPort = 3000), substituted where they are used,
+ * in declaration order so a variable may use the previous ones
+ */
+struct ConstantsCollector<'a> {
+    source: &'a Source,
+    constants: Map<String, Value>,
 }
 
-fn get_images(input: &str) -> Vec<ContainerImageBluePrint> {
-    let images_services: Vec<(String, String, Option<String>)> =
-        Regex::new(r####"((const|let) ?(?P<serviceName>.+) ?= ?)?new docker.Image\("(?P<name>.+)",( ?)(?P<value>\{(\n.+)+[^;s"\n.+])"####)
-            .unwrap()
-            .captures_iter(&input)
-            .map(|container| {
+impl Visit for ConstantsCollector<'_> {
+    fn visit_var_declarator(&mut self, declarator: &VarDeclarator) {
+        if let (Pat::Ident(name), Some(value)) = (&declarator.name, &declarator.init) {
+            let value = Evaluator::new(self.source, &self.constants).evaluate(value);
 
-                let service_name = match container.name("serviceName") {
-                    Some(v) => {
-                        Some(v.as_str().trim().to_string())
-                    },
-                    None => None,
-                };
+            if let Some(value) = value.ok().filter(|value| !value.is_null()) {
+                self.constants.insert(name.id.sym.to_string(), value);
+            }
+        }
 
-                (container["name"].to_owned(), container["value"].to_owned(), service_name)
-})
-            .collect();
+        declarator.visit_children_with(self);
+    }
+}
 
-    let mut images: Vec<ContainerImageBluePrint> = vec![];
+fn get_constants(source: &Source) -> Map<String, Value> {
+    let mut collector = ConstantsCollector {
+        source,
+        constants: Map::new(),
+    };
+    source.program.visit_with(&mut collector);
 
-    for (_image_name, image, service_name) in images_services {
-        let mut s = String::from("");
+    collector.constants
+}
 
-        for line in image.trim().lines() {
-            let parsed_line = parse_line(line);
+/***
+ * The `new` expressions of the given constructors, with the variable they are assigned
+ * to (eg: const remixImage = new docker.Image(...)) and the loops around them
+ */
+struct NewExpressionFinder<'a> {
+    evaluator: Evaluator<'a>,
+    constructors: &'a [String],
+    loops: Vec<Loop>,
+    expressions: Vec<NewExpression>,
+}
 
-            s.push_str(&parsed_line);
+impl NewExpressionFinder<'_> {
+    fn add_expression(&mut self, expression: &NewExpr, variable: Option<String>) {
+        let (Some(constructor), Some(arguments)) =
+            (get_dotted_name(&expression.callee), &expression.args)
+        else {
+            return;
+        };
+
+        if self
+            .constructors
+            .contains(&split_api_version(&constructor).0)
+        {
+            self.expressions.push(NewExpression {
+                variable,
+                constructor,
+                arguments: arguments
+                    .iter()
+                    .map(|argument| (*argument.expr).clone())
+                    .collect(),
+                loops: self.loops.clone(),
+            });
         }
+    }
+
+    /// The body of a loop over the items of the iterable, a known array
+    fn visit_loop<T: VisitWith<Self>>(
+        &mut self,
+        binding: Binding,
+        index: Option<String>,
+        iterable: &Expr,
+        body: &T,
+    ) {
+        let items = self.evaluator.evaluate(iterable).ok();
+
+        self.loops.push(Loop {
+            binding,
+            index,
+            items: items.and_then(|items| items.as_array().cloned()),
+            iterable: self.evaluator.source.text(iterable.span()),
+        });
+        body.visit_with(self);
+        self.loops.pop();
+    }
+}
 
-        s = prune_output(s)
-            // Add custom behavior
-            .replace("imageName", "name");
+impl Visit for NewExpressionFinder<'_> {
+    fn visit_var_declarator(&mut self, declarator: &VarDeclarator) {
+        match (&declarator.name, declarator.init.as_deref()) {
+            (Pat::Ident(name), Some(Expr::New(expression))) => {
+                self.add_expression(expression, Some(name.id.sym.to_string()));
+                expression.visit_children_with(self);
+            }
+            _ => declarator.visit_children_with(self),
+        }
+    }
 
-        let mut serialized: ContainerImageBluePrint = serde_json::from_str(&s).unwrap();
+    fn visit_new_expr(&mut self, expression: &NewExpr) {
+        self.add_expression(expression, None);
+        expression.visit_children_with(self);
+    }
 
-        if service_name.is_some() {
-            serialized.name = Some(service_name.clone().unwrap());
-            serialized.reference_name = Some(format!("{}.imageName", service_name.unwrap()));
+    /// The loops over an array (eg: for (const name of ["api", "worker"]))
+    fn visit_for_of_stmt(&mut self, statement: &ForOfStmt) {
+        let binding = match &statement.left {
+            ForHead::VarDecl(declaration) => declaration
+                .decls
+                .first()
+                .and_then(|declarator| Binding::from_pattern(&declarator.name)),
+            ForHead::Pat(pattern) => Binding::from_pattern(pattern),
+            ForHead::UsingDecl(_) => None,
+        };
+
+        statement.right.visit_with(self);
+        match binding {
+            Some(binding) => self.visit_loop(binding, None, &statement.right, &statement.body),
+            None => statement.body.visit_with(self),
         }
+    }
 
-        images.push(serialized);
+    /// The callbacks of `map` / `forEach` on an array
+    /// (eg: ["api", "worker"].map((name, index) => ...))
+    fn visit_call_expr(&mut self, call: &CallExpr) {
+        let array = match &call.callee {
+            Callee::Expr(callee) => match &**callee {
+                Expr::Member(member)
+                    if member.prop.is_ident_with("map") || member.prop.is_ident_with("forEach") =>
+                {
+                    Some(&member.obj)
+                }
+                _ => None,
+            },
+            _ => None,
+        };
+        let callback = call.args.first().map(|argument| &*argument.expr);
+        let parameters: Vec<&Pat> = match callback {
+            Some(Expr::Arrow(function)) => function.params.iter().collect(),
+            Some(Expr::Fn(function)) => function
+                .function
+                .params
+                .iter()
+                .map(|parameter| &parameter.pat)
+                .collect(),
+            _ => vec![],
+        };
+        let binding = parameters.first().and_then(|x| Binding::from_pattern(x));
+
+        match (array, callback, binding) {
+            (Some(array), Some(callback), Some(binding)) => {
+                array.visit_with(self);
+                let index = parameters.get(1).and_then(|x| get_name(x));
+                self.visit_loop(binding, index, array, callback);
+                call.args[1..].visit_with(self);
+            }
+            _ => call.visit_children_with(self),
+        }
     }
+}
+
+fn find_new_expressions(
+    source: &Source,
+    constants: &Map<String, Value>,
+    constructors: &[String],
+) -> Vec<NewExpression> {
+    let mut finder = NewExpressionFinder {
+        evaluator: Evaluator::new(source, constants),
+        constructors,
+        loops: vec![],
+        expressions: vec![],
+    };
+    source.program.visit_with(&mut finder);
 
-    images
+    finder.expressions
 }
 
-fn get_apps(input: &str) -> Vec<ContainerAppBluePrint> {
-    let container_app_services: Vec<(String, String)> = Regex::new(
-        r####"new app.ContainerApp\("(?P<name>.+)",( ?)(?P<value>\{(\n.+)+[^;s"\n.+])"####,
+const CONTAINER_APP_MODULES: [(&str, &str); 2] = [
+    ("@pulumi/azure-native/app", "ContainerApp"),
+    ("@pulumi/azure-native", "app.ContainerApp"),
+];
+const JOB_MODULES: [(&str, &str); 2] = [
+    ("@pulumi/azure-native/app", "Job"),
+    ("@pulumi/azure-native", "app.Job"),
+];
+// Container apps of the classic azure provider
+const CLASSIC_CONTAINER_APP_MODULES: [(&str, &str); 2] = [
+    ("@pulumi/azure", "containerapp.App"),
+    ("@pulumi/azure/containerapp", "App"),
+];
+const IMAGE_MODULES: [(&str, &str); 1] = [("@pulumi/docker", "Image")];
+
+/***
+ * Modules imported by the program, with their local name, as
+ * `(local, module, member)`, the member is none for the whole module (eg: in
+ * `import * as app from "@pulumi/azure-native/app"` or `const app = require(...)`)
+ */
+fn get_imports(input: &str) -> Vec<(String, String, Option<String>)> {
+    let mut imports = vec![];
+
+    // Namespace and default imports (eg: import * as app from "..." or import app from "...")
+    let modules = Regex::new(
+        r#"import\s+(?:\*\s+as\s+)?(?P<local>[\w$]+)\s+from\s+["'](?P<module>[^"']+)["']|(?:const|let|var|import)\s+(?P<required>[\w$]+)\s*=\s*require\(\s*["'](?P<requiredModule>[^"']+)["']\s*\)"#,
     )
-    .unwrap()
-    .captures_iter(&input)
-    .map(|container| (container["name"].to_owned(), container["value"].to_owned()))
-    .collect();
+    .unwrap();
+
+    for captures in modules.captures_iter(input) {
+        let local = captures.name("local").or(captures.name("required"));
+        let module = captures.name("module").or(captures.name("requiredModule"));
+
+        if let (Some(local), Some(module)) = (local, module) {
+            imports.push((
+                local.as_str().to_string(),
+                module.as_str().to_string(),
+                None,
+            ));
+        }
+    }
 
-    let mut containers: Vec<ContainerAppBluePrint> = vec![];
+    // Named imports (eg: import { ContainerApp as CA } from "..." or const { ContainerApp } = require(...))
+    let members = Regex::new(
+        r#"import\s+(?:[\w$]+\s*,\s*)?\{(?P<imported>[^}]*)\}\s*from\s+["'](?P<module>[^"']+)["']|(?:const|let|var)\s*\{(?P<required>[^}]*)\}\s*=\s*require\(\s*["'](?P<requiredModule>[^"']+)["']\s*\)"#,
+    )
+    .unwrap();
+
+    for captures in members.captures_iter(input) {
+        let (members, module, separator) = match captures.name("imported") {
+            Some(members) => (members.as_str(), &captures["module"], " as "),
+            None => (&captures["required"], &captures["requiredModule"], ":"),
+        };
+
+        for member in members.split(',') {
+            let (imported, local) = member.split_once(separator).unwrap_or((member, member));
+            let (imported, local) = (imported.trim(), local.trim());
+
+            if !imported.is_empty() {
+                imports.push((
+                    local.to_string(),
+                    module.to_string(),
+                    Some(imported.to_string()),
+                ));
+            }
+        }
+    }
 
-    for (_container_name, container) in container_app_services {
-        let mut s = String::from("");
+    imports
+}
 
-        for line in container.trim().lines() {
-            let parsed_line = parse_line(line);
-            s.push_str(&parsed_line);
+/***
+ * API version pinned by a module or a constructor (eg: v20230501 in
+ * @pulumi/azure-native/app/v20230501 or app.v20230501.ContainerApp), with the path without it
+ */
+fn split_api_version(path: &str) -> (String, Option<String>) {
+    let re = Regex::new(r"[./](v\d{8}(?:preview)?)\b").expect("Should match API version regex");
+
+    match re.captures(path) {
+        Some(captures) => (
+            path.replacen(&captures[0], "", 1),
+            Some(captures[1].to_string()),
+        ),
+        None => (path.to_string(), None),
+    }
+}
+
+/***
+ * Expressions constructing the resource of the given modules (eg: app.ContainerApp), with
+ * the API version pinned by their import if any, programs which import none of the modules
+ * are expected to use the conventional alias
+ */
+fn get_versioned_constructors(
+    input: &str,
+    modules: &[(&str, &str)],
+    alias: &str,
+) -> Vec<(String, Option<String>)> {
+    let imports = get_imports(input);
+    let is_imported = imports.iter().any(|(_, module, _)| {
+        let (module, _) = split_api_version(module);
+        modules.iter().any(|(name, _)| *name == module)
+    });
+
+    if !is_imported {
+        return vec![(format!("{}.{}", alias, modules[0].1), None)];
+    }
+
+    imports
+        .into_iter()
+        .filter_map(|(local, module, member)| {
+            let (module, version) = split_api_version(&module);
+            let (_, path) = modules.iter().find(|(name, _)| *name == module)?;
+            let constructor = match member {
+                None => Some(format!("{}.{}", local, path)),
+                // The member is the resource (eg: ContainerApp) or a namespace of it (eg: app)
+                Some(member) if member == *path => Some(local),
+                Some(member) => path
+                    .strip_prefix(&format!("{}.", member))
+                    .map(|path| format!("{}.{}", local, path)),
+            };
+
+            constructor.map(|constructor| (constructor, version))
+        })
+        .collect()
+}
+
+fn get_constructors(input: &str, modules: &[(&str, &str)], alias: &str) -> Vec<String> {
+    get_versioned_constructors(input, modules, alias)
+        .into_iter()
+        .map(|(constructor, _)| constructor)
+        .collect()
+}
+
+// Variable, resource name, arguments and constructor of a resource
+type Declaration = (Option<String>, String, Value, String);
+
+/***
+ * Resources created with the given constructors (eg: new app.ContainerApp("frontend", { ... }))
+ */
+fn get_declarations(
+    input: &str,
+    constructors: &[String],
+) -> Result<Vec<Declaration>, DeserializeError> {
+    let source = Source::parse(input)?;
+    let constants = get_constants(&source);
+    let mut declarations = vec![];
+
+    for expression in find_new_expressions(&source, &constants, constructors) {
+        // Resources created in a loop over an unknown array are reported in the warnings
+        for constants in iterate_loops(&expression.loops, &constants).unwrap_or_default() {
+            let evaluator = Evaluator::new(&source, &constants);
+            let mut arguments = expression
+                .arguments
+                .iter()
+                .map(|argument| evaluator.evaluate(argument))
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(DeserializeError::Parse)?
+                .into_iter();
+            let name = arguments
+                .next()
+                .and_then(|name| name.as_str().map(|name| name.to_string()))
+                .or(expression.variable.clone())
+                .unwrap_or_default();
+
+            declarations.push((
+                expression.variable.clone(),
+                name,
+                arguments.next().unwrap_or_default(),
+                expression.constructor.clone(),
+            ));
         }
-        s = prune_output(s);
+    }
+
+    Ok(declarations)
+}
 
-        let serialized: ContainerAppBluePrint = serde_json::from_str(&s).unwrap();
+/***
+ * Variables of each iteration of the loops, from the outer to the inner one,
+ * none when an array is not known
+ */
+fn iterate_loops(
+    loops: &[Loop],
+    constants: &Map<String, Value>,
+) -> Option<Vec<Map<String, Value>>> {
+    let mut iterations = vec![constants.clone()];
+
+    for scope in loops {
+        let items = scope.items.as_ref()?;
+
+        iterations = iterations
+            .into_iter()
+            .flat_map(|constants| {
+                items.iter().enumerate().map(move |(index, item)| {
+                    let mut constants = constants.clone();
+                    scope.binding.bind(item, &mut constants);
+                    if let Some(name) = &scope.index {
+                        constants.insert(name.clone(), Value::Number(index.into()));
+                    }
 
-        containers.push(serialized);
+                    constants
+                })
+            })
+            .collect();
     }
 
-    containers
+    Some(iterations)
 }
 
-pub fn deserialize(input: &str) -> Result<Vec<ContainerAppConfiguration>, String> {
-    let input = Regex::new(r"[^});](\n){2,}")
-        .unwrap()
-        .replace_all(input, "");
+fn get_images(input: &str) -> Result<Vec<ContainerImageBluePrint>, DeserializeError> {
+    let constructors = get_constructors(input, &IMAGE_MODULES, "docker");
 
-    let images = get_images(&input);
-    let apps = get_apps(&input);
+    Ok(get_declarations(input, &constructors)?
+        .into_iter()
+        .map(|(variable, _, mut arguments, _)| {
+            if let Some(properties) = arguments.as_object_mut() {
+                if let Some(name) = properties.remove("imageName") {
+                    properties.insert("name".to_string(), name);
+                }
+            }
+
+            let mut image: ContainerImageBluePrint =
+                serde_json::from_value(arguments).expect("Image should have a build context");
 
-    let services = pulumi::build_configuration(apps, images);
+            if let Some(variable) = variable {
+                image.reference_name = Some(variable.clone());
+                image.name = image.name.or(Some(variable));
+            }
+
+            image
+        })
+        .collect())
+}
 
-    match services {
-        Some(val) => Ok(val),
-        None => Err("No container to deserialize".to_string()),
+// Name, arguments and pinned API version of an app
+type AppProperties = (String, Value, Option<String>);
+
+/***
+ * Arguments of the container apps, the apps of the classic provider are normalized
+ * to the azure-native shape
+ */
+fn get_app_properties(input: &str) -> Result<Vec<AppProperties>, DeserializeError> {
+    let classic_constructors = get_constructors(input, &CLASSIC_CONTAINER_APP_MODULES, "azure");
+    let versions: Vec<(String, Option<String>)> =
+        get_versioned_constructors(input, &CONTAINER_APP_MODULES, "app");
+    let constructors: Vec<String> = versions
+        .iter()
+        .map(|(constructor, _)| constructor.clone())
+        .chain(classic_constructors.iter().cloned())
+        .collect();
+
+    get_declarations(input, &constructors)?
+        .into_iter()
+        .map(|(_, name, arguments, constructor)| {
+            // The version is pinned by the constructor (eg: app.v20230501.ContainerApp) or its import
+            let (constructor, version) = split_api_version(&constructor);
+            let version = version.or_else(|| {
+                versions
+                    .iter()
+                    .find(|(versioned, _)| *versioned == constructor)
+                    .and_then(|(_, version)| version.clone())
+            });
+
+            match classic_constructors.contains(&constructor) {
+                true => serde_json::to_value(pulumi::normalize_classic_app(&as_yaml_properties(
+                    &arguments,
+                )?))
+                .map(|arguments| (name, arguments, None))
+                .map_err(|e| DeserializeError::Parse(e.to_string())),
+                false => Ok((name, arguments, version)),
+            }
+        })
+        .collect()
+}
+
+fn get_apps(input: &str) -> Result<Vec<ContainerAppBluePrint>, DeserializeError> {
+    get_app_properties(input)?
+        .into_iter()
+        .map(|(name, properties, version)| {
+            let mut app: ContainerAppBluePrint = serde_json::from_value(properties)
+                .map_err(|e| DeserializeError::Parse(e.to_string()))?;
+            app.name = Some(name);
+            app.api_version = version;
+            pulumi::normalize_scale(&mut app);
+
+            Ok(app)
+        })
+        .collect()
+}
+
+/***
+ * Jobs of the program, built as apps which run once
+ */
+fn get_jobs(input: &str) -> Result<Vec<ContainerAppBluePrint>, DeserializeError> {
+    let versions = get_versioned_constructors(input, &JOB_MODULES, "app");
+    let constructors: Vec<String> = versions
+        .iter()
+        .map(|(constructor, _)| constructor.clone())
+        .collect();
+
+    get_declarations(input, &constructors)?
+        .into_iter()
+        .map(|(_, name, arguments, constructor)| {
+            let (constructor, version) = split_api_version(&constructor);
+            let version = version.or_else(|| {
+                versions
+                    .iter()
+                    .find(|(versioned, _)| *versioned == constructor)
+                    .and_then(|(_, version)| version.clone())
+            });
+            let job: JobBluePrint = serde_json::from_value(arguments)
+                .map_err(|e| DeserializeError::Parse(e.to_string()))?;
+            let mut app = ContainerAppBluePrint::from(JobBluePrint {
+                name: Some(name),
+                api_version: version,
+                ..job
+            });
+            pulumi::normalize_scale(&mut app);
+
+            Ok(app)
+        })
+        .collect()
+}
+
+fn as_yaml_properties(properties: &Value) -> Result<serde_yaml::Value, DeserializeError> {
+    serde_yaml::to_value(properties).map_err(|e| DeserializeError::Parse(e.to_string()))
+}
+
+/***
+ * Fields of the apps which are not translated, the input is expected to be valid
+ */
+pub fn collect_warnings(input: &str) -> Vec<String> {
+    let constructors = get_constructors(input, &CONTAINER_APP_MODULES, "app");
+    let mut warnings: Vec<String> = vec![];
+
+    // Apps created in a loop over an unknown array (eg: for (const app of config.requireObject("apps")))
+    let expressions = Source::parse(input)
+        .map(|source| find_new_expressions(&source, &get_constants(&source), &constructors))
+        .unwrap_or_default();
+
+    for scope in expressions.iter().flat_map(|expression| &expression.loops) {
+        let warning = format!(
+            "ignored ContainerApp created in a loop over {}, the array is not known",
+            scope.iterable
+        );
+
+        if scope.items.is_none() && !warnings.contains(&warning) {
+            warnings.push(warning);
+        }
     }
+
+    warnings.extend(pulumi::collect_api_version_warnings(
+        &get_apps(input).unwrap_or_default(),
+    ));
+
+    let ignored_fields = get_app_properties(input)
+        .unwrap_or_default()
+        .into_iter()
+        .flat_map(|(name, properties, _)| {
+            let properties = as_yaml_properties(&properties).unwrap_or_default();
+
+            pulumi::collect_ignored_fields(&properties)
+                .unwrap_or_default()
+                .into_iter()
+                .map(move |path| format!("ignored {} in {}", path, name))
+        });
+
+    warnings.extend(ignored_fields);
+    warnings
 }
 
-mod tests {
-    use crate::serializer::{
-        ConfigurationBluePrint, ContainerBluePrint, DaprBluePrint, IngressBluePrint,
-        TemplateBluePrint,
-    };
-    use std::panic;
+pub fn deserialize(
+    input: &str,
+    options: &BuildOptions,
+) -> Result<Vec<ContainerAppConfiguration>, DeserializeError> {
+    deserialize_per_app(input, options).map(pulumi::flatten_apps)
+}
 
-    use super::*;
+/***
+ * Services of each app, see `pulumi::build_per_app`
+ */
+pub fn deserialize_per_app(
+    input: &str,
+    options: &BuildOptions,
+) -> Result<Vec<(String, Vec<ContainerAppConfiguration>)>, DeserializeError> {
+    let (apps, images) = deserialize_blueprints(input, options)?;
 
-    #[test]
-    fn test_parse_line() {
-        let output = parse_line("key: ");
-        assert_eq!("\"key\":", output);
+    pulumi::build_per_app(apps, images, options.include.as_deref(), options)
+}
 
-        let output = parse_line("key: value");
-        assert_eq!("\"key\":\"value\",", output);
+/***
+ * Apps and images of the input, their references are not resolved yet
+ */
+pub fn deserialize_blueprints(
+    input: &str,
+    options: &BuildOptions,
+) -> Result<pulumi::BluePrints, DeserializeError> {
+    if options.strict {
+        for (_, properties, _) in get_app_properties(input)? {
+            pulumi::validate_known_fields(&as_yaml_properties(&properties)?)?;
+        }
+    }
+
+    let mut apps = get_apps(input)?;
+    apps.extend(get_jobs(input)?);
+    let images = get_images(input)?;
+
+    Ok((apps, images))
+}
+
+// Extensions tried for an import without one, in the order of precedence of tsc
+const MODULE_EXTENSIONS: [&str; 2] = ["ts", "js"];
+
+/***
+ * Relative modules imported by the program (eg: import { api } from "./apps/api")
+ */
+fn get_relative_imports(input: &str) -> Vec<String> {
+    let re =
+        Regex::new(r#"(?:\bfrom|\bimport|\brequire\s*\()\s*["'](?P<module>\.\.?(?:/[^"']*)?)["']"#)
+            .unwrap();
+
+    re.captures_iter(input)
+        .map(|captures| captures["module"].to_string())
+        .collect()
+}
+
+/***
+ * File of a relative module: the file itself, the file with an extension (a `.js` import
+ * may target a `.ts` file) or the index file of a folder
+ */
+fn resolve_module(directory: &Path, module: &str) -> Result<PathBuf, DeserializeError> {
+    let path = directory.join(module);
+    let stem = module.strip_suffix(".js").map(|stem| directory.join(stem));
+    let candidates = std::iter::once(path.clone())
+        .chain(stem.map(|stem| stem.with_extension("ts")))
+        .chain(MODULE_EXTENSIONS.iter().map(|extension| {
+            let mut file = path.clone().into_os_string();
+            file.push(format!(".{}", extension));
+            PathBuf::from(file)
+        }))
+        .chain(
+            MODULE_EXTENSIONS
+                .iter()
+                .map(|extension| path.join(format!("index.{}", extension))),
+        );
+
+    for candidate in candidates {
+        if candidate.is_file() {
+            return Ok(candidate);
+        }
+    }
 
-        let output = parse_line("\"key\":\"value\"");
-        assert_eq!("\"key\":\"value\",", output);
+    Err(DeserializeError::MissingFile(path.display().to_string()))
+}
 
-        let output = parse_line("key:\"value\"");
-        assert_eq!("\"key\":\"value\",", output);
+fn read_module(
+    path: &Path,
+    visited: &mut HashSet<PathBuf>,
+    sources: &mut Vec<String>,
+) -> Result<(), DeserializeError> {
+    let missing_file = |_| DeserializeError::MissingFile(path.display().to_string());
+    let path = path.canonicalize().map_err(missing_file)?;
+
+    // Circular imports are read once
+    if !visited.insert(path.clone()) {
+        return Ok(());
+    }
 
-        let output = parse_line("\"key\":value");
-        assert_eq!("\"key\":\"value\",", output);
+    let input = fs::read_to_string(&path).map_err(missing_file)?;
+    let directory = path.parent().unwrap_or_else(|| Path::new("."));
 
-        let output = parse_line("\"key\":value,");
-        assert_eq!("\"key\":\"value\",", output);
+    for module in get_relative_imports(&input) {
+        read_module(&resolve_module(directory, &module)?, visited, sources)?;
+    }
 
-        let output = parse_line("\"key\":'value',");
-        assert_eq!("\"key\":\"value\",", output);
+    sources.push(input);
+    Ok(())
+}
 
-        let output = parse_line("\"key\":`value`,");
-        assert_eq!("\"key\":\"value\",", output);
+/***
+ * Program of a project split in several files, from its entry point (a file or a folder
+ * with an index file): the files reachable through relative imports are concatenated,
+ * imported files first, so that variables resolve across files
+ */
+pub fn read_project(entry: &Path) -> Result<String, DeserializeError> {
+    let entry = match entry.is_dir() {
+        true => resolve_module(entry, ".")?,
+        false => entry.to_path_buf(),
+    };
+    let mut sources = vec![];
 
-        let output = parse_line("\"key\":\"node-12\",");
-        assert_eq!("\"key\":\"node-12\",", output);
+    read_module(&entry, &mut HashSet::new(), &mut sources)?;
+    Ok(sources.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::serializer::{
+        BuildContextBluePrint, ConfigurationBluePrint, ContainerBluePrint, DaprBluePrint,
+        IngressBluePrint, ScaleBluePrint, TemplateBluePrint,
+    };
+    use std::collections::BTreeMap;
+    use std::panic;
 
-        let output = parse_line("\"key\":\"node:12\",");
-        assert_eq!("\"key\":\"node:12\",", output);
+    use super::*;
 
-        let output = parse_line("\"key\":\"node:12.4\",");
-        assert_eq!("\"key\":\"node:12.4\",", output);
+    /// Value of an expression of the resource arguments
+    fn parse(input: &str) -> Result<Value, DeserializeError> {
+        let source = Source::parse(&format!("({});", input))?;
+        let statement = match &source.program {
+            Program::Module(module) => module.body.first().and_then(|item| item.as_stmt()),
+            Program::Script(script) => script.body.first(),
+        };
+        let expression = statement.and_then(|statement| statement.as_expr()).unwrap();
+
+        Evaluator::new(&source, &Map::new())
+            .evaluate(&expression.expr)
+            .map_err(DeserializeError::Parse)
+    }
 
-        let output = parse_line("\"key\":\"{}\"");
-        assert_eq!("", output);
+    #[test]
+    fn test_parse_value() {
+        assert_eq!(
+            Ok(serde_json::json!({ "key": "value" })),
+            parse(r#"{ "key": 'value', }"#)
+        );
+        assert_eq!(
+            Ok(serde_json::json!({ "image": "localhost:5000/app:v1", "tag": "node:12.4" })),
+            parse(r#"{ image: "localhost:5000/app:v1", tag: `node:12.4` }"#)
+        );
+        assert_eq!(
+            Ok(serde_json::json!({ "targetPort": 3000, "external": true })),
+            parse(r#"{ ["targetPort"]: 3000, /* exposed */ external: true }"#)
+        );
+        assert_eq!(
+            Ok(Value::String("${remixImage.imageName}".to_string())),
+            parse("remixImage.imageName")
+        );
+        assert_eq!(
+            Ok(Value::String(
+                "${registry.loginServer}/remix:v1".to_string()
+            )),
+            parse("pulumi.interpolate`${registry.loginServer}/remix:v1`")
+        );
+        assert_eq!(
+            Ok(Value::String("${image.imageName}".to_string())),
+            parse("image.imageName.apply(name => `${name}`)")
+        );
+        assert_eq!(
+            Ok(Value::String("\n    npm run start\n".to_string())),
+            parse("`\n    npm run start\n`")
+        );
+        // Empty objects set nothing
+        assert_eq!(Ok(serde_json::json!({ "key": null })), parse("{ key: {} }"));
+
+        assert!(parse(r#"{ key: "value""#).is_err());
     }
 
     #[test]
     fn test_get_images() {
         // No valid resource
         let data = r####"
-        const test = new NoResource() {}
+        const test = new NoResource({});
         "####;
-        let output = get_images(data);
+        let output = get_images(data).unwrap();
         let expected: Vec<ContainerImageBluePrint> = vec![];
         assert_eq!(expected, output);
 
@@ -219,13 +1280,11 @@ mod tests {
             },
         });"####;
 
-        let output = get_images(data);
+        let output = get_images(data).unwrap();
         let expected = vec![ContainerImageBluePrint {
-            name: Some("remixImage".to_string()),
-            build: BuildContextBluePrint {
-                context: "../frontend".to_string(),
-            },
-            reference_name: Some("remixImage.imageName".to_string()),
+            name: Some("${registry.loginServer}/remix:v1".to_string()),
+            build: BuildContextBluePrint::new("../frontend"),
+            reference_name: Some("remixImage".to_string()),
         }];
 
         assert_eq!(expected, output);
@@ -239,13 +1298,11 @@ mod tests {
             },
         });"####;
 
-        let output = get_images(data);
+        let output = get_images(data).unwrap();
         let expected = vec![ContainerImageBluePrint {
-            name: Some("remixImage".to_string()),
-            build: BuildContextBluePrint {
-                context: "../frontend".to_string(),
-            },
-            reference_name: Some("remixImage.imageName".to_string()),
+            name: Some("node-18".to_string()),
+            build: BuildContextBluePrint::new("../frontend"),
+            reference_name: Some("remixImage".to_string()),
         }];
 
         assert_eq!(expected, output);
@@ -258,13 +1315,11 @@ mod tests {
                   build: "",
               });"####;
 
-        let output = get_images(data);
+        let output = get_images(data).unwrap();
         let expected = vec![ContainerImageBluePrint {
             name: Some("remixImage".to_string()),
-            build: BuildContextBluePrint {
-                context: "../frontend".to_string(),
-            },
-            reference_name: Some("remixImage.imageName".to_string()),
+            build: BuildContextBluePrint::new("../frontend"),
+            reference_name: Some("remixImage".to_string()),
         }];
 
         assert_eq!(expected, output);*/
@@ -285,9 +1340,9 @@ mod tests {
     fn test_get_apps() {
         // No valid resource
         let data = r####"
-                const test = new NoResource() {}
+                const test = new NoResource({});
                 "####;
-        let output = get_apps(data);
+        let output = get_apps(data).unwrap();
         let expected: Vec<ContainerAppBluePrint> = vec![];
         assert_eq!(expected, output);
 
@@ -313,25 +1368,35 @@ mod tests {
                     },
                 });"####;
 
-        let output = get_apps(data);
+        let output = get_apps(data).unwrap();
         let expected = vec![ContainerAppBluePrint {
+            name: Some("frontend".to_string()),
             configuration: Some(ConfigurationBluePrint {
                 dapr: Some(DaprBluePrint {
                     app_id: Some("remix".to_string()),
+                    command: None,
                     app_port: Some(8000),
                     enabled: Some(true),
+                    sidecar_image: None,
+                    max_concurrency: None,
+                    http_max_request_size: None,
+                    components: None,
                 }),
                 ingress: Some(IngressBluePrint {
                     external: Some(true),
                     target_port: Some(8000),
+                    cors_policy: None,
+                    custom_domains: None,
+                    additional_port_mappings: None,
+                    transport: None,
+                    client_certificate_mode: None,
                 }),
+                ..ConfigurationBluePrint::default()
             }),
-            template: Some(TemplateBluePrint {
-                containers: Some(vec![ContainerBluePrint {
-                    image: "node:12".to_string(),
-                    name: "remix".to_string(),
-                }]),
-            }),
+            template: Some(TemplateBluePrint::new(vec![ContainerBluePrint::new(
+                "remix", "node:12",
+            )])),
+            ..ContainerAppBluePrint::default()
         }];
 
         assert_eq!(expected, output);
@@ -355,22 +1420,26 @@ mod tests {
             },
         });"####;
 
-        let output = get_apps(data);
+        let output = get_apps(data).unwrap();
         let expected = vec![ContainerAppBluePrint {
+            name: Some("frontend".to_string()),
             configuration: Some(ConfigurationBluePrint {
                 dapr: Some(DaprBluePrint {
                     app_id: Some("remix".to_string()),
+                    command: None,
                     app_port: Some(8000),
                     enabled: Some(true),
+                    sidecar_image: None,
+                    max_concurrency: None,
+                    http_max_request_size: None,
+                    components: None,
                 }),
-                ingress: None,
-            }),
-            template: Some(TemplateBluePrint {
-                containers: Some(vec![ContainerBluePrint {
-                    image: "node:12".to_string(),
-                    name: "remix".to_string(),
-                }]),
+                ..ConfigurationBluePrint::default()
             }),
+            template: Some(TemplateBluePrint::new(vec![ContainerBluePrint::new(
+                "remix", "node:12",
+            )])),
+            ..ContainerAppBluePrint::default()
         }];
 
         assert_eq!(expected, output);
@@ -387,15 +1456,13 @@ mod tests {
              },
          });"####;
 
-        let output = get_apps(data);
+        let output = get_apps(data).unwrap();
         let expected = vec![ContainerAppBluePrint {
-            configuration: None,
-            template: Some(TemplateBluePrint {
-                containers: Some(vec![ContainerBluePrint {
-                    image: "node:12".to_string(),
-                    name: "remix".to_string(),
-                }]),
-            }),
+            name: Some("frontend".to_string()),
+            template: Some(TemplateBluePrint::new(vec![ContainerBluePrint::new(
+                "remix", "node:12",
+            )])),
+            ..ContainerAppBluePrint::default()
         }];
 
         assert_eq!(expected, output);
@@ -413,17 +1480,914 @@ mod tests {
              },
          });"####;
 
-        let output = get_apps(data);
+        let output = get_apps(data).unwrap();
         let expected = vec![ContainerAppBluePrint {
-            configuration: None,
-            template: Some(TemplateBluePrint {
-                containers: Some(vec![ContainerBluePrint {
-                    image: "node:12".to_string(),
-                    name: "remix".to_string(),
-                }]),
+            name: Some("frontend".to_string()),
+            template: Some(TemplateBluePrint::new(vec![ContainerBluePrint::new(
+                "remix", "node:12",
+            )])),
+            resource_group_name: Some("${resourceGroup.name}".to_string()),
+            ..ContainerAppBluePrint::default()
+        }];
+
+        assert_eq!(expected, output);
+    }
+
+    #[test]
+    fn test_get_apps_with_configuration_scale() {
+        let data = r####"
+        const myApp = new app.ContainerApp("myapp", {
+            configuration: {
+                scale: {
+                    minReplicas: 1,
+                    maxReplicas: 5,
+                },
+            },
+            template: {
+                containers: [{
+                    name: "myapp",
+                    image: "node-12",
+                }],
+            },
+        });"####;
+
+        let output = get_apps(data).unwrap();
+
+        let expected = vec![ContainerAppBluePrint {
+            name: Some("myapp".to_string()),
+            configuration: Some(ConfigurationBluePrint {
+                scale: Some(ScaleBluePrint {
+                    min_replicas: Some(1),
+                    max_replicas: Some(5),
+                    rules: None,
+                }),
+                ..ConfigurationBluePrint::default()
             }),
+            template: Some(TemplateBluePrint::new(vec![ContainerBluePrint::new(
+                "myapp", "node-12",
+            )])),
+            ..ContainerAppBluePrint::default()
         }];
 
         assert_eq!(expected, output);
     }
+
+    const PULUMI_ALL_PROGRAM: &str = r####"
+const remixImage = new docker.Image("remix", {
+    imageName: pulumi.all([registry.loginServer]).apply(([server]) => `${server}/remix:v1`),
+    build: {
+        context: "../frontend",
+    },
+});
+
+const frontendApp = new app.ContainerApp("frontend", {
+    configuration: {
+        ingress: {
+            external: true,
+            targetPort: 3000,
+        },
+    },
+    template: {
+        containers: [{
+            name: "remix",
+            image: pulumi.all([registry.loginServer, remixImage.imageName]).apply(([server, name]) => `${server}/${name}`),
+        }],
+    },
+});"####;
+
+    #[test]
+    fn test_deserialize_with_pulumi_all() {
+        let output = deserialize(PULUMI_ALL_PROGRAM, &BuildOptions::default()).unwrap();
+
+        assert_eq!(1, output.len());
+        assert_eq!(None, output[0].image);
+        assert_eq!(
+            Some("../frontend".to_string()),
+            output[0].build.as_ref().map(|build| build.context.clone())
+        );
+        assert_eq!(Some(vec!["3000:3000".to_string()]), output[0].ports);
+
+        // Unsupported shapes error instead of being misparsed
+        let input = PULUMI_ALL_PROGRAM.replace(
+            "pulumi.all([registry.loginServer]).apply(([server]) => `${server}/remix:v1`)",
+            "pulumi.all([registry.loginServer]).apply(getName)",
+        );
+        let output = deserialize(&input, &BuildOptions::default());
+
+        assert_eq!(
+            Err(DeserializeError::Parse(
+                "Unsupported pulumi.all expression: pulumi.all([registry.loginServer]).apply(getName) at line 3, column 16"
+                    .to_string()
+            )),
+            output
+        );
+    }
+
+    const APP_BODY: &str = r####"("frontend", {
+    template: {
+        containers: [{
+            name: "frontend",
+            image: "node-12",
+        }],
+    },
+});"####;
+
+    #[test]
+    fn test_get_constructors() {
+        let input = r#"
+import * as pulumi from "@pulumi/pulumi";
+import * as app from "@pulumi/azure-native/app";
+import azure from "@pulumi/azure-native";
+import { ContainerApp as CA } from "@pulumi/azure-native/app";
+const { app: nativeApp } = require("@pulumi/azure-native");
+const { Image } = require("@pulumi/docker");
+"#;
+
+        assert_eq!(
+            vec![
+                "app.ContainerApp",
+                "azure.app.ContainerApp",
+                "CA",
+                "nativeApp.ContainerApp"
+            ],
+            get_constructors(input, &CONTAINER_APP_MODULES, "app")
+        );
+        assert_eq!(
+            vec!["Image"],
+            get_constructors(input, &IMAGE_MODULES, "docker")
+        );
+        // Snippets without imports use the conventional aliases
+        assert_eq!(
+            vec!["docker.Image"],
+            get_constructors("", &IMAGE_MODULES, "docker")
+        );
+    }
+
+    #[test]
+    fn test_get_apps_with_require_destructuring() {
+        let input = format!(
+            "const {{ ContainerApp }} = require(\"@pulumi/azure-native/app\");\nconst frontendApp = new ContainerApp{}",
+            APP_BODY
+        );
+
+        let output = get_apps(&input).unwrap();
+
+        assert_eq!(1, output.len());
+        assert_eq!(Some("frontend".to_string()), output[0].name);
+    }
+
+    #[test]
+    fn test_get_apps_with_namespace_import() {
+        let input = format!(
+            "import * as containerapps from \"@pulumi/azure-native/app\";\nconst frontendApp = new containerapps.ContainerApp{}",
+            APP_BODY
+        );
+
+        let output = get_apps(&input).unwrap();
+
+        assert_eq!(1, output.len());
+        assert_eq!(Some("frontend".to_string()), output[0].name);
+    }
+
+    #[test]
+    fn test_get_apps_with_default_import() {
+        let input = format!(
+            "import azure from \"@pulumi/azure-native\";\nconst frontendApp = new azure.app.ContainerApp{}",
+            APP_BODY
+        );
+
+        let output = get_apps(&input).unwrap();
+
+        assert_eq!(1, output.len());
+        assert_eq!(Some("frontend".to_string()), output[0].name);
+    }
+
+    #[test]
+    fn test_get_apps_with_mixed_imports() {
+        let input = format!(
+            "import * as app from \"@pulumi/azure-native/app\";\nconst docker = require(\"@pulumi/docker\");\nconst frontendApp = new app.ContainerApp{}\n\n{}",
+            APP_BODY,
+            r####"const remixImage = new docker.Image("remix", {
+    imageName: "remix:v1",
+    build: {
+        context: "../frontend",
+    },
+});"####
+        );
+
+        assert_eq!(1, get_apps(&input).unwrap().len());
+        assert_eq!(1, get_images(&input).unwrap().len());
+    }
+
+    #[test]
+    fn test_get_apps_with_unknown_alias() {
+        // `components` is not the Azure Native module, its ContainerApp is not an app
+        let input = format!(
+            "import * as app from \"@pulumi/azure-native/app\";\nimport * as components from \"./components\";\nconst frontendApp = new components.ContainerApp{}",
+            APP_BODY
+        );
+
+        assert!(get_apps(&input).unwrap().is_empty());
+    }
+
+    const FORMATTED_PROGRAM: &str = r####"
+import * as pulumi from "@pulumi/pulumi";
+import * as app from "@pulumi/azure-native/app";
+import * as docker from "@pulumi/docker";
+
+const targetPort = 3000;
+const portKey = "targetPort";
+
+function appName(name: string): string {
+    return `${pulumi.getStack()}-${name}`;
+}
+
+const remixImage = new docker.Image("remix", { imageName: `${registry.loginServer}/remix:v1`,
+    build: { context: "../frontend" } });
+const frontendApp = new app.ContainerApp(
+    "frontend",
+    {
+        // The ingress is public
+        configuration: { ingress: { external: true, [portKey]: targetPort } },
+        template: {
+            containers: [
+                {
+                    name: "remix", /* the container of the app */
+                    image: remixImage.imageName,
+                    command: [`
+                        npm run start
+                    `],
+                },
+            ],
+        },
+    } as app.ContainerAppArgs,
+    { dependsOn: [remixImage] },
+);
+"####;
+
+    #[test]
+    fn test_deserialize_formatted_program() {
+        let output = deserialize(FORMATTED_PROGRAM, &BuildOptions::default()).unwrap();
+
+        assert_eq!(1, output.len());
+        assert_eq!(
+            Some("../frontend".to_string()),
+            output[0].build.as_ref().map(|build| build.context.clone())
+        );
+        assert_eq!(Some(vec!["3000:3000".to_string()]), output[0].ports);
+    }
+
+    #[test]
+    fn test_get_apps_with_shorthand_properties() {
+        let data = r####"
+const template = {
+    containers: [{ name: "remix", image: "node:12" }],
+};
+const frontendApp = new app.ContainerApp("frontend", { template });"####;
+
+        let output = get_apps(data).unwrap();
+
+        assert_eq!(
+            Some(TemplateBluePrint::new(vec![ContainerBluePrint::new(
+                "remix", "node:12",
+            )])),
+            output[0].template
+        );
+    }
+
+    #[test]
+    fn test_get_apps_with_syntax_error() {
+        let data = r####"
+const frontendApp = new app.ContainerApp("frontend", {
+    template: {
+        containers: [{ name: "remix" image: "node:12" }],
+    },
+});"####;
+
+        assert_eq!(
+            Err(DeserializeError::Parse(
+                "Expected ',', got 'ident' at line 4, column 38".to_string()
+            )),
+            get_apps(data)
+        );
+    }
+
+    #[test]
+    fn test_get_apps_in_map() {
+        let data = r####"
+const apps = ["api", "worker"].map((name, index) =>
+    new app.ContainerApp(name, {
+        template: {
+            containers: [{ name: `${name}-app`, image: `node:${index}` }],
+        },
+    })
+);"####;
+
+        let output = get_apps(data).unwrap();
+
+        let names: Vec<Option<String>> = output.iter().map(|app| app.name.clone()).collect();
+        assert_eq!(
+            vec![Some("api".to_string()), Some("worker".to_string())],
+            names
+        );
+        assert_eq!(
+            Some(TemplateBluePrint::new(vec![ContainerBluePrint::new(
+                "worker-app",
+                "node:1",
+            )])),
+            output[1].template
+        );
+    }
+
+    #[test]
+    fn test_get_apps_in_for_of() {
+        let data = r####"
+const services = [
+    { name: "api", port: 8080 },
+    { name: "frontend", port: 3000 },
+];
+
+for (const { name, port } of services) {
+    new app.ContainerApp(name, {
+        configuration: {
+            ingress: { external: true, targetPort: port },
+        },
+        template: {
+            containers: [{ name, image: "node-12" }],
+        },
+    });
+}"####;
+
+        let output = get_apps(data).unwrap();
+
+        let ports: Vec<(Option<String>, Option<u32>)> = output
+            .iter()
+            .map(|app| {
+                let ingress = app
+                    .configuration
+                    .as_ref()
+                    .and_then(|configuration| configuration.ingress.as_ref());
+
+                (
+                    app.name.clone(),
+                    ingress.and_then(|ingress| ingress.target_port),
+                )
+            })
+            .collect();
+        assert_eq!(
+            vec![
+                (Some("api".to_string()), Some(8080)),
+                (Some("frontend".to_string()), Some(3000))
+            ],
+            ports
+        );
+    }
+
+    #[test]
+    fn test_collect_warnings_with_unknown_loop() {
+        let data = r####"
+const config = new pulumi.Config();
+
+for (const name of config.requireObject<string[]>("apps")) {
+    new app.ContainerApp(name, {
+        template: {
+            containers: [{ name, image: "node-12" }],
+        },
+    });
+}"####;
+
+        assert!(get_apps(data).unwrap().is_empty());
+        assert_eq!(
+            vec![
+                "ignored ContainerApp created in a loop over config.requireObject<string[]>(\"apps\"), the array is not known"
+            ],
+            collect_warnings(data)
+        );
+    }
+
+    const PROJECT_FILES: [(&str, &str); 4] = [
+        (
+            "index.ts",
+            r#"import * as pulumi from "@pulumi/pulumi";
+import { api } from "./apps/api";
+import { frontend } from "./apps/frontend";
+
+export const urls = [api.latestRevisionFqdn, frontend.latestRevisionFqdn];"#,
+        ),
+        (
+            "images.ts",
+            r#"import * as docker from "@pulumi/docker";
+
+export const remixImage = new docker.Image("remix", {
+    imageName: "remix:v1",
+    build: { context: "../frontend" },
+});"#,
+        ),
+        (
+            "apps/api.ts",
+            r#"import * as app from "@pulumi/azure-native/app";
+
+export const api = new app.ContainerApp("api", {
+    template: {
+        containers: [{ name: "api", image: "node-12" }],
+    },
+});"#,
+        ),
+        (
+            "apps/frontend.ts",
+            r#"import * as app from "@pulumi/azure-native/app";
+import { remixImage } from "../images.js";
+import { urls } from "..";
+
+export const frontend = new app.ContainerApp("frontend", {
+    configuration: { ingress: { external: true, targetPort: 3000 } },
+    template: {
+        containers: [{ name: "remix", image: remixImage.imageName }],
+    },
+});"#,
+        ),
+    ];
+
+    fn write_project(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let directory =
+            std::env::temp_dir().join(format!("capp_s_{}_{}", name, std::process::id()));
+
+        for (file, content) in files {
+            let path = directory.join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
+        }
+
+        directory
+    }
+
+    #[test]
+    fn test_read_project() {
+        let directory = write_project("project", &PROJECT_FILES);
+        let program = PROJECT_FILES
+            .iter()
+            .map(|(_, content)| *content)
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        // The folder and its index file are the same entry point, the circular import of
+        // the index by apps/frontend.ts is read once
+        let input = read_project(&directory).unwrap();
+        let output = deserialize(&input, &BuildOptions::default());
+
+        assert_eq!(input, read_project(&directory.join("index.ts")).unwrap());
+        assert_eq!(1, input.matches("export const urls").count());
+        assert_eq!(deserialize(&program, &BuildOptions::default()), output);
+        assert_eq!(2, output.as_ref().unwrap().len());
+        assert_eq!(
+            Some("../frontend".to_string()),
+            output.unwrap()[1]
+                .build
+                .as_ref()
+                .map(|build| build.context.clone())
+        );
+
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn test_read_project_with_missing_import() {
+        let directory = write_project(
+            "missing_import",
+            &[("index.ts", r#"import { api } from "./apps/api";"#)],
+        );
+        let output = read_project(&directory.join("index.ts"));
+
+        assert_eq!(
+            Err(DeserializeError::MissingFile(
+                directory
+                    .canonicalize()
+                    .unwrap()
+                    .join("./apps/api")
+                    .display()
+                    .to_string()
+            )),
+            output
+        );
+
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn test_deserialize_classic_provider() {
+        let input = r#"
+import * as app from "@pulumi/azure-native/app";
+import * as azure from "@pulumi/azure";
+
+const nativeApp = new app.ContainerApp("native", {
+    configuration: {
+        ingress: { external: true, targetPort: 3000 },
+        dapr: { enabled: true, appId: "native", appPort: 3000 },
+    },
+    template: {
+        scale: { minReplicas: 1, maxReplicas: 3 },
+        containers: [{ name: "native", image: "node-12", resources: { cpu: 0.5, memory: "1Gi" } }],
+    },
+});
+
+const classicApp = new azure.containerapp.App("classic", {
+    containerAppEnvironmentId: env.id,
+    revisionMode: "Single",
+    ingress: { externalEnabled: true, targetPort: 4000 },
+    dapr: { appId: "classic", appPort: 4000 },
+    template: {
+        minReplicas: 1,
+        maxReplicas: 3,
+        containers: [{ name: "classic", image: "node-12", cpu: 0.5, memory: "1Gi" }],
+    },
+});"#;
+
+        let output = deserialize(input, &BuildOptions::default()).unwrap();
+
+        assert_eq!(4, output.len());
+        assert_eq!(
+            output[0],
+            ContainerAppConfiguration {
+                name: "native".to_string(),
+                ports: Some(vec!["3000:3000".to_string()]),
+                ..output[2].clone()
+            }
+        );
+        assert_eq!(
+            Some(vec![
+                "./daprd".to_string(),
+                "-app-id".to_string(),
+                "classic".to_string(),
+                "-app-port".to_string(),
+                "4000".to_string(),
+            ]),
+            output[3]
+                .command
+                .as_ref()
+                .map(|command| command[..5].to_vec())
+        );
+    }
+
+    #[test]
+    fn test_get_apps_with_versioned_modules() {
+        let input = r#"
+import * as app from "@pulumi/azure-native/app/v20230501";
+import * as azure from "@pulumi/azure-native";
+
+const pinnedApp = new app.ContainerApp("pinned", {
+    template: { containers: [{ name: "pinned", image: "node-12" }] },
+});
+
+const previewApp = new azure.app.v20240802preview.ContainerApp("preview", {
+    template: { containers: [{ name: "preview", image: "node-12" }] },
+});
+
+const latestApp = new azure.app.ContainerApp("latest", {
+    template: { containers: [{ name: "latest", image: "node-12" }] },
+});"#;
+
+        let output = get_apps(input).unwrap();
+
+        assert_eq!(
+            vec![
+                (Some("pinned"), Some("v20230501")),
+                (Some("preview"), Some("v20240802preview")),
+                (Some("latest"), None)
+            ],
+            output
+                .iter()
+                .map(|app| (app.name.as_deref(), app.api_version.as_deref()))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec!["api version v20240802preview of preview is newer than v20240301, its new fields are not mapped"],
+            collect_warnings(input)
+        );
+    }
+
+    #[test]
+    fn test_deserialize_manual_job() {
+        let input = r#"
+import * as app from "@pulumi/azure-native/app";
+
+const migrate = new app.Job("migrate", {
+    configuration: {
+        triggerType: "Manual",
+        replicaRetryLimit: 1,
+        manualTriggerConfig: { parallelism: 1, replicaCompletionCount: 1 },
+    },
+    template: {
+        containers: [{ name: "migrate", image: "mcr.microsoft.com/dotnet/runtime:8.0" }],
+    },
+});"#;
+
+        let output = deserialize(input, &BuildOptions::default()).unwrap();
+
+        assert_eq!(1, output.len());
+        assert_eq!(
+            Some("mcr.microsoft.com/dotnet/runtime:8.0".to_string()),
+            output[0].image
+        );
+        assert_eq!(Some("no".to_string()), output[0].restart);
+        assert_eq!(Some(vec!["jobs".to_string()]), output[0].profiles);
+        assert_eq!(
+            Some(BTreeMap::from([(
+                "capp.job.trigger".to_string(),
+                "Manual".to_string()
+            )])),
+            output[0].labels
+        );
+    }
+
+    #[test]
+    fn test_deserialize_image_with_build_options() {
+        let input = r#"
+import * as app from "@pulumi/azure-native/app";
+import * as docker from "@pulumi/docker";
+
+const apiImage = new docker.Image("api", {
+    imageName: "api:v1",
+    build: {
+        context: "./api",
+        dockerfile: "./Dockerfile.api",
+        args: { NODE_ENV: "production" },
+        target: "runtime",
+        platform: "linux/amd64",
+    },
+});
+
+const api = new app.ContainerApp("api", {
+    template: { containers: [{ name: "api", image: apiImage.imageName }] },
+});"#;
+
+        let services = deserialize(input, &BuildOptions::default()).unwrap();
+        let compose = crate::serializer::to_compose_yaml(&services).unwrap();
+
+        assert!(compose.contains("dockerfile: ./Dockerfile.api"));
+        assert!(compose.contains("- linux/amd64"));
+        assert_eq!(
+            services,
+            crate::serializer::from_compose_yaml(&compose).unwrap()
+        );
+        assert_eq!(
+            Some(BTreeMap::from([(
+                "NODE_ENV".to_string(),
+                "production".to_string()
+            )])),
+            services[0]
+                .build
+                .as_ref()
+                .and_then(|build| build.args.clone())
+        );
+    }
+
+    #[test]
+    fn test_deserialize_interpolated_build_contexts() {
+        let program = |context: &str| {
+            format!(
+                r#"
+import * as pulumi from "@pulumi/pulumi";
+import * as app from "@pulumi/azure-native/app";
+import * as docker from "@pulumi/docker";
+
+const config = new pulumi.Config();
+const root = config.require("root");
+
+const apiImage = new docker.Image("api", {{
+    imageName: "api:v1",
+    build: {{ context: {} }},
+}});
+
+const api = new app.ContainerApp("api", {{
+    template: {{ containers: [{{ name: "api", image: apiImage.imageName }}] }},
+}});"#,
+                context
+            )
+        };
+        let options = BuildOptions {
+            config: BTreeMap::from([("myproject:root".to_string(), "./infra".into())]),
+            ..Default::default()
+        };
+        let build_context = |context: &str| {
+            let mut pulumi = crate::pulumi::Pulumi::new(crate::serializer::Language::Typescript)
+                .unwrap()
+                .with_options(options.clone());
+            crate::serializer::Serializer::deserialize_value(&mut pulumi, &program(context))
+                .unwrap();
+
+            (
+                pulumi.resources.as_ref().unwrap()[0]
+                    .build
+                    .as_ref()
+                    .map(|build| build.context.clone()),
+                pulumi.warnings().to_vec(),
+            )
+        };
+
+        assert_eq!(
+            (Some("./services/api".to_string()), vec![]),
+            build_context("pulumi.interpolate`${pulumi.cwd}/services/api`")
+        );
+        assert_eq!(
+            (Some("./infra/services/api".to_string()), vec![]),
+            build_context("pulumi.interpolate`${config.root}/services/api`")
+        );
+        assert_eq!(
+            (Some("./infra/services/api".to_string()), vec![]),
+            build_context("pulumi.all([root]).apply(([root]) => root + \"/services/api\")")
+        );
+        assert_eq!(
+            (Some("./infra/api".to_string()), vec![]),
+            build_context("root + \"/api\"")
+        );
+        assert_eq!(
+            (
+                Some("./apiImage".to_string()),
+                vec![
+                    "unresolved build context ${pulumi.all} of apiImage, ./apiImage is used instead"
+                        .to_string()
+                ]
+            ),
+            build_context("pulumi.all([root]).apply(([root]) => path.join(root, \"api\"))")
+        );
+    }
+
+    #[test]
+    fn test_deserialize_stack_reference_outputs() {
+        let program = r#"
+import * as pulumi from "@pulumi/pulumi";
+import * as app from "@pulumi/azure-native/app";
+
+const infra = new pulumi.StackReference("org/infra/dev");
+
+const api = new app.ContainerApp("api", {
+    template: { containers: [{ name: "api", image: infra.getOutput("apiImage") }] },
+});
+
+const worker = new app.ContainerApp("worker", {
+    template: { containers: [{ name: "worker", image: infra.requireOutput("workerImage") }] },
+});"#;
+        let deserialize = |stack_outputs: BTreeMap<String, String>| {
+            let mut pulumi = crate::pulumi::Pulumi::new(crate::serializer::Language::Typescript)
+                .unwrap()
+                .with_options(BuildOptions {
+                    stack_outputs,
+                    ..Default::default()
+                });
+            crate::serializer::Serializer::deserialize_value(&mut pulumi, program).unwrap();
+
+            (
+                pulumi
+                    .resources
+                    .as_ref()
+                    .unwrap()
+                    .iter()
+                    .map(|service| service.image.clone().unwrap_or_default())
+                    .collect::<Vec<_>>(),
+                pulumi.warnings().to_vec(),
+            )
+        };
+
+        // Given outputs are substituted
+        let output = deserialize(BTreeMap::from([
+            (
+                "apiImage".to_string(),
+                "myacr.azurecr.io/api:v1".to_string(),
+            ),
+            (
+                "workerImage".to_string(),
+                "myacr.azurecr.io/worker:v1".to_string(),
+            ),
+        ]));
+        assert_eq!(
+            (
+                vec![
+                    "myacr.azurecr.io/api:v1".to_string(),
+                    "myacr.azurecr.io/worker:v1".to_string()
+                ],
+                vec![]
+            ),
+            output
+        );
+
+        // The others are placeholders reported with their stack
+        let output = deserialize(BTreeMap::from([(
+            "apiImage".to_string(),
+            "myacr.azurecr.io/api:v1".to_string(),
+        )]));
+        assert_eq!(
+            (
+                vec![
+                    "myacr.azurecr.io/api:v1".to_string(),
+                    "stack-output/workerimage".to_string()
+                ],
+                vec![
+                    "unresolved output workerImage of stack org/infra/dev, stack-output/workerimage is used instead"
+                        .to_string()
+                ]
+            ),
+            output
+        );
+    }
+
+    #[test]
+    fn test_deserialize_container_env() {
+        let input = r#"
+import * as app from "@pulumi/azure-native/app";
+
+const api = new app.ContainerApp("api", {
+    template: {
+        containers: [{
+            name: "api",
+            image: "node-12",
+            env: [
+                { name: "PORT", value: "3000" },
+                { name: "EMPTY", value: "" },
+                { name: "GREETING", value: "hello 'capp' world" },
+                { name: "DB_PASSWORD", secretRef: "db-password" },
+            ],
+        }],
+    },
+});"#;
+
+        let output = deserialize(input, &BuildOptions::default()).unwrap();
+
+        assert_eq!(
+            Some(vec![
+                "PORT=3000".to_string(),
+                "EMPTY=".to_string(),
+                "GREETING=hello 'capp' world".to_string(),
+                "DB_PASSWORD=${DB_PASSWORD}".to_string(),
+            ]),
+            output[0].environment
+        );
+    }
+
+    #[test]
+    fn test_deserialize_secrets() {
+        let input = r#"
+import * as app from "@pulumi/azure-native/app";
+
+const api = new app.ContainerApp("api", {
+    configuration: {
+        secrets: [
+            { name: "db-password", value: "s3cr3t" },
+            { name: "api-key", keyVaultUrl: "https://shop.vault.azure.net/secrets/api-key", identity: "system" },
+        ],
+    },
+    template: {
+        containers: [{
+            name: "api",
+            image: "node-12",
+            env: [{ name: "API_KEY", secretRef: "api-key" }],
+        }],
+    },
+});"#;
+
+        let output = deserialize(input, &BuildOptions::default()).unwrap();
+        assert_eq!(
+            Some(vec!["API_KEY=${API_KEY}".to_string()]),
+            output[0].environment
+        );
+
+        let mut pulumi =
+            crate::pulumi::Pulumi::new(crate::serializer::Language::Typescript).unwrap();
+        crate::serializer::Serializer::deserialize_value(&mut pulumi, input).unwrap();
+        let warnings = pulumi.warnings();
+        assert_eq!(
+            vec!["secret api-key of api is read from the Key Vault https://shop.vault.azure.net/secrets/api-key, its value must be given locally"],
+            warnings
+        );
+    }
+
+    #[test]
+    fn test_parse_value_not_evaluated() {
+        for expression in [
+            "port * 2",
+            "a === b ? 1 : 2",
+            "-port",
+            "typeof x",
+            "void 0",
+            "await getImage()",
+            "/^api-/",
+            "{ get image() { return image } }",
+            "(a, b)",
+            "1_000n",
+        ] {
+            assert_eq!(
+                Ok(serde_json::json!({ "key": null })),
+                parse(&format!("{{ key: {} }}", expression)),
+                "{}",
+                expression
+            );
+        }
+
+        // Indexers and assertions are references
+        for (expression, reference) in [
+            (r#"config?.["image"]"#, r#"${config?.["image"]}"#),
+            ("<string>value", "${value}"),
+        ] {
+            assert_eq!(Ok(Value::String(reference.to_string())), parse(expression));
+        }
+    }
 }
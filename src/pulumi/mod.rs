@@ -1,57 +1,379 @@
+#[cfg(feature = "csharp")]
+pub mod csharp;
+#[cfg(feature = "go")]
+pub mod golang;
+#[cfg(feature = "js")]
 pub mod js;
+#[cfg(feature = "python")]
+pub mod python;
+#[cfg(feature = "state")]
+pub mod state;
+#[cfg(feature = "yaml")]
 pub mod yaml;
+use crate::error::{DeserializeError, ResolveError};
 use crate::serializer::{
-    BuildContext, ContainerAppBluePrint, ContainerAppConfiguration, ContainerBluePrint,
-    ContainerImageBluePrint, DaprBluePrint, IngressBluePrint, Language, Serializer,
+    read_input, stack_output_name, stack_output_reference, validate_declarations, AzureFilesMode,
+    BuildContext, BuildContextBluePrint, BuildOptions, CommandBluePrint, ConfigurationBluePrint,
+    ContainerAppBluePrint, ContainerAppConfiguration, ContainerBluePrint, ContainerImageBluePrint,
+    DaprBluePrint, DaprComponentBluePrint, DaprRuntimeOptions, DefaultTokenResolver,
+    DependencyCondition, DependsOn, Deploy, DeployResources, HealthCheck, Identity,
+    IngressBluePrint, JobConfigurationBluePrint, Language, ProbeBluePrint, ResourceLimits,
+    ScaleBluePrint, ScaleRuleBluePrint, SecretsMode, Serializer, ServiceConfig, ServiceDependency,
+    ServiceSecret, ServiceVolume, TokenResolver, VolumeBluePrint, VolumeOptions,
 };
-use log::error;
+use log::{error, warn};
 use regex::Regex;
+use std::collections::BTreeMap;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::thread;
+
+// Logs of the Pulumi parsers, whatever their language
+pub(crate) const LOG_TARGET: &str = "capp_s::pulumi";
+const DEFAULT_DAPR_SIDECAR_IMAGE: &str = "daprio/daprd:edge";
+const DEFAULT_RESTART_POLICY: &str = "unless-stopped";
+// Folder of the sidecar the component files are expected in
+const DAPR_COMPONENTS_PATH: &str = "/components";
+// Folder of the compose secrets mounted in the services
+const SECRETS_PATH: &str = "/run/secrets";
+// Container Apps probe defaults
+const DEFAULT_PROBE_PERIOD_SECONDS: u32 = 10;
+const DEFAULT_PROBE_TIMEOUT_SECONDS: u32 = 1;
+const DEFAULT_PROBE_FAILURE_THRESHOLD: u32 = 3;
+// Newest API version of the app resources whose fields are mapped
+const MAPPED_API_VERSION: &str = "v20240301";
+// Other app properties are resource arguments (eg: resourceGroupName) and are not checked
+const STRICT_SECTIONS: [&str; 3] = ["configuration", "template", "identity"];
+
+/// Apps and image resources of an input, before their references are resolved
+pub type BluePrints = (Vec<ContainerAppBluePrint>, Vec<ContainerImageBluePrint>);
 
 pub struct Pulumi {
     language: Language,
+    options: BuildOptions,
     pub resources: Option<Vec<ContainerAppConfiguration>>,
+    apps: Vec<(String, Vec<ContainerAppConfiguration>)>,
+    warnings: Vec<String>,
+    path: Option<PathBuf>,
 }
 
 impl Pulumi {
     pub fn new(language: Language) -> Option<Pulumi> {
+        // Languages are only supported when their parser is compiled in
+        let is_supported = (cfg!(feature = "yaml") && matches!(language, Language::Yaml))
+            || (cfg!(feature = "js")
+                && matches!(language, Language::Typescript | Language::Javascript))
+            || (cfg!(feature = "python") && matches!(language, Language::Python))
+            || (cfg!(feature = "csharp") && matches!(language, Language::CSharp))
+            || (cfg!(feature = "go") && matches!(language, Language::Go))
+            || (cfg!(feature = "state") && matches!(language, Language::StackExport))
+            || (cfg!(any(
+                feature = "yaml",
+                feature = "js",
+                feature = "python",
+                feature = "csharp",
+                feature = "go",
+                feature = "state"
+            )) && matches!(language, Language::Auto));
+
+        is_supported.then(|| Pulumi {
+            language,
+            options: BuildOptions::default(),
+            resources: None,
+            apps: vec![],
+            warnings: vec![],
+            path: None,
+        })
+    }
+
+    pub fn with_options(mut self, options: BuildOptions) -> Pulumi {
+        self.options = options;
+        self
+    }
+
+    /// Path of the program, its extension and its Pulumi.yaml project file
+    /// help detecting the language of `Language::Auto`
+    pub fn with_path(mut self, path: &Path) -> Pulumi {
+        self.path = Some(path.to_path_buf());
+        self
+    }
+
+    /// Language of the input, detected when it is not given
+    fn language(&self, input: &str) -> Result<Language, DeserializeError> {
+        match self.language {
+            Language::Auto => detect_language(input, self.path.as_deref()),
+            language => Ok(language),
+        }
+    }
+
+    /// Services of the last deserialized input by app, for one compose file per app
+    pub fn apps(&self) -> &[(String, Vec<ContainerAppConfiguration>)] {
+        &self.apps
+    }
+
+    /// Features of the last deserialized input which are not translated
+    pub fn warnings(&self) -> &[String] {
+        &self.warnings
+    }
+
+    fn deserialize_blueprints(&self, input: &str) -> Result<BluePrints, DeserializeError> {
+        match self.language(input)? {
+            #[cfg(feature = "yaml")]
+            Language::Yaml => yaml::deserialize_blueprints(input, &self.options),
+            #[cfg(feature = "js")]
+            Language::Typescript | Language::Javascript => {
+                js::deserialize_blueprints(input, &self.options)
+            }
+            #[cfg(feature = "python")]
+            Language::Python => python::deserialize_blueprints(input, &self.options),
+            #[cfg(feature = "csharp")]
+            Language::CSharp => csharp::deserialize_blueprints(input, &self.options),
+            #[cfg(feature = "go")]
+            Language::Go => golang::deserialize_blueprints(input, &self.options),
+            #[cfg(feature = "state")]
+            Language::StackExport => state::deserialize_blueprints(input, &self.options),
+            _ => {
+                error!(target: LOG_TARGET, "Language not supported");
+                Err(DeserializeError::UnsupportedLanguage)
+            }
+        }
+    }
+
+    /// Warnings of the syntax of an input, the ones of its resources are collected over all
+    /// the inputs by `collect_blueprint_warnings` as references are resolved across them
+    fn collect_syntax_warnings(&self, input: &str) -> Vec<String> {
+        match self.language(input).unwrap_or(Language::NotSupported) {
+            #[cfg(feature = "yaml")]
+            Language::Yaml => yaml::collect_warnings(input, &self.options),
+            #[cfg(feature = "js")]
+            Language::Typescript | Language::Javascript => js::collect_warnings(input),
+            #[cfg(feature = "python")]
+            Language::Python => python::collect_warnings(input),
+            #[cfg(feature = "csharp")]
+            Language::CSharp => csharp::collect_warnings(input),
+            #[cfg(feature = "go")]
+            Language::Go => golang::collect_warnings(input),
+            #[cfg(feature = "state")]
+            Language::StackExport => state::collect_warnings(input),
+            _ => vec![],
+        }
+    }
+
+    fn collect_blueprint_warnings(
+        &self,
+        apps: &[ContainerAppBluePrint],
+        images: &[ContainerImageBluePrint],
+        stack_references: &BTreeMap<String, String>,
+    ) -> Vec<String> {
+        [
+            collect_build_context_warnings(images, &self.options),
+            collect_unresolved_image_warnings(apps, images, &self.options),
+            collect_secret_warnings(apps),
+            collect_volume_warnings(apps),
+            collect_resource_warnings(apps),
+            collect_scale_warnings(apps),
+            collect_stack_output_warnings(apps, stack_references, &self.options),
+        ]
+        .concat()
+    }
+
+    /// Deserialize the inputs as a single program (eg: the images of a stack declared in
+    /// another file), the references are resolved across the inputs
+    pub fn deserialize_values(&mut self, inputs: &[&str]) -> Result<&Self, DeserializeError> {
+        let mut apps: Vec<ContainerAppBluePrint> = vec![];
+        let mut images: Vec<ContainerImageBluePrint> = vec![];
+        // Input declaring each app and image, apps and images have their own names
+        let mut origins: BTreeMap<(bool, String), usize> = BTreeMap::new();
+        let mut stack_references: BTreeMap<String, String> = BTreeMap::new();
+
+        for (index, input) in inputs.iter().enumerate() {
+            let (input_apps, input_images) = self.deserialize_blueprints(input)?;
+            let names =
+                input_apps
+                    .iter()
+                    .filter_map(|app| app.name.clone().map(|name| (true, name)))
+                    .chain(input_images.iter().filter_map(|image| {
+                        image.reference_name.clone().map(|name| (false, name))
+                    }));
+
+            // Duplicates of a single input are reported while building the apps
+            for name in names {
+                match origins.insert(name.clone(), index) {
+                    Some(origin) if origin != index => {
+                        return Err(DeserializeError::DuplicateResource {
+                            name: name.1,
+                            inputs: (origin, index),
+                        })
+                    }
+                    _ => {}
+                }
+            }
+
+            apps.extend(input_apps);
+            images.extend(input_images);
+            stack_references.extend(get_stack_references(
+                input,
+                self.language(input).unwrap_or(self.language),
+            ));
+        }
+
+        let warnings = [
+            inputs
+                .iter()
+                .flat_map(|input| self.collect_syntax_warnings(input))
+                .collect(),
+            self.collect_blueprint_warnings(&apps, &images, &stack_references),
+        ]
+        .concat();
+        let value = build_per_app(apps, images, self.options.include.as_deref(), &self.options)?;
+
+        self.resources = Some(flatten_apps(value.clone()));
+        self.apps = value;
+        self.warnings = warnings;
+        Ok(self)
+    }
+
+    /// Deserialize the program of a project folder, its entry point and language are
+    /// read from its Pulumi.yaml project file, see `find_project_entry`
+    pub fn from_project_dir(
+        path: &Path,
+        options: BuildOptions,
+    ) -> Result<Pulumi, DeserializeError> {
+        let (language, entry) = find_project_entry(path)?;
+        let mut pulumi = Pulumi::new(language)
+            .ok_or(DeserializeError::UnsupportedLanguage)?
+            .with_options(options)
+            .with_path(&entry);
+
+        pulumi.deserialize_project(&entry)?;
+
+        Ok(pulumi)
+    }
+
+    /// Deserialize a program split in several files, following the relative imports of
+    /// its entry point (eg: index.ts, or the folder containing it)
+    pub fn deserialize_project(&mut self, path: &Path) -> Result<&Self, DeserializeError> {
+        if self.path.is_none() {
+            self.path = Some(path.to_path_buf());
+        }
+
+        // The language of a folder is the runtime of its project file
+        let language = match (self.language, path.is_file()) {
+            (Language::Auto, true) => {
+                let input = std::fs::read_to_string(path)
+                    .map_err(|_| DeserializeError::MissingFile(path.display().to_string()))?;
+                detect_language(&input, Some(path))?
+            }
+            (Language::Auto, false) => detect_language("", Some(path))?,
+            (language, _) => language,
+        };
+
         match language {
-            Language::Yaml | Language::Typescript | Language::Javascript => Some(Pulumi {
-                language,
-                resources: None,
-            }),
-            _ => None,
+            #[cfg(feature = "js")]
+            Language::Typescript | Language::Javascript => {
+                let input = js::read_project(path)?;
+                self.deserialize_value(&input)
+            }
+            // Programs of other languages are read as a single file
+            _ => {
+                let input = std::fs::read_to_string(path)
+                    .map_err(|_| DeserializeError::MissingFile(path.display().to_string()))?;
+                self.deserialize_value(&input)
+            }
         }
     }
 }
 
 impl Serializer for Pulumi {
     type Output = Pulumi;
-    fn deserialize_value(&mut self, input: &str) -> Result<&Self, String> {
+    fn deserialize_value(&mut self, input: &str) -> Result<&Self, DeserializeError> {
+        self.deserialize_values(&[input])
+    }
+
+    /// YAML programs are parsed as they are read, the other inputs are read first
+    fn deserialize_reader<R: Read>(&mut self, reader: R) -> Result<&Self, DeserializeError> {
         match self.language {
-            Language::Yaml => match yaml::deserialize(input) {
-                Ok(value) => {
-                    self.resources = Some(value);
-                    Ok(self)
-                }
-                Err(err) => Err(err),
-            },
-            Language::Typescript | Language::Javascript => match js::deserialize(input) {
-                Ok(value) => {
-                    self.resources = Some(value);
-                    Ok(self)
-                }
-                Err(err) => Err(err),
-            },
+            #[cfg(feature = "yaml")]
+            Language::Yaml => {
+                let program = yaml::read_program(reader)?;
+                let stack_references = get_program_stack_references(&program);
+                let (apps, images) = yaml::program_blueprints(program.clone(), &self.options)?;
+                let stack_output_warnings =
+                    collect_stack_output_warnings(&apps, &stack_references, &self.options);
+                let app_warnings = [
+                    collect_secret_warnings(&apps),
+                    collect_volume_warnings(&apps),
+                    collect_resource_warnings(&apps),
+                    collect_scale_warnings(&apps),
+                    collect_unresolved_image_warnings(&apps, &images, &self.options),
+                ]
+                .concat();
+                let value = build_per_app(
+                    apps,
+                    images.clone(),
+                    self.options.include.as_deref(),
+                    &self.options,
+                )?;
+
+                self.resources = Some(flatten_apps(value.clone()));
+                self.apps = value;
+                self.warnings = yaml::collect_program_warnings(program, &self.options);
+                self.warnings
+                    .extend(collect_build_context_warnings(&images, &self.options));
+                self.warnings.extend(app_warnings);
+                self.warnings.extend(stack_output_warnings);
+                Ok(self)
+            }
             _ => {
-                error!("Language not supported");
-                // TODO: Refacto this
-                Err("An error occured".to_string())
+                let input = read_input(reader)?;
+
+                self.deserialize_value(&input)
             }
         }
     }
 }
 
-#[derive(Debug, PartialEq)]
+/***
+ * Parse the inputs in parallel, the results are in the order of the inputs
+ */
+pub fn parse_many(
+    inputs: Vec<(String, Language)>,
+    options: &BuildOptions,
+) -> Vec<Result<Vec<ContainerAppConfiguration>, DeserializeError>> {
+    thread::scope(|scope| {
+        let handles: Vec<_> = inputs
+            .iter()
+            .map(|(input, language)| scope.spawn(move || parse_one(input, *language, options)))
+            .collect();
+
+        handles
+            .into_iter()
+            .map(|handle| {
+                handle
+                    .join()
+                    .unwrap_or_else(|_| Err(DeserializeError::Parse("Parser panicked".to_string())))
+            })
+            .collect()
+    })
+}
+
+fn parse_one(
+    input: &str,
+    language: Language,
+    options: &BuildOptions,
+) -> Result<Vec<ContainerAppConfiguration>, DeserializeError> {
+    let mut pulumi = Pulumi::new(language)
+        .ok_or(DeserializeError::UnsupportedLanguage)?
+        .with_options(options.clone());
+
+    Ok(pulumi
+        .deserialize_value(input)?
+        .resources
+        .clone()
+        .unwrap_or_default())
+}
+
+#[derive(Debug, Clone, PartialEq)]
 struct Resource {
     name: String,
     is_reference: bool,
@@ -60,22 +382,55 @@ struct Resource {
 /***
  * Docker Pulumi Formatter image
  */
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct DockerImageForPulumi {
-    name: Option<String>,
-    path: Option<String>,
-    is_context: bool,
+    /// Image to pull, set for literal images
+    pub name: Option<String>,
+    /// Build context, set for referenced images
+    pub path: Option<String>,
+    pub is_context: bool,
+    /// Compose build block of referenced images (eg: with their dockerfile), its context is the path
+    pub build: Option<BuildContext>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct AppConfiguration {
     pub container: ContainerBluePrint,
     pub dapr_configuration: Option<DaprBluePrint>,
     pub ingress_configuration: Option<IngressBluePrint>,
+    pub scale_configuration: Option<ScaleBluePrint>,
+    pub identity: Option<Identity>,
+    pub workload_profile_name: Option<String>,
+    pub revision_suffix: Option<String>,
+    pub max_inactive_revisions: Option<u32>,
+    pub location: Option<String>,
+    pub resource_group_name: Option<String>,
+    /// Services the app services wait for (eg: the init containers)
+    pub depends_on: Option<DependsOn>,
 }
 
+/***
+ * References are detected by the `${resource.property}` syntax only,
+ * parsers wrap bare property accesses (eg: ${myImage.imageName})
+ */
 fn extract_and_parse_resource_name(s: String) -> Result<Resource, ()> {
-    let mut is_reference = s.contains("${");
+    let is_reference = s.contains("${");
+
+    if s.trim().is_empty() {
+        return Err(());
+    }
+
+    // Images pinned by digest (eg: app@sha256:...) are passed through unchanged
+    let is_digest_pinned = Regex::new(r"@[a-z0-9]+:[a-f0-9]{32,}$")
+        .expect("Should match digest regex")
+        .is_match(&s);
+
+    if is_digest_pinned && !is_reference {
+        return Ok(Resource {
+            name: s,
+            is_reference,
+        });
+    }
     match Regex::new(r"\$\{(.+)\.(.+)\}")
         .expect("Should match previous regex")
         .captures(&s)
@@ -85,26 +440,319 @@ fn extract_and_parse_resource_name(s: String) -> Result<Resource, ()> {
 
             Ok(Resource { name, is_reference })
         }
-        None => {
-            if s.contains("imageName") {
-                is_reference = true;
+        // Interpolation without a resource property (eg: ${myImage})
+        None if is_reference => Err(()),
+        None => Ok(Resource {
+            name: s,
+            is_reference,
+        }),
+    }
+}
+
+/***
+ * Parts of a literal image (eg: localhost:5000/app:v1)
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImageReference {
+    /// First segment when it is a host (eg: localhost:5000, myacr.azurecr.io)
+    pub registry: Option<String>,
+    pub name: String,
+    pub tag: Option<String>,
+    pub digest: Option<String>,
+}
+
+/***
+ * Split a literal image, the tag is after the colon of the last segment only,
+ * a colon in the registry is its port (eg: localhost:5000/app:v1)
+ */
+pub fn parse_image_reference(image: &str) -> ImageReference {
+    let (image, digest) = match image.split_once('@') {
+        Some((image, digest)) => (image, Some(digest.to_string())),
+        None => (image, None),
+    };
+    let (registry, path) = match image.split_once('/') {
+        Some((host, path)) if host.contains(['.', ':']) || host == "localhost" => {
+            (Some(host.to_string()), path)
+        }
+        _ => (None, image),
+    };
+    let (name, tag) = match path.rsplit_once(':') {
+        Some((name, tag)) if !tag.contains('/') => (name, Some(tag.to_string())),
+        _ => (path, None),
+    };
+
+    ImageReference {
+        registry,
+        name: name.to_string(),
+        tag,
+        digest,
+    }
+}
+
+/***
+ * Registry interpolation of an image and its repository with its tag or digest (eg:
+ * ${registry.loginServer} and node-app:v1 for ${registry.loginServer}/node-app:v1),
+ * the slashes of the interpolations do not split the image
+ */
+pub fn split_registry_interpolation(image: &str) -> (Option<&str>, &str) {
+    let mut depth = 0;
+
+    for (index, c) in image.char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' => depth -= 1,
+            '/' if depth == 0 => {
+                let registry = &image[..index];
+
+                return match registry.contains("${") {
+                    true => (Some(registry), &image[index + 1..]),
+                    false => (None, image),
+                };
+            }
+            _ => {}
+        }
+    }
+
+    (None, image)
+}
+
+/***
+ * Local tag of an image built from its context, the registry interpolation is stripped
+ * (eg: node-app:v1 for ${registry.loginServer}/node-app:v1), none when it is not known
+ */
+fn build_local_tag(
+    image: &ContainerImageBluePrint,
+    resolver: &dyn TokenResolver,
+) -> Option<String> {
+    let (registry, repository) = split_registry_interpolation(image.name.as_deref()?);
+    registry?;
+
+    // A digest cannot tag a local build
+    let repository = substitute_tokens(repository, resolver);
+    let repository = repository.split('@').next().unwrap_or_default();
+
+    (!repository.is_empty() && !repository.contains("${")).then(|| repository.to_string())
+}
+
+/***
+ * Every `${resource.property}` reference of the input (eg: `${a.b}:${c.d}`), as `(resource, token)`
+ */
+fn extract_resource_references(s: &str) -> Vec<(String, String)> {
+    Regex::new(r"\$\{([^}.]+)\.([^}]+)\}")
+        .expect("Should match reference regex")
+        .captures_iter(s)
+        .map(|v| (v[1].to_string(), format!("{}.{}", &v[1], &v[2])))
+        .collect()
+}
+
+/***
+ * Prefix a relative build context with the base path, absolute contexts are kept as is
+ */
+fn resolve_build_context(context: &str, base_path: Option<&str>) -> String {
+    match base_path {
+        Some(base_path) if !context.starts_with('/') => {
+            let base_path = base_path.trim_end_matches('/');
+
+            match context.trim_start_matches("./") {
+                "" | "." => base_path.to_string(),
+                context => format!("{}/{}", base_path, context),
             }
-            Ok(Resource {
-                name: s,
-                is_reference,
-            })
         }
+        _ => context.to_string(),
+    }
+}
+
+/***
+ * Replace the `${...}` tokens known by the resolver, the others are kept
+ */
+fn substitute_tokens(input: &str, resolver: &dyn TokenResolver) -> String {
+    Regex::new(r"\$\{([^}]+)\}")
+        .expect("Should match token regex")
+        .replace_all(input, |captures: &regex::Captures| {
+            resolver
+                .resolve(&captures[1])
+                .unwrap_or_else(|| captures[0].to_string())
+        })
+        .to_string()
+}
+
+/***
+ * Build context of an image with its tokens resolved (eg: ${pulumi.cwd} or ${config.root}),
+ * the placeholder context named after the image (eg: ./myImage) when a token is not known
+ */
+fn interpolate_build_context(
+    image: &ContainerImageBluePrint,
+    resolver: &dyn TokenResolver,
+) -> Result<String, String> {
+    let context = substitute_tokens(&image.build.context, resolver);
+
+    match context.contains("${") || context.trim().is_empty() {
+        false => Ok(context),
+        true => Err(format!(
+            "./{}",
+            image
+                .reference_name
+                .as_deref()
+                .or(image.name.as_deref())
+                .unwrap_or_default()
+        )),
+    }
+}
+
+/***
+ * Images whose build context cannot be resolved, they are built from a placeholder context
+ */
+pub fn collect_build_context_warnings(
+    images: &[ContainerImageBluePrint],
+    resolver: &dyn TokenResolver,
+) -> Vec<String> {
+    images
+        .iter()
+        .filter_map(|image| {
+            let placeholder = interpolate_build_context(image, resolver).err()?;
+
+            Some(format!(
+                "unresolved build context {} of {}, {} is used instead",
+                image.build.context,
+                image.reference_name.as_deref().unwrap_or_default(),
+                placeholder
+            ))
+        })
+        .collect()
+}
+
+/***
+ * Image used in place of an unresolved stack output (eg: stack-output/apiimage for apiImage)
+ */
+fn stack_output_placeholder(output: &str) -> String {
+    let name: String = output
+        .to_lowercase()
+        .chars()
+        .map(|c| match c.is_ascii_alphanumeric() || "._-".contains(c) {
+            true => c,
+            false => '-',
+        })
+        .collect();
+
+    format!("stack-output/{}", name)
+}
+
+/***
+ * Stack outputs of the app images which are not given, by the stack name of their
+ * `StackReference` (eg: org/infra/dev) and the variable name when the stack is not known
+ */
+pub fn collect_stack_output_warnings(
+    apps: &[ContainerAppBluePrint],
+    stack_references: &BTreeMap<String, String>,
+    resolver: &dyn TokenResolver,
+) -> Vec<String> {
+    let token_regex = Regex::new(r"\$\{([^}]+)\}").expect("Should match token regex");
+    let mut warnings: Vec<String> = vec![];
+
+    let images = apps
+        .iter()
+        .filter_map(|app| app.template.as_ref()?.containers.as_ref())
+        .flatten()
+        .map(|container| substitute_tokens(&container.image, resolver));
+
+    for image in images {
+        let Some((variable, output)) = token_regex
+            .captures_iter(&image)
+            .find_map(|captures| stack_output_reference(&captures[1]))
+        else {
+            continue;
+        };
+
+        let warning = format!(
+            "unresolved output {} of stack {}, {} is used instead",
+            output,
+            stack_references.get(&variable).unwrap_or(&variable),
+            stack_output_placeholder(&output)
+        );
+
+        if !warnings.contains(&warning) {
+            warnings.push(warning);
+        }
+    }
+    warnings
+}
+
+/***
+ * Stack names of the `StackReference`s of a program, by variable or resource name
+ * (eg: infra for const infra = new pulumi.StackReference("org/infra/dev"))
+ */
+pub fn get_stack_references(input: &str, language: Language) -> BTreeMap<String, String> {
+    if language == Language::Yaml {
+        let program: serde_yaml::Value = serde_yaml::from_str(input).unwrap_or_default();
+
+        return get_program_stack_references(&program);
     }
+
+    // eg: infra = pulumi.StackReference("org/infra/dev") or infra, err := pulumi.NewStackReference(ctx, "org/infra/dev", nil)
+    Regex::new(
+        r#"([A-Za-z_$][\w$]*)(?:\s*,\s*[\w$]+)?\s*:?=\s*(?:new\s+)?(?:[\w$]+\.)*(?:New)?StackReference\(\s*(?:ctx\s*,\s*)?["']([^"']+)["']"#,
+    )
+    .expect("Should match stack reference regex")
+    .captures_iter(input)
+    .map(|captures| (captures[1].to_string(), captures[2].to_string()))
+    .collect()
+}
+
+/***
+ * Stack names of the `pulumi:pulumi:StackReference` resources of a YAML program, by resource name,
+ * the stack is the resource name when its `name` property is not set
+ */
+pub fn get_program_stack_references(program: &serde_yaml::Value) -> BTreeMap<String, String> {
+    program
+        .get("resources")
+        .and_then(serde_yaml::Value::as_mapping)
+        .into_iter()
+        .flatten()
+        .filter(|(_, resource)| {
+            resource.get("type").and_then(serde_yaml::Value::as_str)
+                == Some("pulumi:pulumi:StackReference")
+        })
+        .filter_map(|(name, resource)| {
+            let name = name.as_str()?;
+            let stack = resource
+                .get("properties")
+                .and_then(|properties| properties.get("name"))
+                .and_then(serde_yaml::Value::as_str)
+                .unwrap_or(name);
+
+            Some((name.to_string(), stack.to_string()))
+        })
+        .collect()
+}
+
+/***
+ * Values of a stack outputs file (eg: pulumi stack output --json > outputs.json), by output name,
+ * the values which are not strings are kept as JSON
+ */
+pub fn parse_stack_outputs(input: &str) -> Result<BTreeMap<String, String>, DeserializeError> {
+    let outputs: BTreeMap<String, serde_json::Value> =
+        serde_json::from_str(input).map_err(|e| DeserializeError::Parse(e.to_string()))?;
+
+    Ok(outputs
+        .into_iter()
+        .map(|(name, value)| match value {
+            serde_json::Value::String(value) => (name, value),
+            value => (name, value.to_string()),
+        })
+        .collect())
 }
 
 fn check_and_match_reference(
-    images: &Vec<ContainerImageBluePrint>,
+    images: &[ContainerImageBluePrint],
     resource: Resource,
+    base_path: Option<&str>,
+    resolver: &dyn TokenResolver,
 ) -> Option<DockerImageForPulumi> {
     // If has no reference, return contextual image
     if !resource.is_reference {
         return Some(DockerImageForPulumi {
             is_context: false,
+            build: None,
             name: Some(resource.name),
             path: None,
         });
@@ -113,16 +761,29 @@ fn check_and_match_reference(
     let name = &resource.name;
     let val = images
         .iter()
-        .find(|image| &image.reference_name.clone().unwrap() == name);
+        .find(|image| image.reference_name.as_deref() == Some(name));
 
     match val {
         Some(val) => {
-            let has_build_context = &val.build.context;
+            let context = interpolate_build_context(val, resolver).unwrap_or_else(|placeholder| {
+                warn!(
+                    target: LOG_TARGET,
+                    "Unresolved build context {} of {}, {} is used instead",
+                    val.build.context,
+                    name,
+                    placeholder
+                );
+                placeholder
+            });
+            let path = resolve_build_context(&context, base_path);
 
             Some(DockerImageForPulumi {
                 name: None,
-                // TODO: Need to catch all possible pattern (pulumi.cwd, pulumi.all, pulumi.interpolate etc...)
-                path: Some(has_build_context.replace("${pulumi.cwd}", ".")),
+                build: Some(BuildContext {
+                    tags: build_local_tag(val, resolver).map(|tag| vec![tag]),
+                    ..build_context_for_serialization(&val.build, path.clone(), resolver)
+                }),
+                path: Some(path),
                 is_context: true,
             })
         }
@@ -130,30 +791,181 @@ fn check_and_match_reference(
     }
 }
 
+/***
+ * Compose build block of an image, its dockerfile and args support the same tokens as its context
+ */
+fn build_context_for_serialization(
+    build: &BuildContextBluePrint,
+    context: String,
+    resolver: &dyn TokenResolver,
+) -> BuildContext {
+    BuildContext {
+        context,
+        dockerfile: build
+            .dockerfile
+            .as_ref()
+            .map(|dockerfile| substitute_tokens(dockerfile, resolver)),
+        args: build.args.as_ref().map(|args| {
+            args.iter()
+                .map(|(name, value)| {
+                    (
+                        name.clone(),
+                        substitute_tokens(&format_label_value(value), resolver),
+                    )
+                })
+                .collect()
+        }),
+        target: build.target.clone(),
+        platforms: build.platform.clone().map(|platform| vec![platform]),
+        tags: None,
+    }
+}
+
 fn build_image_for_serialization(
-    images: &Vec<ContainerImageBluePrint>,
+    images: &[ContainerImageBluePrint],
     container: ContainerBluePrint,
+    base_path: Option<&str>,
+    resolver: &dyn TokenResolver,
 ) -> Option<DockerImageForPulumi> {
-    let resource =
-        extract_and_parse_resource_name(container.image).expect("Should contains name property");
+    // Fully resolved images are literal ones
+    let image = substitute_tokens(&container.image, resolver);
+    let stack_outputs: Vec<String> = Regex::new(r"\$\{([^}]+)\}")
+        .expect("Should match token regex")
+        .captures_iter(&image)
+        .filter_map(|captures| stack_output_name(&captures[1]))
+        .collect();
+
+    // Outputs of other stacks are only known from the given values (eg: --stack-outputs),
+    // a placeholder image is used instead
+    if !stack_outputs.is_empty() {
+        let placeholder = stack_output_placeholder(&stack_outputs[0]);
+        warn!(
+            target: LOG_TARGET,
+            "Unresolved stack outputs {} of image {}, {} is used instead",
+            stack_outputs.join(", "),
+            image,
+            placeholder
+        );
+
+        return Some(DockerImageForPulumi {
+            name: Some(placeholder),
+            path: None,
+            is_context: false,
+            build: None,
+        });
+    }
+
+    // Images named after a registry (eg: ${registry.loginServer}/node-app:v1) are the image
+    // resource of this name, the others are remote images kept as is
+    if let (Some(_), repository) = split_registry_interpolation(&image) {
+        if !repository.contains("${") {
+            let reference = images.iter().find(|candidate| {
+                candidate
+                    .name
+                    .as_deref()
+                    .is_some_and(|name| substitute_tokens(name, resolver) == image)
+            });
+
+            return match reference.and_then(|reference| reference.reference_name.clone()) {
+                Some(name) => check_and_match_reference(
+                    images,
+                    Resource {
+                        name,
+                        is_reference: true,
+                    },
+                    base_path,
+                    resolver,
+                ),
+                None => Some(DockerImageForPulumi {
+                    name: Some(image),
+                    path: None,
+                    is_context: false,
+                    build: None,
+                }),
+            };
+        }
+    }
+
+    let resource = extract_and_parse_resource_name(image).ok()?;
+
+    check_and_match_reference(images, resource, base_path, resolver)
+}
+
+/***
+ * Resolve the image of a single container, without running the whole pipeline
+ */
+pub fn resolve_image(
+    images: &[ContainerImageBluePrint],
+    container: &ContainerBluePrint,
+) -> Result<DockerImageForPulumi, ResolveError> {
+    let resource = extract_and_parse_resource_name(container.image.clone())
+        .map_err(|_| ResolveError::UnparseableImage(container.image.clone()))?;
+
+    check_and_match_reference(images, resource, None, &DefaultTokenResolver::default())
+        .ok_or_else(|| ResolveError::ReferenceNotFound(container.image.clone()))
+}
+
+/***
+ * Distinct resources referenced by the app containers that match neither an image nor a token
+ */
+pub fn collect_unresolved_references(
+    apps: &[ContainerAppBluePrint],
+    images: &[ContainerImageBluePrint],
+    options: &BuildOptions,
+) -> Vec<String> {
+    let mut unresolved: Vec<String> = vec![];
+
+    let containers = apps
+        .iter()
+        .filter_map(|app| app.template.as_ref())
+        .flat_map(|template| {
+            let containers = template.containers.iter().flatten();
+
+            template.init_containers.iter().flatten().chain(containers)
+        });
+
+    for container in containers {
+        for (name, token) in extract_resource_references(&container.image) {
+            let is_image = images
+                .iter()
+                .any(|image| image.reference_name.as_deref() == Some(name.as_str()));
+
+            if !is_image && options.resolve(&token).is_none() && !unresolved.contains(&name) {
+                unresolved.push(name);
+            }
+        }
+    }
+
+    unresolved
+}
 
-    check_and_match_reference(images, resource)
+/***
+ * TCP ports range from 1 to 65535
+ */
+fn validate_port(field: &str, port: Option<u32>) -> Result<(), DeserializeError> {
+    match port {
+        Some(value) if !(1..=65535).contains(&value) => Err(DeserializeError::InvalidPort {
+            field: field.to_string(),
+            value,
+        }),
+        _ => Ok(()),
+    }
 }
 
 fn build_ports_mapping_for_serialization(
     configuration: AppConfiguration,
-) -> (Option<u32>, Option<Vec<String>>) {
+) -> Result<(Option<u32>, Option<Vec<String>>), DeserializeError> {
     let dapr_configuration = configuration.dapr_configuration;
     let ingress_configuration = configuration.ingress_configuration;
     let container_name = configuration.container.name;
 
     let has_dapr_enabled = match &dapr_configuration {
-        Some(v) => v.enabled.is_some() && v.enabled.unwrap() == true,
+        Some(v) => v.enabled.is_some() && v.enabled.unwrap(),
         None => false,
     };
 
     let has_ingress_exposed = match &ingress_configuration {
-        Some(v) => v.external.is_some() && v.external.unwrap() == true,
+        Some(v) => v.external.is_some() && v.external.unwrap(),
         None => false,
     };
 
@@ -167,11 +979,25 @@ fn build_ports_mapping_for_serialization(
         None => None,
     };
 
-    let ingress_app_port = match ingress_configuration {
+    let ingress_app_port = match &ingress_configuration {
         Some(val) => val.target_port,
         None => None,
     };
 
+    let additional_port_mappings = ingress_configuration
+        .and_then(|val| val.additional_port_mappings)
+        .unwrap_or_default();
+
+    validate_port("dapr.appPort", dapr_app_port)?;
+    validate_port("ingress.targetPort", ingress_app_port)?;
+
+    for (index, mapping) in additional_port_mappings.iter().enumerate() {
+        let field = format!("ingress.additionalPortMappings[{}]", index);
+
+        validate_port(&format!("{}.targetPort", field), mapping.target_port)?;
+        validate_port(&format!("{}.exposedPort", field), mapping.exposed_port)?;
+    }
+
     let mut ports: Vec<String> = vec![];
     // TODO: Assert for now than source and target ports are sames (container name and dapr target)
 
@@ -181,8 +1007,8 @@ fn build_ports_mapping_for_serialization(
         if has_right_target {
             ports.push(format!(
                 "{}:{}",
-                ingress_app_port.unwrap_or_default().to_string(),
-                dapr_app_port.unwrap_or_default().to_string()
+                ingress_app_port.unwrap_or_default(),
+                dapr_app_port.unwrap_or_default()
             ))
         }
     }
@@ -190,475 +1016,5785 @@ fn build_ports_mapping_for_serialization(
     if (!has_dapr_enabled) && has_ingress_exposed {
         ports.push(format!(
             "{}:{}",
-            ingress_app_port.unwrap_or_default().to_string(),
-            ingress_app_port.unwrap_or_default().to_string()
+            ingress_app_port.unwrap_or_default(),
+            ingress_app_port.unwrap_or_default()
         ))
     }
 
-    (
+    // Additional mappings come after the primary one, the Dapr rewrite does not apply
+    for mapping in additional_port_mappings {
+        if mapping.external != Some(true) {
+            continue;
+        }
+
+        let target_port = mapping.target_port.unwrap_or_default();
+        let exposed_port = mapping.exposed_port.unwrap_or(target_port);
+
+        // A host port can only be bound once
+        let is_bound = ports
+            .iter()
+            .any(|port| port.starts_with(&format!("{}:", exposed_port)));
+
+        if !is_bound {
+            ports.push(format!("{}:{}", exposed_port, target_port));
+        }
+    }
+
+    Ok((
         dapr_app_port,
         if !ports.is_empty() { Some(ports) } else { None },
-    )
+    ))
 }
 
-fn parse_app_configuration(
-    images: &Vec<ContainerImageBluePrint>,
-    configuration: AppConfiguration,
-) -> Option<Vec<ContainerAppConfiguration>> {
-    let container = configuration.container.clone();
-    let dapr_configuration = configuration.dapr_configuration.clone();
+/***
+ * Split a shell-form command into arguments, respecting quotes and escapes
+ */
+fn split_shell_command(command: &str) -> Result<Vec<String>, DeserializeError> {
+    let mut args: Vec<String> = vec![];
+    let mut current = String::new();
+    let mut has_token = false;
+    let mut quote: Option<char> = None;
+    let mut chars = command.chars();
 
-    let image = build_image_for_serialization(images, container)?;
-    let name = configuration.container.name.clone();
-    let (dapr_app_port, ports) = build_ports_mapping_for_serialization(configuration);
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some('"'), '\\') | (None, '\\') => match chars.next() {
+                Some(escaped) => current.push(escaped),
+                None => return Err(DeserializeError::InvalidCommand(command.to_string())),
+            },
+            (Some(_), c) => current.push(c),
+            (None, '"' | '\'') => {
+                quote = Some(c);
+                has_token = true;
+            }
+            (None, c) if c.is_whitespace() => {
+                if has_token || !current.is_empty() {
+                    args.push(std::mem::take(&mut current));
+                    has_token = false;
+                }
+            }
+            (None, c) => current.push(c),
+        }
+    }
 
-    let has_dapr_enabled = match dapr_configuration {
-        Some(v) => v.enabled.unwrap(),
-        None => false,
-    };
+    if quote.is_some() {
+        return Err(DeserializeError::InvalidCommand(command.to_string()));
+    }
 
-    let result = if has_dapr_enabled {
-        vec![
+    if has_token || !current.is_empty() {
+        args.push(current);
+    }
+
+    Ok(args)
+}
+
+/***
+ * Replace `${...}` tokens by their value, unknown tokens are logged and stripped
+ */
+fn resolve_pulumi_tokens(input: &str, resolver: &dyn TokenResolver) -> String {
+    Regex::new(r"\$\{([^}]+)\}")
+        .expect("Should match token regex")
+        .replace_all(input, |captures: &regex::Captures| {
+            match resolver.resolve(&captures[1]) {
+                Some(value) => value,
+                None => {
+                    warn!(
+                        target: LOG_TARGET,
+                        "Unresolved token {} is stripped",
+                        &captures[0]
+                    );
+                    String::new()
+                }
+            }
+        })
+        .to_string()
+}
+
+/***
+ * Compose service names are lowercase and only contain [a-z0-9_-]
+ */
+fn build_service_name(container_name: &str, revision_suffix: Option<&str>) -> String {
+    // A suffix without any valid character does not leave a trailing separator
+    let suffix = revision_suffix
+        .map(sanitize_service_name)
+        .map(|suffix| suffix.trim_matches(|c| c == '-' || c == '_').to_string());
+
+    match suffix {
+        Some(suffix) if !suffix.is_empty() => {
+            sanitize_service_name(&format!("{}-{}", container_name, suffix))
+        }
+        _ => sanitize_service_name(container_name),
+    }
+}
+
+fn sanitize_service_name(name: &str) -> String {
+    name.to_lowercase()
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | '0'..='9' | '_' | '-' => c,
+            _ => '-',
+        })
+        .collect()
+}
+
+/***
+ * Tuning flags of the generated daprd command, values must be positive integers
+ */
+fn build_dapr_tuning_flags(
+    dapr_configuration: &DaprBluePrint,
+) -> Result<Vec<String>, DeserializeError> {
+    let options = [
+        (
+            "maxConcurrency",
+            "-app-max-concurrency",
+            dapr_configuration.max_concurrency,
+        ),
+        (
+            "httpMaxRequestSize",
+            "-dapr-http-max-request-size",
+            dapr_configuration.http_max_request_size,
+        ),
+    ];
+    let mut flags: Vec<String> = vec![];
+
+    for (option, flag, value) in options {
+        match value {
+            Some(value) if value > 0 => {
+                flags.push(flag.to_string());
+                flags.push(value.to_string());
+            }
+            Some(_) => return Err(DeserializeError::InvalidDaprOption(option.to_string())),
+            None => {}
+        }
+    }
+
+    Ok(flags)
+}
+
+fn build_dapr_components_flags(dapr_configuration: &DaprBluePrint) -> Vec<String> {
+    match &dapr_configuration.components {
+        Some(components) if !components.is_empty() => vec![
+            "-components-path".to_string(),
+            DAPR_COMPONENTS_PATH.to_string(),
+        ],
+        _ => vec![],
+    }
+}
+
+/***
+ * Dapr component files of the apps, by `<app id>-<component>` name as components of
+ * different apps may share a name. They are mounted in the components path of the
+ * sidecars by `build_dapr_components_configs`
+ */
+pub fn build_dapr_components(
+    apps: &[ContainerAppBluePrint],
+) -> Result<BTreeMap<String, String>, DeserializeError> {
+    let mut files: BTreeMap<String, String> = BTreeMap::new();
+
+    for app in apps {
+        let Some(dapr) = app
+            .configuration
+            .as_ref()
+            .and_then(|configuration| configuration.dapr.as_ref())
+        else {
+            continue;
+        };
+        // Sidecars without app id are named after their container
+        let container_name = app
+            .template
+            .as_ref()
+            .and_then(|template| template.containers.as_ref())
+            .and_then(|containers| containers.first())
+            .map(|container| container.name.as_str())
+            .unwrap_or_default();
+        let app_id = build_dapr_app_id(dapr, container_name);
+
+        for component in dapr.components.iter().flatten() {
+            let key = format_dapr_component_name(&app_id, component);
+
+            if files.contains_key(&key) {
+                return Err(DeserializeError::ConflictingDeclaration(key));
+            }
+            files.insert(key, build_dapr_component_file(component)?);
+        }
+    }
+
+    Ok(files)
+}
+
+fn build_dapr_component_file(
+    component: &DaprComponentBluePrint,
+) -> Result<String, DeserializeError> {
+    let metadata: Vec<serde_json::Value> = component
+        .metadata
+        .iter()
+        .flatten()
+        .map(
+            |(name, value)| serde_json::json!({ "name": name, "value": format_label_value(value) }),
+        )
+        .collect();
+    let definition = serde_json::json!({
+        "apiVersion": "dapr.io/v1alpha1",
+        "kind": "Component",
+        "metadata": { "name": component.name },
+        "spec": {
+            "type": component.component_type,
+            "version": "v1",
+            "metadata": metadata,
+        },
+    });
+
+    serde_yaml::to_string(&definition).map_err(|e| DeserializeError::Parse(e.to_string()))
+}
+
+/***
+ * Id the app is invoked by, the revision suffix of the service name is left out
+ */
+fn build_dapr_app_id(dapr_configuration: &DaprBluePrint, container_name: &str) -> String {
+    dapr_configuration
+        .app_id
+        .clone()
+        .unwrap_or_else(|| sanitize_service_name(container_name))
+}
+
+/***
+ * Component files named `<app id>-<component>`, as the configs are global to the compose file
+ */
+fn format_dapr_component_name(app_id: &str, component: &DaprComponentBluePrint) -> String {
+    format!("{}-{}", app_id, component.name)
+}
+
+/***
+ * Component files mounted in the components path of the sidecar, the config sources are the
+ * names of the files of `build_dapr_components`
+ */
+fn build_dapr_components_configs(
+    app_id: &str,
+    dapr_configuration: &DaprBluePrint,
+) -> Result<Option<Vec<ServiceConfig>>, DeserializeError> {
+    let configs = dapr_configuration
+        .components
+        .iter()
+        .flatten()
+        .map(|component| {
+            Ok(ServiceConfig {
+                source: format_dapr_component_name(app_id, component),
+                target: format!("{}/{}.yaml", DAPR_COMPONENTS_PATH, component.name),
+                content: Some(build_dapr_component_file(component)?),
+            })
+        })
+        .collect::<Result<Vec<_>, DeserializeError>>()?;
+
+    Ok(Some(configs).filter(|configs| !configs.is_empty()))
+}
+
+/***
+ * Flags are `-flag value` pairs, the ones already given by a custom command are kept as is
+ */
+fn append_dapr_flags(mut command: Vec<String>, flags: Vec<String>) -> Vec<String> {
+    for pair in flags.chunks(2) {
+        if !command.contains(&pair[0]) {
+            command.extend_from_slice(pair);
+        }
+    }
+    command
+}
+
+fn build_dapr_command(
+    name: &str,
+    dapr_app_port: Option<u32>,
+    command: Option<CommandBluePrint>,
+    flags: Vec<String>,
+) -> Result<Vec<String>, DeserializeError> {
+    match command {
+        Some(CommandBluePrint::Shell(command)) => {
+            Ok(append_dapr_flags(split_shell_command(&command)?, flags))
+        }
+        Some(CommandBluePrint::Args(args)) => Ok(append_dapr_flags(args, flags)),
+        None => Ok([
+            vec![
+                "./daprd".to_string(),
+                "-app-id".to_string(),
+                String::from(name),
+                "-app-port".to_string(),
+                format!("{}", dapr_app_port.unwrap_or_default()),
+                "-placement-host-address".to_string(),
+                "placement:50006".to_string(),
+            ],
+            flags,
+            vec!["air".to_string()],
+        ]
+        .concat()),
+    }
+}
+
+/***
+ * Sidecar image precedence: per-app, then global option, then built-in default
+ */
+fn resolve_dapr_sidecar_image(
+    dapr_configuration: &DaprBluePrint,
+    dapr_options: &DaprRuntimeOptions,
+) -> String {
+    dapr_configuration
+        .sidecar_image
+        .clone()
+        .or_else(|| dapr_options.sidecar_image.clone())
+        .unwrap_or_else(|| DEFAULT_DAPR_SIDECAR_IMAGE.to_string())
+}
+
+fn format_label_value(value: &serde_yaml::Value) -> String {
+    match value {
+        serde_yaml::Value::String(value) => value.clone(),
+        serde_yaml::Value::Null => String::new(),
+        value => serde_yaml::to_string(value)
+            .unwrap_or_default()
+            .trim()
+            .to_string(),
+    }
+}
+
+/***
+ * Scale rules are not enforced by compose, they are kept as `capp.scale.rule.<name>.*` labels
+ */
+fn build_scale_rule_labels(
+    labels: &mut BTreeMap<String, String>,
+    index: usize,
+    rule: &ScaleRuleBluePrint,
+) {
+    let name = rule.name.clone().unwrap_or_else(|| index.to_string());
+    let prefix = format!("capp.scale.rule.{}", name);
+
+    let (rule_type, metadata) = match (&rule.http, &rule.custom) {
+        (Some(http), _) => (Some("http".to_string()), &http.metadata),
+        (None, Some(custom)) => (custom.rule_type.clone(), &custom.metadata),
+        (None, None) => return,
+    };
+
+    if let Some(rule_type) = rule_type {
+        labels.insert(format!("{}.type", prefix), rule_type);
+    }
+
+    for (key, value) in metadata.iter().flatten() {
+        labels.insert(format!("{}.{}", prefix, key), format_label_value(value));
+    }
+}
+
+/***
+ * Shared by the app service and its sidecar, both run on the same profile
+ */
+fn build_workload_profile_labels(configuration: &AppConfiguration) -> BTreeMap<String, String> {
+    match &configuration.workload_profile_name {
+        Some(profile) => BTreeMap::from([("capp.workloadProfile".to_string(), profile.clone())]),
+        None => BTreeMap::new(),
+    }
+}
+
+/***
+ * Metadata that cannot be expressed in compose is kept as `capp.*` labels
+ */
+fn build_labels_for_serialization(
+    configuration: &AppConfiguration,
+    resolver: &dyn TokenResolver,
+) -> Option<BTreeMap<String, String>> {
+    let mut labels: BTreeMap<String, String> = BTreeMap::new();
+
+    let cors_policy = configuration
+        .ingress_configuration
+        .as_ref()
+        .and_then(|ingress| ingress.cors_policy.as_ref());
+
+    if let Some(cors_policy) = cors_policy {
+        let allowlists = [
+            ("allowedOrigins", &cors_policy.allowed_origins),
+            ("allowedMethods", &cors_policy.allowed_methods),
+        ];
+
+        for (key, values) in allowlists {
+            match values {
+                Some(values) if !values.is_empty() => {
+                    labels.insert(format!("capp.ingress.cors.{}", key), values.join(","));
+                }
+                _ => (),
+            }
+        }
+    }
+
+    let custom_domains = configuration
+        .ingress_configuration
+        .as_ref()
+        .and_then(|ingress| ingress.custom_domains.as_ref());
+
+    for domain in custom_domains.iter().copied().flatten() {
+        // Domains without certificate binding are disabled
+        let binding_type = domain
+            .binding_type
+            .clone()
+            .unwrap_or_else(|| "Disabled".to_string());
+
+        labels.insert(
+            format!("capp.ingress.domain.{}.bindingType", domain.name),
+            binding_type,
+        );
+    }
+
+    let transport = configuration
+        .ingress_configuration
+        .as_ref()
+        .and_then(|ingress| ingress.transport.as_deref());
+
+    // Compose has no HTTP/2 ingress, other transports map to the plain port
+    if transport.is_some_and(|transport| transport.eq_ignore_ascii_case("http2")) {
+        labels.insert("capp.ingress.transport".to_string(), "http2".to_string());
+    }
+
+    // Compose has no mutual TLS, the mode is kept for reference
+    if let Some(mode) = configuration
+        .ingress_configuration
+        .as_ref()
+        .and_then(|ingress| ingress.client_certificate_mode)
+    {
+        labels.insert("capp.ingress.clientCertMode".to_string(), mode.to_string());
+    }
+
+    if let Some(identity) = &configuration.identity {
+        labels.insert(
+            "capp.identity.type".to_string(),
+            identity.identity_type.clone(),
+        );
+
+        let user_assigned = identity
+            .user_assigned_identities
+            .as_ref()
+            .map(|identities| identities.ids())
+            .unwrap_or_default();
+
+        if !user_assigned.is_empty() {
+            labels.insert(
+                "capp.identity.userAssigned".to_string(),
+                user_assigned.join(","),
+            );
+        }
+    }
+
+    let scale_rules = configuration
+        .scale_configuration
+        .as_ref()
+        .and_then(|scale| scale.rules.as_ref());
+
+    for (index, rule) in scale_rules.iter().copied().flatten().enumerate() {
+        build_scale_rule_labels(&mut labels, index, rule);
+    }
+
+    labels.append(&mut build_workload_profile_labels(configuration));
+    labels.append(&mut build_probe_labels(&configuration.container));
+
+    // Azure placement, kept to trace the service back to its app
+    let placement = [
+        ("capp.location", &configuration.location),
+        ("capp.resourceGroup", &configuration.resource_group_name),
+    ];
+
+    for (key, value) in placement {
+        let Some(value) = value else {
+            continue;
+        };
+        let value = substitute_tokens(value, resolver);
+
+        // Compose would read the unresolved output as an invalid interpolation
+        if value.contains("${") {
+            warn!(
+                target: LOG_TARGET,
+                "ignored {} of {}, its value {} cannot be resolved",
+                key,
+                configuration.container.name,
+                value
+            );
+            continue;
+        }
+        labels.insert(key.to_string(), value);
+    }
+
+    // Revision retention has no compose equivalent, kept as metadata
+    if let Some(max_inactive_revisions) = configuration.max_inactive_revisions {
+        labels.insert(
+            "capp.maxInactiveRevisions".to_string(),
+            max_inactive_revisions.to_string(),
+        );
+    }
+
+    if !labels.is_empty() {
+        Some(labels)
+    } else {
+        None
+    }
+}
+
+/***
+ * Probe timing translated to healthcheck (interval, timeout, retries)
+ */
+fn build_probe_timing(probe: &ProbeBluePrint) -> (String, String, u32) {
+    (
+        format!(
+            "{}s",
+            probe.period_seconds.unwrap_or(DEFAULT_PROBE_PERIOD_SECONDS)
+        ),
+        format!(
+            "{}s",
+            probe
+                .timeout_seconds
+                .unwrap_or(DEFAULT_PROBE_TIMEOUT_SECONDS)
+        ),
+        probe
+            .failure_threshold
+            .unwrap_or(DEFAULT_PROBE_FAILURE_THRESHOLD),
+    )
+}
+
+fn flatten_label_values(
+    labels: &mut BTreeMap<String, String>,
+    prefix: &str,
+    value: &serde_yaml::Value,
+) {
+    match value {
+        serde_yaml::Value::Mapping(mapping) => {
+            for (key, value) in mapping {
+                let prefix = format!("{}.{}", prefix, format_label_value(key));
+
+                flatten_label_values(labels, &prefix, value);
+            }
+        }
+        serde_yaml::Value::Null => (),
+        value => {
+            labels.insert(prefix.to_string(), format_label_value(value));
+        }
+    }
+}
+
+/***
+ * Compose has a single healthcheck, startup and readiness probes are kept as labels
+ * (eg: capp.probe.startup.httpGet.path)
+ */
+fn build_probe_labels(container: &ContainerBluePrint) -> BTreeMap<String, String> {
+    let mut labels: BTreeMap<String, String> = BTreeMap::new();
+
+    for probe in container.probes.iter().flatten() {
+        let kind = match probe.probe_type.as_deref() {
+            Some(kind) if kind.eq_ignore_ascii_case("startup") => "startup",
+            Some(kind) if kind.eq_ignore_ascii_case("readiness") => "readiness",
+            _ => continue,
+        };
+        let probe = ProbeBluePrint {
+            probe_type: None,
+            ..probe.clone()
+        };
+
+        if let Ok(value) = serde_yaml::to_value(probe) {
+            flatten_label_values(&mut labels, &format!("capp.probe.{}", kind), &value);
+        }
+    }
+
+    labels
+}
+
+/***
+ * Container resources translated to deploy limits, binary units are
+ * converted to the compose ones (eg: 1Gi to 1g)
+ */
+fn build_deploy_for_serialization(
+    container: &ContainerBluePrint,
+    replicas: Option<u32>,
+) -> Option<Deploy> {
+    let resources = container
+        .resources
+        .as_ref()
+        .filter(|resources| resources.cpu.is_some() || resources.memory.is_some())
+        .map(|resources| DeployResources {
+            limits: ResourceLimits {
+                cpus: resources.cpu.map(|cpu| cpu.to_string()),
+                memory: resources.memory.as_ref().map(|memory| {
+                    memory
+                        .replace("Ki", "k")
+                        .replace("Mi", "m")
+                        .replace("Gi", "g")
+                }),
+            },
+        });
+
+    if resources.is_none() && replicas.is_none() {
+        return None;
+    }
+
+    Some(Deploy {
+        replicas,
+        resources,
+    })
+}
+
+/***
+ * Replicas run locally, a single instance is run below 2 `minReplicas` and for the
+ * Dapr apps as their sidecar shares the network of one instance only
+ */
+fn build_replicas_for_serialization(
+    scale: Option<&ScaleBluePrint>,
+    has_dapr_enabled: bool,
+) -> Option<u32> {
+    scale
+        .and_then(|scale| scale.min_replicas)
+        .filter(|replicas| *replicas >= 2 && !has_dapr_enabled)
+}
+
+/***
+ * Replicas cannot bind the same host port, only the container ports are published
+ * and compose picks an ephemeral host port for each replica
+ */
+fn unpublish_host_ports(ports: &[String]) -> Vec<String> {
+    let mut container_ports: Vec<String> = vec![];
+
+    for port in ports {
+        let container_port = port.rsplit(':').next().unwrap_or(port).to_string();
+
+        if !container_ports.contains(&container_port) {
+            container_ports.push(container_port);
+        }
+    }
+    container_ports
+}
+
+/***
+ * Scale which cannot be run locally, scale to zero, replicated Dapr apps or
+ * replicated apps with fixed host ports
+ */
+pub fn collect_scale_warnings(apps: &[ContainerAppBluePrint]) -> Vec<String> {
+    let mut warnings: Vec<String> = vec![];
+
+    for app in apps {
+        let Some(configuration) = &app.configuration else {
+            continue;
+        };
+        let name = app.name.as_deref().unwrap_or_default();
+        let has_dapr_enabled = configuration
+            .dapr
+            .as_ref()
+            .and_then(|dapr| dapr.enabled)
+            .unwrap_or_default();
+        let has_published_ports = configuration.ingress.as_ref().is_some_and(|ingress| {
+            ingress.external == Some(true)
+                || ingress
+                    .additional_port_mappings
+                    .iter()
+                    .flatten()
+                    .any(|mapping| mapping.external == Some(true))
+        });
+
+        match configuration
+            .scale
+            .as_ref()
+            .and_then(|scale| scale.min_replicas)
+        {
+            Some(0) => warnings.push(format!(
+                "scale to zero of {} cannot be modeled, a single instance is run",
+                name
+            )),
+            Some(replicas) if replicas >= 2 && has_dapr_enabled => warnings.push(format!(
+                "{} replicas of {} are not run, its Dapr sidecar cannot be replicated",
+                replicas, name
+            )),
+            Some(replicas) if replicas >= 2 && has_published_ports => warnings.push(format!(
+                "host ports of {} are not published, its {} replicas cannot bind the same port",
+                name, replicas
+            )),
+            _ => {}
+        }
+    }
+    warnings
+}
+
+/***
+ * Variable of an app secret (eg: DB_PASSWORD for db-password)
+ */
+fn format_secret_variable(secret: &str) -> String {
+    secret
+        .chars()
+        .map(|c| match c.is_ascii_alphanumeric() {
+            true => c.to_ascii_uppercase(),
+            false => '_',
+        })
+        .collect()
+}
+
+/***
+ * Expand the `envFrom` configs given in the options, the others are
+ * returned to be kept as a label. The `env` variables follow in their order
+ * and win over the expanded ones, the secrets are read from the variable
+ * of their name (eg: ${DB_PASSWORD} for db-password) or from their file
+ * with `SecretsMode::Files`
+ */
+fn build_environment_for_serialization(
+    container: &ContainerBluePrint,
+    options: &BuildOptions,
+) -> (Option<Vec<String>>, Vec<String>) {
+    let mut environment: Vec<String> = vec![];
+    let mut unresolved: Vec<String> = vec![];
+    let is_overridden = |key: &str| {
+        container
+            .env
+            .iter()
+            .flatten()
+            .any(|variable| variable.name == key)
+    };
+
+    for reference in container.env_from.iter().flatten() {
+        match options.env_from.get(reference) {
+            Some(variables) => environment.extend(
+                variables
+                    .iter()
+                    .filter(|(key, _)| !is_overridden(key))
+                    .map(|(key, value)| format!("{}={}", key, value)),
+            ),
+            None => unresolved.push(reference.clone()),
+        }
+    }
+
+    for variable in container.env.iter().flatten() {
+        let value = match (&variable.value, &variable.secret_ref) {
+            (Some(value), _) if !value.is_null() => {
+                let value = substitute_tokens(&format_label_value(value), options);
+
+                // Compose would interpolate the output of another resource
+                if value.contains("${") {
+                    warn!(
+                        target: LOG_TARGET,
+                        "ignored variable {} of {}, its value {} cannot be resolved",
+                        variable.name,
+                        container.name,
+                        value
+                    );
+                    continue;
+                }
+                // A literal `$` is escaped, compose only interpolates the secret placeholders
+                value.replace('$', "$$")
+            }
+            (_, Some(secret)) if options.secrets == SecretsMode::Files => {
+                environment.push(format!(
+                    "{}_FILE={}/{}",
+                    variable.name, SECRETS_PATH, secret
+                ));
+                continue;
+            }
+            (_, Some(secret)) => format!("${{{}}}", format_secret_variable(secret)),
+            (_, None) => String::new(),
+        };
+
+        environment.push(format!("{}={}", variable.name, value));
+    }
+
+    (
+        Some(environment).filter(|environment| !environment.is_empty()),
+        unresolved,
+    )
+}
+
+/***
+ * Test of the healthcheck running a probe, none when the probe has no check
+ */
+fn build_probe_test(probe: &ProbeBluePrint) -> Option<Vec<String>> {
+    let shell = |test: String| Some(vec!["CMD-SHELL".to_string(), test]);
+
+    match (&probe.http_get, &probe.tcp_socket, &probe.exec) {
+        (Some(http_get), _, _) => shell(format!(
+            "curl -f http://localhost:{}{} || exit 1",
+            http_get.port.unwrap_or_default(),
+            http_get.path.as_deref().unwrap_or("/")
+        )),
+        (None, Some(tcp_socket), _) => shell(format!(
+            "nc -z localhost {} || exit 1",
+            tcp_socket.port.unwrap_or_default()
+        )),
+        // Commands are run as is, without a shell
+        (None, None, Some(exec)) if !exec.command.is_empty() => {
+            Some([vec!["CMD".to_string()], exec.command.clone()].concat())
+        }
+        _ => None,
+    }
+}
+
+/***
+ * The first liveness probe (the default probe type) with a check is translated to
+ * a healthcheck, the first readiness one when there is none
+ */
+fn build_healthcheck_for_serialization(container: &ContainerBluePrint) -> Option<HealthCheck> {
+    let (probe, test) = ["liveness", "readiness"].iter().find_map(|kind| {
+        container
+            .probes
+            .iter()
+            .flatten()
+            .filter(|probe| {
+                probe
+                    .probe_type
+                    .as_deref()
+                    .unwrap_or("liveness")
+                    .eq_ignore_ascii_case(kind)
+            })
+            .find_map(|probe| Some((probe, build_probe_test(probe)?)))
+    })?;
+    let (interval, timeout, retries) = build_probe_timing(probe);
+
+    Some(HealthCheck {
+        test,
+        interval: Some(interval),
+        timeout: Some(timeout),
+        retries: Some(retries),
+        start_period: probe
+            .initial_delay_seconds
+            .map(|seconds| format!("{}s", seconds)),
+    })
+}
+
+/***
+ * Services of both dependencies, the long form is used as soon as a condition is set
+ */
+fn merge_depends_on(base: DependsOn, extra: Option<DependsOn>) -> DependsOn {
+    let into_conditions = |depends_on: DependsOn| match depends_on {
+        DependsOn::Services(services) => services
+            .into_iter()
+            .map(|service| {
+                (
+                    service,
+                    ServiceDependency {
+                        condition: DependencyCondition::ServiceStarted,
+                    },
+                )
+            })
+            .collect(),
+        DependsOn::Conditions(conditions) => conditions,
+    };
+
+    match (base, extra) {
+        (base, None) => base,
+        (DependsOn::Services(mut base), Some(DependsOn::Services(extra))) => {
+            for service in extra {
+                if !base.contains(&service) {
+                    base.push(service);
+                }
+            }
+            DependsOn::Services(base)
+        }
+        (base, Some(extra)) => {
+            let mut conditions: BTreeMap<String, ServiceDependency> = into_conditions(extra);
+            conditions.extend(into_conditions(base));
+
+            DependsOn::Conditions(conditions)
+        }
+    }
+}
+
+/***
+ * The sidecar waits for its app to be healthy when a healthcheck is defined
+ */
+fn build_sidecar_depends_on(service: &ContainerAppConfiguration) -> DependsOn {
+    match service.healthcheck {
+        Some(_) => DependsOn::Conditions(BTreeMap::from([(
+            service.name.clone(),
+            ServiceDependency {
+                condition: DependencyCondition::ServiceHealthy,
+            },
+        )])),
+        None => DependsOn::Services(vec![service.name.clone()]),
+    }
+}
+
+/***
+ * Secrets of the `env` variables mounted from their file with `SecretsMode::Files`,
+ * secrets are global to the compose file, they are prefixed by the service name
+ */
+fn build_secrets_for_serialization(
+    name: &str,
+    container: &ContainerBluePrint,
+    options: &BuildOptions,
+) -> Option<Vec<ServiceSecret>> {
+    if options.secrets != SecretsMode::Files {
+        return None;
+    }
+
+    let mut secrets: Vec<ServiceSecret> = vec![];
+
+    for secret in container
+        .env
+        .iter()
+        .flatten()
+        .filter_map(|variable| variable.secret_ref.as_ref())
+    {
+        if !secrets.iter().any(|candidate| &candidate.target == secret) {
+            secrets.push(ServiceSecret {
+                source: format!("{}_{}", name, secret),
+                target: secret.clone(),
+                file: format!("./secrets/{}/{}", name, secret),
+            });
+        }
+    }
+
+    Some(secrets).filter(|secrets| !secrets.is_empty())
+}
+
+/***
+ * Mounts of a container as compose volumes. EmptyDir volumes are named volumes of the app
+ * (eg: myapp_data) shared by its containers, Azure Files shares are named volumes of their
+ * storage shared by the apps, or host folders with `AzureFilesMode::Bind`
+ */
+fn build_volumes_for_serialization(
+    app_name: &str,
+    volumes: &[VolumeBluePrint],
+    container: &ContainerBluePrint,
+    options: &BuildOptions,
+) -> Option<Vec<ServiceVolume>> {
+    let mut service_volumes: Vec<ServiceVolume> = vec![];
+
+    for mount in container.volume_mounts.iter().flatten() {
+        let Some(volume) = volumes
+            .iter()
+            .find(|volume| volume.name == mount.volume_name)
+        else {
+            warn!(
+                target: LOG_TARGET,
+                "Volume {} mounted by {} is not declared", mount.volume_name, container.name
+            );
+            continue;
+        };
+        let storage = volume.storage_name.as_deref().unwrap_or(&volume.name);
+        let sub_path = mount
+            .sub_path
+            .as_ref()
+            .filter(|sub_path| !sub_path.is_empty());
+
+        let (volume_type, source, sub_path, share) =
+            match (volume.storage_type.as_deref(), options.azure_files) {
+                (None | Some("EmptyDir"), _) => (
+                    "volume",
+                    sanitize_service_name(&format!("{}_{}", app_name, volume.name)),
+                    sub_path,
+                    None,
+                ),
+                (Some("AzureFile" | "NfsAzureFile"), AzureFilesMode::Volume) => (
+                    "volume",
+                    sanitize_service_name(storage),
+                    sub_path,
+                    Some(storage.to_string()),
+                ),
+                // Host folders have no sub path option, it is part of the source
+                (Some("AzureFile" | "NfsAzureFile"), AzureFilesMode::Bind) => match sub_path {
+                    Some(sub_path) => (
+                        "bind",
+                        format!("./volumes/{}/{}", storage, sub_path),
+                        None,
+                        None,
+                    ),
+                    None => ("bind", format!("./volumes/{}", storage), None, None),
+                },
+                // Secret volumes, see `collect_volume_warnings`
+                _ => continue,
+            };
+
+        service_volumes.push(ServiceVolume {
+            volume_type: volume_type.to_string(),
+            source,
+            target: mount.mount_path.clone(),
+            read_only: mount.read_only.filter(|read_only| *read_only),
+            volume: sub_path.map(|sub_path| VolumeOptions {
+                subpath: sub_path.clone(),
+            }),
+            share,
+        });
+    }
+
+    Some(service_volumes).filter(|volumes| !volumes.is_empty())
+}
+
+/***
+ * Volumes of the apps which are not mounted, their storage type has no compose equivalent
+ * (eg: Secret) or they are not declared in the template
+ */
+pub fn collect_volume_warnings(apps: &[ContainerAppBluePrint]) -> Vec<String> {
+    let mut warnings: Vec<String> = vec![];
+
+    for app in apps {
+        let Some(template) = &app.template else {
+            continue;
+        };
+        let name = app.name.as_deref().unwrap_or_default();
+        let volumes = template.volumes.as_deref().unwrap_or_default();
+
+        for volume in volumes {
+            if let Some(storage_type) = volume.storage_type.as_deref().filter(|storage_type| {
+                !["EmptyDir", "AzureFile", "NfsAzureFile"].contains(storage_type)
+            }) {
+                warnings.push(format!(
+                    "ignored volume {} of {}, {} volumes are not supported",
+                    volume.name, name, storage_type
+                ));
+            }
+        }
+
+        let containers = [
+            template.containers.as_deref().unwrap_or_default(),
+            template.init_containers.as_deref().unwrap_or_default(),
+        ]
+        .concat();
+
+        for (container, mount) in containers.iter().flat_map(|container| {
+            container
+                .volume_mounts
+                .iter()
+                .flatten()
+                .map(move |mount| (container, mount))
+        }) {
+            if !volumes
+                .iter()
+                .any(|volume| volume.name == mount.volume_name)
+            {
+                warnings.push(format!(
+                    "ignored mount of {} in {}, the volume is not declared in {}",
+                    mount.volume_name, container.name, name
+                ));
+            }
+        }
+    }
+    warnings
+}
+
+/***
+ * Memory of a container in bytes (eg: 1073741824 for 1Gi)
+ */
+fn parse_memory_bytes(memory: &str) -> Option<f64> {
+    let memory = memory.trim();
+    let (value, unit) = memory.split_at(
+        memory
+            .find(|c: char| c.is_ascii_alphabetic())
+            .unwrap_or(memory.len()),
+    );
+    let multiplier = match unit {
+        "" => 1.0,
+        "Ki" => 1024.0,
+        "Mi" => 1024.0 * 1024.0,
+        "Gi" => 1024.0 * 1024.0 * 1024.0,
+        "K" | "k" => 1e3,
+        "M" => 1e6,
+        "G" => 1e9,
+        _ => return None,
+    };
+    Some(value.trim().parse::<f64>().ok()? * multiplier)
+}
+
+/***
+ * Container resources outside the Container Apps range (0.25 to 4 cores and 0.5Gi to 8Gi),
+ * their limits are kept but the app cannot be deployed with them
+ */
+pub fn collect_resource_warnings(apps: &[ContainerAppBluePrint]) -> Vec<String> {
+    let mut warnings: Vec<String> = vec![];
+
+    for app in apps {
+        let Some(template) = &app.template else {
+            continue;
+        };
+        let containers = [
+            template.init_containers.as_deref().unwrap_or_default(),
+            template.containers.as_deref().unwrap_or_default(),
+        ]
+        .concat();
+
+        for container in &containers {
+            let Some(resources) = &container.resources else {
+                continue;
+            };
+
+            if let Some(cpu) = resources.cpu.filter(|cpu| !(0.25..=4.0).contains(cpu)) {
+                warnings.push(format!(
+                    "cpu {} of {} is out of the allowed range (0.25 to 4)",
+                    cpu, container.name
+                ));
+            }
+            if let Some(memory) = &resources.memory {
+                match parse_memory_bytes(memory) {
+                    Some(bytes) if (0.5..=8.0).contains(&(bytes / 1024_f64.powi(3))) => {}
+                    Some(_) => warnings.push(format!(
+                        "memory {} of {} is out of the allowed range (0.5Gi to 8Gi)",
+                        memory, container.name
+                    )),
+                    None => {
+                        warnings.push(format!("invalid memory {} of {}", memory, container.name))
+                    }
+                }
+            }
+        }
+    }
+    warnings
+}
+
+/***
+ * Secrets of the apps read from a Key Vault, their value cannot be materialized locally
+ */
+pub fn collect_secret_warnings(apps: &[ContainerAppBluePrint]) -> Vec<String> {
+    apps.iter()
+        .flat_map(|app| {
+            app.configuration
+                .iter()
+                .flat_map(|configuration| configuration.secrets.iter().flatten())
+                .filter_map(|secret| {
+                    Some(format!(
+                        "secret {} of {} is read from the Key Vault {}, its value must be given locally",
+                        secret.name,
+                        app.name.as_deref().unwrap_or_default(),
+                        secret.key_vault_url.as_ref()?
+                    ))
+                })
+        })
+        .collect()
+}
+
+/***
+ * Services of a container, none when its image cannot be resolved: the other containers of
+ * the app are kept and the container is reported by `collect_unresolved_image_warnings`
+ */
+fn parse_container_configuration(
+    images: &[ContainerImageBluePrint],
+    configuration: AppConfiguration,
+    options: &BuildOptions,
+    unresolved_images: &mut Vec<DeserializeError>,
+) -> Result<Vec<ContainerAppConfiguration>, DeserializeError> {
+    match parse_app_configuration(images, configuration, options) {
+        Err(error @ DeserializeError::UnresolvedImage { .. }) => {
+            warn!(target: LOG_TARGET, "{}", error);
+            unresolved_images.push(error);
+            Ok(vec![])
+        }
+        result => result,
+    }
+}
+
+/***
+ * Containers ignored as their image cannot be resolved, their app fails when none is resolved
+ */
+pub fn collect_unresolved_image_warnings(
+    apps: &[ContainerAppBluePrint],
+    images: &[ContainerImageBluePrint],
+    options: &BuildOptions,
+) -> Vec<String> {
+    let mut warnings: Vec<String> = vec![];
+
+    for app in apps {
+        let Some(template) = &app.template else {
+            continue;
+        };
+        let containers = [
+            template.init_containers.as_deref().unwrap_or_default(),
+            template.containers.as_deref().unwrap_or_default(),
+        ]
+        .concat();
+
+        for container in containers {
+            let image = container.image.clone();
+            let name = container.name.clone();
+
+            if build_image_for_serialization(
+                images,
+                container,
+                options.base_path.as_deref(),
+                options,
+            )
+            .is_none()
+            {
+                warnings.push(format!(
+                    "ignored container {} of {}, its image {} cannot be resolved",
+                    name,
+                    app.name.as_deref().unwrap_or_default(),
+                    image
+                ));
+            }
+        }
+    }
+    warnings
+}
+
+fn parse_app_configuration(
+    images: &[ContainerImageBluePrint],
+    configuration: AppConfiguration,
+    options: &BuildOptions,
+) -> Result<Vec<ContainerAppConfiguration>, DeserializeError> {
+    let container = configuration.container.clone();
+    let dapr_configuration = configuration.dapr_configuration.clone();
+
+    let image =
+        build_image_for_serialization(images, container, options.base_path.as_deref(), options)
+            .ok_or_else(|| DeserializeError::UnresolvedImage {
+                container: configuration.container.name.clone(),
+                reference: configuration.container.image.clone(),
+            })?;
+    let name = build_service_name(
+        &configuration.container.name,
+        configuration.revision_suffix.as_deref(),
+    );
+    let restart = options
+        .restart
+        .clone()
+        .unwrap_or(DEFAULT_RESTART_POLICY.to_string());
+    let healthcheck = build_healthcheck_for_serialization(&configuration.container);
+    let replicas = build_replicas_for_serialization(
+        configuration.scale_configuration.as_ref(),
+        dapr_configuration
+            .as_ref()
+            .and_then(|dapr| dapr.enabled)
+            .unwrap_or_default(),
+    );
+    let deploy = build_deploy_for_serialization(&configuration.container, replicas);
+    let user = configuration.container.user.clone();
+    let working_dir = configuration.container.working_dir.clone();
+    let depends_on = configuration.depends_on.clone();
+    let (environment, unresolved_env_from) =
+        build_environment_for_serialization(&configuration.container, options);
+    let secrets = build_secrets_for_serialization(&name, &configuration.container, options);
+    let mut labels = build_labels_for_serialization(&configuration, options);
+
+    if !unresolved_env_from.is_empty() {
+        labels
+            .get_or_insert_with(BTreeMap::new)
+            .insert("capp.envFrom".to_string(), unresolved_env_from.join(","));
+    }
+
+    let sidecar_labels =
+        Some(build_workload_profile_labels(&configuration)).filter(|labels| !labels.is_empty());
+    let container_name = configuration.container.name.clone();
+    let (dapr_app_port, ports) = build_ports_mapping_for_serialization(configuration)?;
+    let ports = match replicas {
+        Some(_) => ports.map(|ports| unpublish_host_ports(&ports)),
+        None => ports,
+    };
+
+    let (has_dapr_enabled, dapr_app_id, dapr_command, dapr_flags, sidecar_image, sidecar_configs) =
+        match dapr_configuration {
+            Some(v) => (
+                v.enabled.unwrap_or(false),
+                build_dapr_app_id(&v, &container_name),
+                v.command.clone(),
+                [
+                    build_dapr_tuning_flags(&v)?,
+                    build_dapr_components_flags(&v),
+                ]
+                .concat(),
+                resolve_dapr_sidecar_image(&v, &options.dapr),
+                build_dapr_components_configs(&build_dapr_app_id(&v, &container_name), &v)?,
+            ),
+            None => (
+                false,
+                String::new(),
+                None,
+                vec![],
+                DEFAULT_DAPR_SIDECAR_IMAGE.to_string(),
+                None,
+            ),
+        };
+
+    // The sidecar cannot reach an app without port
+    if has_dapr_enabled && dapr_app_port.is_none() {
+        return Err(DeserializeError::MissingDaprAppPort(name));
+    }
+
+    let result = if has_dapr_enabled {
+        let service = ContainerAppConfiguration {
+            image: image.name,
+            build: image.build,
+            name: name.clone(),
+            depends_on: Some(merge_depends_on(
+                DependsOn::Services(vec!["placement".to_string()]),
+                depends_on,
+            )),
+            networks: Some(vec![String::from("dapr-network")]),
+            network_mode: None,
+            environment,
+            ports: ports.clone(),
+            command: None,
+            healthcheck,
+            labels,
+            restart: Some(restart.clone()),
+            user,
+            working_dir,
+            deploy,
+            stop_grace_period: None,
+            configs: None,
+            secrets,
+            volumes: None,
+            profiles: None,
+            extra: None,
+        };
+        let depends_on = build_sidecar_depends_on(&service);
+
+        vec![
+            service,
+            // Dapr Sidecar config
+            ContainerAppConfiguration {
+                image: Some(sidecar_image),
+                name: format!("{}_dapr", name.clone()),
+                depends_on: Some(depends_on),
+                network_mode: Some(format!("service:{}", String::from(&name))),
+                environment: None,
+                // No exposed ports for dapr sidecar
+                ports: None,
+                networks: None,
+                build: None,
+                command: Some(build_dapr_command(
+                    &dapr_app_id,
+                    dapr_app_port,
+                    dapr_command,
+                    dapr_flags,
+                )?),
+                labels: sidecar_labels,
+                healthcheck: None,
+                restart: Some(restart),
+                user: None,
+                working_dir: None,
+                deploy: None,
+                stop_grace_period: None,
+                configs: sidecar_configs,
+                secrets: None,
+                volumes: None,
+                profiles: None,
+                extra: None,
+            },
+        ]
+    } else {
+        vec![ContainerAppConfiguration {
+            image: image.name,
+            build: image.build,
+            name,
+            depends_on,
+            // No Dapr network
+            networks: None,
+            environment,
+            network_mode: None,
+            ports: ports.clone(),
+            command: None,
+            labels,
+            healthcheck,
+            restart: Some(restart),
+            user,
+            working_dir,
+            deploy,
+            stop_grace_period: None,
+            configs: None,
+            secrets,
+            volumes: None,
+            profiles: None,
+            extra: None,
+        }]
+    };
+
+    Ok(result)
+}
+
+/***
+ * Containers of all the apps end up in the same compose file, they are compared by their
+ * service name as different container names may be sanitized to the same one
+ */
+fn validate_unique_service_names(
+    apps: &[ContainerAppBluePrint],
+    options: &BuildOptions,
+) -> Result<(), DeserializeError> {
+    let mut names: BTreeMap<String, String> = BTreeMap::new();
+
+    for template in apps.iter().filter_map(|app| app.template.as_ref()) {
+        let revision_suffix = template
+            .revision_suffix
+            .as_ref()
+            .map(|suffix| resolve_pulumi_tokens(suffix, options));
+        let containers = [
+            template.init_containers.as_deref().unwrap_or_default(),
+            template.containers.as_deref().unwrap_or_default(),
+        ]
+        .concat();
+
+        for container in containers {
+            let name = build_service_name(&container.name, revision_suffix.as_deref());
+
+            if let Some(other) = names.insert(name.clone(), container.name.clone()) {
+                return Err(DeserializeError::DuplicateContainer {
+                    name,
+                    containers: vec![other, container.name],
+                });
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/***
+ * Only the host side of a mapping can conflict (eg: 3000 for 3000:80)
+ */
+fn validate_host_ports(services: &[ContainerAppConfiguration]) -> Result<(), DeserializeError> {
+    let mut bindings: BTreeMap<&str, Vec<String>> = BTreeMap::new();
+
+    for service in services {
+        for port in service.ports.iter().flatten() {
+            if let Some((host, _)) = port.rsplit_once(':') {
+                let names = bindings.entry(host).or_default();
+
+                if !names.contains(&service.name) {
+                    names.push(service.name.clone());
+                }
+            }
+        }
+    }
+
+    match bindings.into_iter().find(|(_, names)| names.len() > 1) {
+        Some((port, services)) => Err(DeserializeError::PortConflict {
+            port: port.to_string(),
+            services,
+        }),
+        None => Ok(()),
+    }
+}
+
+/***
+ * A service joining the network stack of another one (eg: the Dapr sidecars) cannot join networks
+ */
+fn validate_network_mode(services: &[ContainerAppConfiguration]) -> Result<(), DeserializeError> {
+    match services
+        .iter()
+        .find(|service| service.has_network_mode_conflict())
+    {
+        Some(service) => Err(DeserializeError::NetworkModeConflict(service.name.clone())),
+        None => Ok(()),
+    }
+}
+
+fn validate_unique_image_references(
+    images: &[ContainerImageBluePrint],
+) -> Result<(), DeserializeError> {
+    let mut references: Vec<&str> = vec![];
+
+    for reference in images
+        .iter()
+        .filter_map(|image| image.reference_name.as_deref())
+    {
+        if references.contains(&reference) {
+            return Err(DeserializeError::DuplicateImageReference(
+                reference.to_string(),
+            ));
+        }
+        references.push(reference);
+    }
+
+    Ok(())
+}
+
+/***
+ * A service joins each of its networks once
+ */
+fn normalize_networks(services: &mut [ContainerAppConfiguration]) {
+    for service in services.iter_mut() {
+        if let Some(networks) = service.networks.as_mut() {
+            let mut seen: Vec<String> = vec![];
+            networks.retain(|network| {
+                let is_new = !seen.contains(network);
+                seen.push(network.clone());
+
+                is_new
+            });
+        }
+    }
+}
+
+/***
+ * Resource of the azure-native app module of a type token (eg: ContainerApp), with the API
+ * version the token pins if any (eg: v20230501 for azure-native:app/v20230501:ContainerApp)
+ */
+pub fn parse_app_resource_type(token: &str) -> Option<(&str, Option<&str>)> {
+    let captures = Regex::new(
+        r"^azure-native:app(?:/(v\d{8}(?:preview)?))?:(ContainerApp|Job|ManagedEnvironment|DaprComponent)$",
+    )
+    .expect("Should match resource type regex")
+    .captures(token)?;
+
+    Some((
+        captures.get(2)?.as_str(),
+        captures.get(1).map(|version| version.as_str()),
+    ))
+}
+
+/***
+ * Apps pinning an API version newer than the mapped one, the fields it introduces are ignored
+ */
+pub fn collect_api_version_warnings(apps: &[ContainerAppBluePrint]) -> Vec<String> {
+    // Versions are compared by date (eg: 20230501 for v20230501preview)
+    let date = |version: &str| version.get(1..9).unwrap_or_default().to_string();
+
+    apps.iter()
+        .filter_map(|app| {
+            let version = app.api_version.as_deref()?;
+
+            (date(version) > date(MAPPED_API_VERSION)).then(|| {
+                format!(
+                    "api version {} of {} is newer than {}, its new fields are not mapped",
+                    version,
+                    app.name.as_deref().unwrap_or_default(),
+                    MAPPED_API_VERSION
+                )
+            })
+        })
+        .collect()
+}
+
+/***
+ * Keys in camelCase (eg: target_port for targetPort), the tags are user keys
+ */
+fn to_camel_case_keys(value: &serde_yaml::Value) -> serde_yaml::Value {
+    match value {
+        serde_yaml::Value::Mapping(mapping) => mapping
+            .iter()
+            .map(|(key, value)| match key.as_str() {
+                Some("tags") => (key.clone(), value.clone()),
+                Some(key) => {
+                    let mut words = key.split('_');
+                    let head = words.next().unwrap_or_default().to_string();
+                    let key = words.fold(head, |key, word| {
+                        let mut chars = word.chars();
+                        match chars.next() {
+                            Some(first) => key + &first.to_uppercase().to_string() + chars.as_str(),
+                            None => key,
+                        }
+                    });
+
+                    (serde_yaml::Value::from(key), to_camel_case_keys(value))
+                }
+                None => (key.clone(), to_camel_case_keys(value)),
+            })
+            .collect::<serde_yaml::Mapping>()
+            .into(),
+        serde_yaml::Value::Sequence(sequence) => sequence.iter().map(to_camel_case_keys).collect(),
+        value => value.clone(),
+    }
+}
+
+fn rename_key(mapping: &mut serde_yaml::Mapping, from: &str, to: &str) {
+    if let Some(value) = mapping.remove(from) {
+        mapping.insert(to.into(), value);
+    }
+}
+
+/***
+ * Probes of a container of the classic provider (eg: livenessProbes with a transport)
+ * as azure-native ones (eg: probes with a type and an httpGet)
+ */
+fn normalize_classic_probes(container: &mut serde_yaml::Mapping) {
+    let mut probes = vec![];
+
+    for (key, probe_type) in [
+        ("livenessProbes", "Liveness"),
+        ("readinessProbes", "Readiness"),
+        ("startupProbes", "Startup"),
+    ] {
+        let classic_probes = match container.remove(key) {
+            Some(serde_yaml::Value::Sequence(classic_probes)) => classic_probes,
+            _ => continue,
+        };
+
+        for mut probe in classic_probes.into_iter().filter_map(|probe| match probe {
+            serde_yaml::Value::Mapping(probe) => Some(probe),
+            _ => None,
+        }) {
+            let transport = probe.remove("transport");
+            let mut action = serde_yaml::Mapping::new();
+
+            for key in ["path", "port", "host"] {
+                if let Some(value) = probe.remove(key) {
+                    action.insert(key.into(), value);
+                }
+            }
+
+            match transport.as_ref().and_then(serde_yaml::Value::as_str) {
+                Some("TCP") => {
+                    action.remove("path");
+                    probe.insert("tcpSocket".into(), action.into());
+                }
+                transport => {
+                    if let Some(transport) = transport {
+                        action.insert("scheme".into(), transport.into());
+                    }
+                    probe.insert("httpGet".into(), action.into());
+                }
+            }
+
+            rename_key(&mut probe, "initialDelay", "initialDelaySeconds");
+            rename_key(&mut probe, "intervalSeconds", "periodSeconds");
+            rename_key(&mut probe, "timeout", "timeoutSeconds");
+            rename_key(&mut probe, "failureCountThreshold", "failureThreshold");
+            probe.insert("type".into(), probe_type.into());
+            probes.push(serde_yaml::Value::Mapping(probe));
+        }
+    }
+
+    if !probes.is_empty() {
+        container.insert("probes".into(), probes.into());
+    }
+}
+
+/***
+ * Properties of a container app of the classic provider (eg: azure:containerapp:App) in the
+ * shape of the azure-native ones: the ingress, Dapr and revision settings move under the
+ * configuration and the classic provider has no `enabled` flag, a `dapr` block enables Dapr
+ */
+pub fn normalize_classic_app(properties: &serde_yaml::Value) -> serde_yaml::Value {
+    let mut properties = match to_camel_case_keys(properties) {
+        serde_yaml::Value::Mapping(properties) => properties,
+        properties => return properties,
+    };
+    let mut configuration = serde_yaml::Mapping::new();
+
+    if let Some(mut dapr) = properties.remove("dapr") {
+        if let Some(dapr) = dapr.as_mapping_mut() {
+            dapr.entry("enabled".into()).or_insert(true.into());
+        }
+        configuration.insert("dapr".into(), dapr);
+    }
+
+    if let Some(mut ingress) = properties.remove("ingress") {
+        if let Some(ingress) = ingress.as_mapping_mut() {
+            rename_key(ingress, "externalEnabled", "external");
+            rename_key(ingress, "allowInsecureConnections", "allowInsecure");
+        }
+        configuration.insert("ingress".into(), ingress);
+    }
+
+    for (classic, native) in [
+        ("revisionMode", "activeRevisionsMode"),
+        ("secrets", "secrets"),
+        ("registries", "registries"),
+    ] {
+        if let Some(value) = properties.remove(classic) {
+            configuration.insert(native.into(), value);
+        }
+    }
+
+    if let Some(template) = properties
+        .get_mut("template")
+        .and_then(serde_yaml::Value::as_mapping_mut)
+    {
+        let scale: serde_yaml::Mapping = ["minReplicas", "maxReplicas"]
+            .iter()
+            .filter_map(|key| template.remove(*key).map(|value| ((*key).into(), value)))
+            .collect();
+
+        if !scale.is_empty() {
+            template.insert("scale".into(), scale.into());
+        }
+
+        for key in ["containers", "initContainers"] {
+            let containers = template
+                .get_mut(key)
+                .and_then(serde_yaml::Value::as_sequence_mut)
+                .into_iter()
+                .flatten()
+                .filter_map(serde_yaml::Value::as_mapping_mut);
+
+            for container in containers {
+                let resources: serde_yaml::Mapping = ["cpu", "memory"]
+                    .iter()
+                    .filter_map(|key| container.remove(*key).map(|value| ((*key).into(), value)))
+                    .collect();
+
+                if !resources.is_empty() {
+                    container.insert("resources".into(), resources.into());
+                }
+
+                rename_key(container, "envs", "env");
+                normalize_classic_probes(container);
+
+                // eg: volume_mounts { name = "data", path = "/data" }
+                let mounts = container
+                    .get_mut("volumeMounts")
+                    .and_then(serde_yaml::Value::as_sequence_mut)
+                    .into_iter()
+                    .flatten()
+                    .filter_map(serde_yaml::Value::as_mapping_mut);
+
+                for mount in mounts {
+                    rename_key(mount, "name", "volumeName");
+                    rename_key(mount, "path", "mountPath");
+                }
+            }
+        }
+    }
+
+    rename_key(
+        &mut properties,
+        "containerAppEnvironmentId",
+        "managedEnvironmentId",
+    );
+
+    if !configuration.is_empty() {
+        properties.insert("configuration".into(), configuration.into());
+    }
+
+    properties.into()
+}
+
+/***
+ * Scale can be declared under the template or the configuration,
+ * keep it under the configuration only
+ */
+pub fn normalize_scale(app: &mut ContainerAppBluePrint) {
+    let template_scale = match app.template.as_mut() {
+        Some(template) => template.scale.take(),
+        None => None,
+    };
+
+    if let Some(template_scale) = template_scale {
+        let configuration = app.configuration.get_or_insert(ConfigurationBluePrint {
+            ingress: None,
+            dapr: None,
+            scale: None,
+            max_inactive_revisions: None,
+            secrets: None,
+        });
+
+        match &configuration.scale {
+            Some(scale) if scale != &template_scale => warn!(
+                target: LOG_TARGET,
+                "Scale is declared under both template and configuration, using the template one"
+            ),
+            _ => {}
+        }
+
+        configuration.scale = Some(template_scale);
+    }
+}
+
+/***
+ * Walk the input and collect the path of the keys dropped by the blueprint
+ */
+fn collect_unknown_fields(
+    input: &serde_yaml::Value,
+    known: &serde_yaml::Value,
+    path: &str,
+    fields: &mut Vec<String>,
+) {
+    match (input, known) {
+        (serde_yaml::Value::Mapping(input), known) => {
+            for (key, value) in input {
+                let path = format!("{}.{}", path, format_label_value(key));
+
+                match known.get(key) {
+                    Some(known) => collect_unknown_fields(value, known, &path, fields),
+                    // Null values are dropped while serializing back
+                    None if value.is_null() => continue,
+                    None => fields.push(path),
+                }
+            }
+        }
+        (serde_yaml::Value::Sequence(input), serde_yaml::Value::Sequence(known)) => {
+            for (index, (input, known)) in input.iter().zip(known).enumerate() {
+                collect_unknown_fields(input, known, &format!("{}[{}]", path, index), fields);
+            }
+        }
+        _ => {}
+    }
+}
+
+/***
+ * Paths of the app properties the blueprint does not know, in input order
+ */
+pub fn collect_ignored_fields(
+    properties: &serde_yaml::Value,
+) -> Result<Vec<String>, DeserializeError> {
+    let blueprint: ContainerAppBluePrint = serde_yaml::from_value(properties.clone())
+        .map_err(|e| DeserializeError::Parse(e.to_string()))?;
+    let known =
+        serde_yaml::to_value(blueprint).map_err(|e| DeserializeError::Parse(e.to_string()))?;
+    let mut fields: Vec<String> = vec![];
+
+    for section in STRICT_SECTIONS {
+        if let Some(input) = properties.get(section) {
+            let known = known.get(section).unwrap_or(&serde_yaml::Value::Null);
+
+            collect_unknown_fields(input, known, section, &mut fields);
+        }
+    }
+
+    Ok(fields)
+}
+
+/***
+ * Used in strict mode, fails on the first key of the app properties
+ * the blueprint does not know
+ */
+pub fn validate_known_fields(properties: &serde_yaml::Value) -> Result<(), DeserializeError> {
+    match collect_ignored_fields(properties)?.into_iter().next() {
+        Some(path) => Err(DeserializeError::UnknownField(path)),
+        None => Ok(()),
+    }
+}
+
+/***
+ * Language of a program by the extension of its path (eg: index.ts)
+ */
+fn language_of_extension(path: &Path) -> Option<Language> {
+    match path.extension().and_then(|x| x.to_str())? {
+        "yml" | "yaml" => Some(Language::Yaml),
+        "ts" => Some(Language::Typescript),
+        "js" | "mjs" | "cjs" => Some(Language::Javascript),
+        "py" => Some(Language::Python),
+        "cs" => Some(Language::CSharp),
+        "go" => Some(Language::Go),
+        "json" => Some(Language::StackExport),
+        _ => None,
+    }
+}
+
+/***
+ * Language of the `runtime` of a project file (eg: runtime: nodejs), a Javascript
+ * nodejs project disables typescript in the runtime options
+ */
+fn language_of_runtime(project: &serde_yaml::Value) -> Option<Language> {
+    let runtime = project.get("runtime")?;
+    let name = runtime.as_str().or_else(|| runtime.get("name")?.as_str())?;
+    let is_typescript = runtime
+        .get("options")
+        .and_then(|options| options.get("typescript"))
+        .and_then(serde_yaml::Value::as_bool)
+        .unwrap_or(true);
+
+    match name {
+        "yaml" => Some(Language::Yaml),
+        "nodejs" if is_typescript => Some(Language::Typescript),
+        "nodejs" => Some(Language::Javascript),
+        "python" => Some(Language::Python),
+        "dotnet" => Some(Language::CSharp),
+        "go" => Some(Language::Go),
+        _ => None,
+    }
+}
+
+// Entry points of the runtimes without `main`, in the program folder
+const RUNTIME_ENTRIES: [(&str, &[&str]); 5] = [
+    ("nodejs", &["index.ts", "index.js"]),
+    ("yaml", &["Main.yaml"]),
+    ("python", &["__main__.py"]),
+    ("dotnet", &["Program.cs"]),
+    ("go", &["main.go"]),
+];
+
+/***
+ * Entry point of a project folder and its language, from the `runtime` and `main` of its
+ * Pulumi.yaml project file (eg: main: bin/ for bin/index.js). A nodejs project may set its
+ * entry point in its package.json, a yaml one may declare its resources in Pulumi.yaml itself
+ */
+pub fn find_project_entry(path: &Path) -> Result<(Language, PathBuf), DeserializeError> {
+    let project_file = ["Pulumi.yaml", "Pulumi.yml"]
+        .iter()
+        .map(|name| path.join(name))
+        .find(|file| file.is_file())
+        .ok_or_else(|| {
+            DeserializeError::MissingFile(path.join("Pulumi.yaml").display().to_string())
+        })?;
+    let project: serde_yaml::Value = std::fs::read_to_string(&project_file)
+        .map_err(|_| DeserializeError::MissingFile(project_file.display().to_string()))
+        .and_then(|input| {
+            serde_yaml::from_str(&input).map_err(|e| DeserializeError::Parse(e.to_string()))
+        })?;
+    let language = language_of_runtime(&project).ok_or(DeserializeError::UnsupportedLanguage)?;
+    let runtime = project
+        .get("runtime")
+        .and_then(|runtime| runtime.as_str().or_else(|| runtime.get("name")?.as_str()))
+        .unwrap_or_default();
+
+    let main = project
+        .get("main")
+        .and_then(serde_yaml::Value::as_str)
+        .map(|main| path.join(main))
+        .or_else(|| {
+            let package = std::fs::read_to_string(path.join("package.json")).ok()?;
+            let package: serde_json::Value = serde_json::from_str(&package).ok()?;
+            let main = package.get("main")?.as_str()?;
+            Some(path.join(main))
+        });
+
+    if let Some(main) = main.as_ref().filter(|main| main.is_file()) {
+        return Ok((language, main.clone()));
+    }
+
+    if runtime == "yaml" && main.is_none() && project.get("resources").is_some() {
+        return Ok((language, project_file));
+    }
+
+    let folder = main.unwrap_or_else(|| path.to_path_buf());
+    let entries = RUNTIME_ENTRIES
+        .iter()
+        .find(|(name, _)| *name == runtime)
+        .map(|(_, entries)| *entries)
+        .unwrap_or_default();
+
+    entries
+        .iter()
+        .map(|entry| folder.join(entry))
+        .find(|entry| entry.is_file())
+        .map(|entry| {
+            // A Javascript entry point of a Typescript project is a compiled one
+            let language = match entry.extension().and_then(|x| x.to_str()) {
+                Some("js") => Language::Javascript,
+                _ => language,
+            };
+            (language, entry)
+        })
+        .ok_or_else(|| {
+            let attempted = entries.first().copied().unwrap_or("Pulumi.yaml");
+            DeserializeError::MissingFile(folder.join(attempted).display().to_string())
+        })
+}
+
+/***
+ * Languages whose programs look like the input, Typescript and Javascript programs
+ * are told apart by their syntax (eg: type annotations or require calls)
+ */
+fn languages_of_content(input: &str) -> Vec<Language> {
+    let patterns = [
+        (
+            Language::Yaml,
+            r"(?m)^resources:\s*$[\s\S]*\btype:\s*azure(-native)?:",
+        ),
+        (
+            Language::Typescript,
+            r#"\bnew\s+[\w.]*ContainerApp\s*\(|\b(from|require\s*\()\s*["']@pulumi/"#,
+        ),
+        (
+            Language::Python,
+            r"(?m)^\s*(import\s+pulumi|from\s+pulumi\w*\s+import)\b",
+        ),
+        (Language::CSharp, r"(?m)^\s*using\s+Pulumi\b"),
+        (
+            Language::Go,
+            r#"(?m)^package\s+\w+[\s\S]*"github\.com/pulumi/"#,
+        ),
+        (
+            Language::StackExport,
+            r#"\A\s*\{[\s\S]*"(deployment|steps)"\s*:"#,
+        ),
+    ];
+
+    patterns
+        .into_iter()
+        .filter(|(_, pattern)| Regex::new(pattern).unwrap().is_match(input))
+        .map(|(language, _)| match language {
+            Language::Typescript => {
+                let is_javascript = Regex::new(r"\brequire\s*\(").unwrap().is_match(input)
+                    && !Regex::new(r"(?m)^\s*import\s").unwrap().is_match(input);
+
+                match is_javascript {
+                    true => Language::Javascript,
+                    false => Language::Typescript,
+                }
+            }
+            language => language,
+        })
+        .collect()
+}
+
+/***
+ * Language of a program from the extension of its path, the runtime of the Pulumi.yaml
+ * project file next to it, then its content. An ambiguous content is an error listing
+ * the hints which were tried
+ */
+pub fn detect_language(input: &str, path: Option<&Path>) -> Result<Language, DeserializeError> {
+    let mut hints = vec![];
+
+    if let Some(path) = path {
+        if let Some(language) = language_of_extension(path).filter(|_| path.is_file()) {
+            return Ok(language);
+        }
+        hints.push(format!("the extension of {}", path.display()));
+
+        let project = match path.is_dir() {
+            true => path.join("Pulumi.yaml"),
+            false => path.with_file_name("Pulumi.yaml"),
+        };
+        let language = std::fs::read_to_string(&project)
+            .ok()
+            .and_then(|project| serde_yaml::from_str(&project).ok())
+            .and_then(|project| language_of_runtime(&project));
+
+        if let Some(language) = language {
+            return Ok(language);
+        }
+        hints.push(format!("the runtime of {}", project.display()));
+    }
+
+    match languages_of_content(input).as_slice() {
+        [language] => Ok(*language),
+        [] => {
+            hints.push("the content, matching no language".to_string());
+            Err(DeserializeError::UndetectedLanguage(hints))
+        }
+        languages => {
+            let names: Vec<String> = languages
+                .iter()
+                .map(|language| format!("{:?}", language))
+                .collect();
+            hints.push(format!("the content, matching {}", names.join(" and ")));
+            Err(DeserializeError::UndetectedLanguage(hints))
+        }
+    }
+}
+
+/***
+ * Values of a stack config file (eg: Pulumi.dev.yaml), by namespaced key (eg: myproject:appPort)
+ */
+pub fn parse_stack_config(
+    input: &str,
+) -> Result<BTreeMap<String, serde_yaml::Value>, DeserializeError> {
+    let value: serde_yaml::Value =
+        serde_yaml::from_str(input).map_err(|e| DeserializeError::Parse(e.to_string()))?;
+
+    Ok(value
+        .get("config")
+        .and_then(serde_yaml::Value::as_mapping)
+        .into_iter()
+        .flatten()
+        .filter_map(|(key, value)| key.as_str().map(|key| (key.to_string(), value.clone())))
+        .collect())
+}
+
+pub fn build_configuration(
+    apps: Vec<ContainerAppBluePrint>,
+    images: Vec<ContainerImageBluePrint>,
+    include: Option<&[String]>,
+    options: &BuildOptions,
+) -> Result<Vec<ContainerAppConfiguration>, DeserializeError> {
+    build_per_app(apps, images, include, options).map(flatten_apps)
+}
+
+pub fn flatten_apps(
+    apps: Vec<(String, Vec<ContainerAppConfiguration>)>,
+) -> Vec<ContainerAppConfiguration> {
+    apps.into_iter()
+        .flat_map(|(_, services)| services)
+        .collect()
+}
+
+/***
+ * Trigger of a job kept as `capp.job.*` labels (eg: the cron expression of a scheduled job)
+ */
+fn build_job_labels(job: &JobConfigurationBluePrint) -> BTreeMap<String, String> {
+    let mut labels: BTreeMap<String, String> = BTreeMap::new();
+
+    labels.insert("capp.job.trigger".to_string(), job.trigger_type.clone());
+    if let Some(schedule) = &job.schedule_trigger_config {
+        labels.insert(
+            "capp.job.schedule".to_string(),
+            schedule.cron_expression.clone(),
+        );
+    }
+
+    labels
+}
+
+/***
+ * Services of each app (eg: its containers and their Dapr sidecars), by app name.
+ * The placement service and the Dapr network are shared, the serializer declares them
+ */
+pub fn build_per_app(
+    apps: Vec<ContainerAppBluePrint>,
+    images: Vec<ContainerImageBluePrint>,
+    include: Option<&[String]>,
+    options: &BuildOptions,
+) -> Result<Vec<(String, Vec<ContainerAppConfiguration>)>, DeserializeError> {
+    let mut groups: Vec<(String, Vec<ContainerAppConfiguration>)> = Vec::new();
+
+    validate_unique_image_references(&images)?;
+
+    // Sidecars are generated from their app, filtering the app is enough
+    let apps: Vec<ContainerAppBluePrint> = apps
+        .into_iter()
+        .filter(|app| match (include, &app.name) {
+            (Some(include), Some(name)) => include.contains(name),
+            (Some(_), None) => false,
+            (None, _) => true,
+        })
+        .collect();
+
+    validate_unique_service_names(&apps, options)?;
+
+    for app in apps {
+        // An empty configuration behaves as a missing one
+        let (
+            dapr_configuration,
+            ingress_configuration,
+            scale_configuration,
+            max_inactive_revisions,
+        ) = match app.configuration {
+            Some(ConfigurationBluePrint {
+                dapr,
+                ingress,
+                scale,
+                max_inactive_revisions,
+                ..
+            }) => (dapr, ingress, scale, max_inactive_revisions),
+            None => (None, None, None, None),
+        };
+        let dapr_configuration = dapr_configuration.filter(|_| !options.disable_dapr);
+
+        let template = app.template.ok_or(DeserializeError::NoContainer)?;
+        let revision_suffix = template
+            .revision_suffix
+            .map(|suffix| resolve_pulumi_tokens(&suffix, options));
+        let containers = template.containers.ok_or(DeserializeError::NoContainer)?;
+        let init_containers = template.init_containers.unwrap_or_default();
+        let volumes = template.volumes.unwrap_or_default();
+        let termination_grace_period_seconds = template.termination_grace_period_seconds;
+
+        let app_name = app.name.clone();
+        // Volumes of the app are named after it, or after its first container
+        let volume_prefix = app_name
+            .clone()
+            .or_else(|| containers.first().map(|container| container.name.clone()))
+            .unwrap_or_default();
+        let mut services: Vec<ContainerAppConfiguration> = Vec::new();
+        let mut init_services: Vec<String> = vec![];
+        let mut unresolved_images: Vec<DeserializeError> = vec![];
+
+        // Init containers run once, they get no ingress, Dapr sidecar nor restart policy
+        for container in init_containers {
+            let service_volumes =
+                build_volumes_for_serialization(&volume_prefix, &volumes, &container, options);
+            let mut a = parse_container_configuration(
+                &images,
+                AppConfiguration {
+                    container,
+                    dapr_configuration: None,
+                    ingress_configuration: None,
+                    scale_configuration: None,
+                    identity: None,
+                    workload_profile_name: None,
+                    revision_suffix: revision_suffix.clone(),
+                    max_inactive_revisions: None,
+                    location: None,
+                    resource_group_name: None,
+                    depends_on: None,
+                },
+                options,
+                &mut unresolved_images,
+            )?;
+
+            for service in a.iter_mut() {
+                service.restart = None;
+                service.volumes = service_volumes.clone();
+                init_services.push(service.name.clone());
+            }
+            services.append(&mut a);
+        }
+
+        // App containers start once the init containers have completed
+        let depends_on = (!init_services.is_empty()).then(|| {
+            DependsOn::Conditions(
+                init_services
+                    .into_iter()
+                    .map(|service| {
+                        (
+                            service,
+                            ServiceDependency {
+                                condition: DependencyCondition::ServiceCompletedSuccessfully,
+                            },
+                        )
+                    })
+                    .collect(),
+            )
+        });
+
+        for container in containers {
+            let service_volumes =
+                build_volumes_for_serialization(&volume_prefix, &volumes, &container, options);
+            let mut a = parse_container_configuration(
+                &images,
+                AppConfiguration {
+                    container,
+                    dapr_configuration: dapr_configuration.clone(),
+                    ingress_configuration: ingress_configuration.clone(),
+                    scale_configuration: scale_configuration.clone(),
+                    identity: app.identity.clone(),
+                    workload_profile_name: app.workload_profile_name.clone(),
+                    revision_suffix: revision_suffix.clone(),
+                    max_inactive_revisions,
+                    location: app.location.clone(),
+                    resource_group_name: app.resource_group_name.clone(),
+                    depends_on: depends_on.clone(),
+                },
+                options,
+                &mut unresolved_images,
+            )?;
+
+            // The sidecar does not mount the volumes of its app
+            if let Some(service) = a.first_mut() {
+                service.volumes = service_volumes;
+            }
+            services.append(&mut a);
+        }
+
+        // An app without any resolved image cannot be run
+        if services.is_empty() {
+            if let Some(error) = unresolved_images.into_iter().next() {
+                return Err(error);
+            }
+        }
+
+        // Every container of the app gets the same grace period
+        if let Some(seconds) = termination_grace_period_seconds {
+            for service in services.iter_mut() {
+                service.stop_grace_period = Some(format!("{}s", seconds));
+            }
+        }
+
+        // Jobs run once on demand, their schedule is left to the user
+        if let Some(job) = &app.job {
+            for service in services.iter_mut() {
+                service.restart = Some("no".to_string());
+                service.profiles = Some(vec!["jobs".to_string()]);
+                service
+                    .labels
+                    .get_or_insert_with(BTreeMap::new)
+                    .append(&mut build_job_labels(job));
+            }
+        }
+
+        normalize_networks(&mut services);
+
+        // Unnamed apps are named after their first service
+        let app_name = app_name
+            .or_else(|| services.first().map(|service| service.name.clone()))
+            .unwrap_or_default();
+        groups.push((app_name, services));
+    }
+
+    // Host ports and networks are shared by the apps, even once split
+    let services: Vec<ContainerAppConfiguration> = groups
+        .iter()
+        .flat_map(|(_, services)| services.clone())
+        .collect();
+    validate_host_ports(&services)?;
+    validate_network_mode(&services)?;
+    validate_declarations(&services)?;
+
+    Ok(groups)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::serializer::{
+        ConfigurationBluePrint, ContainerResourcesBluePrint, CorsPolicyBluePrint, CustomDomain,
+        CustomTokenResolver, ExecProbeBluePrint, HttpGetProbeBluePrint, PortMappingBluePrint,
+        TcpSocketProbeBluePrint, TemplateBluePrint,
+    };
+
+    use super::*;
+    #[test]
+    fn test_extract_and_parse_resource_name() {
+        let input1 = "${resource.property}".to_string();
+        let expected = Ok(Resource {
+            name: "resource".to_string(),
+            is_reference: true,
+        });
+        let output = extract_and_parse_resource_name(input1);
+        assert_eq!(expected, output);
+
+        let input2 = "resource".to_string();
+        let expected = Ok(Resource {
+            name: "resource".to_string(),
+            is_reference: false,
+        });
+        let output = extract_and_parse_resource_name(input2);
+        assert_eq!(expected, output);
+
+        // Literal images merely containing `imageName`
+        let input3 = "my-imageName-tool:1".to_string();
+        let expected = Ok(Resource {
+            name: "my-imageName-tool:1".to_string(),
+            is_reference: false,
+        });
+        let output = extract_and_parse_resource_name(input3);
+        assert_eq!(expected, output);
+    }
+
+    #[test]
+    fn test_build_image_for_serialization() {
+        // Container with a reference to an existing image with build context
+        let container = ContainerBluePrint::new("myapp", "${myImage.name}");
+        let images = vec![ContainerImageBluePrint {
+            name: Some("myImage".to_string()),
+            build: BuildContextBluePrint::new("${pulumi.cwd}/node-app"),
+            reference_name: Some("myImage".to_string()),
+        }];
+
+        let output =
+            build_image_for_serialization(&images, container, None, &BuildOptions::default())
+                .unwrap();
+
+        let expected = DockerImageForPulumi {
+            name: None,
+            path: Some("./node-app".to_string()),
+            is_context: true,
+            build: Some(BuildContext {
+                context: "./node-app".to_string(),
+                ..Default::default()
+            }),
+        };
+
+        assert_eq!(expected, output);
+
+        // Container with a reference to an non-existing image
+        let container = ContainerBluePrint::new("myapp", "${referenceDoNotMatch.name}");
+        let images = vec![ContainerImageBluePrint {
+            name: Some("myImage".to_string()),
+            build: BuildContextBluePrint::new("${pulumi.cwd}/node-app"),
+            reference_name: Some("myImage".to_string()),
+        }];
+
+        let output =
+            build_image_for_serialization(&images, container, None, &BuildOptions::default());
+
+        assert_eq!(None, output);
+
+        // Container with a remote image without context
+        let container = ContainerBluePrint::new("myapp", "node-12");
+        let images = vec![ContainerImageBluePrint {
+            name: Some("myImage".to_string()),
+            build: BuildContextBluePrint::new("${pulumi.cwd}/node-app"),
+            reference_name: Some("myImage".to_string()),
+        }];
+
+        let output =
+            build_image_for_serialization(&images, container, None, &BuildOptions::default())
+                .unwrap();
+
+        let expected = DockerImageForPulumi {
+            name: Some("node-12".to_string()),
+            path: None,
+            is_context: false,
+            build: None,
+        };
+
+        assert_eq!(expected, output);
+    }
+
+    #[test]
+    fn test_build_ports_mapping_for_serialization() {
+        // Assert that None dapr and ingress generate None ports
+        let container = ContainerBluePrint::new("some-app", "${myImage.name}");
+
+        let dapr_configuration = None;
+        let ingress_configuration = None;
+
+        let configuration = AppConfiguration {
+            container,
+            dapr_configuration,
+            ingress_configuration,
+            scale_configuration: None,
+            identity: None,
+            workload_profile_name: None,
+            revision_suffix: None,
+            max_inactive_revisions: None,
+            location: None,
+            resource_group_name: None,
+            depends_on: None,
+        };
+
+        let (dapr_app_port, ports) = build_ports_mapping_for_serialization(configuration).unwrap();
+
+        assert_eq!(dapr_app_port, None);
+        assert_eq!(ports, None);
+
+        // Assert that dapr.enabled:false generate None ports
+        let container = ContainerBluePrint::new("some-app", "${myImage.name}");
+
+        let dapr_configuration = Some(DaprBluePrint {
+            app_port: Some(80),
+            enabled: Some(false),
+            app_id: Some("t".to_string()),
+            command: None,
+            sidecar_image: None,
+            max_concurrency: None,
+            http_max_request_size: None,
+            components: None,
+        });
+        let ingress_configuration = None;
+
+        let configuration = AppConfiguration {
+            container,
+            dapr_configuration,
+            ingress_configuration,
+            scale_configuration: None,
+            identity: None,
+            workload_profile_name: None,
+            revision_suffix: None,
+            max_inactive_revisions: None,
+            location: None,
+            resource_group_name: None,
+            depends_on: None,
+        };
+
+        let (dapr_app_port, ports) = build_ports_mapping_for_serialization(configuration).unwrap();
+
+        assert_eq!(dapr_app_port, Some(80));
+        assert_eq!(ports, None);
+
+        //TODO
+        // Assert that dapr.enabled:true without ingress generate None ports
+        let container = ContainerBluePrint::new("some-app", "${myImage.name}");
+
+        let dapr_configuration = Some(DaprBluePrint {
+            app_port: Some(80),
+            enabled: Some(true),
+            app_id: Some("t".to_string()),
+            command: None,
+            sidecar_image: None,
+            max_concurrency: None,
+            http_max_request_size: None,
+            components: None,
+        });
+        let ingress_configuration = None;
+
+        let configuration = AppConfiguration {
+            container,
+            dapr_configuration,
+            ingress_configuration,
+            scale_configuration: None,
+            identity: None,
+            workload_profile_name: None,
+            revision_suffix: None,
+            max_inactive_revisions: None,
+            location: None,
+            resource_group_name: None,
+            depends_on: None,
+        };
+
+        let (dapr_app_port, ports) = build_ports_mapping_for_serialization(configuration).unwrap();
+
+        assert_eq!(dapr_app_port, Some(80));
+        assert_eq!(ports, None);
+
+        // Assert that dapr.enabled:true with ingress generate None ports if app_id doesn't match with existing container
+        let container = ContainerBluePrint::new("t", "${myImage.name}");
+
+        let dapr_configuration = Some(DaprBluePrint {
+            app_port: Some(80),
+            enabled: Some(true),
+            app_id: Some("some-app".to_string()),
+            command: None,
+            sidecar_image: None,
+            max_concurrency: None,
+            http_max_request_size: None,
+            components: None,
+        });
+        let ingress_configuration = Some(IngressBluePrint {
+            external: Some(true),
+            target_port: Some(3000),
+            cors_policy: None,
+            custom_domains: None,
+            additional_port_mappings: None,
+            transport: None,
+            client_certificate_mode: None,
+        });
+
+        let configuration = AppConfiguration {
+            container,
+            dapr_configuration,
+            ingress_configuration,
+            scale_configuration: None,
+            identity: None,
+            workload_profile_name: None,
+            revision_suffix: None,
+            max_inactive_revisions: None,
+            location: None,
+            resource_group_name: None,
+            depends_on: None,
+        };
+
+        let (dapr_app_port, ports) = build_ports_mapping_for_serialization(configuration).unwrap();
+
+        assert_eq!(dapr_app_port, Some(80));
+        assert_eq!(ports, None);
+
+        // Assert that dapr.enabled:true with ingress generate ports if app_id match with existing container
+        let container = ContainerBluePrint::new("some-app", "${myImage.name}");
+
+        let dapr_configuration = Some(DaprBluePrint {
+            app_port: Some(80),
+            enabled: Some(true),
+            app_id: Some("some-app".to_string()),
+            command: None,
+            sidecar_image: None,
+            max_concurrency: None,
+            http_max_request_size: None,
+            components: None,
+        });
+        let ingress_configuration = Some(IngressBluePrint {
+            external: Some(true),
+            target_port: Some(3000),
+            cors_policy: None,
+            custom_domains: None,
+            additional_port_mappings: None,
+            transport: None,
+            client_certificate_mode: None,
+        });
+
+        let configuration = AppConfiguration {
+            container,
+            dapr_configuration,
+            ingress_configuration,
+            scale_configuration: None,
+            identity: None,
+            workload_profile_name: None,
+            revision_suffix: None,
+            max_inactive_revisions: None,
+            location: None,
+            resource_group_name: None,
+            depends_on: None,
+        };
+
+        let (dapr_app_port, ports) = build_ports_mapping_for_serialization(configuration).unwrap();
+
+        assert_eq!(dapr_app_port, Some(80));
+        assert_eq!(ports, Some(vec!["3000:80".to_string()]));
+
+        // Assert that dapr.enabled:false with ingress.enabled:true  generate  Ingress ports
+        let container = ContainerBluePrint::new("some-app", "${myImage.name}");
+
+        let dapr_configuration = Some(DaprBluePrint {
+            app_port: Some(80),
+            enabled: Some(false),
+            app_id: Some("t".to_string()),
+            command: None,
+            sidecar_image: None,
+            max_concurrency: None,
+            http_max_request_size: None,
+            components: None,
+        });
+        let ingress_configuration = Some(IngressBluePrint {
+            external: Some(true),
+            target_port: Some(3000),
+            cors_policy: None,
+            custom_domains: None,
+            additional_port_mappings: None,
+            transport: None,
+            client_certificate_mode: None,
+        });
+
+        let configuration = AppConfiguration {
+            container,
+            dapr_configuration,
+            ingress_configuration,
+            scale_configuration: None,
+            identity: None,
+            workload_profile_name: None,
+            revision_suffix: None,
+            max_inactive_revisions: None,
+            location: None,
+            resource_group_name: None,
+            depends_on: None,
+        };
+
+        let (dapr_app_port, ports) = build_ports_mapping_for_serialization(configuration).unwrap();
+
+        assert_eq!(dapr_app_port, Some(80));
+        assert_eq!(ports, Some(vec!["3000:3000".to_string()]));
+    }
+
+    #[test]
+    fn test_parse_app_configuration() {
+        let configuration = AppConfiguration {
+            container: ContainerBluePrint::new("myapp", "${myImage.name}"),
+            dapr_configuration: Some(DaprBluePrint {
+                app_port: Some(3000),
+                enabled: Some(true),
+                app_id: Some("myapp".to_string()),
+                command: None,
+                sidecar_image: None,
+                max_concurrency: None,
+                http_max_request_size: None,
+                components: None,
+            }),
+            ingress_configuration: Some(IngressBluePrint {
+                external: Some(true),
+                target_port: Some(80),
+                cors_policy: None,
+                custom_domains: None,
+                additional_port_mappings: None,
+                transport: None,
+                client_certificate_mode: None,
+            }),
+            scale_configuration: None,
+            identity: None,
+            workload_profile_name: None,
+            revision_suffix: None,
+            max_inactive_revisions: None,
+            location: None,
+            resource_group_name: None,
+            depends_on: None,
+        };
+
+        let images = vec![ContainerImageBluePrint {
+            name: Some("${registry.loginServer}/node-app:v1.0.0".to_string()),
+            build: BuildContextBluePrint::new("${pulumi.cwd}/node-app"),
+            reference_name: Some("myImage".to_string()),
+        }];
+
+        let output = parse_app_configuration(&images, configuration, &BuildOptions::default());
+
+        let expected = vec![
             ContainerAppConfiguration {
-                image: image.name,
-                build: image.is_context.then(|| BuildContext {
-                    context: image.path.unwrap(),
+                build: Some(BuildContext {
+                    context: "./node-app".to_string(),
+                    tags: Some(vec!["node-app:v1.0.0".to_string()]),
+                    ..Default::default()
                 }),
-                name: name.clone(),
-                depends_on: Some(vec!["placement".to_string()]),
+                depends_on: Some(DependsOn::Services(vec!["placement".to_string()])),
                 networks: Some(vec![String::from("dapr-network")]),
-                network_mode: None,
-                environment: None,
-                ports: ports.clone(),
+                ports: Some(vec!["80:3000".to_string()]),
+                restart: Some("unless-stopped".to_string()),
+                ..ContainerAppConfiguration::new("myapp")
+            },
+            ContainerAppConfiguration {
+                image: Some(String::from("daprio/daprd:edge")),
+                depends_on: Some(DependsOn::Services(vec![String::from("myapp")])),
+                network_mode: Some(format!("service:{}", String::from("myapp"))),
+                command: Some(vec![
+                    "./daprd".to_string(),
+                    "-app-id".to_string(),
+                    String::from("myapp"),
+                    "-app-port".to_string(),
+                    "3000".to_string(),
+                    "-placement-host-address".to_string(),
+                    "placement:50006".to_string(),
+                    "air".to_string(),
+                ]),
+                restart: Some("unless-stopped".to_string()),
+                ..ContainerAppConfiguration::new("myapp_dapr")
+            },
+        ];
+
+        assert_eq!(Ok(expected), output);
+
+        let configuration = AppConfiguration {
+            container: ContainerBluePrint::new("myapp", "node-12"),
+            dapr_configuration: Some(DaprBluePrint {
+                app_port: Some(3000),
+                enabled: Some(false),
+                app_id: Some("myapp".to_string()),
+                command: None,
+                sidecar_image: None,
+                max_concurrency: None,
+                http_max_request_size: None,
+                components: None,
+            }),
+            ingress_configuration: Some(IngressBluePrint {
+                external: Some(false),
+                target_port: Some(80),
+                cors_policy: None,
+                custom_domains: None,
+                additional_port_mappings: None,
+                transport: None,
+                client_certificate_mode: None,
+            }),
+            scale_configuration: None,
+            identity: None,
+            workload_profile_name: None,
+            revision_suffix: None,
+            max_inactive_revisions: None,
+            location: None,
+            resource_group_name: None,
+            depends_on: None,
+        };
+
+        let images = vec![ContainerImageBluePrint {
+            name: Some("${registry.loginServer}/node-app:v1.0.0".to_string()),
+            build: BuildContextBluePrint::new("${pulumi.cwd}/node-app"),
+            reference_name: Some("myImage".to_string()),
+        }];
+
+        let output = parse_app_configuration(&images, configuration, &BuildOptions::default());
+
+        let expected = vec![ContainerAppConfiguration {
+            image: Some("node-12".to_string()),
+            restart: Some("unless-stopped".to_string()),
+            ..ContainerAppConfiguration::new("myapp")
+        }];
+
+        assert_eq!(Ok(expected), output);
+    }
+
+    #[test]
+    fn test_split_shell_command() {
+        // Simple command
+        let output = split_shell_command("./daprd -app-id foo");
+        let expected = Ok(vec![
+            "./daprd".to_string(),
+            "-app-id".to_string(),
+            "foo".to_string(),
+        ]);
+        assert_eq!(expected, output);
+
+        // Quoted arguments are kept as a single argument
+        let output =
+            split_shell_command(r#"./daprd -app-id "my app" -config '/dapr/config file.yml'"#);
+        let expected = Ok(vec![
+            "./daprd".to_string(),
+            "-app-id".to_string(),
+            "my app".to_string(),
+            "-config".to_string(),
+            "/dapr/config file.yml".to_string(),
+        ]);
+        assert_eq!(expected, output);
+
+        // Unterminated quote
+        let output = split_shell_command(r#"./daprd -app-id "foo"#);
+        let expected = Err(DeserializeError::InvalidCommand(
+            r#"./daprd -app-id "foo"#.to_string(),
+        ));
+        assert_eq!(expected, output);
+    }
+
+    #[test]
+    fn test_resolve_dapr_sidecar_image() {
+        let dapr_configuration = DaprBluePrint {
+            app_port: Some(3000),
+            enabled: Some(true),
+            app_id: Some("myapp".to_string()),
+            command: None,
+            sidecar_image: None,
+            max_concurrency: None,
+            http_max_request_size: None,
+            components: None,
+        };
+
+        // Built-in default
+        let output =
+            resolve_dapr_sidecar_image(&dapr_configuration, &DaprRuntimeOptions::default());
+        assert_eq!("daprio/daprd:edge".to_string(), output);
+
+        // Global option over built-in default
+        let dapr_options = DaprRuntimeOptions {
+            sidecar_image: Some("daprio/daprd:1.12.0".to_string()),
+        };
+        let output = resolve_dapr_sidecar_image(&dapr_configuration, &dapr_options);
+        assert_eq!("daprio/daprd:1.12.0".to_string(), output);
+
+        // Per-app image over global option
+        let dapr_configuration = DaprBluePrint {
+            sidecar_image: Some("daprio/daprd:1.13.0".to_string()),
+            ..dapr_configuration
+        };
+        let output = resolve_dapr_sidecar_image(&dapr_configuration, &dapr_options);
+        assert_eq!("daprio/daprd:1.13.0".to_string(), output);
+    }
+
+    #[test]
+    fn test_build_configuration_with_duplicate_container_names() {
+        let apps = vec![ContainerAppBluePrint {
+            template: Some(TemplateBluePrint::new(vec![
+                ContainerBluePrint::new("myapp", "node-12"),
+                ContainerBluePrint::new("myapp", "node-18"),
+            ])),
+            ..ContainerAppBluePrint::default()
+        }];
+
+        let output = build_configuration(apps, vec![], None, &BuildOptions::default());
+
+        assert_eq!(
+            Err(DeserializeError::DuplicateContainer {
+                name: "myapp".to_string(),
+                containers: vec!["myapp".to_string(), "myapp".to_string()],
+            }),
+            output
+        );
+
+        // Names are compared once sanitized, across the apps
+        let app = |container: &str| ContainerAppBluePrint {
+            name: Some(container.to_string()),
+            template: Some(TemplateBluePrint::new(vec![ContainerBluePrint::new(
+                container, "node-12",
+            )])),
+            ..ContainerAppBluePrint::default()
+        };
+        let output = build_configuration(
+            vec![app("My.App"), app("my-app")],
+            vec![],
+            None,
+            &BuildOptions::default(),
+        );
+
+        assert_eq!(
+            Err(DeserializeError::DuplicateContainer {
+                name: "my-app".to_string(),
+                containers: vec!["My.App".to_string(), "my-app".to_string()],
+            }),
+            output
+        );
+    }
+
+    #[test]
+    fn test_build_labels_for_serialization() {
+        let configuration = AppConfiguration {
+            container: ContainerBluePrint::new("myapp", "node-12"),
+            dapr_configuration: None,
+            ingress_configuration: Some(IngressBluePrint {
+                external: Some(true),
+                target_port: Some(80),
+                cors_policy: Some(CorsPolicyBluePrint {
+                    allowed_origins: Some(vec![
+                        "https://a.example.com".to_string(),
+                        "https://b.example.com".to_string(),
+                    ]),
+                    allowed_methods: Some(vec!["GET".to_string(), "POST".to_string()]),
+                }),
+                custom_domains: None,
+                additional_port_mappings: None,
+                transport: None,
+                client_certificate_mode: None,
+            }),
+            scale_configuration: None,
+            identity: None,
+            workload_profile_name: None,
+            revision_suffix: None,
+            max_inactive_revisions: None,
+            location: None,
+            resource_group_name: None,
+            depends_on: None,
+        };
+
+        let output = build_labels_for_serialization(&configuration, &BuildOptions::default());
+
+        let expected = BTreeMap::from([
+            (
+                "capp.ingress.cors.allowedMethods".to_string(),
+                "GET,POST".to_string(),
+            ),
+            (
+                "capp.ingress.cors.allowedOrigins".to_string(),
+                "https://a.example.com,https://b.example.com".to_string(),
+            ),
+        ]);
+
+        assert_eq!(Some(expected), output);
+
+        // Empty allowlists are omitted
+        let configuration = AppConfiguration {
+            ingress_configuration: Some(IngressBluePrint {
+                external: Some(true),
+                target_port: Some(80),
+                cors_policy: Some(CorsPolicyBluePrint {
+                    allowed_origins: Some(vec![]),
+                    allowed_methods: None,
+                }),
+                custom_domains: None,
+                additional_port_mappings: None,
+                transport: None,
+                client_certificate_mode: None,
+            }),
+            ..configuration
+        };
+
+        let output = build_labels_for_serialization(&configuration, &BuildOptions::default());
+
+        assert_eq!(None, output);
+    }
+
+    #[test]
+    fn test_new_depends_on_language_features() {
+        assert_eq!(
+            cfg!(feature = "yaml"),
+            Pulumi::new(Language::Yaml).is_some()
+        );
+        assert_eq!(
+            cfg!(feature = "js"),
+            Pulumi::new(Language::Typescript).is_some()
+        );
+        assert_eq!(
+            cfg!(feature = "js"),
+            Pulumi::new(Language::Javascript).is_some()
+        );
+        assert_eq!(
+            cfg!(feature = "python"),
+            Pulumi::new(Language::Python).is_some()
+        );
+        assert_eq!(
+            cfg!(feature = "csharp"),
+            Pulumi::new(Language::CSharp).is_some()
+        );
+        assert_eq!(cfg!(feature = "go"), Pulumi::new(Language::Go).is_some());
+        assert_eq!(
+            cfg!(feature = "state"),
+            Pulumi::new(Language::StackExport).is_some()
+        );
+        assert!(Pulumi::new(Language::Bicep).is_none());
+    }
+
+    #[test]
+    fn test_build_scale_rule_labels() {
+        let scale = r#"
+        minReplicas: 1
+        maxReplicas: 10
+        rules:
+          - name: http-rule
+            http:
+              metadata:
+                concurrentRequests: "50"
+        "#;
+
+        let configuration = AppConfiguration {
+            container: ContainerBluePrint::new("myapp", "node-12"),
+            dapr_configuration: None,
+            ingress_configuration: None,
+            scale_configuration: Some(serde_yaml::from_str(scale).unwrap()),
+            identity: None,
+            workload_profile_name: None,
+            revision_suffix: None,
+            max_inactive_revisions: None,
+            location: None,
+            resource_group_name: None,
+            depends_on: None,
+        };
+
+        let output = build_labels_for_serialization(&configuration, &BuildOptions::default());
+
+        let expected = BTreeMap::from([
+            (
+                "capp.scale.rule.http-rule.concurrentRequests".to_string(),
+                "50".to_string(),
+            ),
+            (
+                "capp.scale.rule.http-rule.type".to_string(),
+                "http".to_string(),
+            ),
+        ]);
+
+        assert_eq!(Some(expected), output);
+    }
+
+    #[test]
+    fn test_sanitize_service_name() {
+        assert_eq!("my-app", sanitize_service_name("My.App"));
+        assert_eq!("my_app-1", sanitize_service_name("my_app-1"));
+
+        let configuration = AppConfiguration {
+            container: ContainerBluePrint::new("My.App", "node-12"),
+            dapr_configuration: Some(DaprBluePrint {
+                app_port: Some(3000),
+                enabled: Some(true),
+                app_id: Some("My.App".to_string()),
                 command: None,
+                sidecar_image: None,
+                max_concurrency: None,
+                http_max_request_size: None,
+                components: None,
+            }),
+            ingress_configuration: None,
+            scale_configuration: None,
+            identity: None,
+            workload_profile_name: None,
+            revision_suffix: None,
+            max_inactive_revisions: None,
+            location: None,
+            resource_group_name: None,
+            depends_on: None,
+        };
+
+        let output = parse_app_configuration(&[], configuration, &BuildOptions::default()).unwrap();
+
+        assert_eq!("my-app", output[0].name);
+        assert_eq!("my-app_dapr", output[1].name);
+        assert_eq!(
+            Some(DependsOn::Services(vec!["my-app".to_string()])),
+            output[1].depends_on
+        );
+        assert_eq!(Some("service:my-app".to_string()), output[1].network_mode);
+    }
+
+    #[test]
+    fn test_build_image_with_base_path() {
+        let container = ContainerBluePrint::new("myapp", "${myImage.name}");
+        let images = vec![ContainerImageBluePrint {
+            name: Some("myImage".to_string()),
+            build: BuildContextBluePrint::new("${pulumi.cwd}/node-app"),
+            reference_name: Some("myImage".to_string()),
+        }];
+
+        let output = build_image_for_serialization(
+            &images,
+            container,
+            Some("infra"),
+            &BuildOptions::default(),
+        )
+        .unwrap();
+
+        let expected = DockerImageForPulumi {
+            name: None,
+            path: Some("infra/node-app".to_string()),
+            is_context: true,
+            build: Some(BuildContext {
+                context: "infra/node-app".to_string(),
+                ..Default::default()
+            }),
+        };
+
+        assert_eq!(expected, output);
+
+        assert_eq!("infra", resolve_build_context(".", Some("infra/")));
+        assert_eq!(
+            "infra/../frontend",
+            resolve_build_context("../frontend", Some("infra"))
+        );
+        assert_eq!("/src/app", resolve_build_context("/src/app", Some("infra")));
+        assert_eq!("./node-app", resolve_build_context("./node-app", None));
+    }
+
+    #[test]
+    fn test_split_registry_interpolation() {
+        assert_eq!(
+            (Some("${registry.loginServer}"), "node-app:v1.0.0"),
+            split_registry_interpolation("${registry.loginServer}/node-app:v1.0.0")
+        );
+        // Tagless images
+        assert_eq!(
+            (Some("${registry.loginServer}"), "team/node-app"),
+            split_registry_interpolation("${registry.loginServer}/team/node-app")
+        );
+        // Digest references
+        assert_eq!(
+            (Some("${registry.loginServer}"), "node-app@sha256:4f53"),
+            split_registry_interpolation("${registry.loginServer}/node-app@sha256:4f53")
+        );
+        // Nested interpolations, with their own slashes
+        assert_eq!(
+            (
+                Some("${format(\"${a}/{0}\", b)}"),
+                "node-app:${pulumi.stack}"
+            ),
+            split_registry_interpolation("${format(\"${a}/{0}\", b)}/node-app:${pulumi.stack}")
+        );
+        // Literal registries and references are not split
+        assert_eq!(
+            (None, "myacr.azurecr.io/node-app:v1"),
+            split_registry_interpolation("myacr.azurecr.io/node-app:v1")
+        );
+        assert_eq!(
+            (None, "${myImage.imageName}"),
+            split_registry_interpolation("${myImage.imageName}")
+        );
+    }
+
+    #[test]
+    fn test_build_image_with_registry_name() {
+        let images = vec![ContainerImageBluePrint {
+            name: Some("${registry.loginServer}/node-app@sha256:4f53".to_string()),
+            build: BuildContextBluePrint::new("./node-app"),
+            reference_name: Some("myImage".to_string()),
+        }];
+        let build_image = |image: &str| {
+            build_image_for_serialization(
+                &images,
+                ContainerBluePrint::new("myapp", image),
+                None,
+                &BuildOptions::default(),
+            )
+            .unwrap()
+        };
+
+        // Built images are tagged locally, without the registry nor the digest
+        let output = build_image("${myImage.imageName}");
+        assert_eq!(
+            Some(vec!["node-app".to_string()]),
+            output.build.and_then(|build| build.tags)
+        );
+
+        // The image is matched by its name
+        let output = build_image("${registry.loginServer}/node-app@sha256:4f53");
+        assert_eq!(Some("./node-app".to_string()), output.path);
+
+        // Remote images are kept as is
+        let output = build_image("${registry.loginServer}/api:v2");
+        assert_eq!(
+            (Some("${registry.loginServer}/api:v2".to_string()), None),
+            (output.name, output.build)
+        );
+    }
+
+    #[test]
+    fn test_build_image_with_build_options() {
+        let build_image = |build: BuildContextBluePrint| {
+            let images = vec![ContainerImageBluePrint {
+                name: Some("myImage".to_string()),
+                build,
+                reference_name: Some("myImage".to_string()),
+            }];
+            let container = ContainerBluePrint::new("myapp", "${myImage.name}");
+
+            build_image_for_serialization(&images, container, None, &BuildOptions::default())
+                .and_then(|image| image.build)
+                .unwrap()
+        };
+        let context = BuildContextBluePrint::new("${pulumi.cwd}/api");
+        let expected = BuildContext {
+            context: "./api".to_string(),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            BuildContext {
+                dockerfile: Some("./Dockerfile.api".to_string()),
+                ..expected.clone()
             },
-            // Dapr Sidecar config
-            ContainerAppConfiguration {
-                image: Some(String::from("daprio/daprd:edge")),
-                name: format!("{}_dapr", name.clone()),
-                depends_on: Some(vec![String::from(&name)]),
-                network_mode: Some(format!("service:{}", String::from(&name))),
-                environment: None,
-                // No exposed ports for dapr sidecar
-                ports: None,
-                networks: None,
-                build: None,
-                command: Some(vec![
-                    "./daprd".to_string(),
-                    "-app-id".to_string(),
-                    String::from(&name),
-                    "-app-port".to_string(),
-                    format!("{}", dapr_app_port.unwrap_or_default()),
-                    "-placement-host-address".to_string(),
-                    "placement:50006".to_string(),
-                    "air".to_string(),
-                ]),
+            build_image(BuildContextBluePrint {
+                dockerfile: Some("${pulumi.cwd}/Dockerfile.api".to_string()),
+                ..context.clone()
+            })
+        );
+        assert_eq!(
+            BuildContext {
+                args: Some(BTreeMap::from([
+                    ("NODE_ENV".to_string(), "production".to_string()),
+                    ("PORT".to_string(), "3000".to_string()),
+                ])),
+                ..expected.clone()
             },
-        ]
-    } else {
-        vec![ContainerAppConfiguration {
-            image: image.name,
-            build: image.is_context.then(|| BuildContext {
-                context: image.path.unwrap(),
+            build_image(BuildContextBluePrint {
+                args: Some(BTreeMap::from([
+                    ("NODE_ENV".to_string(), "production".into()),
+                    ("PORT".to_string(), 3000.into()),
+                ])),
+                ..context.clone()
+            })
+        );
+        assert_eq!(
+            BuildContext {
+                target: Some("runtime".to_string()),
+                ..expected.clone()
+            },
+            build_image(BuildContextBluePrint {
+                target: Some("runtime".to_string()),
+                ..context.clone()
+            })
+        );
+        assert_eq!(
+            BuildContext {
+                platforms: Some(vec!["linux/amd64".to_string()]),
+                ..expected.clone()
+            },
+            build_image(BuildContextBluePrint {
+                platform: Some("linux/amd64".to_string()),
+                ..context.clone()
+            })
+        );
+
+        // Every option together
+        let build: BuildContextBluePrint = serde_yaml::from_str(
+            r#"
+            context: ${pulumi.cwd}/api
+            dockerfile: ./Dockerfile.api
+            args:
+              NODE_ENV: production
+            target: runtime
+            platform: linux/amd64
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            BuildContext {
+                context: "./api".to_string(),
+                dockerfile: Some("./Dockerfile.api".to_string()),
+                args: Some(BTreeMap::from([(
+                    "NODE_ENV".to_string(),
+                    "production".to_string()
+                )])),
+                target: Some("runtime".to_string()),
+                platforms: Some(vec!["linux/amd64".to_string()]),
+                tags: None,
+            },
+            build_image(build)
+        );
+    }
+
+    #[test]
+    fn test_build_configuration_with_duplicate_image_references() {
+        let image = ContainerImageBluePrint {
+            name: Some("myImage".to_string()),
+            build: BuildContextBluePrint::new("${pulumi.cwd}/node-app"),
+            reference_name: Some("myImage".to_string()),
+        };
+        let images = vec![
+            image.clone(),
+            ContainerImageBluePrint {
+                build: BuildContextBluePrint::new("${pulumi.cwd}/other-app"),
+                ..image
+            },
+        ];
+
+        let output = build_configuration(vec![], images, None, &BuildOptions::default());
+
+        assert_eq!(
+            Err(DeserializeError::DuplicateImageReference(
+                "myImage".to_string()
+            )),
+            output
+        );
+    }
+
+    #[test]
+    fn test_build_custom_domain_labels() {
+        let configuration = AppConfiguration {
+            container: ContainerBluePrint::new("myapp", "node-12"),
+            dapr_configuration: None,
+            ingress_configuration: Some(IngressBluePrint {
+                external: Some(true),
+                target_port: Some(80),
+                cors_policy: None,
+                custom_domains: Some(vec![
+                    CustomDomain {
+                        name: "api.example.com".to_string(),
+                        binding_type: Some("SniEnabled".to_string()),
+                        certificate_id: Some("${certificate.id}".to_string()),
+                    },
+                    CustomDomain {
+                        name: "www.example.com".to_string(),
+                        binding_type: None,
+                        certificate_id: None,
+                    },
+                ]),
+                additional_port_mappings: None,
+                transport: None,
+                client_certificate_mode: None,
             }),
-            name,
+            scale_configuration: None,
+            identity: None,
+            workload_profile_name: None,
+            revision_suffix: None,
+            max_inactive_revisions: None,
+            location: None,
+            resource_group_name: None,
             depends_on: None,
-            // No Dapr network
-            networks: None,
-            environment: None,
-            network_mode: None,
-            ports: ports.clone(),
+        };
+
+        let output = build_labels_for_serialization(&configuration, &BuildOptions::default());
+
+        let expected = BTreeMap::from([
+            (
+                "capp.ingress.domain.api.example.com.bindingType".to_string(),
+                "SniEnabled".to_string(),
+            ),
+            (
+                "capp.ingress.domain.www.example.com.bindingType".to_string(),
+                "Disabled".to_string(),
+            ),
+        ]);
+
+        assert_eq!(Some(expected), output);
+    }
+
+    #[test]
+    fn test_clone_resolved_image() {
+        let container = ContainerBluePrint::new("myapp", "${myImage.name}");
+        let images = vec![ContainerImageBluePrint {
+            name: Some("myImage".to_string()),
+            build: BuildContextBluePrint::new("${pulumi.cwd}/node-app"),
+            reference_name: Some("myImage".to_string()),
+        }];
+
+        let image =
+            build_image_for_serialization(&images, container, None, &BuildOptions::default())
+                .unwrap();
+
+        assert_eq!(image, image.clone());
+    }
+
+    #[test]
+    fn test_build_identity_labels() {
+        let identity: Identity = serde_yaml::from_str(
+            r#"
+            type: SystemAssigned,UserAssigned
+            userAssignedIdentities:
+              - ${identity.id}
+            "#,
+        )
+        .unwrap();
+
+        let mut configuration = AppConfiguration {
+            container: ContainerBluePrint::new("myapp", "node-12"),
+            dapr_configuration: None,
+            ingress_configuration: None,
+            scale_configuration: None,
+            identity: Some(identity),
+            workload_profile_name: None,
+            revision_suffix: None,
+            max_inactive_revisions: None,
+            location: None,
+            resource_group_name: None,
+            depends_on: None,
+        };
+
+        let output = build_labels_for_serialization(&configuration, &BuildOptions::default());
+
+        let expected = BTreeMap::from([
+            (
+                "capp.identity.type".to_string(),
+                "SystemAssigned,UserAssigned".to_string(),
+            ),
+            (
+                "capp.identity.userAssigned".to_string(),
+                "${identity.id}".to_string(),
+            ),
+        ]);
+
+        assert_eq!(Some(expected), output);
+
+        // System assigned only
+        configuration.identity = Some(Identity {
+            identity_type: "SystemAssigned".to_string(),
+            user_assigned_identities: None,
+        });
+
+        let output = build_labels_for_serialization(&configuration, &BuildOptions::default());
+
+        let expected = BTreeMap::from([(
+            "capp.identity.type".to_string(),
+            "SystemAssigned".to_string(),
+        )]);
+
+        assert_eq!(Some(expected), output);
+    }
+
+    #[test]
+    fn test_build_additional_ports_mapping() {
+        let ingress_configuration = Some(IngressBluePrint {
+            external: Some(true),
+            target_port: Some(80),
+            cors_policy: None,
+            custom_domains: None,
+            additional_port_mappings: Some(vec![
+                PortMappingBluePrint {
+                    external: Some(true),
+                    target_port: Some(9090),
+                    exposed_port: Some(9091),
+                },
+                // Already mapped by the primary ingress
+                PortMappingBluePrint {
+                    external: Some(true),
+                    target_port: Some(80),
+                    exposed_port: None,
+                },
+                PortMappingBluePrint {
+                    external: Some(false),
+                    target_port: Some(5432),
+                    exposed_port: None,
+                },
+            ]),
+            transport: None,
+            client_certificate_mode: None,
+        });
+
+        let configuration = AppConfiguration {
+            container: ContainerBluePrint::new("myapp", "node-12"),
+            dapr_configuration: None,
+            ingress_configuration: ingress_configuration.clone(),
+            scale_configuration: None,
+            identity: None,
+            workload_profile_name: None,
+            revision_suffix: None,
+            max_inactive_revisions: None,
+            location: None,
+            resource_group_name: None,
+            depends_on: None,
+        };
+
+        let (_, ports) = build_ports_mapping_for_serialization(configuration).unwrap();
+
+        assert_eq!(
+            Some(vec!["80:80".to_string(), "9091:9090".to_string()]),
+            ports
+        );
+
+        // The Dapr rewrite only applies to the primary ingress
+        let configuration = AppConfiguration {
+            container: ContainerBluePrint::new("myapp", "node-12"),
+            dapr_configuration: Some(DaprBluePrint {
+                app_port: Some(3000),
+                enabled: Some(true),
+                app_id: Some("myapp".to_string()),
+                command: None,
+                sidecar_image: None,
+                max_concurrency: None,
+                http_max_request_size: None,
+                components: None,
+            }),
+            ingress_configuration,
+            scale_configuration: None,
+            identity: None,
+            workload_profile_name: None,
+            revision_suffix: None,
+            max_inactive_revisions: None,
+            location: None,
+            resource_group_name: None,
+            depends_on: None,
+        };
+
+        let (_, ports) = build_ports_mapping_for_serialization(configuration).unwrap();
+
+        assert_eq!(
+            Some(vec!["80:3000".to_string(), "9091:9090".to_string()]),
+            ports
+        );
+    }
+
+    #[test]
+    fn test_build_configuration_with_include() {
+        let build_app = |name: &str| ContainerAppBluePrint {
+            name: Some(name.to_string()),
+            configuration: Some(ConfigurationBluePrint {
+                dapr: Some(DaprBluePrint {
+                    app_port: Some(3000),
+                    enabled: Some(true),
+                    app_id: Some(name.to_string()),
+                    command: None,
+                    sidecar_image: None,
+                    max_concurrency: None,
+                    http_max_request_size: None,
+                    components: None,
+                }),
+                ..ConfigurationBluePrint::default()
+            }),
+            template: Some(TemplateBluePrint::new(vec![ContainerBluePrint::new(
+                name.to_string(),
+                "node-12",
+            )])),
+            ..ContainerAppBluePrint::default()
+        };
+        let apps = vec![build_app("frontend"), build_app("backend")];
+
+        let include = vec!["backend".to_string()];
+        let output = build_configuration(
+            apps.clone(),
+            vec![],
+            Some(&include),
+            &BuildOptions::default(),
+        )
+        .unwrap();
+
+        let names: Vec<&str> = output.iter().map(|service| service.name.as_str()).collect();
+        assert_eq!(vec!["backend", "backend_dapr"], names);
+
+        let output = build_configuration(apps.clone(), vec![], Some(&[]), &BuildOptions::default());
+        assert_eq!(Ok(vec![]), output);
+
+        let output = build_configuration(apps, vec![], None, &BuildOptions::default()).unwrap();
+        assert_eq!(4, output.len());
+    }
+
+    #[test]
+    fn test_build_sidecar_depends_on() {
+        let mut service = ContainerAppConfiguration {
+            image: Some("node-12".to_string()),
+            depends_on: Some(DependsOn::Services(vec!["placement".to_string()])),
+            networks: Some(vec!["dapr-network".to_string()]),
+            ..ContainerAppConfiguration::new("myapp")
+        };
+
+        let output = build_sidecar_depends_on(&service);
+        assert_eq!(DependsOn::Services(vec!["myapp".to_string()]), output);
+
+        service.healthcheck = Some(HealthCheck {
+            test: vec![
+                "CMD".to_string(),
+                "curl".to_string(),
+                "-f".to_string(),
+                "http://localhost".to_string(),
+            ],
+            interval: Some("10s".to_string()),
+            timeout: None,
+            retries: Some(3),
+            start_period: None,
+        });
+
+        let output = build_sidecar_depends_on(&service);
+
+        assert_eq!(
+            "myapp:\n  condition: service_healthy\n",
+            serde_yaml::to_string(&output).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_parse_image_reference() {
+        let output = parse_image_reference("localhost:5000/app:v1");
+        let expected = ImageReference {
+            registry: Some("localhost:5000".to_string()),
+            name: "app".to_string(),
+            tag: Some("v1".to_string()),
+            digest: None,
+        };
+        assert_eq!(expected, output);
+
+        let output = parse_image_reference("daprio/dapr");
+        let expected = ImageReference {
+            registry: None,
+            name: "daprio/dapr".to_string(),
+            tag: None,
+            digest: None,
+        };
+        assert_eq!(expected, output);
+
+        // The registry port is neither a tag nor a resource reference
+        let image = "localhost:5000/app:v1";
+        let output = build_image_for_serialization(
+            &[],
+            ContainerBluePrint::new("myapp", image),
+            None,
+            &BuildOptions::default(),
+        );
+        let expected = Some(DockerImageForPulumi {
+            name: Some(image.to_string()),
+            path: None,
+            is_context: false,
+            build: None,
+        });
+        assert_eq!(expected, output);
+    }
+
+    #[test]
+    fn test_build_image_with_stack_outputs() {
+        let container = ContainerBluePrint::new("myapp", r#"${stackRef.outputs["imageName"]}"#);
+        let options = BuildOptions {
+            stack_outputs: BTreeMap::from([(
+                "imageName".to_string(),
+                "myacr.azurecr.io/app:v1".to_string(),
+            )]),
+            ..Default::default()
+        };
+
+        let output = build_image_for_serialization(&[], container.clone(), None, &options);
+        let expected = Some(DockerImageForPulumi {
+            name: Some("myacr.azurecr.io/app:v1".to_string()),
+            path: None,
+            is_context: false,
+            build: None,
+        });
+        assert_eq!(expected, output);
+
+        // Unknown outputs are a placeholder image
+        let output = build_image_for_serialization(&[], container, None, &BuildOptions::default());
+        let expected = Some(DockerImageForPulumi {
+            name: Some("stack-output/imagename".to_string()),
+            path: None,
+            is_context: false,
+            build: None,
+        });
+        assert_eq!(expected, output);
+    }
+
+    #[test]
+    fn test_build_digest_pinned_image() {
+        let image = "myacr.azurecr.io/app@sha256:4c1e3e5c2db0c2e3f7c8b8b9e6d1f0a7e9c4b2a1d3f5e7c9b1a3d5f7e9c1b3a5";
+        let container = ContainerBluePrint::new("myapp", image.to_string());
+
+        let output = build_image_for_serialization(&[], container, None, &BuildOptions::default());
+
+        let expected = Some(DockerImageForPulumi {
+            name: Some(image.to_string()),
+            path: None,
+            is_context: false,
+            build: None,
+        });
+
+        assert_eq!(expected, output);
+    }
+
+    #[test]
+    fn test_build_configuration_restart_policy() {
+        let apps = vec![ContainerAppBluePrint {
+            name: Some("myapp".to_string()),
+            template: Some(TemplateBluePrint {
+                init_containers: Some(vec![ContainerBluePrint::new("migrate", "busybox")]),
+                ..TemplateBluePrint::new(vec![ContainerBluePrint::new("myapp", "node-12")])
+            }),
+            ..ContainerAppBluePrint::default()
+        }];
+
+        let output =
+            build_configuration(apps.clone(), vec![], None, &BuildOptions::default()).unwrap();
+
+        let policies: Vec<(&str, Option<&str>)> = output
+            .iter()
+            .map(|service| (service.name.as_str(), service.restart.as_deref()))
+            .collect();
+        assert_eq!(
+            vec![("migrate", None), ("myapp", Some("unless-stopped"))],
+            policies
+        );
+
+        // Configurable for the app containers only
+        let options = BuildOptions {
+            restart: Some("always".to_string()),
+            ..BuildOptions::default()
+        };
+        let output = build_configuration(apps, vec![], None, &options).unwrap();
+
+        assert_eq!(None, output[0].restart);
+        assert_eq!(Some("always".to_string()), output[1].restart);
+    }
+
+    #[test]
+    fn test_build_workload_profile_labels() {
+        let app = ContainerAppBluePrint {
+            name: Some("myapp".to_string()),
+            configuration: Some(ConfigurationBluePrint {
+                dapr: Some(DaprBluePrint {
+                    app_port: Some(3000),
+                    enabled: Some(true),
+                    app_id: Some("myapp".to_string()),
+                    command: None,
+                    sidecar_image: None,
+                    max_concurrency: None,
+                    http_max_request_size: None,
+                    components: None,
+                }),
+                ..ConfigurationBluePrint::default()
+            }),
+            template: Some(TemplateBluePrint::new(vec![ContainerBluePrint::new(
+                "myapp", "node-12",
+            )])),
+            workload_profile_name: Some("Dedicated-D4".to_string()),
+            ..ContainerAppBluePrint::default()
+        };
+
+        let output =
+            build_configuration(vec![app.clone()], vec![], None, &BuildOptions::default()).unwrap();
+
+        let expected = Some(BTreeMap::from([(
+            "capp.workloadProfile".to_string(),
+            "Dedicated-D4".to_string(),
+        )]));
+
+        assert_eq!(expected, output[0].labels);
+        assert_eq!(expected, output[1].labels);
+
+        // No profile, no label
+        let app = ContainerAppBluePrint {
+            workload_profile_name: None,
+            ..app
+        };
+        let output =
+            build_configuration(vec![app], vec![], None, &BuildOptions::default()).unwrap();
+
+        assert_eq!(None, output[0].labels);
+        assert_eq!(None, output[1].labels);
+    }
+
+    #[test]
+    fn test_build_configuration_without_images() {
+        let literal = ContainerBluePrint::new("literal", "node-12");
+        let reference = ContainerBluePrint::new("reference", "${myImage.name}");
+
+        // Literal images do not need an image resource
+        let output = check_and_match_reference(
+            &[],
+            extract_and_parse_resource_name(literal.image.clone()).unwrap(),
+            None,
+            &BuildOptions::default(),
+        );
+        assert_eq!(
+            Some(DockerImageForPulumi {
+                name: Some("node-12".to_string()),
+                path: None,
+                is_context: false,
+                build: None,
+            }),
+            output
+        );
+
+        let app = ContainerAppBluePrint {
+            name: Some("myapp".to_string()),
+            template: Some(TemplateBluePrint::new(vec![
+                literal.clone(),
+                reference.clone(),
+            ])),
+            ..ContainerAppBluePrint::default()
+        };
+
+        // The unresolved container is ignored, the other one is still run
+        let output =
+            build_configuration(vec![app.clone()], vec![], None, &BuildOptions::default()).unwrap();
+        assert_eq!(1, output.len());
+        assert_eq!("literal", output[0].name);
+        assert_eq!(Some("node-12".to_string()), output[0].image);
+
+        let warnings = collect_unresolved_image_warnings(&[app], &[], &BuildOptions::default());
+        assert_eq!(
+            vec![
+                "ignored container reference of myapp, its image ${myImage.name} cannot be resolved"
+                    .to_string()
+            ],
+            warnings
+        );
+
+        // An app without any resolved image fails
+        let app = ContainerAppBluePrint {
+            name: Some("myapp".to_string()),
+            template: Some(TemplateBluePrint::new(vec![reference])),
+            ..ContainerAppBluePrint::default()
+        };
+        let output = build_configuration(vec![app], vec![], None, &BuildOptions::default());
+        assert_eq!(
+            Err(DeserializeError::UnresolvedImage {
+                container: "reference".to_string(),
+                reference: "${myImage.name}".to_string(),
+            }),
+            output
+        );
+    }
+
+    #[test]
+    fn test_build_dapr_tuning_flags() {
+        let mut dapr_configuration = DaprBluePrint {
+            app_port: Some(3000),
+            enabled: Some(true),
+            app_id: Some("myapp".to_string()),
             command: None,
-        }]
-    };
+            sidecar_image: None,
+            max_concurrency: Some(10),
+            http_max_request_size: Some(16),
+            components: None,
+        };
+
+        let flags = build_dapr_tuning_flags(&dapr_configuration).unwrap();
+        let output = build_dapr_command("myapp", Some(3000), None, flags);
+
+        assert_eq!(
+            Ok(vec![
+                "./daprd".to_string(),
+                "-app-id".to_string(),
+                "myapp".to_string(),
+                "-app-port".to_string(),
+                "3000".to_string(),
+                "-placement-host-address".to_string(),
+                "placement:50006".to_string(),
+                "-app-max-concurrency".to_string(),
+                "10".to_string(),
+                "-dapr-http-max-request-size".to_string(),
+                "16".to_string(),
+                "air".to_string(),
+            ]),
+            output
+        );
+
+        // Custom commands get the flags they do not already give
+        let flags = build_dapr_tuning_flags(&dapr_configuration).unwrap();
+        let output = build_dapr_command(
+            "myapp",
+            Some(3000),
+            Some(CommandBluePrint::Shell(
+                "./daprd -app-id myapp -app-max-concurrency 5".to_string(),
+            )),
+            flags,
+        );
+
+        assert_eq!(
+            Ok(vec![
+                "./daprd".to_string(),
+                "-app-id".to_string(),
+                "myapp".to_string(),
+                "-app-max-concurrency".to_string(),
+                "5".to_string(),
+                "-dapr-http-max-request-size".to_string(),
+                "16".to_string(),
+            ]),
+            output
+        );
+
+        dapr_configuration.max_concurrency = Some(0);
+        let output = build_dapr_tuning_flags(&dapr_configuration);
+
+        assert_eq!(
+            Err(DeserializeError::InvalidDaprOption(
+                "maxConcurrency".to_string()
+            )),
+            output
+        );
+    }
+
+    #[test]
+    fn test_resolve_image() {
+        let images = vec![ContainerImageBluePrint {
+            name: Some("myImage".to_string()),
+            build: BuildContextBluePrint::new("${pulumi.cwd}/node-app"),
+            reference_name: Some("myImage".to_string()),
+        }];
+        let build_container = |image: &str| ContainerBluePrint::new("myapp", image.to_string());
+
+        let output = resolve_image(&images, &build_container("${myImage.name}"));
+        assert_eq!(
+            Ok(DockerImageForPulumi {
+                name: None,
+                path: Some("./node-app".to_string()),
+                is_context: true,
+                build: Some(BuildContext {
+                    context: "./node-app".to_string(),
+                    ..Default::default()
+                }),
+            }),
+            output
+        );
+
+        let output = resolve_image(&images, &build_container("${otherImage.name}"));
+        assert_eq!(
+            Err(ResolveError::ReferenceNotFound(
+                "${otherImage.name}".to_string()
+            )),
+            output
+        );
+
+        let output = resolve_image(&images, &build_container("${myImage}"));
+        assert_eq!(
+            Err(ResolveError::UnparseableImage("${myImage}".to_string())),
+            output
+        );
+    }
+
+    #[test]
+    fn test_build_configuration_with_revision_suffix() {
+        let app = ContainerAppBluePrint {
+            name: Some("myapp".to_string()),
+            template: Some(TemplateBluePrint {
+                revision_suffix: Some("${pulumi.stack}".to_string()),
+                ..TemplateBluePrint::new(vec![ContainerBluePrint::new("myapp", "node-12")])
+            }),
+            ..ContainerAppBluePrint::default()
+        };
+        let options = BuildOptions {
+            tokens: BTreeMap::from([("pulumi.stack".to_string(), "prod".to_string())]),
+            ..BuildOptions::default()
+        };
+
+        let output = build_configuration(vec![app.clone()], vec![], None, &options).unwrap();
+        assert_eq!("myapp-prod", output[0].name);
+
+        // Unresolved tokens are stripped
+        let output =
+            build_configuration(vec![app.clone()], vec![], None, &BuildOptions::default()).unwrap();
+        assert_eq!("myapp", output[0].name);
 
-    Some(result)
-}
+        // Separators left by the sanitization are trimmed
+        let options = BuildOptions {
+            tokens: BTreeMap::from([("pulumi.stack".to_string(), "!@#".to_string())]),
+            ..BuildOptions::default()
+        };
+        let output = build_configuration(vec![app.clone()], vec![], None, &options).unwrap();
+        assert_eq!("myapp", output[0].name);
 
-pub fn build_configuration(
-    apps: Vec<ContainerAppBluePrint>,
-    images: Vec<ContainerImageBluePrint>,
-) -> Option<Vec<ContainerAppConfiguration>> {
-    let mut services: Vec<ContainerAppConfiguration> = Vec::new();
+        let options = BuildOptions {
+            tokens: BTreeMap::from([("pulumi.stack".to_string(), "_Prod!".to_string())]),
+            ..BuildOptions::default()
+        };
+        let output = build_configuration(vec![app.clone()], vec![], None, &options).unwrap();
+        assert_eq!("myapp-prod", output[0].name);
 
-    for app in apps {
-        let dapr_configuration = match app.configuration.clone() {
-            Some(config) => config.dapr,
-            None => None,
+        // The Dapr app id stays the same whatever the suffix
+        let app_id = |output: &[ContainerAppConfiguration]| {
+            let command = output[1].command.clone().unwrap();
+            let position = command.iter().position(|arg| arg == "-app-id").unwrap();
+            command[position + 1].clone()
+        };
+        let dapr = |app_id: Option<&str>| ContainerAppBluePrint {
+            configuration: Some(ConfigurationBluePrint {
+                dapr: Some(DaprBluePrint {
+                    app_port: Some(3000),
+                    enabled: Some(true),
+                    app_id: app_id.map(|app_id| app_id.to_string()),
+                    command: None,
+                    sidecar_image: None,
+                    max_concurrency: None,
+                    http_max_request_size: None,
+                    components: None,
+                }),
+                ..ConfigurationBluePrint::default()
+            }),
+            ..app.clone()
+        };
+
+        let output = build_configuration(vec![dapr(Some("api"))], vec![], None, &options).unwrap();
+        assert_eq!("myapp-prod", output[0].name);
+        assert_eq!("myapp-prod_dapr", output[1].name);
+        assert_eq!("api", app_id(&output));
+
+        let output = build_configuration(vec![dapr(None)], vec![], None, &options).unwrap();
+        assert_eq!("myapp", app_id(&output));
+    }
+
+    #[cfg(feature = "yaml")]
+    #[test]
+    fn test_parse_many() {
+        let program = |name: &str| {
+            format!(
+                r#"
+      resources:
+        {name}:
+          type: azure-native:app:ContainerApp
+          properties:
+            template:
+              containers:
+                - image: node-12
+                  name: {name}
+      "#
+            )
         };
-        let ingress_configuration = match app.configuration {
-            Some(config) => config.ingress,
-            None => None,
+
+        let output = parse_many(
+            vec![
+                (program("frontend"), Language::Yaml),
+                ("resources: [".to_string(), Language::Yaml),
+                (program("backend"), Language::Yaml),
+            ],
+            &BuildOptions::default(),
+        );
+
+        assert_eq!(3, output.len());
+        assert_eq!("frontend", output[0].as_ref().unwrap()[0].name);
+        assert!(matches!(output[1], Err(DeserializeError::Parse(_))));
+        assert_eq!("backend", output[2].as_ref().unwrap()[0].name);
+    }
+
+    #[cfg(feature = "yaml")]
+    #[test]
+    fn test_deserialize_value_warnings() {
+        let input = r#"
+      resources:
+        myapp:
+          type: azure-native:app:ContainerApp
+          properties:
+            configuration:
+              secrets:
+                - name: token
+                  value: ${token}
+                - name: api-key
+                  keyVaultUrl: https://shop.vault.azure.net/secrets/api-key
+                  identity: system
+              ingress:
+                external: true
+                targetPort: 80
+            template:
+              containers:
+                - image: node-12
+                  name: myapp
+      "#;
+        let mut pulumi = Pulumi::new(Language::Yaml).unwrap();
+
+        assert!(pulumi.warnings().is_empty());
+
+        let output = pulumi.deserialize_value(input).unwrap();
+
+        assert_eq!(
+            vec![
+                "unresolved variable ${token} at configuration.secrets[0].value in myapp"
+                    .to_string(),
+                "secret api-key of myapp is read from the Key Vault https://shop.vault.azure.net/secrets/api-key, its value must be given locally"
+                    .to_string()
+            ],
+            output.warnings()
+        );
+    }
+
+    #[test]
+    fn test_build_per_app() {
+        let build_app = |name: &str, port: u32| {
+            serde_yaml::from_str::<ContainerAppBluePrint>(&format!(
+                r#"
+                configuration:
+                  dapr:
+                    enabled: true
+                    appId: {name}
+                    appPort: {port}
+                template:
+                  containers:
+                    - image: node-12
+                      name: {name}
+                "#
+            ))
+            .map(|app| ContainerAppBluePrint {
+                name: Some(name.to_string()),
+                ..app
+            })
+            .unwrap()
         };
 
-        let mut a: Vec<ContainerAppConfiguration> = app
-            .template?
-            .containers?
+        let output = build_per_app(
+            vec![build_app("frontend", 3000), build_app("backend", 4000)],
+            vec![],
+            None,
+            &BuildOptions::default(),
+        )
+        .unwrap();
+
+        let names: Vec<(&str, Vec<&str>)> = output
             .iter()
-            .flat_map(|container| {
-                parse_app_configuration(
-                    &images,
-                    AppConfiguration {
-                        container: container.to_owned(),
-                        dapr_configuration: dapr_configuration.clone(),
-                        ingress_configuration: ingress_configuration.clone(),
-                    },
+            .map(|(app, services)| {
+                (
+                    app.as_str(),
+                    services
+                        .iter()
+                        .map(|service| service.name.as_str())
+                        .collect(),
                 )
             })
-            .flatten()
             .collect();
+        assert_eq!(
+            vec![
+                ("frontend", vec!["frontend", "frontend_dapr"]),
+                ("backend", vec!["backend", "backend_dapr"]),
+            ],
+            names
+        );
+    }
+
+    #[test]
+    fn test_validate_network_mode() {
+        let app: ContainerAppBluePrint = serde_yaml::from_str(
+            r#"
+            configuration:
+              dapr:
+                enabled: true
+                appId: myapp
+                appPort: 3000
+            template:
+              containers:
+                - image: node-12
+                  name: myapp
+            "#,
+        )
+        .unwrap();
+        let mut services =
+            build_configuration(vec![app], vec![], None, &BuildOptions::default()).unwrap();
+
+        assert_eq!(Ok(()), validate_network_mode(&services));
 
-        services.append(&mut a);
+        services[1].networks = Some(vec!["dapr-network".to_string()]);
+
+        assert_eq!(
+            Err(DeserializeError::NetworkModeConflict(
+                "myapp_dapr".to_string()
+            )),
+            validate_network_mode(&services)
+        );
     }
-    Some(services)
-}
 
-mod tests {
-    use crate::serializer::{BuildContextBluePrint, ConfigurationBluePrint, TemplateBluePrint};
+    #[test]
+    fn test_build_configuration_with_port_conflict() {
+        let build_app = |name: &str| ContainerAppBluePrint {
+            name: Some(name.to_string()),
+            configuration: Some(ConfigurationBluePrint {
+                ingress: Some(IngressBluePrint {
+                    external: Some(true),
+                    target_port: Some(3000),
+                    cors_policy: None,
+                    custom_domains: None,
+                    additional_port_mappings: None,
+                    transport: None,
+                    client_certificate_mode: None,
+                }),
+                ..ConfigurationBluePrint::default()
+            }),
+            template: Some(TemplateBluePrint::new(vec![ContainerBluePrint::new(
+                name.to_string(),
+                "node-12",
+            )])),
+            ..ContainerAppBluePrint::default()
+        };
+
+        let output = build_configuration(
+            vec![build_app("frontend"), build_app("backend")],
+            vec![],
+            None,
+            &BuildOptions::default(),
+        );
+
+        assert_eq!(
+            Err(DeserializeError::PortConflict {
+                port: "3000".to_string(),
+                services: vec!["frontend".to_string(), "backend".to_string()],
+            }),
+            output
+        );
+    }
 
-    use super::*;
     #[test]
-    fn test_extract_and_parse_resource_name() {
-        let input1 = "${resource.property}".to_string();
-        let expected = Ok(Resource {
-            name: "resource".to_string(),
-            is_reference: true,
-        });
-        let output = extract_and_parse_resource_name(input1);
+    fn test_build_probe_timing() {
+        let probe = ProbeBluePrint {
+            probe_type: Some("Liveness".to_string()),
+            http_get: Some(HttpGetProbeBluePrint {
+                path: Some("/health".to_string()),
+                port: Some(3000),
+            }),
+            tcp_socket: None,
+            exec: None,
+            initial_delay_seconds: Some(5),
+            period_seconds: Some(30),
+            timeout_seconds: Some(5),
+            failure_threshold: Some(10),
+        };
+
+        let output = build_probe_timing(&probe);
+        assert_eq!(("30s".to_string(), "5s".to_string(), 10), output);
+
+        // Container Apps defaults
+        let probe = ProbeBluePrint {
+            probe_type: None,
+            http_get: None,
+            tcp_socket: Some(TcpSocketProbeBluePrint { port: Some(3000) }),
+            exec: None,
+            initial_delay_seconds: None,
+            period_seconds: None,
+            timeout_seconds: None,
+            failure_threshold: None,
+        };
+
+        let output = build_probe_timing(&probe);
+        assert_eq!(("10s".to_string(), "1s".to_string(), 3), output);
+
+        let container = ContainerBluePrint {
+            probes: Some(vec![probe]),
+            ..ContainerBluePrint::new("myapp", "node-12")
+        };
+
+        assert_eq!(
+            Some(HealthCheck {
+                test: vec![
+                    "CMD-SHELL".to_string(),
+                    "nc -z localhost 3000 || exit 1".to_string()
+                ],
+                interval: Some("10s".to_string()),
+                timeout: Some("1s".to_string()),
+                retries: Some(3),
+                start_period: None,
+            }),
+            build_healthcheck_for_serialization(&container)
+        );
+    }
+
+    #[cfg(feature = "yaml")]
+    #[test]
+    fn test_deserialize_reader() {
+        let input = r#"
+      resources:
+        myapp:
+          type: azure-native:app:ContainerApp
+          properties:
+            configuration:
+              ingress:
+                external: true
+                targetPort: 80
+            template:
+              containers:
+                - image: node-12
+                  name: myapp
+      "#;
+
+        let mut from_reader = Pulumi::new(Language::Yaml).unwrap();
+        let mut from_str = Pulumi::new(Language::Yaml).unwrap();
+
+        let output = from_reader
+            .deserialize_reader(std::io::Cursor::new(input.as_bytes()))
+            .unwrap();
+        let expected = from_str.deserialize_value(input).unwrap();
+
+        assert!(output.resources.is_some());
+        assert_eq!(expected.resources, output.resources);
+
+        // Byte order marks of exported files are ignored
+        let output = from_reader
+            .deserialize_reader(std::io::Cursor::new(format!("\u{feff}{}", input)))
+            .unwrap();
+        assert_eq!(expected.resources, output.resources);
+
+        // Other languages are read before being parsed
+        #[cfg(feature = "js")]
+        {
+            let input = r#"
+            const frontendApp = new app.ContainerApp("frontend", {
+                template: {
+                    containers: [{ name: "remix", image: "node:12" }],
+                },
+            });"#;
+            let mut from_reader = Pulumi::new(Language::Typescript).unwrap();
+            let mut from_str = Pulumi::new(Language::Typescript).unwrap();
+            let output = from_reader
+                .deserialize_reader(std::io::Cursor::new(format!("\u{feff}{}", input)))
+                .unwrap();
+            let expected = from_str.deserialize_value(input).unwrap();
+            assert!(output.resources.is_some());
+            assert_eq!(expected.resources, output.resources);
+        }
+
+        // Invalid UTF-8 fails like unparseable input
+        let output = from_reader.deserialize_reader(std::io::Cursor::new(vec![0xff, 0xfe]));
+        assert!(matches!(output, Err(DeserializeError::Parse(_))));
+    }
+
+    #[test]
+    fn test_build_configuration_with_empty_configuration() {
+        let app = ContainerAppBluePrint {
+            name: Some("myapp".to_string()),
+            configuration: Some(ConfigurationBluePrint::default()),
+            template: Some(TemplateBluePrint::new(vec![ContainerBluePrint::new(
+                "myapp", "node-12",
+            )])),
+            ..ContainerAppBluePrint::default()
+        };
+
+        let output = build_configuration(vec![app.clone()], vec![], None, &BuildOptions::default());
+        let expected = build_configuration(
+            vec![ContainerAppBluePrint {
+                configuration: None,
+                ..app
+            }],
+            vec![],
+            None,
+            &BuildOptions::default(),
+        );
+
         assert_eq!(expected, output);
 
-        let input2 = "resource".to_string();
-        let expected = Ok(Resource {
-            name: "resource".to_string(),
-            is_reference: false,
-        });
-        let output = extract_and_parse_resource_name(input2);
+        let output = output.unwrap();
+        assert_eq!(1, output.len());
+        assert_eq!(None, output[0].depends_on);
+        assert_eq!(None, output[0].networks);
+        assert_eq!(None, output[0].ports);
+    }
+
+    #[test]
+    fn test_build_configuration_with_init_container_resources() {
+        let apps = vec![ContainerAppBluePrint {
+            name: Some("myapp".to_string()),
+            template: Some(TemplateBluePrint {
+                init_containers: Some(vec![ContainerBluePrint {
+                    resources: Some(ContainerResourcesBluePrint {
+                        cpu: Some(0.1),
+                        memory: None,
+                    }),
+                    ..ContainerBluePrint::new("migrate", "busybox")
+                }]),
+                ..TemplateBluePrint::new(vec![ContainerBluePrint {
+                    resources: Some(ContainerResourcesBluePrint {
+                        cpu: Some(0.5),
+                        memory: Some("1Gi".to_string()),
+                    }),
+                    ..ContainerBluePrint::new("myapp", "node-12")
+                }])
+            }),
+            ..ContainerAppBluePrint::default()
+        }];
+
+        let output = build_configuration(apps, vec![], None, &BuildOptions::default()).unwrap();
+
+        let limits = |cpus: &str, memory: Option<&str>| {
+            Some(Deploy {
+                replicas: None,
+                resources: Some(DeployResources {
+                    limits: ResourceLimits {
+                        cpus: Some(cpus.to_string()),
+                        memory: memory.map(|x| x.to_string()),
+                    },
+                }),
+            })
+        };
+        assert_eq!("migrate", output[0].name);
+        assert_eq!(limits("0.1", None), output[0].deploy);
+        assert_eq!("myapp", output[1].name);
+        assert_eq!(limits("0.5", Some("1g")), output[1].deploy);
+    }
+
+    #[test]
+    fn test_collect_unresolved_references() {
+        let apps = vec![
+            ContainerAppBluePrint {
+                name: Some("frontend".to_string()),
+                template: Some(TemplateBluePrint::new(vec![ContainerBluePrint::new(
+                    "frontend",
+                    "${frontendImage.imageName}",
+                )])),
+                ..ContainerAppBluePrint::default()
+            },
+            ContainerAppBluePrint {
+                name: Some("backend".to_string()),
+                template: Some(TemplateBluePrint::new(vec![ContainerBluePrint::new(
+                    "backend",
+                    "${backendImage.imageName}",
+                )])),
+                ..ContainerAppBluePrint::default()
+            },
+        ];
+        let images = vec![ContainerImageBluePrint {
+            name: Some("frontendImage".to_string()),
+            build: BuildContextBluePrint::new("${pulumi.cwd}/frontend"),
+            reference_name: Some("frontendImage".to_string()),
+        }];
+
+        let output = collect_unresolved_references(&apps, &images, &BuildOptions::default());
+
+        assert_eq!(vec!["backendImage".to_string()], output);
+
+        // Substitutions are resolved
+        let options = BuildOptions {
+            tokens: BTreeMap::from([("backendImage.imageName".to_string(), "node-12".to_string())]),
+            ..BuildOptions::default()
+        };
+        let output = collect_unresolved_references(&apps, &images, &options);
+
+        assert!(output.is_empty());
+    }
+
+    #[test]
+    fn test_build_client_certificate_mode_label() {
+        let app: ContainerAppBluePrint = serde_yaml::from_str(
+            r#"
+            configuration:
+              ingress:
+                external: true
+                targetPort: 3000
+                clientCertificateMode: require
+            template:
+              containers:
+                - image: node-12
+                  name: myapp
+            "#,
+        )
+        .unwrap();
+
+        let output =
+            build_configuration(vec![app], vec![], None, &BuildOptions::default()).unwrap();
+
+        let expected = BTreeMap::from([(
+            "capp.ingress.clientCertMode".to_string(),
+            "require".to_string(),
+        )]);
+        assert_eq!(Some(expected), output[0].labels);
+
+        let output = serde_yaml::from_str::<IngressBluePrint>("clientCertificateMode: sometimes");
+        assert!(output.is_err());
+    }
+
+    #[test]
+    fn test_build_http2_transport_labels() {
+        let configuration = |transport: &str| AppConfiguration {
+            container: ContainerBluePrint::new("myapp", "node-12"),
+            dapr_configuration: None,
+            ingress_configuration: Some(IngressBluePrint {
+                external: Some(true),
+                target_port: Some(50051),
+                cors_policy: None,
+                custom_domains: None,
+                additional_port_mappings: None,
+                transport: Some(transport.to_string()),
+                client_certificate_mode: None,
+            }),
+            scale_configuration: None,
+            identity: None,
+            workload_profile_name: None,
+            revision_suffix: None,
+            max_inactive_revisions: None,
+            location: None,
+            resource_group_name: None,
+            depends_on: None,
+        };
+
+        let output =
+            parse_app_configuration(&[], configuration("http2"), &BuildOptions::default()).unwrap();
+
+        let expected =
+            BTreeMap::from([("capp.ingress.transport".to_string(), "http2".to_string())]);
+        assert_eq!(Some(expected), output[0].labels);
+        assert_eq!(Some(vec!["50051:50051".to_string()]), output[0].ports);
+
+        for transport in ["auto", "http", "tcp"] {
+            let output =
+                parse_app_configuration(&[], configuration(transport), &BuildOptions::default())
+                    .unwrap();
+
+            assert_eq!(None, output[0].labels);
+            assert_eq!(Some(vec!["50051:50051".to_string()]), output[0].ports);
+        }
+    }
+
+    #[test]
+    fn test_build_probe_labels() {
+        let probe = |probe_type: &str, path: &str| ProbeBluePrint {
+            probe_type: Some(probe_type.to_string()),
+            http_get: Some(HttpGetProbeBluePrint {
+                path: Some(path.to_string()),
+                port: Some(3000),
+            }),
+            tcp_socket: None,
+            exec: None,
+            initial_delay_seconds: None,
+            period_seconds: Some(5),
+            timeout_seconds: None,
+            failure_threshold: None,
+        };
+        let container = ContainerBluePrint {
+            probes: Some(vec![
+                probe("Liveness", "/healthz"),
+                probe("Startup", "/started"),
+                probe("Readiness", "/ready"),
+            ]),
+            ..ContainerBluePrint::new("myapp", "node-12")
+        };
+
+        let output = build_healthcheck_for_serialization(&container).unwrap();
+
+        assert_eq!(
+            vec![
+                "CMD-SHELL".to_string(),
+                "curl -f http://localhost:3000/healthz || exit 1".to_string()
+            ],
+            output.test
+        );
+
+        let output = build_probe_labels(&container);
+
+        let expected = BTreeMap::from([
+            (
+                "capp.probe.readiness.httpGet.path".to_string(),
+                "/ready".to_string(),
+            ),
+            (
+                "capp.probe.readiness.httpGet.port".to_string(),
+                "3000".to_string(),
+            ),
+            (
+                "capp.probe.readiness.periodSeconds".to_string(),
+                "5".to_string(),
+            ),
+            (
+                "capp.probe.startup.httpGet.path".to_string(),
+                "/started".to_string(),
+            ),
+            (
+                "capp.probe.startup.httpGet.port".to_string(),
+                "3000".to_string(),
+            ),
+            (
+                "capp.probe.startup.periodSeconds".to_string(),
+                "5".to_string(),
+            ),
+        ]);
+        assert_eq!(expected, output);
+    }
+
+    struct CapturingLogger {
+        records: std::sync::Mutex<Vec<(log::Level, String, String)>>,
+    }
+
+    impl log::Log for CapturingLogger {
+        fn enabled(&self, _metadata: &log::Metadata) -> bool {
+            true
+        }
+
+        fn log(&self, record: &log::Record) {
+            self.records.lock().unwrap().push((
+                record.level(),
+                record.target().to_string(),
+                record.args().to_string(),
+            ));
+        }
+
+        fn flush(&self) {}
+    }
+
+    static LOGGER: CapturingLogger = CapturingLogger {
+        records: std::sync::Mutex::new(Vec::new()),
+    };
+
+    #[test]
+    fn test_unsupported_language_logs() {
+        log::set_logger(&LOGGER).unwrap();
+        log::set_max_level(log::LevelFilter::Trace);
+
+        let mut provider = Pulumi {
+            language: Language::Bicep,
+            options: BuildOptions::default(),
+            resources: None,
+            apps: vec![],
+            warnings: vec![],
+            path: None,
+        };
+        let output = provider.deserialize_value("");
+
+        assert!(matches!(output, Err(DeserializeError::UnsupportedLanguage)));
+        assert!(LOGGER.records.lock().unwrap().contains(&(
+            log::Level::Error,
+            "capp_s::pulumi".to_string(),
+            "Language not supported".to_string()
+        )));
+    }
+
+    #[test]
+    fn test_build_ports_mapping_with_invalid_ports() {
+        let configuration = |target_port: u32| AppConfiguration {
+            container: ContainerBluePrint::new("myapp", "node-12"),
+            dapr_configuration: None,
+            ingress_configuration: Some(IngressBluePrint {
+                external: Some(true),
+                target_port: Some(target_port),
+                cors_policy: None,
+                custom_domains: None,
+                additional_port_mappings: None,
+                transport: None,
+                client_certificate_mode: None,
+            }),
+            scale_configuration: None,
+            identity: None,
+            workload_profile_name: None,
+            revision_suffix: None,
+            max_inactive_revisions: None,
+            location: None,
+            resource_group_name: None,
+            depends_on: None,
+        };
+
+        let output = build_ports_mapping_for_serialization(configuration(65535));
+        assert_eq!(Ok((None, Some(vec!["65535:65535".to_string()]))), output);
+
+        for port in [0, 70000] {
+            let output = build_ports_mapping_for_serialization(configuration(port));
+
+            assert_eq!(
+                Err(DeserializeError::InvalidPort {
+                    field: "ingress.targetPort".to_string(),
+                    value: port,
+                }),
+                output
+            );
+        }
+    }
+
+    #[test]
+    fn test_build_dapr_components() {
+        let app: ContainerAppBluePrint = serde_yaml::from_str(
+            r#"
+            configuration:
+              dapr:
+                enabled: true
+                appId: myapp
+                appPort: 3000
+                components:
+                  - name: statestore
+                    type: state.redis
+                    metadata:
+                      redisHost: redis:6379
+            template:
+              containers:
+                - image: node-12
+                  name: myapp
+            "#,
+        )
+        .unwrap();
+
+        let output =
+            build_configuration(vec![app.clone()], vec![], None, &BuildOptions::default()).unwrap();
+
+        let command = output[1].command.clone().unwrap();
+        let position = command
+            .iter()
+            .position(|arg| arg == "-components-path")
+            .unwrap();
+        assert_eq!("/components", command[position + 1]);
+
+        let output = build_dapr_components(std::slice::from_ref(&app)).unwrap();
+
+        let expected = BTreeMap::from([(
+            "myapp-statestore".to_string(),
+            r#"apiVersion: dapr.io/v1alpha1
+kind: Component
+metadata:
+  name: statestore
+spec:
+  metadata:
+  - name: redisHost
+    value: redis:6379
+  type: state.redis
+  version: v1
+"#
+            .to_string(),
+        )]);
         assert_eq!(expected, output);
+
+        // Components of different apps sharing a name are both kept
+        let mut other = app.clone();
+        if let Some(dapr) = other
+            .configuration
+            .as_mut()
+            .and_then(|configuration| configuration.dapr.as_mut())
+        {
+            dapr.app_id = Some("other".to_string());
+        }
+        let output = build_dapr_components(&[app.clone(), other]).unwrap();
+        assert_eq!(
+            vec!["myapp-statestore", "other-statestore"],
+            output.keys().collect::<Vec<_>>()
+        );
+
+        let output = build_dapr_components(&[app.clone(), app]);
+        assert_eq!(
+            Err(DeserializeError::ConflictingDeclaration(
+                "myapp-statestore".to_string()
+            )),
+            output
+        );
+    }
+
+    #[test]
+    fn test_dapr_components_configs() {
+        let app: ContainerAppBluePrint = serde_yaml::from_str(
+            r#"
+            configuration:
+              dapr:
+                enabled: true
+                appId: orders
+                appPort: 3000
+                components:
+                  - name: statestore
+                    type: state.redis
+            template:
+              containers:
+                - image: node-12
+                  name: myapp
+            "#,
+        )
+        .unwrap();
+
+        let output =
+            build_configuration(vec![app.clone()], vec![], None, &BuildOptions::default()).unwrap();
+        let compose: serde_yaml::Value =
+            serde_yaml::from_str(&crate::serializer::to_compose_yaml(&output).unwrap()).unwrap();
+
+        let content = compose["configs"]["orders-statestore"]["content"]
+            .as_str()
+            .unwrap();
+        assert!(content.contains("type: state.redis"));
+
+        // The config source is the name of the component file
+        let mount = &compose["services"]["myapp_dapr"]["configs"][0];
+        let files = build_dapr_components(&[app]).unwrap();
+        assert_eq!(vec!["orders-statestore"], files.keys().collect::<Vec<_>>());
+        assert_eq!("orders-statestore", mount["source"].as_str().unwrap());
+        assert_eq!(
+            "/components/statestore.yaml",
+            mount["target"].as_str().unwrap()
+        );
+
+        let command = output[1].command.clone().unwrap();
+        assert!(command.contains(&"/components".to_string()));
+    }
+
+    #[test]
+    fn test_build_configuration_depends_on_init_containers() {
+        let app: ContainerAppBluePrint = serde_yaml::from_str(
+            r#"
+            template:
+              initContainers:
+                - image: busybox
+                  name: migrate
+              containers:
+                - image: node-12
+                  name: myapp
+            "#,
+        )
+        .unwrap();
+
+        let output =
+            build_configuration(vec![app.clone()], vec![], None, &BuildOptions::default()).unwrap();
+
+        let expected = DependsOn::Conditions(BTreeMap::from([(
+            "migrate".to_string(),
+            ServiceDependency {
+                condition: DependencyCondition::ServiceCompletedSuccessfully,
+            },
+        )]));
+        assert_eq!(None, output[0].depends_on);
+        assert_eq!(Some(expected), output[1].depends_on);
+
+        // Merged with the placement dependency of Dapr apps
+        let app = ContainerAppBluePrint {
+            configuration: serde_yaml::from_str(
+                "dapr: { enabled: true, appId: myapp, appPort: 3000 }",
+            )
+            .ok(),
+            ..app
+        };
+        let output =
+            build_configuration(vec![app], vec![], None, &BuildOptions::default()).unwrap();
+
+        let expected = DependsOn::Conditions(BTreeMap::from([
+            (
+                "migrate".to_string(),
+                ServiceDependency {
+                    condition: DependencyCondition::ServiceCompletedSuccessfully,
+                },
+            ),
+            (
+                "placement".to_string(),
+                ServiceDependency {
+                    condition: DependencyCondition::ServiceStarted,
+                },
+            ),
+        ]));
+        assert_eq!(Some(expected), output[1].depends_on);
+    }
+
+    #[test]
+    fn test_sidecar_command_block_style() {
+        let app: ContainerAppBluePrint = serde_yaml::from_str(
+            r#"
+            configuration:
+              dapr: { enabled: true, appId: myapp, appPort: 3000 }
+            template:
+              containers:
+                - image: node-12
+                  name: myapp
+            "#,
+        )
+        .unwrap();
+        let services =
+            build_configuration(vec![app], vec![], None, &BuildOptions::default()).unwrap();
+
+        let output = crate::serializer::to_compose_yaml(&services).unwrap();
+
+        // One argument per line instead of a flow sequence
+        assert!(output.contains(
+            "    command:\n    - ./daprd\n    - -app-id\n    - myapp\n    - -app-port\n    - '3000'\n"
+        ));
+        // Short commands stay on one line
+        assert!(output.contains("    command: [\"./placement\", \"-port\", \"50006\"]\n"));
+    }
+
+    #[test]
+    fn test_build_configuration_with_dapr_disabled() {
+        let app: ContainerAppBluePrint = serde_yaml::from_str(
+            r#"
+            configuration:
+              dapr: { enabled: true, appId: myapp, appPort: 3000 }
+              ingress: { external: true, targetPort: 3000 }
+            template:
+              containers:
+                - image: node-12
+                  name: myapp
+            "#,
+        )
+        .unwrap();
+        let options = BuildOptions {
+            disable_dapr: true,
+            ..BuildOptions::default()
+        };
+
+        let output = build_configuration(vec![app], vec![], None, &options).unwrap();
+
+        assert_eq!(1, output.len());
+        assert_eq!("myapp", output[0].name);
+        assert_eq!(Some(vec!["3000:3000".to_string()]), output[0].ports);
+        assert_eq!(None, output[0].networks);
+        assert_eq!(None, output[0].depends_on);
+    }
+
+    #[test]
+    fn test_build_configuration_without_dapr_app_port() {
+        let app: ContainerAppBluePrint = serde_yaml::from_str(
+            r#"
+            configuration:
+              dapr: { enabled: true, appId: myapp }
+            template:
+              containers:
+                - image: node-12
+                  name: myapp
+            "#,
+        )
+        .unwrap();
+
+        let output = build_configuration(vec![app], vec![], None, &BuildOptions::default());
+
+        assert_eq!(
+            Err(DeserializeError::MissingDaprAppPort("myapp".to_string())),
+            output
+        );
+    }
+
+    struct RegistryResolver {}
+
+    impl TokenResolver for RegistryResolver {
+        fn resolve(&self, token: &str) -> Option<String> {
+            (token == "registry.loginServer").then(|| "myregistry.azurecr.io".to_string())
+        }
+    }
+
+    #[test]
+    fn test_build_configuration_with_token_resolver() {
+        let app: ContainerAppBluePrint = serde_yaml::from_str(
+            r#"
+            template:
+              containers:
+                - image: ${registry.loginServer}/app:v1
+                  name: myapp
+            "#,
+        )
+        .unwrap();
+        let options = BuildOptions {
+            token_resolver: Some(CustomTokenResolver(std::sync::Arc::new(
+                RegistryResolver {},
+            ))),
+            ..BuildOptions::default()
+        };
+
+        let output = build_configuration(vec![app.clone()], vec![], None, &options).unwrap();
+
+        assert_eq!(
+            Some("myregistry.azurecr.io/app:v1".to_string()),
+            output[0].image
+        );
+
+        // Unknown registries are remote images, kept as is
+        let output = build_configuration(vec![app], vec![], None, &BuildOptions::default());
+
+        assert_eq!(
+            Some("${registry.loginServer}/app:v1".to_string()),
+            output.unwrap()[0].image
+        );
+    }
+
+    #[cfg(feature = "yaml")]
+    #[test]
+    fn test_deserialize_values() {
+        let apps = r#"
+      resources:
+        frontend:
+          type: azure-native:app:ContainerApp
+          properties:
+            template:
+              containers:
+                - image: ${remixImage.name}
+                  name: remix
+      "#;
+        let images = r#"
+      resources:
+        remixImage:
+          type: docker:RegistryImage
+          properties:
+            name: remix
+            build:
+              context: ./frontend
+      "#;
+        let mut pulumi = Pulumi::new(Language::Yaml).unwrap();
+
+        // The app of the first input references the image of the second one
+        let output = pulumi.deserialize_values(&[apps, images]).unwrap();
+        let resources = output.resources.as_ref().unwrap();
+
+        assert_eq!(1, resources.len());
+        assert_eq!(
+            Some("./frontend".to_string()),
+            resources[0]
+                .build
+                .as_ref()
+                .map(|build| build.context.clone())
+        );
+        assert_eq!(1, output.apps().len());
+        // The image of the other input resolves the container, it is not reported
+        assert!(output.warnings.is_empty());
+
+        let output = pulumi.deserialize_value(apps).map(|_| ());
+
+        assert_eq!(
+            Err(DeserializeError::UnresolvedImage {
+                container: "remix".to_string(),
+                reference: "${remixImage.name}".to_string()
+            }),
+            output
+        );
+
+        let output = pulumi
+            .deserialize_values(&[images, apps, images])
+            .map(|_| ());
+
+        assert_eq!(
+            Err(DeserializeError::DuplicateResource {
+                name: "remixImage".to_string(),
+                inputs: (0, 2)
+            }),
+            output
+        );
+    }
+
+    #[test]
+    fn test_parse_app_resource_type() {
+        assert_eq!(
+            Some(("ContainerApp", None)),
+            parse_app_resource_type("azure-native:app:ContainerApp")
+        );
+        assert_eq!(
+            Some(("ContainerApp", Some("v20230501"))),
+            parse_app_resource_type("azure-native:app/v20230501:ContainerApp")
+        );
+        assert_eq!(
+            Some(("Job", Some("v20240802preview"))),
+            parse_app_resource_type("azure-native:app/v20240802preview:Job")
+        );
+        assert_eq!(
+            Some(("ManagedEnvironment", None)),
+            parse_app_resource_type("azure-native:app:ManagedEnvironment")
+        );
+        assert_eq!(
+            Some(("DaprComponent", Some("v20221001"))),
+            parse_app_resource_type("azure-native:app/v20221001:DaprComponent")
+        );
+        assert_eq!(
+            None,
+            parse_app_resource_type("azure-native:app/latest:ContainerApp")
+        );
+        assert_eq!(
+            None,
+            parse_app_resource_type("azure-native:web:ContainerApp")
+        );
+    }
+
+    #[test]
+    fn test_detect_language() {
+        let yaml = r#"
+name: shop
+runtime: yaml
+resources:
+  api:
+    type: azure-native:app:ContainerApp
+    properties:
+      template:
+        containers:
+          - name: api
+            image: node:20
+"#;
+        let typescript = r#"
+import * as app from "@pulumi/azure-native/app";
+
+const name: string = "api";
+new app.ContainerApp(name, { template: { containers: [{ name, image: "node:20" }] } });
+"#;
+        let javascript = r#"
+const app = require("@pulumi/azure-native/app");
+
+new app.ContainerApp("api", { template: { containers: [{ name: "api", image: "node:20" }] } });
+"#;
+
+        assert_eq!(Ok(Language::Yaml), detect_language(yaml, None));
+        assert_eq!(Ok(Language::Typescript), detect_language(typescript, None));
+        assert_eq!(Ok(Language::Javascript), detect_language(javascript, None));
+
+        assert_eq!(
+            Err(DeserializeError::UndetectedLanguage(vec![
+                "the content, matching no language".to_string()
+            ])),
+            detect_language("Hello world", None)
+        );
+        // A Typescript program embedded in a YAML one is ambiguous
+        let ambiguous = format!("{}\n# {}", yaml, typescript.replace('\n', " "));
+        assert_eq!(
+            Err(DeserializeError::UndetectedLanguage(vec![
+                "the content, matching Yaml and Typescript".to_string()
+            ])),
+            detect_language(&ambiguous, None)
+        );
+    }
+
+    #[test]
+    fn test_detect_language_from_path() {
+        let directory = std::env::temp_dir().join(format!("capp_s_detect_{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        std::fs::write(directory.join("index.ts"), "").unwrap();
+        std::fs::write(directory.join("program"), "").unwrap();
+        std::fs::write(
+            directory.join("Pulumi.yaml"),
+            "name: shop\nruntime:\n  name: nodejs\n  options:\n    typescript: false\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            Ok(Language::Typescript),
+            detect_language("", Some(&directory.join("index.ts")))
+        );
+        // Files without known extension and folders take the runtime of the project file
+        assert_eq!(
+            Ok(Language::Javascript),
+            detect_language("", Some(&directory.join("program")))
+        );
+        assert_eq!(
+            Ok(Language::Javascript),
+            detect_language("", Some(&directory))
+        );
+
+        std::fs::remove_file(directory.join("Pulumi.yaml")).unwrap();
+
+        assert_eq!(
+            Err(DeserializeError::UndetectedLanguage(vec![
+                format!("the extension of {}", directory.join("program").display()),
+                format!("the runtime of {}", directory.join("Pulumi.yaml").display()),
+                "the content, matching no language".to_string(),
+            ])),
+            detect_language("", Some(&directory.join("program")))
+        );
+
+        std::fs::remove_dir_all(directory).unwrap();
     }
 
+    #[cfg(feature = "yaml")]
     #[test]
-    fn test_build_image_for_serialization() {
-        // Container with a reference to an existing image with build context
-        let container = ContainerBluePrint {
-            image: "${myImage.name}".to_string(),
-            name: "myapp".to_string(),
-        };
-        let images = vec![ContainerImageBluePrint {
-            name: Some("myImage".to_string()),
-            build: BuildContextBluePrint {
-                context: "${pulumi.cwd}/node-app".to_string(),
-            },
-            reference_name: Some("myImage".to_string()),
-        }];
+    fn test_deserialize_value_with_auto_language() {
+        let input = r#"
+resources:
+  api:
+    type: azure-native:app:ContainerApp
+    properties:
+      template:
+        containers:
+          - name: api
+            image: node:20
+"#;
+        let mut provider = Pulumi::new(Language::Auto).unwrap();
 
-        let output = build_image_for_serialization(&images, container).unwrap();
+        let output = provider.deserialize_value(input).unwrap();
 
-        let expected = DockerImageForPulumi {
-            name: None,
-            path: Some("./node-app".to_string()),
-            is_context: true,
-        };
+        assert_eq!(
+            Some(vec![ContainerAppConfiguration {
+                image: Some("node:20".to_string()),
+                restart: Some("unless-stopped".to_string()),
+                ..ContainerAppConfiguration::new("api")
+            }]),
+            output.resources
+        );
+        assert!(matches!(
+            provider.deserialize_value("Hello world"),
+            Err(DeserializeError::UndetectedLanguage(_))
+        ));
+    }
 
-        assert_eq!(expected, output);
+    fn write_project(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let directory =
+            std::env::temp_dir().join(format!("capp_s_{}_{}", name, std::process::id()));
 
-        // Container with a reference to an non-existing image
-        let container = ContainerBluePrint {
-            image: "${referenceDoNotMatch.name}".to_string(),
-            name: "myapp".to_string(),
-        };
-        let images = vec![ContainerImageBluePrint {
-            name: Some("myImage".to_string()),
-            build: BuildContextBluePrint {
-                context: "${pulumi.cwd}/node-app".to_string(),
-            },
-            reference_name: Some("myImage".to_string()),
-        }];
+        for (file, content) in files {
+            let path = directory.join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, content).unwrap();
+        }
 
-        let output = build_image_for_serialization(&images, container);
+        directory
+    }
 
-        assert_eq!(None, output);
+    const NODEJS_PROGRAM: &str = r#"
+import * as app from "@pulumi/azure-native/app";
 
-        // Container with a remote image without context
-        let container = ContainerBluePrint {
-            image: "node-12".to_string(),
-            name: "myapp".to_string(),
-        };
-        let images = vec![ContainerImageBluePrint {
-            name: Some("myImage".to_string()),
-            build: BuildContextBluePrint {
-                context: "${pulumi.cwd}/node-app".to_string(),
-            },
-            reference_name: Some("myImage".to_string()),
-        }];
+new app.ContainerApp("api", {
+    template: { containers: [{ name: "api", image: "node:20" }] },
+});
+"#;
 
-        let output = build_image_for_serialization(&images, container).unwrap();
+    const YAML_PROGRAM: &str = r#"
+resources:
+  api:
+    type: azure-native:app:ContainerApp
+    properties:
+      template:
+        containers:
+          - name: api
+            image: node:20
+"#;
 
-        let expected = DockerImageForPulumi {
-            name: Some("node-12".to_string()),
-            path: None,
-            is_context: false,
-        };
+    #[test]
+    fn test_find_project_entry() {
+        let nodejs = write_project(
+            "entry_nodejs",
+            &[
+                ("Pulumi.yaml", "name: shop\nruntime: nodejs\n"),
+                ("index.ts", NODEJS_PROGRAM),
+            ],
+        );
+        let compiled = write_project(
+            "entry_compiled",
+            &[
+                ("Pulumi.yaml", "name: shop\nruntime: nodejs\nmain: bin/\n"),
+                ("bin/index.js", NODEJS_PROGRAM),
+            ],
+        );
+        let package = write_project(
+            "entry_package",
+            &[
+                ("Pulumi.yaml", "name: shop\nruntime: nodejs\n"),
+                ("package.json", r#"{ "main": "src/main.ts" }"#),
+                ("src/main.ts", NODEJS_PROGRAM),
+            ],
+        );
+        let yaml = write_project(
+            "entry_yaml",
+            &[(
+                "Pulumi.yaml",
+                &format!("name: shop\nruntime: yaml\n{}", YAML_PROGRAM),
+            )],
+        );
+        let main_yaml = write_project(
+            "entry_main_yaml",
+            &[
+                ("Pulumi.yaml", "name: shop\nruntime: yaml\n"),
+                ("Main.yaml", YAML_PROGRAM),
+            ],
+        );
+        let missing = write_project(
+            "entry_missing",
+            &[("Pulumi.yaml", "name: shop\nruntime: yaml\nmain: infra/\n")],
+        );
 
-        assert_eq!(expected, output);
+        assert_eq!(
+            Ok((Language::Typescript, nodejs.join("index.ts"))),
+            find_project_entry(&nodejs)
+        );
+        assert_eq!(
+            Ok((Language::Javascript, compiled.join("bin/").join("index.js"))),
+            find_project_entry(&compiled)
+        );
+        assert_eq!(
+            Ok((Language::Typescript, package.join("src/main.ts"))),
+            find_project_entry(&package)
+        );
+        assert_eq!(
+            Ok((Language::Yaml, yaml.join("Pulumi.yaml"))),
+            find_project_entry(&yaml)
+        );
+        assert_eq!(
+            Ok((Language::Yaml, main_yaml.join("Main.yaml"))),
+            find_project_entry(&main_yaml)
+        );
+        assert_eq!(
+            Err(DeserializeError::MissingFile(
+                missing
+                    .join("infra/")
+                    .join("Main.yaml")
+                    .display()
+                    .to_string()
+            )),
+            find_project_entry(&missing)
+        );
+        assert_eq!(
+            Err(DeserializeError::MissingFile(
+                missing
+                    .join("none")
+                    .join("Pulumi.yaml")
+                    .display()
+                    .to_string()
+            )),
+            find_project_entry(&missing.join("none"))
+        );
+
+        for directory in [nodejs, compiled, package, yaml, main_yaml, missing] {
+            std::fs::remove_dir_all(directory).unwrap();
+        }
     }
 
+    #[cfg(all(feature = "yaml", feature = "js"))]
     #[test]
-    fn test_build_ports_mapping_for_serialization() {
-        // Assert that None dapr and ingress generate None ports
-        let container = ContainerBluePrint {
-            image: "${myImage.name}".to_string(),
-            name: "some-app".to_string(),
-        };
+    fn test_from_project_dir() {
+        let nodejs = write_project(
+            "project_nodejs",
+            &[
+                ("Pulumi.yaml", "name: shop\nruntime: nodejs\n"),
+                ("index.ts", NODEJS_PROGRAM),
+            ],
+        );
+        let yaml = write_project(
+            "project_yaml",
+            &[
+                ("Pulumi.yaml", "name: shop\nruntime: yaml\n"),
+                ("Main.yaml", YAML_PROGRAM),
+            ],
+        );
+        let expected = Some(vec![ContainerAppConfiguration {
+            image: Some("node:20".to_string()),
+            restart: Some("unless-stopped".to_string()),
+            ..ContainerAppConfiguration::new("api")
+        }]);
 
-        let dapr_configuration = None;
-        let ingress_configuration = None;
+        for directory in [nodejs, yaml] {
+            let output = Pulumi::from_project_dir(&directory, BuildOptions::default()).unwrap();
 
-        let configuration = AppConfiguration {
-            container,
-            dapr_configuration,
-            ingress_configuration,
-        };
+            assert_eq!(expected, output.resources);
+            std::fs::remove_dir_all(directory).unwrap();
+        }
+    }
 
-        let (dapr_app_port, ports) = build_ports_mapping_for_serialization(configuration);
+    #[test]
+    fn test_get_stack_references() {
+        let expected = BTreeMap::from([("infra".to_string(), "org/infra/dev".to_string())]);
 
-        assert_eq!(dapr_app_port, None);
-        assert_eq!(ports, None);
+        let yaml = r#"
+resources:
+  infra:
+    type: pulumi:pulumi:StackReference
+    properties:
+      name: org/infra/dev
+"#;
+        assert_eq!(expected, get_stack_references(yaml, Language::Yaml));
 
-        // Assert that dapr.enabled:false generate None ports
-        let container = ContainerBluePrint {
-            image: "${myImage.name}".to_string(),
-            name: "some-app".to_string(),
-        };
+        let python = r#"infra = pulumi.StackReference("org/infra/dev")"#;
+        assert_eq!(expected, get_stack_references(python, Language::Python));
 
-        let dapr_configuration = Some(DaprBluePrint {
-            app_port: Some(80),
-            enabled: Some(false),
-            app_id: Some("t".to_string()),
-        });
-        let ingress_configuration = None;
+        let csharp = r#"var infra = new StackReference("org/infra/dev");"#;
+        assert_eq!(expected, get_stack_references(csharp, Language::CSharp));
 
-        let configuration = AppConfiguration {
-            container,
-            dapr_configuration,
-            ingress_configuration,
-        };
+        let go = r#"infra, err := pulumi.NewStackReference(ctx, "org/infra/dev", nil)"#;
+        assert_eq!(expected, get_stack_references(go, Language::Go));
 
-        let (dapr_app_port, ports) = build_ports_mapping_for_serialization(configuration);
+        // Stack outputs files keep the values which are not strings as JSON
+        let output = parse_stack_outputs(r#"{"apiImage": "api:v1", "replicas": 2}"#).unwrap();
+        assert_eq!(
+            BTreeMap::from([
+                ("apiImage".to_string(), "api:v1".to_string()),
+                ("replicas".to_string(), "2".to_string()),
+            ]),
+            output
+        );
+    }
 
-        assert_eq!(dapr_app_port, Some(80));
-        assert_eq!(ports, None);
+    #[test]
+    fn test_build_configuration_with_secrets() {
+        let app: ContainerAppBluePrint = serde_yaml::from_str(
+            r#"
+            configuration:
+              secrets:
+                - name: db-password
+                  value: s3cr3t
+            template:
+              containers:
+                - image: node-12
+                  name: myapp
+                  env:
+                    - name: DB_PASSWORD
+                      secretRef: db-password
+            "#,
+        )
+        .unwrap();
 
-        //TODO
-        // Assert that dapr.enabled:true without ingress generate None ports
-        let container = ContainerBluePrint {
-            image: "${myImage.name}".to_string(),
-            name: "some-app".to_string(),
-        };
+        // Variables of the host listed in the .env.example file
+        let output =
+            build_configuration(vec![app.clone()], vec![], None, &BuildOptions::default()).unwrap();
+        let compose: serde_yaml::Value =
+            serde_yaml::from_str(&crate::serializer::to_compose_yaml(&output).unwrap()).unwrap();
 
-        let dapr_configuration = Some(DaprBluePrint {
-            app_port: Some(80),
-            enabled: Some(true),
-            app_id: Some("t".to_string()),
-        });
-        let ingress_configuration = None;
+        assert_eq!(
+            "DB_PASSWORD=${DB_PASSWORD}",
+            compose["services"]["myapp"]["environment"][0]
+                .as_str()
+                .unwrap()
+        );
+        assert!(compose.get("secrets").is_none());
+        assert_eq!("DB_PASSWORD=\n", crate::serializer::to_env_example(&output));
 
-        let configuration = AppConfiguration {
-            container,
-            dapr_configuration,
-            ingress_configuration,
+        // Compose secrets read from files
+        let options = BuildOptions {
+            secrets: SecretsMode::Files,
+            ..Default::default()
         };
+        let output = build_configuration(vec![app], vec![], None, &options).unwrap();
+        let compose: serde_yaml::Value =
+            serde_yaml::from_str(&crate::serializer::to_compose_yaml(&output).unwrap()).unwrap();
 
-        let (dapr_app_port, ports) = build_ports_mapping_for_serialization(configuration);
+        assert_eq!(
+            "DB_PASSWORD_FILE=/run/secrets/db-password",
+            compose["services"]["myapp"]["environment"][0]
+                .as_str()
+                .unwrap()
+        );
+        let mount = &compose["services"]["myapp"]["secrets"][0];
+        assert_eq!("myapp_db-password", mount["source"].as_str().unwrap());
+        assert_eq!("db-password", mount["target"].as_str().unwrap());
+        assert_eq!(
+            "./secrets/myapp/db-password",
+            compose["secrets"]["myapp_db-password"]["file"]
+                .as_str()
+                .unwrap()
+        );
+        assert_eq!("", crate::serializer::to_env_example(&output));
+        // Values are never written
+        assert!(!crate::serializer::to_compose_yaml(&output)
+            .unwrap()
+            .contains("s3cr3t"));
+    }
 
-        assert_eq!(dapr_app_port, Some(80));
-        assert_eq!(ports, None);
+    #[test]
+    fn test_build_configuration_with_volumes() {
+        let mut app: ContainerAppBluePrint = serde_yaml::from_str(
+            r#"
+            template:
+              volumes:
+                - name: data
+                - name: share
+                  storageType: AzureFile
+                  storageName: uploads
+                - name: certificates
+                  storageType: Secret
+              containers:
+                - image: node-12
+                  name: api
+                  volumeMounts:
+                    - volumeName: data
+                      mountPath: /data
+                    - volumeName: share
+                      mountPath: /uploads
+                      subPath: images
+                - image: busybox
+                  name: worker
+                  volumeMounts:
+                    - volumeName: data
+                      mountPath: /input
+                      readOnly: true
+                    - volumeName: certificates
+                      mountPath: /certs
+            "#,
+        )
+        .unwrap();
+        app.name = Some("myapp".to_string());
 
-        // Assert that dapr.enabled:true with ingress generate None ports if app_id doesn't match with existing container
-        let container = ContainerBluePrint {
-            image: "${myImage.name}".to_string(),
-            name: "t".to_string(),
-        };
+        // The volume of the template is shared by its containers
+        let output =
+            build_configuration(vec![app.clone()], vec![], None, &BuildOptions::default()).unwrap();
+        let compose: serde_yaml::Value =
+            serde_yaml::from_str(&crate::serializer::to_compose_yaml(&output).unwrap()).unwrap();
 
-        let dapr_configuration = Some(DaprBluePrint {
-            app_port: Some(80),
-            enabled: Some(true),
-            app_id: Some("some-app".to_string()),
-        });
-        let ingress_configuration = Some(IngressBluePrint {
-            external: Some(true),
-            target_port: Some(3000),
-        });
+        let data =
+            |service: &str, index: usize| compose["services"][service]["volumes"][index].clone();
+        assert_eq!("volume", data("api", 0)["type"].as_str().unwrap());
+        assert_eq!("myapp_data", data("api", 0)["source"].as_str().unwrap());
+        assert_eq!("/data", data("api", 0)["target"].as_str().unwrap());
+        assert_eq!("uploads", data("api", 1)["source"].as_str().unwrap());
+        assert_eq!(
+            "images",
+            data("api", 1)["volume"]["subpath"].as_str().unwrap()
+        );
+        assert_eq!("myapp_data", data("worker", 0)["source"].as_str().unwrap());
+        assert_eq!("/input", data("worker", 0)["target"].as_str().unwrap());
+        assert_eq!(Some(true), data("worker", 0)["read_only"].as_bool());
+        // Secret volumes are not mounted
+        assert_eq!(
+            1,
+            compose["services"]["worker"]["volumes"]
+                .as_sequence()
+                .unwrap()
+                .len()
+        );
 
-        let configuration = AppConfiguration {
-            container,
-            dapr_configuration,
-            ingress_configuration,
+        let volumes = compose["volumes"].as_mapping().unwrap();
+        assert_eq!(2, volumes.len());
+        assert!(volumes.contains_key("myapp_data"));
+        assert!(volumes.contains_key("uploads"));
+
+        // Shares as host folders
+        let options = BuildOptions {
+            azure_files: AzureFilesMode::Bind,
+            ..Default::default()
         };
+        let output = build_configuration(vec![app.clone()], vec![], None, &options).unwrap();
 
-        let (dapr_app_port, ports) = build_ports_mapping_for_serialization(configuration);
+        assert_eq!(
+            Some(&ServiceVolume {
+                volume_type: "bind".to_string(),
+                source: "./volumes/uploads/images".to_string(),
+                target: "/uploads".to_string(),
+                read_only: None,
+                volume: None,
+                share: None,
+            }),
+            output[0].volumes.as_ref().unwrap().get(1)
+        );
 
-        assert_eq!(dapr_app_port, Some(80));
-        assert_eq!(ports, None);
+        assert_eq!(
+            vec!["ignored volume certificates of myapp, Secret volumes are not supported"],
+            collect_volume_warnings(&[app])
+        );
+    }
 
-        // Assert that dapr.enabled:true with ingress generate ports if app_id match with existing container
-        let container = ContainerBluePrint {
-            image: "${myImage.name}".to_string(),
-            name: "some-app".to_string(),
+    #[test]
+    fn test_build_healthcheck_for_serialization() {
+        let probe = |probe_type: &str| ProbeBluePrint {
+            probe_type: Some(probe_type.to_string()),
+            http_get: None,
+            tcp_socket: None,
+            exec: None,
+            initial_delay_seconds: Some(15),
+            period_seconds: Some(20),
+            timeout_seconds: Some(2),
+            failure_threshold: Some(5),
         };
-
-        let dapr_configuration = Some(DaprBluePrint {
-            app_port: Some(80),
-            enabled: Some(true),
-            app_id: Some("some-app".to_string()),
-        });
-        let ingress_configuration = Some(IngressBluePrint {
-            external: Some(true),
-            target_port: Some(3000),
-        });
-
-        let configuration = AppConfiguration {
-            container,
-            dapr_configuration,
-            ingress_configuration,
+        let healthcheck = |container: ContainerBluePrint| {
+            build_healthcheck_for_serialization(&container).map(|healthcheck| {
+                (
+                    healthcheck.test,
+                    healthcheck.interval,
+                    healthcheck.timeout,
+                    healthcheck.retries,
+                    healthcheck.start_period,
+                )
+            })
         };
 
-        let (dapr_app_port, ports) = build_ports_mapping_for_serialization(configuration);
+        // httpGet liveness probe
+        let container = ContainerBluePrint {
+            probes: Some(vec![ProbeBluePrint {
+                http_get: Some(HttpGetProbeBluePrint {
+                    path: Some("/healthz".to_string()),
+                    port: Some(8080),
+                }),
+                ..probe("Liveness")
+            }]),
+            ..ContainerBluePrint::new("myapp", "node-12")
+        };
+        assert_eq!(
+            Some((
+                vec![
+                    "CMD-SHELL".to_string(),
+                    "curl -f http://localhost:8080/healthz || exit 1".to_string()
+                ],
+                Some("20s".to_string()),
+                Some("2s".to_string()),
+                Some(5),
+                Some("15s".to_string())
+            )),
+            healthcheck(container)
+        );
 
-        assert_eq!(dapr_app_port, Some(80));
-        assert_eq!(ports, Some(vec!["3000:80".to_string()]));
+        // tcpSocket readiness probe, used without liveness probe
+        let container = ContainerBluePrint {
+            probes: Some(vec![
+                probe("Startup"),
+                ProbeBluePrint {
+                    tcp_socket: Some(TcpSocketProbeBluePrint { port: Some(5432) }),
+                    ..probe("Readiness")
+                },
+            ]),
+            ..ContainerBluePrint::new("myapp", "node-12")
+        };
+        assert_eq!(
+            vec![
+                "CMD-SHELL".to_string(),
+                "nc -z localhost 5432 || exit 1".to_string()
+            ],
+            healthcheck(container).unwrap().0
+        );
 
-        // Assert that dapr.enabled:false with ingress.enabled:true  generate  Ingress ports
+        // exec liveness probe wins over the readiness one
         let container = ContainerBluePrint {
-            image: "${myImage.name}".to_string(),
-            name: "some-app".to_string(),
+            probes: Some(vec![
+                ProbeBluePrint {
+                    tcp_socket: Some(TcpSocketProbeBluePrint { port: Some(5432) }),
+                    ..probe("Readiness")
+                },
+                ProbeBluePrint {
+                    exec: Some(ExecProbeBluePrint {
+                        command: vec![
+                            "pg_isready".to_string(),
+                            "-U".to_string(),
+                            "app".to_string(),
+                        ],
+                    }),
+                    ..probe("Liveness")
+                },
+            ]),
+            ..ContainerBluePrint::new("myapp", "node-12")
         };
+        assert_eq!(
+            vec![
+                "CMD".to_string(),
+                "pg_isready".to_string(),
+                "-U".to_string(),
+                "app".to_string()
+            ],
+            healthcheck(container).unwrap().0
+        );
 
-        let dapr_configuration = Some(DaprBluePrint {
-            app_port: Some(80),
-            enabled: Some(false),
-            app_id: Some("t".to_string()),
-        });
-        let ingress_configuration = Some(IngressBluePrint {
-            external: Some(true),
-            target_port: Some(3000),
-        });
+        // Probes without check
+        let container = ContainerBluePrint {
+            probes: Some(vec![probe("Liveness"), probe("Startup")]),
+            ..ContainerBluePrint::new("myapp", "node-12")
+        };
+        assert_eq!(None, healthcheck(container));
+    }
 
-        let configuration = AppConfiguration {
-            container,
-            dapr_configuration,
-            ingress_configuration,
+    #[test]
+    fn test_build_deploy_for_serialization() {
+        let deploy = |cpu: Option<f64>, memory: Option<&str>| {
+            build_deploy_for_serialization(
+                &ContainerBluePrint {
+                    resources: Some(ContainerResourcesBluePrint {
+                        cpu,
+                        memory: memory.map(|x| x.to_string()),
+                    }),
+                    ..ContainerBluePrint::new("myapp", "node-12")
+                },
+                None,
+            )
+            .and_then(|deploy| deploy.resources)
+            .map(|resources| (resources.limits.cpus, resources.limits.memory))
         };
 
-        let (dapr_app_port, ports) = build_ports_mapping_for_serialization(configuration);
+        assert_eq!(
+            Some((Some("0.5".to_string()), Some("1g".to_string()))),
+            deploy(Some(0.5), Some("1Gi"))
+        );
+        assert_eq!(
+            Some((Some("1.75".to_string()), Some("3.5g".to_string()))),
+            deploy(Some(1.75), Some("3.5Gi"))
+        );
+        assert_eq!(
+            Some((None, Some("512m".to_string()))),
+            deploy(None, Some("512Mi"))
+        );
+        assert_eq!(None, deploy(None, None));
+        assert_eq!(
+            None,
+            build_deploy_for_serialization(&ContainerBluePrint::new("myapp", "node-12"), None)
+        );
+    }
 
-        assert_eq!(dapr_app_port, Some(80));
-        assert_eq!(ports, Some(vec!["3000:3000".to_string()]));
+    #[test]
+    fn test_collect_resource_warnings() {
+        let app = |resources: Vec<(f64, &str)>| ContainerAppBluePrint {
+            name: Some("myapp".to_string()),
+            template: Some(TemplateBluePrint::new(
+                resources
+                    .into_iter()
+                    .enumerate()
+                    .map(|(index, (cpu, memory))| ContainerBluePrint {
+                        resources: Some(ContainerResourcesBluePrint {
+                            cpu: Some(cpu),
+                            memory: Some(memory.to_string()),
+                        }),
+                        ..ContainerBluePrint::new(format!("container{}", index), "node-12")
+                    })
+                    .collect(),
+            )),
+            ..ContainerAppBluePrint::default()
+        };
+
+        assert_eq!(
+            Vec::<String>::new(),
+            collect_resource_warnings(&[app(vec![(0.25, "0.5Gi"), (4.0, "8Gi"), (1.0, "2048Mi")])])
+        );
+        assert_eq!(
+            vec![
+                "cpu 0.1 of container0 is out of the allowed range (0.25 to 4)",
+                "memory 256Mi of container0 is out of the allowed range (0.5Gi to 8Gi)",
+                "memory 16Gi of container1 is out of the allowed range (0.5Gi to 8Gi)",
+                "invalid memory 1Tb of container2",
+            ],
+            collect_resource_warnings(&[app(vec![(0.1, "256Mi"), (2.0, "16Gi"), (1.0, "1Tb")])])
+        );
     }
 
     #[test]
-    fn test_parse_app_configuration() {
-        let configuration = AppConfiguration {
-            container: ContainerBluePrint {
-                image: "${myImage.name}".to_string(),
-                name: "myapp".to_string(),
-            },
-            dapr_configuration: Some(DaprBluePrint {
-                app_port: Some(3000),
-                enabled: Some(true),
-                app_id: Some("myapp".to_string()),
-            }),
-            ingress_configuration: Some(IngressBluePrint {
-                external: Some(true),
-                target_port: Some(80),
-            }),
+    fn test_build_configuration_with_min_replicas() {
+        let app = |min_replicas: u32, dapr: bool| {
+            let mut app: ContainerAppBluePrint = serde_yaml::from_str(&format!(
+                r#"
+                configuration:
+                  ingress:
+                    targetPort: 80
+                  dapr:
+                    enabled: {}
+                    appPort: 80
+                template:
+                  containers:
+                    - image: node-12
+                      name: myapp
+                  scale:
+                    minReplicas: {}
+                    maxReplicas: 10
+                "#,
+                dapr, min_replicas
+            ))
+            .unwrap();
+            app.name = Some("myapp".to_string());
+            normalize_scale(&mut app);
+            app
+        };
+        let replicas = |app: ContainerAppBluePrint| {
+            build_configuration(vec![app], vec![], None, &BuildOptions::default())
+                .unwrap()
+                .into_iter()
+                .map(|service| {
+                    (
+                        service.name,
+                        service.deploy.and_then(|deploy| deploy.replicas),
+                    )
+                })
+                .collect::<Vec<_>>()
         };
 
-        let images = vec![ContainerImageBluePrint {
-            name: Some("${registry.loginServer}/node-app:v1.0.0".to_string()),
-            build: BuildContextBluePrint {
-                context: "${pulumi.cwd}/node-app".to_string(),
-            },
-            reference_name: Some("myImage".to_string()),
-        }];
+        assert_eq!(vec![("myapp".to_string(), None)], replicas(app(0, false)));
+        assert_eq!(
+            vec!["scale to zero of myapp cannot be modeled, a single instance is run"],
+            collect_scale_warnings(&[app(0, false)])
+        );
 
-        let output = parse_app_configuration(&images, configuration);
+        assert_eq!(vec![("myapp".to_string(), None)], replicas(app(1, false)));
+        assert_eq!(
+            Vec::<String>::new(),
+            collect_scale_warnings(&[app(1, false)])
+        );
 
-        let expected = vec![
-            ContainerAppConfiguration {
-                image: None,
-                build: Some(BuildContext {
-                    context: "./node-app".to_string(),
-                }),
-                name: "myapp".to_string(),
-                depends_on: Some(vec!["placement".to_string()]),
-                networks: Some(vec![String::from("dapr-network")]),
-                network_mode: None,
-                environment: None,
-                ports: Some(vec!["80:3000".to_string()]),
-                command: None,
-            },
-            ContainerAppConfiguration {
-                image: Some(String::from("daprio/daprd:edge")),
-                name: format!("myapp_dapr"),
-                depends_on: Some(vec![String::from("myapp")]),
-                network_mode: Some(format!("service:{}", String::from("myapp"))),
-                environment: None,
-                ports: None,
-                networks: None,
-                build: None,
-                command: Some(vec![
-                    "./daprd".to_string(),
-                    "-app-id".to_string(),
-                    String::from("myapp"),
-                    "-app-port".to_string(),
-                    "3000".to_string(),
-                    "-placement-host-address".to_string(),
-                    "placement:50006".to_string(),
-                    "air".to_string(),
-                ]),
-            },
+        assert_eq!(
+            vec![("myapp".to_string(), Some(3))],
+            replicas(app(3, false))
+        );
+        assert_eq!(
+            Vec::<String>::new(),
+            collect_scale_warnings(&[app(3, false)])
+        );
+
+        // The Dapr sidecar shares the network of a single instance
+        assert_eq!(
+            vec![
+                ("myapp".to_string(), None),
+                ("myapp_dapr".to_string(), None)
+            ],
+            replicas(app(3, true))
+                .into_iter()
+                .filter(|(name, _)| name != "placement")
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec!["3 replicas of myapp are not run, its Dapr sidecar cannot be replicated"],
+            collect_scale_warnings(&[app(3, true)])
+        );
+
+        let output =
+            build_configuration(vec![app(3, false)], vec![], None, &BuildOptions::default())
+                .unwrap();
+        assert_eq!(
+            "replicas: 3\n",
+            serde_yaml::to_string(&output[0].deploy).unwrap()
+        );
+
+        // Replicas of an exposed app only publish the container port
+        let mut exposed = app(2, false);
+        if let Some(ingress) = exposed
+            .configuration
+            .as_mut()
+            .and_then(|configuration| configuration.ingress.as_mut())
+        {
+            ingress.external = Some(true);
+        }
+
+        let output = build_configuration(
+            vec![exposed.clone()],
+            vec![],
+            None,
+            &BuildOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(Some(vec!["80".to_string()]), output[0].ports);
+        assert_eq!(
+            Some(2),
+            output[0].deploy.as_ref().and_then(|deploy| deploy.replicas)
+        );
+        assert_eq!(
+            vec!["host ports of myapp are not published, its 2 replicas cannot bind the same port"],
+            collect_scale_warnings(&[exposed])
+        );
+    }
+
+    #[test]
+    fn test_unpublish_host_ports() {
+        let ports = vec![
+            "80:80".to_string(),
+            "9091:9090".to_string(),
+            "8080:80".to_string(),
         ];
 
-        assert_eq!(Some(expected), output);
+        assert_eq!(
+            vec!["80".to_string(), "9090".to_string()],
+            unpublish_host_ports(&ports)
+        );
+    }
 
-        let configuration = AppConfiguration {
-            container: ContainerBluePrint {
-                image: "node-12".to_string(),
-                name: "myapp".to_string(),
-            },
-            dapr_configuration: Some(DaprBluePrint {
-                app_port: Some(3000),
-                enabled: Some(false),
-                app_id: Some("myapp".to_string()),
-            }),
-            ingress_configuration: Some(IngressBluePrint {
-                external: Some(false),
-                target_port: Some(80),
-            }),
+    #[test]
+    fn test_build_configuration_with_shared_declarations() {
+        let app = |name: &str, volume: &str| {
+            let mut app: ContainerAppBluePrint = serde_yaml::from_str(&format!(
+                r#"
+                configuration:
+                  ingress:
+                    targetPort: 80
+                  dapr:
+                    enabled: true
+                    appPort: 80
+                template:
+                  volumes:
+                    {}
+                  containers:
+                    - image: node-12
+                      name: {}
+                      volumeMounts:
+                        - volumeName: data
+                          mountPath: /data
+                "#,
+                volume, name
+            ))
+            .unwrap();
+            app.name = Some(name.to_string());
+            app
         };
+        let share = "- name: data\n                      storageType: AzureFile\n                      storageName: uploads";
 
-        let images = vec![ContainerImageBluePrint {
-            name: Some("${registry.loginServer}/node-app:v1.0.0".to_string()),
-            build: BuildContextBluePrint {
-                context: "${pulumi.cwd}/node-app".to_string(),
-            },
-            reference_name: Some("myImage".to_string()),
-        }];
-
-        let output = parse_app_configuration(&images, configuration);
+        // Both apps mount the same share and join the Dapr network
+        let output = build_configuration(
+            vec![app("frontend", share), app("backend", share)],
+            vec![],
+            None,
+            &BuildOptions::default(),
+        )
+        .unwrap();
+        let compose: serde_yaml::Value =
+            serde_yaml::from_str(&crate::serializer::to_compose_yaml(&output).unwrap()).unwrap();
 
-        let expected = vec![ContainerAppConfiguration {
-            image: Some("node-12".to_string()),
-            build: None,
-            name: "myapp".to_string(),
-            depends_on: None,
-            networks: None,
-            network_mode: None,
-            environment: None,
-            ports: None,
-            command: None,
-        }];
+        let networks = compose["networks"].as_mapping().unwrap();
+        assert_eq!(1, networks.len());
+        assert!(networks.contains_key("dapr-network"));
+        let volumes = compose["volumes"].as_mapping().unwrap();
+        assert_eq!(1, volumes.len());
+        assert_eq!(
+            "uploads",
+            compose["volumes"]["uploads"]["labels"]["capp.share"]
+                .as_str()
+                .unwrap()
+        );
 
-        assert_eq!(Some(expected), output);
+        // The scratch volume of an app is named as the share of another one
+        let output = build_configuration(
+            vec![
+                app("uploads", "- name: data"),
+                app(
+                    "backend",
+                    &share.replace("storageName: uploads", "storageName: uploads_data"),
+                ),
+            ],
+            vec![],
+            None,
+            &BuildOptions::default(),
+        );
+        assert_eq!(
+            Err(DeserializeError::ConflictingDeclaration(
+                "uploads_data".to_string()
+            )),
+            output
+        );
     }
 }
@@ -0,0 +1,779 @@
+use regex::Regex;
+use serde_json::{Map, Number, Value};
+
+use crate::error::DeserializeError;
+use crate::pulumi;
+use crate::serializer::{
+    BuildOptions, ContainerAppBluePrint, ContainerAppConfiguration, ContainerImageBluePrint,
+};
+
+/***
+ * Keyword arguments are snake_case, the blueprint properties camelCase (eg: target_port)
+ */
+fn to_camel_case(name: &str) -> String {
+    let mut output = String::new();
+    let mut is_upper = false;
+
+    for c in name.chars() {
+        match c {
+            '_' => is_upper = !output.is_empty(),
+            c if is_upper => {
+                output.extend(c.to_uppercase());
+                is_upper = false;
+            }
+            c => output.push(c),
+        }
+    }
+
+    output
+}
+
+/***
+ * Python expressions of the resource arguments as JSON values.
+ * `*Args` classes and dicts are objects, property accesses are `${...}` references
+ * and calls which cannot be evaluated (eg: output.apply(lambda ...)) are null
+ */
+struct Parser<'a> {
+    chars: Vec<char>,
+    position: usize,
+    constants: &'a Map<String, Value>,
+}
+
+impl<'a> Parser<'a> {
+    fn new(input: &str, position: usize, constants: &'a Map<String, Value>) -> Parser<'a> {
+        Parser {
+            chars: input.chars().collect(),
+            position,
+            constants,
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.position).copied()
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<char> {
+        self.chars.get(self.position + offset).copied()
+    }
+
+    fn line(&self) -> usize {
+        self.chars[..self.position.min(self.chars.len())]
+            .iter()
+            .filter(|c| **c == '\n')
+            .count()
+            + 1
+    }
+
+    fn error(&self, message: &str) -> String {
+        format!("{} at line {}", message, self.line())
+    }
+
+    fn skip_blanks(&mut self) {
+        while let Some(c) = self.peek() {
+            match c {
+                '#' => {
+                    while !matches!(self.peek(), Some('\n') | None) {
+                        self.position += 1;
+                    }
+                }
+                c if c.is_whitespace() || c == '\\' => self.position += 1,
+                _ => break,
+            }
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        self.skip_blanks();
+
+        match self.peek() {
+            Some(c) if c == expected => {
+                self.position += 1;
+                Ok(())
+            }
+            Some(c) => Err(self.error(&format!("Expected `{}`, found `{}`", expected, c))),
+            None => Err(self.error(&format!("Expected `{}`", expected))),
+        }
+    }
+
+    fn parse_identifier(&mut self) -> String {
+        let mut identifier = String::new();
+
+        while let Some(c) = self.peek().filter(|c| c.is_alphanumeric() || *c == '_') {
+            identifier.push(c);
+            self.position += 1;
+        }
+
+        identifier
+    }
+
+    fn parse_value(&mut self) -> Result<Value, String> {
+        self.skip_blanks();
+
+        let value = match self.peek() {
+            Some('{') => self.parse_dict()?,
+            Some('[') => Value::Array(self.parse_sequence('[', ']')?),
+            // Parenthesized expressions and tuples
+            Some('(') => {
+                let mut items = self.parse_sequence('(', ')')?;
+
+                match items.len() {
+                    1 => items.remove(0),
+                    _ => Value::Array(items),
+                }
+            }
+            Some('"' | '\'') => Value::String(self.parse_string(false)?),
+            Some(c) if c.is_ascii_digit() || c == '-' => self.parse_number()?,
+            Some(c) if c.is_alphabetic() || c == '_' => self.parse_name()?,
+            Some(c) => return Err(self.error(&format!("Unexpected `{}`", c))),
+            None => return Err(self.error("Unexpected end of input")),
+        };
+
+        // String concatenations (eg: prefix + "-app")
+        self.skip_blanks();
+        if self.peek() == Some('+') {
+            self.position += 1;
+
+            return match (value, self.parse_value()?) {
+                (Value::String(left), Value::String(right)) => Ok(Value::String(left + &right)),
+                _ => Ok(Value::Null),
+            };
+        }
+
+        Ok(value)
+    }
+
+    fn parse_sequence(&mut self, open: char, close: char) -> Result<Vec<Value>, String> {
+        let mut items = vec![];
+        self.expect(open)?;
+
+        loop {
+            self.skip_blanks();
+            if self.peek() == Some(close) {
+                self.position += 1;
+                return Ok(items);
+            }
+
+            items.push(self.parse_value()?);
+
+            self.skip_blanks();
+            match self.peek() {
+                Some(',') => self.position += 1,
+                Some(c) if c == close => {}
+                _ => return Err(self.error(&format!("Expected `,` or `{}`", close))),
+            }
+        }
+    }
+
+    fn parse_dict(&mut self) -> Result<Value, String> {
+        let mut fields = Map::new();
+        self.expect('{')?;
+
+        loop {
+            self.skip_blanks();
+            if self.peek() == Some('}') {
+                self.position += 1;
+                return Ok(Value::Object(fields));
+            }
+
+            let key = match self.parse_value()? {
+                Value::String(key) => key,
+                key => key.to_string(),
+            };
+            self.expect(':')?;
+            let value = self.parse_value()?;
+            fields.insert(to_camel_case(&key), value);
+
+            self.skip_blanks();
+            match self.peek() {
+                Some(',') => self.position += 1,
+                Some('}') => {}
+                _ => return Err(self.error("Expected `,` or `}`")),
+            }
+        }
+    }
+
+    /// Positional and keyword arguments of a call, from its opening parenthesis
+    fn parse_arguments(&mut self) -> Result<(Vec<Value>, Map<String, Value>), String> {
+        let mut positional = vec![];
+        let mut keywords = Map::new();
+        self.expect('(')?;
+
+        loop {
+            self.skip_blanks();
+            match self.peek() {
+                Some(')') => {
+                    self.position += 1;
+                    return Ok((positional, keywords));
+                }
+                // Unpacked arguments (eg: **common_args) cannot be evaluated
+                Some('*') => {
+                    while self.peek() == Some('*') {
+                        self.position += 1;
+                    }
+                    self.parse_value()?;
+                }
+                _ => {
+                    let start = self.position;
+                    let identifier = self.parse_identifier();
+                    self.skip_blanks();
+
+                    if !identifier.is_empty()
+                        && self.peek() == Some('=')
+                        && self.peek_at(1) != Some('=')
+                    {
+                        self.position += 1;
+                        let value = self.parse_value()?;
+                        keywords.insert(to_camel_case(&identifier), value);
+                    } else {
+                        self.position = start;
+                        positional.push(self.parse_value()?);
+                    }
+                }
+            }
+
+            self.skip_blanks();
+            match self.peek() {
+                Some(',') => self.position += 1,
+                Some(')') => {}
+                _ => return Err(self.error("Expected `,` or `)`")),
+            }
+        }
+    }
+
+    /// Quoted strings, f-strings placeholders become `${...}` tokens
+    fn parse_string(&mut self, is_format: bool) -> Result<String, String> {
+        let quote = self.peek().ok_or_else(|| self.error("Expected a string"))?;
+        let is_triple = self.peek_at(1) == Some(quote) && self.peek_at(2) == Some(quote);
+        self.position += if is_triple { 3 } else { 1 };
+
+        let mut output = String::new();
+
+        loop {
+            match self.peek() {
+                None => return Err(self.error("Unterminated string")),
+                Some(c) if c == quote => {
+                    if !is_triple {
+                        self.position += 1;
+                        return Ok(output);
+                    }
+                    if self.peek_at(1) == Some(quote) && self.peek_at(2) == Some(quote) {
+                        self.position += 3;
+                        return Ok(output);
+                    }
+                    output.push(c);
+                    self.position += 1;
+                }
+                Some('\\') => {
+                    match self.peek_at(1) {
+                        Some('n') => output.push('\n'),
+                        Some('t') => output.push('\t'),
+                        Some(c) => output.push(c),
+                        None => return Err(self.error("Unterminated string")),
+                    }
+                    self.position += 2;
+                }
+                Some('{') if is_format && self.peek_at(1) == Some('{') => {
+                    output.push('{');
+                    self.position += 2;
+                }
+                Some('}') if is_format && self.peek_at(1) == Some('}') => {
+                    output.push('}');
+                    self.position += 2;
+                }
+                Some('{') if is_format => {
+                    let mut expression = String::new();
+                    self.position += 1;
+
+                    while let Some(c) = self.peek().filter(|c| *c != '}') {
+                        expression.push(c);
+                        self.position += 1;
+                    }
+                    self.position += 1;
+
+                    // Format specs are dropped (eg: {port:d})
+                    let expression = expression.split(':').next().unwrap_or_default().trim();
+                    output.push_str(&format!("${{{}}}", expression));
+                }
+                Some(c) => {
+                    output.push(c);
+                    self.position += 1;
+                }
+            }
+        }
+    }
+
+    fn parse_number(&mut self) -> Result<Value, String> {
+        let mut number = String::new();
+
+        while let Some(c) = self
+            .peek()
+            .filter(|c| c.is_ascii_digit() || matches!(c, '-' | '.' | '_' | 'e' | 'E'))
+        {
+            number.push(c);
+            self.position += 1;
+        }
+
+        let number = number.replace('_', "");
+
+        match number.parse::<i64>() {
+            Ok(number) => Ok(Value::Number(number.into())),
+            Err(_) => number
+                .parse::<f64>()
+                .ok()
+                .and_then(Number::from_f64)
+                .map(Value::Number)
+                .ok_or_else(|| self.error(&format!("Invalid number `{}`", number))),
+        }
+    }
+
+    fn skip_balanced(&mut self, open: char, close: char) -> Result<(), String> {
+        let mut depth = 0;
+
+        while let Some(c) = self.peek() {
+            match c {
+                '"' | '\'' => {
+                    self.parse_string(false)?;
+                    continue;
+                }
+                c if c == open => depth += 1,
+                c if c == close => {
+                    depth -= 1;
+                    if depth == 0 {
+                        self.position += 1;
+                        return Ok(());
+                    }
+                }
+                _ => {}
+            }
+            self.position += 1;
+        }
+
+        Err(self.error(&format!("Expected `{}`", close)))
+    }
+
+    /// Attributes and calls chained to a skipped call (eg: .apply(lambda name: name))
+    fn skip_chain(&mut self) -> Result<(), String> {
+        loop {
+            match self.peek() {
+                Some('.') => {
+                    self.position += 1;
+                    self.parse_identifier();
+                }
+                Some('(') => self.skip_balanced('(', ')')?,
+                Some('[') => self.skip_balanced('[', ']')?,
+                _ => return Ok(()),
+            }
+        }
+    }
+
+    fn parse_name(&mut self) -> Result<Value, String> {
+        let mut name = self.parse_identifier();
+
+        // Prefixed strings (eg: f"{registry.login_server}/app")
+        if matches!(self.peek(), Some('"' | '\''))
+            && name.len() <= 2
+            && name.chars().all(|c| "fFrRbBuU".contains(c))
+        {
+            return Ok(Value::String(
+                self.parse_string(name.to_lowercase().contains('f'))?,
+            ));
+        }
+
+        while self.peek() == Some('.')
+            && self
+                .peek_at(1)
+                .is_some_and(|c| c.is_alphabetic() || c == '_')
+        {
+            self.position += 1;
+            name.push('.');
+            name.push_str(&self.parse_identifier());
+        }
+
+        match name.as_str() {
+            "True" => return Ok(Value::Bool(true)),
+            "False" => return Ok(Value::Bool(false)),
+            "None" => return Ok(Value::Null),
+            _ => {}
+        }
+
+        match self.peek() {
+            Some('(')
+                if name == "dict" || name.ends_with("Args") || name.ends_with("DockerBuild") =>
+            {
+                let (_, keywords) = self.parse_arguments()?;
+
+                Ok(Value::Object(keywords))
+            }
+            // The image reference of the outputs, as for pulumi.all in Typescript
+            Some('(') if name == "pulumi.Output.all" => {
+                let (positional, _) = self.parse_arguments()?;
+                self.skip_chain()?;
+
+                let references: Vec<&str> = positional.iter().filter_map(|x| x.as_str()).collect();
+
+                Ok(references
+                    .iter()
+                    .find(|reference| reference.ends_with(".image_name}"))
+                    .or(references.first())
+                    .map(|reference| Value::String(reference.to_string()))
+                    .unwrap_or_default())
+            }
+            // Applied outputs keep the reference of the output (eg: image.image_name.apply(...))
+            Some('(') if name.contains('.') && name.ends_with(".apply") => {
+                self.skip_chain()?;
+
+                Ok(Value::String(format!(
+                    "${{{}}}",
+                    name.trim_end_matches(".apply")
+                )))
+            }
+            Some('(') => {
+                self.skip_chain()?;
+
+                Ok(Value::Null)
+            }
+            // Subscripts are kept as tokens (eg: stack_ref.outputs["imageName"])
+            Some('[') => {
+                let start = self.position;
+                self.skip_balanced('[', ']')?;
+                let subscript: String = self.chars[start..self.position].iter().collect();
+
+                Ok(Value::String(format!("${{{}{}}}", name, subscript)))
+            }
+            _ if name.contains('.') => Ok(Value::String(format!("${{{}}}", name))),
+            _ => Ok(self
+                .constants
+                .get(&name)
+                .cloned()
+                .unwrap_or_else(|| Value::String(format!("${{{}}}", name)))),
+        }
+    }
+}
+
+fn char_position(input: &str, byte_position: usize) -> usize {
+    input[..byte_position].chars().count()
+}
+
+/***
+ * Module level literals (eg: app_port = 3000), substituted where they are used
+ */
+fn get_constants(input: &str) -> Map<String, Value> {
+    let empty = Map::new();
+    let mut constants = Map::new();
+
+    for captures in Regex::new(r"(?m)^(?P<name>[A-Za-z_]\w*)[ \t]*=[ \t]*[^=\s]")
+        .unwrap()
+        .captures_iter(input)
+    {
+        let start = char_position(input, captures.get(0).unwrap().end() - 1);
+        let value = Parser::new(input, start, &empty).parse_value();
+
+        match value {
+            Ok(Value::String(value)) if !value.contains("${") => {
+                constants.insert(captures["name"].to_string(), Value::String(value));
+            }
+            Ok(value @ (Value::Number(_) | Value::Bool(_))) => {
+                constants.insert(captures["name"].to_string(), value);
+            }
+            _ => {}
+        }
+    }
+
+    constants
+}
+
+// Variable, resource name and keyword arguments of a resource
+type Declaration = (Option<String>, String, Map<String, Value>);
+
+/***
+ * Resources declared with the given constructor (eg: app.ContainerApp("myapp", ...))
+ */
+fn get_declarations(input: &str, constructor: &str) -> Result<Vec<Declaration>, DeserializeError> {
+    let constants = get_constants(input);
+    let re = Regex::new(&format!(
+        r"(?m)^[ \t]*(?:(?P<variable>[A-Za-z_]\w*)[ \t]*=[ \t]*)?(?:[A-Za-z_]\w*\.)*{}\(",
+        constructor
+    ))
+    .unwrap();
+
+    re.captures_iter(input)
+        .map(|captures| {
+            let start = char_position(input, captures.get(0).unwrap().end() - 1);
+            let mut parser = Parser::new(input, start, &constants);
+            let (positional, keywords) =
+                parser.parse_arguments().map_err(DeserializeError::Parse)?;
+            let variable = captures.name("variable").map(|x| x.as_str().to_string());
+            // The resource name is the first argument, or the `resource_name` one
+            let name = positional
+                .first()
+                .or(keywords.get("resourceName"))
+                .and_then(|name| name.as_str())
+                .map(|name| name.to_string())
+                .or(variable.clone())
+                .unwrap_or_default();
+
+            Ok((variable, name, keywords))
+        })
+        .collect()
+}
+
+fn get_images(input: &str) -> Result<Vec<ContainerImageBluePrint>, DeserializeError> {
+    let images = get_declarations(input, "Image")?
+        .into_iter()
+        .filter_map(|(variable, _, mut keywords)| {
+            if let Some(name) = keywords.remove("imageName") {
+                keywords.insert("name".to_string(), name);
+            }
+            // Short form of the build arguments (eg: build="./app")
+            if let Some(Value::String(context)) = keywords.get("build").cloned() {
+                keywords.insert(
+                    "build".to_string(),
+                    serde_json::json!({ "context": context }),
+                );
+            }
+
+            // Images without build context are remote ones, they are not referenced
+            let mut image: ContainerImageBluePrint =
+                serde_json::from_value(Value::Object(keywords)).ok()?;
+            image.reference_name = variable.clone();
+            image.name = image.name.or(variable);
+
+            Some(image)
+        })
+        .collect();
+
+    Ok(images)
+}
+
+fn get_app_properties(input: &str) -> Result<Vec<(String, Value)>, DeserializeError> {
+    Ok(get_declarations(input, "ContainerApp")?
+        .into_iter()
+        .map(|(_, name, keywords)| (name, Value::Object(keywords)))
+        .collect())
+}
+
+fn get_apps(input: &str) -> Result<Vec<ContainerAppBluePrint>, DeserializeError> {
+    get_app_properties(input)?
+        .into_iter()
+        .map(|(name, properties)| {
+            let mut app: ContainerAppBluePrint = serde_json::from_value(properties)
+                .map_err(|e| DeserializeError::Parse(e.to_string()))?;
+            app.name = Some(name);
+            pulumi::normalize_scale(&mut app);
+
+            Ok(app)
+        })
+        .collect()
+}
+
+fn as_yaml_properties(properties: &Value) -> Result<serde_yaml::Value, DeserializeError> {
+    serde_yaml::to_value(properties).map_err(|e| DeserializeError::Parse(e.to_string()))
+}
+
+/***
+ * Fields of the apps which are not translated, the input is expected to be valid
+ */
+pub fn collect_warnings(input: &str) -> Vec<String> {
+    get_app_properties(input)
+        .unwrap_or_default()
+        .into_iter()
+        .flat_map(|(name, properties)| {
+            let properties = as_yaml_properties(&properties).unwrap_or_default();
+
+            pulumi::collect_ignored_fields(&properties)
+                .unwrap_or_default()
+                .into_iter()
+                .map(move |path| format!("ignored {} in {}", path, name))
+        })
+        .collect()
+}
+
+pub fn deserialize(
+    input: &str,
+    options: &BuildOptions,
+) -> Result<Vec<ContainerAppConfiguration>, DeserializeError> {
+    deserialize_per_app(input, options).map(pulumi::flatten_apps)
+}
+
+/***
+ * Services of each app, see `pulumi::build_per_app`
+ */
+pub fn deserialize_per_app(
+    input: &str,
+    options: &BuildOptions,
+) -> Result<Vec<(String, Vec<ContainerAppConfiguration>)>, DeserializeError> {
+    let (apps, images) = deserialize_blueprints(input, options)?;
+
+    pulumi::build_per_app(apps, images, options.include.as_deref(), options)
+}
+
+/***
+ * Apps and images of the input, their references are not resolved yet
+ */
+pub fn deserialize_blueprints(
+    input: &str,
+    options: &BuildOptions,
+) -> Result<pulumi::BluePrints, DeserializeError> {
+    if options.strict {
+        for (_, properties) in get_app_properties(input)? {
+            pulumi::validate_known_fields(&as_yaml_properties(&properties)?)?;
+        }
+    }
+
+    let images = get_images(input)?;
+    let apps = get_apps(input)?;
+
+    Ok((apps, images))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::serializer::BuildContextBluePrint;
+
+    use super::*;
+
+    const PROGRAM: &str = r#"
+import pulumi
+import pulumi_docker as docker
+from pulumi_azure_native import app, resources
+
+app_port = 3000
+
+resource_group = resources.ResourceGroup("rg")
+
+managed_env = app.ManagedEnvironment("env",
+    resource_group_name=resource_group.name,
+)
+
+node_image = docker.Image("node-app",
+    image_name=f"{registry.login_server}/node-app:v1.0.0",
+    build=docker.DockerBuildArgs(context="./node-app"),
+)
+
+container_app = app.ContainerApp("myapp",
+    resource_group_name=resource_group.name,
+    managed_environment_id=managed_env.id,
+    configuration=app.ConfigurationArgs(
+        ingress=app.IngressArgs(external=True, target_port=app_port),
+        # The sidecar reaches the app on its port
+        dapr={"enabled": True, "app_id": "myapp", "app_port": app_port},
+    ),
+    template=app.TemplateArgs(
+        containers=[app.ContainerArgs(
+            name="myapp",
+            image=node_image.image_name,
+        )],
+    ),
+)
+
+pulumi.export("url", container_app.configuration.apply(lambda c: c.ingress.fqdn))
+"#;
+
+    #[test]
+    fn test_to_camel_case() {
+        assert_eq!("targetPort", to_camel_case("target_port"));
+        assert_eq!("external", to_camel_case("external"));
+        assert_eq!("appId", to_camel_case("appId"));
+    }
+
+    #[test]
+    fn test_parse_value() {
+        let constants = Map::new();
+        let parse = |input: &str| Parser::new(input, 0, &constants).parse_value();
+
+        let expected = serde_json::json!({ "external": true, "targetPort": 3000 });
+        assert_eq!(
+            Ok(expected.clone()),
+            parse("app.IngressArgs(external=True, target_port=3000)")
+        );
+        assert_eq!(
+            Ok(expected),
+            parse(r#"{"external": True, "target_port": 3000}"#)
+        );
+
+        assert_eq!(
+            Ok(Value::String("${image.image_name}".to_string())),
+            parse("image.image_name")
+        );
+        assert_eq!(
+            Ok(Value::String("${registry.login_server}/app:v1".to_string())),
+            parse(r#"f"{registry.login_server}/app:v1""#)
+        );
+        assert_eq!(
+            Ok(Value::String("${image.image_name}".to_string())),
+            parse("image.image_name.apply(lambda name: f\"{name}\")")
+        );
+        assert_eq!(Ok(Value::Null), parse("get_name(\"app\")"));
+
+        assert!(parse("app.IngressArgs(external=True").is_err());
+    }
+
+    #[test]
+    fn test_get_images() {
+        let output = get_images(PROGRAM).unwrap();
+
+        let expected = vec![ContainerImageBluePrint {
+            name: Some("${registry.login_server}/node-app:v1.0.0".to_string()),
+            build: BuildContextBluePrint::new("./node-app"),
+            reference_name: Some("node_image".to_string()),
+        }];
+        assert_eq!(expected, output);
+    }
+
+    #[cfg(feature = "yaml")]
+    #[test]
+    fn test_deserialize_as_yaml() {
+        let yaml = r#"
+      resources:
+        node_image:
+          type: docker:RegistryImage
+          properties:
+            name: ${registry.loginServer}/node-app:v1.0.0
+            build:
+              context: ${pulumi.cwd}/node-app
+        myapp:
+          type: azure-native:app:ContainerApp
+          properties:
+            resourceGroupName: ${resource_group.name}
+            managedEnvironmentId: ${managed_env.id}
+            configuration:
+              ingress:
+                external: true
+                targetPort: 3000
+              dapr:
+                enabled: true
+                appId: myapp
+                appPort: 3000
+            template:
+              containers:
+                - name: myapp
+                  image: ${node_image.name}
+      "#;
+
+        let output = deserialize(PROGRAM, &BuildOptions::default()).unwrap();
+        let expected = pulumi::yaml::deserialize(yaml, &BuildOptions::default()).unwrap();
+
+        assert_eq!(2, output.len());
+        assert_eq!(expected, output);
+    }
+
+    #[test]
+    fn test_deserialize_invalid_program() {
+        let output = deserialize(
+            r#"container_app = app.ContainerApp("myapp", template=app.TemplateArgs("#,
+            &BuildOptions::default(),
+        );
+
+        assert!(matches!(output, Err(DeserializeError::Parse(_))));
+    }
+
+    #[test]
+    fn test_deserialize_dapr_without_enabled() {
+        let program = PROGRAM.replace(r#""enabled": True, "#, "");
+        let output = deserialize(&program, &BuildOptions::default()).unwrap();
+
+        let names: Vec<&str> = output.iter().map(|service| service.name.as_str()).collect();
+        // Dapr is disabled by default, as in Container Apps
+        assert_eq!(vec!["myapp"], names);
+    }
+}
@@ -0,0 +1,594 @@
+use log::warn;
+use serde_json::Value;
+
+use crate::error::DeserializeError;
+use crate::pulumi::{self, LOG_TARGET};
+use crate::serializer::{
+    snippet_at, BuildContextBluePrint, BuildOptions, ContainerAppBluePrint,
+    ContainerAppConfiguration, ContainerImageBluePrint,
+};
+
+const IMAGE_TYPE: &str = "docker:index/image:Image";
+// Image outputs a container may refer to
+const IMAGE_OUTPUTS: [&str; 3] = ["imageName", "repoDigest", "baseImageName"];
+// Outputs which are not known before the deployment (eg: in a preview)
+const UNKNOWN_VALUE: &str = "04da6b54-80e4-46f7-96ec-b56ff0331ba9";
+
+/***
+ * A resource of a stack export (`deployment.resources`, with its outputs)
+ * or of a preview (`steps[].newState`, with its planned inputs)
+ */
+struct StateResource<'a> {
+    name: &'a str,
+    resource_type: &'a str,
+    properties: &'a Value,
+    /// URNs of the resources the properties depend on
+    dependencies: Vec<&'a str>,
+}
+
+/***
+ * Logical name of a resource, the last segment of its URN
+ * (eg: urn:pulumi:dev::project::azure-native:app:ContainerApp::frontend)
+ */
+fn get_resource_name(urn: &str) -> &str {
+    urn.rsplit("::").next().unwrap_or_default()
+}
+
+fn as_strings(value: Option<&Value>) -> Vec<&str> {
+    value
+        .and_then(|x| x.as_array())
+        .map(|x| x.iter().filter_map(|x| x.as_str()).collect())
+        .unwrap_or_default()
+}
+
+fn get_resources(value: &Value) -> Vec<StateResource<'_>> {
+    let (states, properties): (Vec<&Value>, &str) =
+        match value.get("steps").and_then(|x| x.as_array()) {
+            Some(steps) => (
+                steps
+                    .iter()
+                    .filter_map(|step| step.get("newState"))
+                    .collect(),
+                "inputs",
+            ),
+            None => (
+                value
+                    .pointer("/deployment/resources")
+                    .and_then(|x| x.as_array())
+                    .map(|resources| resources.iter().collect())
+                    .unwrap_or_default(),
+                "outputs",
+            ),
+        };
+    states
+        .into_iter()
+        .filter_map(|state| {
+            // The images of the apps are the dependencies of their template
+            let dependencies = match state.pointer("/propertyDependencies/template") {
+                Some(dependencies) => as_strings(Some(dependencies)),
+                None => as_strings(state.get("dependencies")),
+            };
+
+            Some(StateResource {
+                name: get_resource_name(state.get("urn")?.as_str()?),
+                resource_type: state.get("type")?.as_str()?,
+                properties: state.get(properties)?,
+                dependencies,
+            })
+        })
+        .collect()
+}
+
+/***
+ * Paths of the unknown values of the properties (eg: template.containers[0].image)
+ */
+fn get_unknown_paths(value: &Value, path: &str) -> Vec<String> {
+    let join = |key: &str| match path {
+        "" => key.to_string(),
+        path => format!("{}.{}", path, key),
+    };
+
+    match value {
+        Value::String(value) if value == UNKNOWN_VALUE => vec![path.to_string()],
+        Value::Object(properties) => properties
+            .iter()
+            .flat_map(|(key, value)| get_unknown_paths(value, &join(key)))
+            .collect(),
+        Value::Array(values) => values
+            .iter()
+            .enumerate()
+            .flat_map(|(index, value)| get_unknown_paths(value, &format!("{}[{}]", path, index)))
+            .collect(),
+        _ => vec![],
+    }
+}
+
+/***
+ * Unknown values are dropped, their fields are unset
+ */
+fn without_unknown_values(value: &Value) -> Value {
+    match value {
+        Value::Object(properties) => Value::Object(
+            properties
+                .iter()
+                .filter(|(_, value)| value.as_str() != Some(UNKNOWN_VALUE))
+                .map(|(key, value)| (key.clone(), without_unknown_values(value)))
+                .collect(),
+        ),
+        Value::Array(values) => Value::Array(values.iter().map(without_unknown_values).collect()),
+        value => value.clone(),
+    }
+}
+
+/***
+ * Built images by logical name, with the concrete image names containers may use
+ */
+fn get_images(resources: &[StateResource]) -> Vec<(ContainerImageBluePrint, Vec<String>)> {
+    resources
+        .iter()
+        .filter(|resource| resource.resource_type == IMAGE_TYPE)
+        .filter_map(|resource| {
+            let properties = resource.properties;
+            // Images without build context are remote ones, they are not referenced
+            let context = properties.pointer("/build/context")?.as_str()?.to_string();
+            let image_names = IMAGE_OUTPUTS
+                .iter()
+                .filter_map(|output| properties.get(output).and_then(|x| x.as_str()))
+                .filter(|image_name| *image_name != UNKNOWN_VALUE)
+                .map(|x| x.to_string())
+                .collect();
+
+            Some((
+                ContainerImageBluePrint {
+                    name: Some(resource.name.to_string()),
+                    build: BuildContextBluePrint::new(context),
+                    reference_name: Some(resource.name.to_string()),
+                },
+                image_names,
+            ))
+        })
+        .collect()
+}
+
+/***
+ * The images of the state are concrete, the ones of a built image become its reference.
+ * An unknown image is the built image the app depends on, when there is only one
+ */
+fn reference_built_images(
+    app: &mut ContainerAppBluePrint,
+    resource: &StateResource,
+    images: &[(ContainerImageBluePrint, Vec<String>)],
+) {
+    let dependencies: Vec<&str> = resource
+        .dependencies
+        .iter()
+        .map(|urn| get_resource_name(urn))
+        .filter(|name| {
+            images
+                .iter()
+                .any(|(image, _)| image.reference_name.as_deref() == Some(name))
+        })
+        .collect();
+    let containers = app
+        .template
+        .iter_mut()
+        .flat_map(|template| {
+            template
+                .containers
+                .iter_mut()
+                .chain(template.init_containers.iter_mut())
+        })
+        .flatten();
+
+    for container in containers {
+        let reference = match dependencies.as_slice() {
+            [dependency] if container.image == UNKNOWN_VALUE => Some(dependency.to_string()),
+            _ => images
+                .iter()
+                .find(|(_, image_names)| image_names.contains(&container.image))
+                .and_then(|(image, _)| image.reference_name.clone()),
+        };
+
+        match reference {
+            Some(reference) => container.image = format!("${{{}.imageName}}", reference),
+            // Fails as an unresolved image, the value is reported in the warnings
+            None if container.image == UNKNOWN_VALUE => container.image = "${unknown}".to_string(),
+            None => {}
+        }
+    }
+}
+
+/***
+ * Unknown container images are kept, they are resolved from the dependencies
+ */
+fn as_app_properties(properties: &Value) -> Value {
+    let mut properties = without_unknown_values(properties);
+    let template = properties
+        .get_mut("template")
+        .and_then(|x| x.as_object_mut());
+
+    if let Some(template) = template {
+        for key in ["containers", "initContainers"] {
+            let containers = template.get_mut(key).and_then(|x| x.as_array_mut());
+
+            for container in containers.into_iter().flatten() {
+                if let Some(container) = container.as_object_mut() {
+                    container
+                        .entry("image")
+                        .or_insert(Value::String(UNKNOWN_VALUE.to_string()));
+                }
+            }
+        }
+    }
+
+    properties
+}
+
+// Versioned types (eg: azure-native:app/v20230501:ContainerApp) are container apps too
+fn is_container_app(resource_type: &str) -> bool {
+    matches!(
+        pulumi::parse_app_resource_type(resource_type),
+        Some(("ContainerApp", _))
+    )
+}
+
+fn get_apps(
+    resources: &[StateResource],
+    images: &[(ContainerImageBluePrint, Vec<String>)],
+) -> Result<Vec<ContainerAppBluePrint>, DeserializeError> {
+    let resources: Vec<&StateResource> = resources
+        .iter()
+        .filter(|resource| is_container_app(resource.resource_type))
+        .collect();
+
+    if resources.is_empty() {
+        return Err(DeserializeError::NoContainerApp);
+    }
+
+    resources
+        .into_iter()
+        .map(|resource| {
+            let mut app: ContainerAppBluePrint =
+                serde_json::from_value(as_app_properties(resource.properties))
+                    .map_err(|e| DeserializeError::Parse(e.to_string()))?;
+            app.name = Some(resource.name.to_string());
+            app.api_version = pulumi::parse_app_resource_type(resource.resource_type)
+                .and_then(|(_, version)| version.map(str::to_string));
+            pulumi::normalize_scale(&mut app);
+            reference_built_images(&mut app, resource, images);
+
+            Ok(app)
+        })
+        .collect()
+}
+
+/***
+ * Values of the resources which are unknown until the deployment, the input is expected to be valid
+ */
+pub fn collect_warnings(input: &str) -> Vec<String> {
+    let value = serde_json::from_str::<Value>(input).unwrap_or_default();
+    let resources = get_resources(&value);
+    let apps = get_apps(&resources, &get_images(&resources)).unwrap_or_default();
+
+    resources
+        .iter()
+        .filter(|resource| {
+            is_container_app(resource.resource_type) || resource.resource_type == IMAGE_TYPE
+        })
+        .flat_map(|resource| {
+            get_unknown_paths(resource.properties, "")
+                .into_iter()
+                .map(|path| format!("unresolved output {} in {}", path, resource.name))
+        })
+        .chain(pulumi::collect_api_version_warnings(&apps))
+        .collect()
+}
+
+pub fn deserialize(
+    input: &str,
+    options: &BuildOptions,
+) -> Result<Vec<ContainerAppConfiguration>, DeserializeError> {
+    deserialize_per_app(input, options).map(pulumi::flatten_apps)
+}
+
+/***
+ * Services of each app, see `pulumi::build_per_app`
+ */
+pub fn deserialize_per_app(
+    input: &str,
+    options: &BuildOptions,
+) -> Result<Vec<(String, Vec<ContainerAppConfiguration>)>, DeserializeError> {
+    let (apps, images) = deserialize_blueprints(input, options)?;
+
+    pulumi::build_per_app(apps, images, options.include.as_deref(), options)
+}
+
+/***
+ * Apps and images of the input, their references are not resolved yet.
+ * The stack export outputs hold every computed property of the apps (eg: provisioningState),
+ * they are not checked in strict mode
+ */
+pub fn deserialize_blueprints(
+    input: &str,
+    _options: &BuildOptions,
+) -> Result<pulumi::BluePrints, DeserializeError> {
+    match serde_json::from_str::<Value>(input) {
+        Ok(v) => {
+            let resources = get_resources(&v);
+            let images = get_images(&resources);
+            let apps = get_apps(&resources, &images)?;
+            let images = images.into_iter().map(|(image, _)| image).collect();
+
+            Ok((apps, images))
+        }
+        Err(e) => {
+            warn!(target: LOG_TARGET, "{} near `{}`", e, snippet_at(input, e.line()));
+            Err(DeserializeError::Parse(e.to_string()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXPORT: &str = r#"{
+        "version": 3,
+        "deployment": {
+            "manifest": {
+                "time": "2024-03-12T10:21:43.412Z",
+                "magic": "0e4b4af5c4b4e4d1b7ba8a4a1a21b542e48ab36c0de39d52f4a1d1e5c21f8c71",
+                "version": "v3.109.0"
+            },
+            "resources": [
+                {
+                    "urn": "urn:pulumi:dev::capps::pulumi:pulumi:Stack::capps-dev",
+                    "custom": false,
+                    "type": "pulumi:pulumi:Stack"
+                },
+                {
+                    "urn": "urn:pulumi:dev::capps::docker:index/image:Image::node-app",
+                    "custom": true,
+                    "id": "myacr.azurecr.io/node-app@sha256:5f1c0d3a5b9a7e2c3d4b6a8f9e0c1d2b3a4f5e6d7c8b9a0f1e2d3c4b5a6f7e8d",
+                    "type": "docker:index/image:Image",
+                    "inputs": {
+                        "build": { "context": "./node-app" },
+                        "imageName": "myacr.azurecr.io/node-app:v1.0.0"
+                    },
+                    "outputs": {
+                        "baseImageName": "myacr.azurecr.io/node-app:v1.0.0",
+                        "build": { "context": "./node-app" },
+                        "imageName": "myacr.azurecr.io/node-app:v1.0.0",
+                        "registryServer": "myacr.azurecr.io",
+                        "repoDigest": "myacr.azurecr.io/node-app@sha256:5f1c0d3a5b9a7e2c3d4b6a8f9e0c1d2b3a4f5e6d7c8b9a0f1e2d3c4b5a6f7e8d"
+                    }
+                },
+                {
+                    "urn": "urn:pulumi:dev::capps::azure-native:app:ContainerApp::backend",
+                    "custom": true,
+                    "id": "/subscriptions/0000/resourceGroups/rg/providers/Microsoft.App/containerApps/backend4f1c2d",
+                    "type": "azure-native:app:ContainerApp",
+                    "outputs": {
+                        "configuration": {
+                            "activeRevisionsMode": "Single",
+                            "dapr": {
+                                "appId": "backend",
+                                "appPort": 4000,
+                                "appProtocol": "http",
+                                "enabled": true
+                            }
+                        },
+                        "latestRevisionName": "backend4f1c2d--x2b9k1",
+                        "location": "westeurope",
+                        "managedEnvironmentId": "/subscriptions/0000/resourceGroups/rg/providers/Microsoft.App/managedEnvironments/env",
+                        "name": "backend4f1c2d",
+                        "provisioningState": "Succeeded",
+                        "template": {
+                            "containers": [
+                                {
+                                    "image": "myacr.azurecr.io/node-app@sha256:5f1c0d3a5b9a7e2c3d4b6a8f9e0c1d2b3a4f5e6d7c8b9a0f1e2d3c4b5a6f7e8d",
+                                    "name": "backend",
+                                    "resources": { "cpu": 0.5, "ephemeralStorage": "2Gi", "memory": "1Gi" }
+                                }
+                            ],
+                            "scale": { "maxReplicas": 10 }
+                        }
+                    }
+                },
+                {
+                    "urn": "urn:pulumi:dev::capps::azure-native:app:ContainerApp::frontend",
+                    "custom": true,
+                    "type": "azure-native:app:ContainerApp",
+                    "outputs": {
+                        "configuration": {
+                            "ingress": {
+                                "external": true,
+                                "fqdn": "frontend.kindwave-1234.westeurope.azurecontainerapps.io",
+                                "targetPort": 3000,
+                                "transport": "Auto"
+                            }
+                        },
+                        "template": {
+                            "containers": [
+                                { "image": "nginx:1.25", "name": "frontend" }
+                            ]
+                        }
+                    }
+                }
+            ]
+        }
+    }"#;
+
+    #[test]
+    fn test_get_images() {
+        let value = serde_json::from_str(EXPORT).unwrap();
+        let output = get_images(&get_resources(&value));
+
+        assert_eq!(1, output.len());
+        assert_eq!(Some("node-app".to_string()), output[0].0.reference_name);
+        assert_eq!("./node-app", output[0].0.build.context);
+        assert_eq!(3, output[0].1.len());
+    }
+
+    #[test]
+    fn test_deserialize() {
+        let output = deserialize(EXPORT, &BuildOptions::default()).unwrap();
+
+        let names: Vec<&str> = output.iter().map(|service| service.name.as_str()).collect();
+        assert_eq!(vec!["backend", "backend_dapr", "frontend"], names);
+
+        // The digest of the built image is its build context
+        assert_eq!(None, output[0].image);
+        assert_eq!(
+            Some("./node-app".to_string()),
+            output[0].build.as_ref().map(|build| build.context.clone())
+        );
+        assert!(output[1]
+            .command
+            .as_ref()
+            .is_some_and(|command| command.contains(&"4000".to_string())));
+        assert_eq!(Some("nginx:1.25".to_string()), output[2].image);
+        assert_eq!(Some(vec!["3000:3000".to_string()]), output[2].ports);
+    }
+
+    #[test]
+    fn test_deserialize_without_container_app() {
+        let export = r#"{ "version": 3, "deployment": { "resources": [] } }"#;
+
+        assert_eq!(
+            Err(DeserializeError::NoContainerApp),
+            deserialize(export, &BuildOptions::default())
+        );
+    }
+
+    const PREVIEW: &str = r#"{
+        "config": { "azure-native:location": "westeurope" },
+        "steps": [
+            {
+                "op": "create",
+                "urn": "urn:pulumi:dev::capps::docker:index/image:Image::api-image",
+                "newState": {
+                    "urn": "urn:pulumi:dev::capps::docker:index/image:Image::api-image",
+                    "custom": true,
+                    "type": "docker:index/image:Image",
+                    "inputs": {
+                        "build": { "context": "./api" },
+                        "imageName": "myacr.azurecr.io/api:v2"
+                    }
+                }
+            },
+            {
+                "op": "create",
+                "urn": "urn:pulumi:dev::capps::docker:index/image:Image::worker-image",
+                "newState": {
+                    "urn": "urn:pulumi:dev::capps::docker:index/image:Image::worker-image",
+                    "custom": true,
+                    "type": "docker:index/image:Image",
+                    "inputs": {
+                        "build": { "context": "./worker" },
+                        "imageName": "04da6b54-80e4-46f7-96ec-b56ff0331ba9"
+                    }
+                }
+            },
+            {
+                "op": "create",
+                "urn": "urn:pulumi:dev::capps::azure-native:app:ContainerApp::api",
+                "newState": {
+                    "urn": "urn:pulumi:dev::capps::azure-native:app:ContainerApp::api",
+                    "custom": true,
+                    "type": "azure-native:app:ContainerApp",
+                    "inputs": {
+                        "configuration": {
+                            "ingress": { "external": true, "targetPort": 8080 }
+                        },
+                        "managedEnvironmentId": "04da6b54-80e4-46f7-96ec-b56ff0331ba9",
+                        "template": {
+                            "containers": [{ "image": "myacr.azurecr.io/api:v2", "name": "api" }]
+                        }
+                    }
+                }
+            },
+            {
+                "op": "create",
+                "urn": "urn:pulumi:dev::capps::azure-native:app:ContainerApp::worker",
+                "newState": {
+                    "urn": "urn:pulumi:dev::capps::azure-native:app:ContainerApp::worker",
+                    "custom": true,
+                    "type": "azure-native:app:ContainerApp",
+                    "inputs": {
+                        "template": {
+                            "containers": [
+                                { "image": "04da6b54-80e4-46f7-96ec-b56ff0331ba9", "name": "worker" }
+                            ]
+                        }
+                    },
+                    "dependencies": [
+                        "urn:pulumi:dev::capps::azure-native:resources:ResourceGroup::rg",
+                        "urn:pulumi:dev::capps::docker:index/image:Image::worker-image"
+                    ],
+                    "propertyDependencies": {
+                        "template": ["urn:pulumi:dev::capps::docker:index/image:Image::worker-image"]
+                    }
+                }
+            },
+            {
+                "op": "delete",
+                "urn": "urn:pulumi:dev::capps::azure-native:app:ContainerApp::legacy",
+                "oldState": {
+                    "urn": "urn:pulumi:dev::capps::azure-native:app:ContainerApp::legacy",
+                    "type": "azure-native:app:ContainerApp"
+                }
+            }
+        ],
+        "changeSummary": { "create": 4, "delete": 1 }
+    }"#;
+
+    #[test]
+    fn test_deserialize_preview() {
+        let output = deserialize(PREVIEW, &BuildOptions::default()).unwrap();
+
+        let names: Vec<&str> = output.iter().map(|service| service.name.as_str()).collect();
+        assert_eq!(vec!["api", "worker"], names);
+
+        // Resolved from the image name
+        assert_eq!(
+            Some("./api".to_string()),
+            output[0].build.as_ref().map(|build| build.context.clone())
+        );
+        assert_eq!(Some(vec!["8080:8080".to_string()]), output[0].ports);
+        // Resolved from the dependencies of the template
+        assert_eq!(
+            Some("./worker".to_string()),
+            output[1].build.as_ref().map(|build| build.context.clone())
+        );
+    }
+
+    #[test]
+    fn test_collect_warnings() {
+        assert_eq!(
+            vec![
+                "unresolved output imageName in worker-image",
+                "unresolved output managedEnvironmentId in api",
+                "unresolved output template.containers[0].image in worker",
+            ],
+            collect_warnings(PREVIEW)
+        );
+        assert!(collect_warnings(EXPORT).is_empty());
+    }
+
+    #[test]
+    fn test_deserialize_preview_with_unknown_image() {
+        let preview = PREVIEW.replace(
+            r#""template": ["urn:pulumi:dev::capps::docker:index/image:Image::worker-image"]"#,
+            r#""template": []"#,
+        );
+
+        assert_eq!(
+            Err(DeserializeError::UnresolvedImage {
+                container: "worker".to_string(),
+                reference: "${unknown}".to_string(),
+            }),
+            deserialize(&preview, &BuildOptions::default())
+        );
+    }
+}
@@ -1,12 +1,214 @@
-use log::error;
+use log::warn;
+use regex::Regex;
 use serde::Deserialize;
 use serde_yaml::{Mapping, Value};
+use std::collections::{BTreeMap, HashSet};
+use std::io::Read;
 
+use crate::error::DeserializeError;
 use crate::pulumi;
 use crate::serializer::{
-    ContainerAppBluePrint, ContainerAppConfiguration, ContainerImageBluePrint,
+    snippet_at, BuildOptions, ContainerAppBluePrint, ContainerAppConfiguration,
+    ContainerImageBluePrint, JobBluePrint, TokenResolver,
 };
 
+/***
+ * Config and built-in variables of a program (eg: ${appPort} or ${pulumi.stack})
+ */
+struct Variables<'a> {
+    values: BTreeMap<String, Value>,
+    // Names of the resources and of the `variables` section, which are not config variables
+    references: HashSet<String>,
+    options: &'a BuildOptions,
+}
+
+impl<'a> Variables<'a> {
+    /// Defaults of the `config` section of the program, overridden by the stack config
+    fn new(program: &Value, options: &'a BuildOptions) -> Variables<'a> {
+        let project = program.get("name").and_then(Value::as_str);
+        let mut values = BTreeMap::new();
+
+        if let Some(project) = project {
+            values.insert("pulumi.project".to_string(), Value::from(project));
+        }
+
+        for (key, value) in program
+            .get("config")
+            .and_then(Value::as_mapping)
+            .into_iter()
+            .flatten()
+        {
+            let default = match value {
+                Value::Mapping(declaration) => declaration.get("default").cloned(),
+                value => Some(value.clone()),
+            };
+
+            if let (Some(key), Some(default)) = (key.as_str(), default) {
+                values.insert(key.to_string(), default);
+            }
+        }
+
+        for (key, value) in &options.config {
+            // Secure values are encrypted, they are left unresolved
+            if value.get("secure").is_some() {
+                continue;
+            }
+
+            // Keys of the project are referenced without their namespace (eg: myproject:appPort)
+            let key = match (key.split_once(':'), project) {
+                (Some((namespace, key)), Some(project)) if namespace == project => key,
+                _ => key,
+            };
+            values.insert(key.to_string(), value.clone());
+        }
+
+        let references = ["resources", "variables"]
+            .iter()
+            .filter_map(|section| program.get(section).and_then(Value::as_mapping))
+            .flat_map(|mapping| mapping.keys())
+            .filter_map(|key| key.as_str().map(str::to_string))
+            .collect();
+
+        Variables {
+            values,
+            references,
+            options,
+        }
+    }
+
+    /// Other tokens are resource properties (eg: ${myImage.repoDigest})
+    fn contains(&self, token: &str) -> bool {
+        token.starts_with("pulumi.")
+            || !(token.contains(['.', '[']) || self.references.contains(token))
+    }
+
+    fn get(&self, token: &str) -> Option<Value> {
+        self.values.get(token).cloned().or_else(|| {
+            token
+                .starts_with("pulumi.")
+                .then(|| self.options.resolve(token).map(Value::from))
+                .flatten()
+        })
+    }
+}
+
+/***
+ * Replace the variables of the value, the unresolved ones are kept
+ * and returned with their property path
+ */
+fn resolve_variables(
+    value: &mut Value,
+    path: &str,
+    variables: &Variables,
+    unresolved: &mut Vec<(String, String)>,
+) {
+    let at = |key: String| match path.is_empty() {
+        true => key,
+        false => format!("{}.{}", path, key),
+    };
+
+    match value {
+        Value::Mapping(mapping) => {
+            for (key, value) in mapping.iter_mut() {
+                let key = key.as_str().unwrap_or_default().to_string();
+                resolve_variables(value, &at(key), variables, unresolved);
+            }
+        }
+        Value::Sequence(sequence) => {
+            for (index, value) in sequence.iter_mut().enumerate() {
+                resolve_variables(
+                    value,
+                    &format!("{}[{}]", path, index),
+                    variables,
+                    unresolved,
+                );
+            }
+        }
+        Value::String(input) => {
+            let re = Regex::new(r"\$\{([^}]+)\}").expect("Should match token regex");
+
+            // A value made of a single variable takes its type (eg: a number for a port)
+            let token = re
+                .captures(input)
+                .filter(|captures| captures[0].len() == input.len())
+                .map(|captures| captures[1].to_string())
+                .filter(|token| variables.contains(token));
+
+            if let Some(token) = token {
+                match variables.get(&token) {
+                    Some(Value::String(variable)) => {
+                        *value = match serde_yaml::from_str(&variable) {
+                            Ok(number @ Value::Number(_)) => number,
+                            _ => Value::String(variable),
+                        }
+                    }
+                    Some(variable) => *value = variable,
+                    None => unresolved.push((format!("${{{}}}", token), path.to_string())),
+                }
+                return;
+            }
+
+            *input = re
+                .replace_all(input, |captures: &regex::Captures| {
+                    let variable = variables
+                        .contains(&captures[1])
+                        .then(|| variables.get(&captures[1]))
+                        .flatten();
+
+                    match variable {
+                        Some(Value::String(variable)) => variable,
+                        Some(Value::Number(variable)) => variable.to_string(),
+                        Some(Value::Bool(variable)) => variable.to_string(),
+                        _ => {
+                            if variables.contains(&captures[1]) {
+                                unresolved.push((captures[0].to_string(), path.to_string()));
+                            }
+                            captures[0].to_string()
+                        }
+                    }
+                })
+                .to_string();
+        }
+        _ => {}
+    }
+}
+
+/***
+ * Replace the variables of the resource properties before their deserialization,
+ * returns a warning by unresolved variable
+ */
+fn resolve_program_variables(program: &mut Value, options: &BuildOptions) -> Vec<String> {
+    let variables = Variables::new(program, options);
+    let mut warnings = vec![];
+    let resources = program
+        .get_mut("resources")
+        .and_then(Value::as_mapping_mut)
+        .into_iter()
+        .flatten();
+
+    for (name, resource) in resources {
+        let mut unresolved = vec![];
+
+        if let Some(properties) = resource.get_mut("properties") {
+            resolve_variables(properties, "", &variables, &mut unresolved);
+        }
+
+        warnings.extend(unresolved.into_iter().map(|(token, path)| {
+            format!(
+                "unresolved variable {} at {} in {}",
+                token,
+                path,
+                name.as_str().unwrap_or_default()
+            )
+        }));
+    }
+
+    warnings
+}
+
+// Container apps of the classic azure provider
+const CLASSIC_CONTAINER_APP_TYPE: &str = "azure:containerapp:App";
+
 fn filter_by_type(val: &&Value, resource_type: &str) -> bool {
     match val.get("type") {
         Some(x) => x.as_str() == Some(resource_type),
@@ -17,7 +219,7 @@ fn filter_by_type(val: &&Value, resource_type: &str) -> bool {
 fn get_images(mapping: &Mapping) -> Vec<ContainerImageBluePrint> {
     mapping
         .keys()
-        .map(|key| match mapping.get(key) {
+        .filter_map(|key| match mapping.get(key) {
             Some(resource) => {
                 if filter_by_type(&resource, "docker:RegistryImage") {
                     let mut image: ContainerImageBluePrint =
@@ -32,56 +234,220 @@ fn get_images(mapping: &Mapping) -> Vec<ContainerImageBluePrint> {
             }
             None => None,
         })
-        .flatten()
         .collect()
 }
 
-fn get_apps(mapping: &Mapping) -> Vec<ContainerAppBluePrint> {
+// Name, properties and pinned API version of an app
+type AppProperties = (String, Value, Option<String>);
+
+/***
+ * Properties of the container apps, the apps of the classic provider are normalized
+ * to the azure-native shape
+ */
+fn get_app_properties(mapping: &Mapping) -> Vec<AppProperties> {
     mapping
-        .values()
-        .filter(|x| filter_by_type(x, "azure-native:app:ContainerApp"))
-        .map(|container| {
-            serde_yaml::from_value(container.get("properties").unwrap().to_owned()).unwrap()
+        .iter()
+        .filter_map(|(key, container)| {
+            let (is_classic, version) = match container.get("type").and_then(Value::as_str) {
+                Some(CLASSIC_CONTAINER_APP_TYPE) => (true, None),
+                Some(token) => match pulumi::parse_app_resource_type(token)? {
+                    ("ContainerApp", version) => (false, version.map(str::to_string)),
+                    _ => return None,
+                },
+                None => return None,
+            };
+            let properties = container.get("properties").unwrap();
+            let properties = match is_classic {
+                true => pulumi::normalize_classic_app(properties),
+                false => properties.clone(),
+            };
+
+            Some((key.as_str().unwrap().to_string(), properties, version))
         })
         .collect()
 }
 
-pub fn deserialize(input: &str) -> Result<Vec<ContainerAppConfiguration>, String> {
-    let deserialized_map = serde_yaml::Deserializer::from_str(input);
-    let value = Value::deserialize(deserialized_map);
+fn get_apps(mapping: &Mapping) -> Result<Vec<ContainerAppBluePrint>, DeserializeError> {
+    get_app_properties(mapping)
+        .into_iter()
+        .map(|(name, properties, version)| {
+            let mut app: ContainerAppBluePrint = serde_yaml::from_value(properties)
+                .map_err(|e| DeserializeError::Parse(e.to_string()))?;
+            app.name = Some(name);
+            app.api_version = version;
+            pulumi::normalize_scale(&mut app);
 
-    match value {
-        Ok(v) => {
-            // If resources exists, then iterate over containersApp applications
-            let as_mapping = v
-                .get("resources")
-                .expect("Resources need to be defined")
-                .as_mapping()
-                .expect("A mapping need to be generated");
-
-            let images: Vec<ContainerImageBluePrint> = get_images(as_mapping);
-            let apps: Vec<ContainerAppBluePrint> = get_apps(as_mapping);
-
-            let services = pulumi::build_configuration(apps, images);
-
-            match services {
-                Some(val) => Ok(val),
-                None => Err("No container to deserialize".to_string()),
-            }
-        }
+            Ok(app)
+        })
+        .collect()
+}
+
+/***
+ * Jobs of the program, built as apps which run once
+ */
+fn get_jobs(mapping: &Mapping) -> Result<Vec<ContainerAppBluePrint>, DeserializeError> {
+    mapping
+        .iter()
+        .filter_map(|(key, resource)| {
+            let token = resource.get("type").and_then(Value::as_str)?;
+            let version = match pulumi::parse_app_resource_type(token)? {
+                ("Job", version) => version.map(str::to_string),
+                _ => return None,
+            };
+            let properties = resource.get("properties").cloned().unwrap_or_default();
+
+            Some(
+                serde_yaml::from_value(properties)
+                    .map(|job: JobBluePrint| {
+                        let mut app = ContainerAppBluePrint::from(JobBluePrint {
+                            name: key.as_str().map(str::to_string),
+                            api_version: version,
+                            ..job
+                        });
+                        pulumi::normalize_scale(&mut app);
+                        app
+                    })
+                    .map_err(|e| DeserializeError::Parse(e.to_string())),
+            )
+        })
+        .collect()
+}
+
+/***
+ * Program with its aliases expanded and its merge keys (eg: `<<: *base`) applied,
+ * the keys of a mapping override the merged ones
+ */
+fn parse_program(input: &str) -> Result<Value, serde_yaml::Error> {
+    let mut value = Value::deserialize(serde_yaml::Deserializer::from_str(input))?;
+    value.apply_merge()?;
+
+    Ok(value)
+}
+
+/***
+ * Fields of the apps which are not translated, the input is expected to be valid
+ */
+pub fn collect_warnings(input: &str, options: &BuildOptions) -> Vec<String> {
+    collect_program_warnings(parse_program(input).unwrap_or_default(), options)
+}
+
+/***
+ * Fields of the apps of a parsed program which are not translated, see `collect_warnings`
+ */
+pub fn collect_program_warnings(mut value: Value, options: &BuildOptions) -> Vec<String> {
+    let mut warnings = resolve_program_variables(&mut value, options);
+    let resources = value.get("resources").and_then(|x| x.as_mapping());
+
+    warnings.extend(pulumi::collect_api_version_warnings(
+        &resources
+            .map(get_apps)
+            .and_then(Result::ok)
+            .unwrap_or_default(),
+    ));
+    warnings.extend(
+        resources
+            .map(get_app_properties)
+            .unwrap_or_default()
+            .into_iter()
+            .flat_map(|(name, properties, _)| {
+                pulumi::collect_ignored_fields(&properties)
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(move |path| format!("ignored {} in {}", path, name))
+            }),
+    );
+    warnings
+}
+
+pub fn deserialize(
+    input: &str,
+    options: &BuildOptions,
+) -> Result<Vec<ContainerAppConfiguration>, DeserializeError> {
+    deserialize_per_app(input, options).map(pulumi::flatten_apps)
+}
+
+/***
+ * Services of each app, see `pulumi::build_per_app`
+ */
+pub fn deserialize_per_app(
+    input: &str,
+    options: &BuildOptions,
+) -> Result<Vec<(String, Vec<ContainerAppConfiguration>)>, DeserializeError> {
+    let (apps, images) = deserialize_blueprints(input, options)?;
+
+    pulumi::build_per_app(apps, images, options.include.as_deref(), options)
+}
+
+/***
+ * Apps and images of the input, their references are not resolved yet
+ */
+pub fn deserialize_blueprints(
+    input: &str,
+    options: &BuildOptions,
+) -> Result<pulumi::BluePrints, DeserializeError> {
+    match parse_program(input) {
+        Ok(v) => program_blueprints(v, options),
 
         Err(e) => {
-            error!("{}", e);
-            Err(e.to_string())
+            let snippet = e
+                .location()
+                .map(|location| snippet_at(input, location.line()))
+                .unwrap_or_default();
+            warn!(target: pulumi::LOG_TARGET, "{} near `{}`", e, snippet);
+            Err(DeserializeError::Parse(e.to_string()))
         }
     }
 }
 
+/***
+ * Program parsed as it is read (eg: from stdin), see `parse_program`
+ */
+pub fn read_program<R: Read>(reader: R) -> Result<Value, DeserializeError> {
+    Value::deserialize(serde_yaml::Deserializer::from_reader(reader))
+        .and_then(|mut value| value.apply_merge().map(|_| value))
+        .map_err(|e| {
+            warn!(target: pulumi::LOG_TARGET, "{}", e);
+            DeserializeError::Parse(e.to_string())
+        })
+}
+
+/***
+ * Apps and images of a parsed program, see `deserialize_blueprints`
+ */
+pub fn program_blueprints(
+    mut v: Value,
+    options: &BuildOptions,
+) -> Result<pulumi::BluePrints, DeserializeError> {
+    resolve_program_variables(&mut v, options);
+
+    // If resources exists, then iterate over containersApp applications
+    let as_mapping = v
+        .get("resources")
+        .expect("Resources need to be defined")
+        .as_mapping()
+        .expect("A mapping need to be generated");
+
+    if options.strict {
+        for (_, properties, _) in get_app_properties(as_mapping) {
+            pulumi::validate_known_fields(&properties)?;
+        }
+    }
+
+    let images: Vec<ContainerImageBluePrint> = get_images(as_mapping);
+    let mut apps: Vec<ContainerAppBluePrint> = get_apps(as_mapping)?;
+    apps.extend(get_jobs(as_mapping)?);
+
+    Ok((apps, images))
+}
+
+#[cfg(test)]
 mod tests {
+    use crate::serializer::{from_compose_yaml, to_compose_yaml};
     use crate::serializer::{
         BuildContextBluePrint, ConfigurationBluePrint, ContainerBluePrint, DaprBluePrint,
-        IngressBluePrint, TemplateBluePrint,
+        IngressBluePrint, ScaleBluePrint, TemplateBluePrint,
     };
+    use std::collections::BTreeMap;
 
     use super::*;
 
@@ -120,9 +486,7 @@ mod tests {
         let expected = vec![ContainerImageBluePrint {
             reference_name: Some("myImage".to_string()),
             name: Some("${registry.loginServer}/node-app:v1.0.0".to_string()),
-            build: BuildContextBluePrint {
-                context: "${pulumi.cwd}/node-app".to_string(),
-            },
+            build: BuildContextBluePrint::new("${pulumi.cwd}/node-app"),
         }];
 
         assert_eq!(expected, output);
@@ -173,26 +537,37 @@ mod tests {
             .as_mapping()
             .expect("A mapping need to be generated");
 
-        let output = get_apps(as_mapping);
+        let output = get_apps(as_mapping).unwrap();
 
         let expected = vec![ContainerAppBluePrint {
+            name: Some("containerapp".to_string()),
             configuration: Some(ConfigurationBluePrint {
                 ingress: Some(IngressBluePrint {
                     external: Some(true),
                     target_port: Some(80),
+                    cors_policy: None,
+                    custom_domains: None,
+                    additional_port_mappings: None,
+                    transport: None,
+                    client_certificate_mode: None,
                 }),
                 dapr: Some(DaprBluePrint {
                     app_id: Some("myapp".to_string()),
+                    command: None,
                     app_port: Some(3000),
                     enabled: Some(true),
+                    sidecar_image: None,
+                    max_concurrency: None,
+                    http_max_request_size: None,
+                    components: None,
                 }),
+                ..ConfigurationBluePrint::default()
             }),
-            template: Some(TemplateBluePrint {
-                containers: Some(vec![ContainerBluePrint {
-                    name: "myapp".to_string(),
-                    image: "${myImage.name}".to_string(),
-                }]),
-            }),
+            template: Some(TemplateBluePrint::new(vec![ContainerBluePrint::new(
+                "myapp",
+                "${myImage.name}",
+            )])),
+            ..ContainerAppBluePrint::default()
         }];
 
         assert_eq!(expected, output);
@@ -219,8 +594,731 @@ mod tests {
                       name: myapp
           "#;
 
-        let output = deserialize(wrong_format);
+        let output = deserialize(wrong_format, &BuildOptions::default());
+
+        assert_eq!(Err(DeserializeError::Parse("did not find expected key at line 4 column 15, while parsing a block mapping at line 2 column 11".to_string())), output);
+    }
+
+    #[test]
+    fn test_deserialize_strict() {
+        let input = r#"
+      resources:
+        myapp:
+          type: azure-native:app:ContainerApp
+          properties:
+            resourceGroupName: ${resourceGroup.name}
+            configuration:
+              ingress:
+                externl: true
+                targetPort: 80
+            template:
+              containers:
+                - image: node-12
+                  name: myapp
+      "#;
+
+        // Lenient by default, the misspelled field is ignored
+        let output = deserialize(input, &BuildOptions::default());
+        assert!(output.is_ok());
+
+        let options = BuildOptions {
+            strict: true,
+            ..BuildOptions::default()
+        };
+        let output = deserialize(input, &options);
+
+        assert_eq!(
+            Err(DeserializeError::UnknownField(
+                "configuration.ingress.externl".to_string()
+            )),
+            output
+        );
+    }
+
+    #[test]
+    fn test_get_apps_with_template_scale() {
+        let apps = r#"
+      resources:
+        myapp:
+          type: azure-native:app:ContainerApp
+          properties:
+            template:
+              containers:
+                - image: node-12
+                  name: myapp
+              scale:
+                minReplicas: 1
+                maxReplicas: 5
+      "#;
+
+        let value: Value = serde_yaml::from_str(apps).unwrap();
+        let as_mapping = value.get("resources").unwrap().as_mapping().unwrap();
+
+        let output = get_apps(as_mapping).unwrap();
+
+        let expected = vec![ContainerAppBluePrint {
+            name: Some("myapp".to_string()),
+            configuration: Some(ConfigurationBluePrint {
+                scale: Some(ScaleBluePrint {
+                    min_replicas: Some(1),
+                    max_replicas: Some(5),
+                    rules: None,
+                }),
+                ..ConfigurationBluePrint::default()
+            }),
+            template: Some(TemplateBluePrint::new(vec![ContainerBluePrint::new(
+                "myapp", "node-12",
+            )])),
+            ..ContainerAppBluePrint::default()
+        }];
+
+        assert_eq!(expected, output);
+    }
+
+    #[test]
+    fn test_deserialize_user_and_working_dir() {
+        let input = r#"
+      resources:
+        myapp:
+          type: azure-native:app:ContainerApp
+          properties:
+            template:
+              containers:
+                - image: node-12
+                  name: myapp
+                  user: "1000:1000"
+                  workingDir: /app
+      "#;
+
+        let output = deserialize(input, &BuildOptions::default()).unwrap();
+        let service = serde_yaml::to_value(&output[0]).unwrap();
+
+        assert_eq!(Some(&Value::from("1000:1000")), service.get("user"));
+        assert_eq!(Some(&Value::from("/app")), service.get("working_dir"));
+
+        // Absent values omit the keys
+        let input = input
+            .replace("                  user: \"1000:1000\"\n", "")
+            .replace("                  workingDir: /app\n", "");
+        let output = deserialize(&input, &BuildOptions::default()).unwrap();
+        let service = serde_yaml::to_value(&output[0]).unwrap();
+
+        assert_eq!(None, service.get("user"));
+        assert_eq!(None, service.get("working_dir"));
+    }
+
+    #[test]
+    fn test_deserialize_max_inactive_revisions() {
+        let input = r#"
+      resources:
+        myapp:
+          type: azure-native:app:ContainerApp
+          properties:
+            configuration:
+              maxInactiveRevisions: 5
+            template:
+              containers:
+                - image: node-12
+                  name: myapp
+      "#;
+
+        let output = deserialize(input, &BuildOptions::default()).unwrap();
+
+        let expected = BTreeMap::from([("capp.maxInactiveRevisions".to_string(), "5".to_string())]);
+        assert_eq!(Some(expected), output[0].labels);
+
+        let output = deserialize(
+            &input.replace("maxInactiveRevisions: 5", "maxInactiveRevisions: -1"),
+            &BuildOptions::default(),
+        );
+
+        assert!(matches!(output, Err(DeserializeError::Parse(_))));
+    }
+
+    #[test]
+    fn test_deserialize_env_from() {
+        let input = r#"
+      resources:
+        myapp:
+          type: azure-native:app:ContainerApp
+          properties:
+            template:
+              containers:
+                - image: node-12
+                  name: myapp
+                  envFrom:
+                    - appConfig
+      "#;
+        let options = BuildOptions {
+            env_from: BTreeMap::from([(
+                "appConfig".to_string(),
+                BTreeMap::from([
+                    ("API_URL".to_string(), "http://api".to_string()),
+                    ("NODE_ENV".to_string(), "production".to_string()),
+                ]),
+            )]),
+            ..BuildOptions::default()
+        };
+
+        let output = deserialize(input, &options).unwrap();
+
+        assert_eq!(
+            Some(vec![
+                "API_URL=http://api".to_string(),
+                "NODE_ENV=production".to_string()
+            ]),
+            output[0].environment
+        );
+        assert_eq!(None, output[0].labels);
+
+        // Without substitution, the reference is kept as a label
+        let output = deserialize(input, &BuildOptions::default()).unwrap();
+
+        let expected = BTreeMap::from([("capp.envFrom".to_string(), "appConfig".to_string())]);
+        assert_eq!(None, output[0].environment);
+        assert_eq!(Some(expected), output[0].labels);
+    }
+
+    #[test]
+    fn test_compose_roundtrip() {
+        let input = r#"
+      resources:
+        myapp:
+          type: azure-native:app:ContainerApp
+          properties:
+            configuration:
+              dapr:
+                enabled: true
+                appPort: 3000
+                appId: myapp
+              ingress:
+                external: true
+                targetPort: 3000
+            template:
+              containers:
+                - image: node-12
+                  name: myapp
+        backend:
+          type: azure-native:app:ContainerApp
+          properties:
+            template:
+              containers:
+                - image: node-12
+                  name: backend
+                  workingDir: /app
+      "#;
+
+        let services = deserialize(input, &BuildOptions::default()).unwrap();
+        let compose = to_compose_yaml(&services).unwrap();
+
+        let output = from_compose_yaml(&compose).unwrap();
+
+        let expected: Vec<ContainerAppConfiguration> = services
+            .into_iter()
+            .filter(|service| !service.name.ends_with("_dapr"))
+            .collect();
+        assert_eq!(2, expected.len());
+        assert_eq!(expected, output);
+    }
+
+    #[test]
+    fn test_deserialize_location_labels() {
+        let input = r#"
+      resources:
+        myapp:
+          type: azure-native:app:ContainerApp
+          properties:
+            location: westeurope
+            resourceGroupName: my-group
+            template:
+              containers:
+                - image: node-12
+                  name: myapp
+      "#;
+
+        let output = deserialize(input, &BuildOptions::default()).unwrap();
+
+        let expected = BTreeMap::from([
+            ("capp.location".to_string(), "westeurope".to_string()),
+            ("capp.resourceGroup".to_string(), "my-group".to_string()),
+        ]);
+        assert_eq!(Some(expected), output[0].labels);
+
+        // Outputs of other resources are resolved, or the label is dropped
+        let input = input.replace("my-group", "${resourceGroup.name}");
+        let options = BuildOptions {
+            tokens: BTreeMap::from([("resourceGroup.name".to_string(), "shop".to_string())]),
+            ..BuildOptions::default()
+        };
+
+        let output = deserialize(&input, &options).unwrap();
+        assert_eq!(
+            Some(&"shop".to_string()),
+            output[0].labels.as_ref().unwrap().get("capp.resourceGroup")
+        );
+
+        let output = deserialize(&input, &BuildOptions::default()).unwrap();
+        let expected = BTreeMap::from([("capp.location".to_string(), "westeurope".to_string())]);
+        assert_eq!(Some(expected), output[0].labels);
+    }
+
+    #[test]
+    fn test_deserialize_termination_grace_period() {
+        let input = r#"
+      resources:
+        myapp:
+          type: azure-native:app:ContainerApp
+          properties:
+            template:
+              terminationGracePeriodSeconds: 45
+              containers:
+                - image: node-12
+                  name: myapp
+      "#;
+
+        let output = deserialize(input, &BuildOptions::default()).unwrap();
+        let service = serde_yaml::to_value(&output[0]).unwrap();
+
+        assert_eq!(Some(&Value::from("45s")), service.get("stop_grace_period"));
+
+        // Absent value omits the key
+        let input = input.replace("              terminationGracePeriodSeconds: 45\n", "");
+        let output = deserialize(&input, &BuildOptions::default()).unwrap();
+        let service = serde_yaml::to_value(&output[0]).unwrap();
+
+        assert_eq!(None, service.get("stop_grace_period"));
+    }
+
+    const ANCHORED_PROGRAM: &str = r#"
+      resources:
+        api:
+          type: azure-native:app:ContainerApp
+          properties:
+            configuration:
+              dapr: &commonDapr
+                enabled: true
+                appPort: 3000
+                appProtocol: http
+            template:
+              containers:
+                - &nodeContainer
+                  image: node-12
+                  name: node
+        worker:
+          type: azure-native:app:ContainerApp
+          properties:
+            configuration:
+              dapr: *commonDapr
+            template:
+              containers:
+                - <<: *nodeContainer
+                  name: worker
+        scheduler:
+          type: azure-native:app:ContainerApp
+          properties:
+            configuration:
+              dapr: *commonDapr
+            template:
+              containers:
+                - <<: *nodeContainer
+                  name: scheduler
+        frontend:
+          type: azure-native:app:ContainerApp
+          properties:
+            configuration:
+              dapr:
+                <<: *commonDapr
+                appPort: 8080
+            template:
+              containers:
+                - image: remix
+                  name: frontend
+      "#;
+
+    const EXPANDED_PROGRAM: &str = r#"
+      resources:
+        api:
+          type: azure-native:app:ContainerApp
+          properties:
+            configuration:
+              dapr:
+                enabled: true
+                appPort: 3000
+                appProtocol: http
+            template:
+              containers:
+                - image: node-12
+                  name: node
+        worker:
+          type: azure-native:app:ContainerApp
+          properties:
+            configuration:
+              dapr:
+                enabled: true
+                appPort: 3000
+                appProtocol: http
+            template:
+              containers:
+                - image: node-12
+                  name: worker
+        scheduler:
+          type: azure-native:app:ContainerApp
+          properties:
+            configuration:
+              dapr:
+                enabled: true
+                appPort: 3000
+                appProtocol: http
+            template:
+              containers:
+                - image: node-12
+                  name: scheduler
+        frontend:
+          type: azure-native:app:ContainerApp
+          properties:
+            configuration:
+              dapr:
+                enabled: true
+                appPort: 8080
+                appProtocol: http
+            template:
+              containers:
+                - image: remix
+                  name: frontend
+      "#;
+
+    #[test]
+    fn test_deserialize_with_anchors() {
+        let output = deserialize(ANCHORED_PROGRAM, &BuildOptions::default()).unwrap();
+
+        assert_eq!(
+            deserialize(EXPANDED_PROGRAM, &BuildOptions::default()).unwrap(),
+            output
+        );
+        assert_eq!(8, output.len());
+
+        // The merged block keeps the overridden key and the other anchored ones
+        let ports: Vec<&str> = output
+            .iter()
+            .filter_map(|service| service.command.as_ref())
+            .map(|command| command[4].as_str())
+            .collect();
+        assert_eq!(vec!["3000", "3000", "3000", "8080"], ports);
+        assert_eq!(
+            vec!["node-12", "node-12", "node-12", "remix"],
+            output
+                .iter()
+                .filter(|service| service.command.is_none())
+                .filter_map(|service| service.image.as_deref())
+                .collect::<Vec<_>>()
+        );
+    }
+
+    const CONFIG_PROGRAM: &str = r#"
+      name: shop
+      config:
+        appPort:
+          type: integer
+          default: 80
+        nodeVersion: "16"
+      resources:
+        registry:
+          type: azure-native:containerregistry:Registry
+        myapp:
+          type: azure-native:app:ContainerApp
+          properties:
+            configuration:
+              ingress:
+                external: true
+                targetPort: ${appPort}
+            template:
+              containers:
+                - image: ${registry.loginServer}/node:${nodeVersion}-${pulumi.stack}
+                  name: ${pulumi.project}
+                  env:
+                    - name: REGION
+                      value: ${azure-native:location}
+      "#;
+
+    #[test]
+    fn test_deserialize_with_config_variables() {
+        let options = BuildOptions {
+            tokens: BTreeMap::from([
+                ("pulumi.stack".to_string(), "dev".to_string()),
+                (
+                    "registry.loginServer".to_string(),
+                    "shop.azurecr.io".to_string(),
+                ),
+            ]),
+            config: pulumi::parse_stack_config(
+                r#"
+                config:
+                  shop:appPort: "3000"
+                  shop:secret:
+                    secure: AAABAKbd
+                  other:nodeVersion: "12"
+                "#,
+            )
+            .unwrap(),
+            ..BuildOptions::default()
+        };
+
+        let output = deserialize(CONFIG_PROGRAM, &options).unwrap();
+
+        assert_eq!(Some(vec!["3000:3000".to_string()]), output[0].ports);
+        assert_eq!(
+            Some("shop.azurecr.io/node:16-dev".to_string()),
+            output[0].image
+        );
+        assert_eq!("shop", output[0].name);
+
+        // Without stack config, the defaults of the program are used
+        let options = BuildOptions {
+            config: BTreeMap::new(),
+            ..options
+        };
+        let output = deserialize(CONFIG_PROGRAM, &options).unwrap();
+
+        assert_eq!(Some(vec!["80:80".to_string()]), output[0].ports);
+    }
+
+    #[test]
+    fn test_collect_warnings_with_config_variables() {
+        let output = collect_warnings(CONFIG_PROGRAM, &BuildOptions::default());
+
+        assert_eq!(
+            vec![
+                "unresolved variable ${pulumi.stack} at template.containers[0].image in myapp",
+                "unresolved variable ${azure-native:location} at template.containers[0].env[0].value in myapp",
+            ],
+            output[..2]
+        );
+    }
+
+    const NATIVE_APP: &str = r#"
+        myapp:
+          type: azure-native:app:ContainerApp
+          properties:
+            managedEnvironmentId: ${env.id}
+            configuration:
+              ingress:
+                external: true
+                targetPort: 3000
+              dapr:
+                enabled: true
+                appId: myapp
+                appPort: 3000
+            template:
+              scale:
+                minReplicas: 1
+                maxReplicas: 3
+              containers:
+                - image: node-12
+                  name: myapp
+                  resources:
+                    cpu: 0.5
+                    memory: 1Gi
+                  probes:
+                    - type: Liveness
+                      httpGet:
+                        path: /health
+                        port: 3000
+                        scheme: HTTP
+                      periodSeconds: 10
+"#;
+
+    const CLASSIC_APP: &str = r#"
+        myapp:
+          type: azure:containerapp:App
+          properties:
+            container_app_environment_id: ${env.id}
+            ingress:
+              externalEnabled: true
+              target_port: 3000
+            dapr:
+              appId: myapp
+              appPort: 3000
+            template:
+              minReplicas: 1
+              maxReplicas: 3
+              containers:
+                - image: node-12
+                  name: myapp
+                  cpu: 0.5
+                  memory: 1Gi
+                  livenessProbes:
+                    - transport: HTTP
+                      path: /health
+                      port: 3000
+                      intervalSeconds: 10
+"#;
+
+    #[test]
+    fn test_deserialize_classic_provider() {
+        let program = format!(
+            "resources:{}{}",
+            NATIVE_APP,
+            CLASSIC_APP
+                .replace("myapp", "legacy")
+                .replace("3000", "4000")
+        );
+        let output = deserialize(&program, &BuildOptions::default()).unwrap();
+
+        assert_eq!(
+            vec!["myapp", "myapp_dapr", "legacy", "legacy_dapr"],
+            output
+                .iter()
+                .map(|service| service.name.as_str())
+                .collect::<Vec<_>>()
+        );
+
+        // Both shapes produce the same services
+        let native = deserialize(
+            &format!("resources:{}", NATIVE_APP),
+            &BuildOptions::default(),
+        );
+        let classic = deserialize(
+            &format!("resources:{}", CLASSIC_APP),
+            &BuildOptions::default(),
+        );
+
+        assert_eq!(native, classic);
+        assert_eq!(
+            vec![
+                "ignored template.containers[0].probes[0].httpGet.scheme in myapp",
+                "ignored template.containers[0].probes[0].httpGet.scheme in legacy"
+            ],
+            collect_warnings(&program, &BuildOptions::default())
+        );
+    }
+
+    #[test]
+    fn test_deserialize_versioned_types() {
+        let input = r#"
+      resources:
+        myapp:
+          type: azure-native:app:ContainerApp
+          properties:
+            template:
+              containers:
+                - image: node-12
+                  name: myapp
+        pinned:
+          type: azure-native:app/v20230501:ContainerApp
+          properties:
+            template:
+              containers:
+                - image: node-12
+                  name: pinned
+        preview:
+          type: azure-native:app/v20240802preview:ContainerApp
+          properties:
+            template:
+              containers:
+                - image: node-12
+                  name: preview
+      "#;
+        let mapping: Value = serde_yaml::from_str(input).unwrap();
+        let apps = get_apps(mapping["resources"].as_mapping().unwrap()).unwrap();
+
+        assert_eq!(
+            vec![None, Some("v20230501"), Some("v20240802preview")],
+            apps.iter()
+                .map(|app| app.api_version.as_deref())
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            3,
+            deserialize(input, &BuildOptions::default()).unwrap().len()
+        );
+        assert_eq!(
+            vec!["api version v20240802preview of preview is newer than v20240301, its new fields are not mapped"],
+            collect_warnings(input, &BuildOptions::default())
+        );
+    }
+
+    #[test]
+    fn test_deserialize_scheduled_job() {
+        let input = r#"
+      resources:
+        myImage:
+          type: docker:RegistryImage
+          properties:
+            name: node-app:v1.0.0
+            build:
+              context: ./node-app
+        cleanup:
+          type: azure-native:app:Job
+          properties:
+            configuration:
+              triggerType: Schedule
+              replicaTimeout: 300
+              scheduleTriggerConfig:
+                cronExpression: "*/5 * * * *"
+                parallelism: 1
+            template:
+              containers:
+                - image: ${myImage.imageName}
+                  name: cleanup
+      "#;
+
+        let output = deserialize(input, &BuildOptions::default()).unwrap();
+
+        assert_eq!(1, output.len());
+        assert_eq!("cleanup", output[0].name);
+        assert!(output[0].build.is_some());
+        assert_eq!(None, output[0].image);
+        assert_eq!(Some("no".to_string()), output[0].restart);
+        assert_eq!(Some(vec!["jobs".to_string()]), output[0].profiles);
+        assert_eq!(
+            Some(BTreeMap::from([
+                ("capp.job.schedule".to_string(), "*/5 * * * *".to_string()),
+                ("capp.job.trigger".to_string(), "Schedule".to_string()),
+            ])),
+            output[0].labels
+        );
+    }
+
+    #[test]
+    fn test_deserialize_container_env() {
+        let input = r#"
+      resources:
+        myapp:
+          type: azure-native:app:ContainerApp
+          properties:
+            template:
+              containers:
+                - image: node-12
+                  name: myapp
+                  env:
+                    - name: PORT
+                      value: "3000"
+                    - name: EMPTY
+                      value: ""
+                    - name: GREETING
+                      value: hello "capp" world
+                    - name: DATABASE_URL
+                      value: postgres://db:5432/app?ssl=true&mode=a=b
+                    - name: DB_PASSWORD
+                      secretRef: db-password
+                    - name: PRICE
+                      value: cost$5
+                    - name: API_URL
+                      value: ${api.url}
+      "#;
+
+        let output = deserialize(input, &BuildOptions::default()).unwrap();
 
-        assert_eq!(Err("did not find expected key at line 4 column 15, while parsing a block mapping at line 2 column 11".to_string()), output);
+        assert_eq!(
+            Some(vec![
+                "PORT=3000".to_string(),
+                "EMPTY=".to_string(),
+                "GREETING=hello \"capp\" world".to_string(),
+                "DATABASE_URL=postgres://db:5432/app?ssl=true&mode=a=b".to_string(),
+                "DB_PASSWORD=${DB_PASSWORD}".to_string(),
+                "PRICE=cost$$5".to_string(),
+            ]),
+            output[0].environment
+        );
     }
 }
@@ -1,74 +1,796 @@
+use crate::error::DeserializeError;
+use regex::Regex;
 use serde::{Deserialize, Serialize};
 use serde_yaml::Mapping;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::Read;
+use std::sync::Arc;
 
-#[derive(Debug, Clone, Copy)]
+// Longest command written as a flow sequence by `CommandStyle::Auto`
+const FLOW_COMMAND_MAX_ARGUMENTS: usize = 3;
+/// Indent of the keys of a service, below `services:` and the service name
+const SERVICE_KEY_INDENT: &str = "    ";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Language {
     Yaml,
     Typescript,
     Javascript,
+    Python,
+    CSharp,
+    Go,
+    /// State of a stack, resolved (eg: pulumi stack export) or planned (eg: pulumi preview --json)
+    StackExport,
     Json,
+    Arm,
     Bicep,
+    /// Terraform configuration (eg: main.tf)
+    Hcl,
+    /// Language of a Pulumi program detected from its input, see `pulumi::detect_language`
+    Auto,
     NotSupported,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
 pub struct BuildContext {
     pub context: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dockerfile: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub args: Option<BTreeMap<String, String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub platforms: Option<Vec<String>>,
+    /// Local tags of the built image (eg: node-app:v1 for ${registry.loginServer}/node-app:v1)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tags: Option<Vec<String>>,
 }
 
+/// Custom command, either as a shell string or as an argument vector
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(untagged)]
+pub enum CommandBluePrint {
+    Shell(String),
+    Args(Vec<String>),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
 #[serde(rename_all = "camelCase")]
+#[non_exhaustive]
 pub struct DaprBluePrint {
     pub app_port: Option<u32>,
     pub enabled: Option<bool>,
     pub app_id: Option<String>,
+    pub command: Option<CommandBluePrint>,
+    pub sidecar_image: Option<String>,
+    /// Translated to `-app-max-concurrency`
+    pub max_concurrency: Option<i64>,
+    /// Translated to `-dapr-http-max-request-size`, in MB
+    pub http_max_request_size: Option<i64>,
+    /// Components of the app (eg: state stores, pub/sub)
+    pub components: Option<Vec<DaprComponentBluePrint>>,
 }
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+#[non_exhaustive]
+pub struct DaprComponentBluePrint {
+    pub name: String,
+    /// Component type (eg: state.redis)
+    #[serde(rename = "type")]
+    pub component_type: String,
+    pub metadata: Option<BTreeMap<String, serde_yaml::Value>>,
+}
+
+/// Global Dapr settings applied to every app unless overridden by the app itself
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DaprRuntimeOptions {
+    pub sidecar_image: Option<String>,
+}
+
+/// Resolves the `${...}` tokens of the images (eg: `registry.loginServer`)
+pub trait TokenResolver {
+    fn resolve(&self, token: &str) -> Option<String>;
+}
+
+/// Pulumi built-in tokens and the given substitutions
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DefaultTokenResolver {
+    pub tokens: BTreeMap<String, String>,
+    /// Outputs of the `StackReference`s, by output name
+    pub stack_outputs: BTreeMap<String, String>,
+}
+
+impl TokenResolver for DefaultTokenResolver {
+    fn resolve(&self, token: &str) -> Option<String> {
+        match (self.tokens.get(token), token) {
+            (Some(value), _) => Some(value.clone()),
+            // Build contexts are relative to the program
+            (None, "pulumi.cwd") => Some(".".to_string()),
+            (None, _) => stack_output_name(token)
+                .and_then(|name| self.stack_outputs.get(&name))
+                .cloned(),
+        }
+    }
+}
+
+/***
+ * Name of the output of a `StackReference` token (eg: imageName for stackRef.outputs["imageName"])
+ */
+pub fn stack_output_name(token: &str) -> Option<String> {
+    stack_output_reference(token).map(|(_, name)| name)
+}
+
+/***
+ * Variable of the `StackReference` and name of the output of a token
+ * (eg: (stackRef, imageName) for stackRef.outputs["imageName"])
+ */
+pub fn stack_output_reference(token: &str) -> Option<(String, String)> {
+    Regex::new(r#"^([A-Za-z_$][\w$]*)\.outputs\[\s*["']([^"']+)["']\s*\]$"#)
+        .expect("Should match stack output regex")
+        .captures(token)
+        .map(|captures| (captures[1].to_string(), captures[2].to_string()))
+}
+
+/// A user resolver, shared between the builds
+#[derive(Clone)]
+pub struct CustomTokenResolver(pub Arc<dyn TokenResolver + Send + Sync>);
+
+impl fmt::Debug for CustomTokenResolver {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("CustomTokenResolver")
+    }
+}
+
+impl PartialEq for CustomTokenResolver {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+/// How the services read the secrets of their app
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum SecretsMode {
+    /// Variables of the host or its `.env` file (eg: DB_PASSWORD=${DB_PASSWORD})
+    #[default]
+    Env,
+    /// Compose secrets read from files (eg: ./secrets/myapp/db-password), their path
+    /// is given in the `<variable>_FILE` variable (eg: DB_PASSWORD_FILE=/run/secrets/db-password)
+    Files,
+}
+
+/// How the Azure Files shares are mounted in the services
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum AzureFilesMode {
+    /// Named volumes of the storage name, shared by the apps
+    #[default]
+    Volume,
+    /// Folders of the host named after the storage (eg: ./volumes/share)
+    Bind,
+}
+
+/// How the `command` sequences of the services are rendered
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum CommandStyle {
+    /// Flow sequences up to 3 arguments, one argument per line for the longer commands
+    #[default]
+    Auto,
+    /// One argument per line
+    Block,
+    /// Arguments on the line of the key (eg: command: ["npm", "start"])
+    Flow,
+}
+
+/// Options applied while writing the compose files
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SerializeOptions {
+    pub command_style: CommandStyle,
+}
+
+/// Options applied while building the compose services
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BuildOptions {
+    pub dapr: DaprRuntimeOptions,
+    /// Prefix applied to resolved build contexts (eg: the folder of the input file)
+    pub base_path: Option<String>,
+    /// Fail on keys the blueprints do not know instead of ignoring them
+    pub strict: bool,
+    /// Names of the apps to serialize, all of them when not set
+    pub include: Option<Vec<String>>,
+    /// Restart policy of the app services, `unless-stopped` when not set
+    pub restart: Option<String>,
+    /// Values of the Pulumi tokens (eg: `pulumi.stack` for `${pulumi.stack}`)
+    pub tokens: BTreeMap<String, String>,
+    /// Outputs of the `StackReference`s (eg: `imageName` for `${stackRef.outputs["imageName"]}`)
+    pub stack_outputs: BTreeMap<String, String>,
+    /// Values of the stack config (eg: the `config` of Pulumi.dev.yaml), by namespaced key
+    pub config: BTreeMap<String, serde_yaml::Value>,
+    /// Values of the template parameters (eg: the `parameters` of an ARM parameters file), by name
+    pub parameters: BTreeMap<String, serde_json::Value>,
+    /// Variables of the configs referenced by `envFrom`, by config name
+    pub env_from: BTreeMap<String, BTreeMap<String, String>>,
+    /// Serialize every app without its Dapr sidecar, whatever `dapr.enabled`
+    pub disable_dapr: bool,
+    pub secrets: SecretsMode,
+    pub azure_files: AzureFilesMode,
+    /// Consulted before the tokens and the Pulumi built-in ones
+    pub token_resolver: Option<CustomTokenResolver>,
+}
+
+impl TokenResolver for BuildOptions {
+    fn resolve(&self, token: &str) -> Option<String> {
+        self.token_resolver
+            .as_ref()
+            .and_then(|resolver| resolver.0.resolve(token))
+            .or_else(|| {
+                DefaultTokenResolver {
+                    tokens: self.tokens.clone(),
+                    stack_outputs: self.stack_outputs.clone(),
+                }
+                .resolve(token)
+            })
+            .or_else(|| {
+                // Stack config values, with or without their namespace (eg: ${config.root} for myproject:root)
+                let key = token.strip_prefix("config.")?;
+                let value = self.config.iter().find_map(|(name, value)| {
+                    let name = name.split_once(':').map_or(name.as_str(), |(_, name)| name);
+                    (name == key).then_some(value)
+                })?;
+
+                match value {
+                    serde_yaml::Value::String(value) => Some(value.clone()),
+                    serde_yaml::Value::Number(value) => Some(value.to_string()),
+                    serde_yaml::Value::Bool(value) => Some(value.to_string()),
+                    // Secure values are encrypted
+                    _ => None,
+                }
+            })
+    }
+}
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+#[serde(rename_all = "camelCase")]
+#[non_exhaustive]
+pub struct CorsPolicyBluePrint {
+    pub allowed_origins: Option<Vec<String>>,
+    pub allowed_methods: Option<Vec<String>>,
+}
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+#[serde(rename_all = "camelCase")]
+#[non_exhaustive]
+pub struct CustomDomain {
+    pub name: String,
+    pub binding_type: Option<String>,
+    pub certificate_id: Option<String>,
+}
+/// Port exposed next to the main ingress target port
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
 #[serde(rename_all = "camelCase")]
+#[non_exhaustive]
+pub struct PortMappingBluePrint {
+    pub external: Option<bool>,
+    pub target_port: Option<u32>,
+    /// Defaults to the target port
+    pub exposed_port: Option<u32>,
+}
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+#[serde(rename_all = "camelCase")]
+#[non_exhaustive]
 pub struct IngressBluePrint {
     pub external: Option<bool>,
     pub target_port: Option<u32>,
+    pub cors_policy: Option<CorsPolicyBluePrint>,
+    pub custom_domains: Option<Vec<CustomDomain>>,
+    pub additional_port_mappings: Option<Vec<PortMappingBluePrint>>,
+    /// Ingress transport (eg: auto, http, http2, tcp)
+    pub transport: Option<String>,
+    /// Mutual TLS mode of the client certificates
+    pub client_certificate_mode: Option<ClientCertificateMode>,
 }
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ClientCertificateMode {
+    Require,
+    Accept,
+    Ignore,
+}
+
+impl fmt::Display for ClientCertificateMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ClientCertificateMode::Require => write!(f, "require"),
+            ClientCertificateMode::Accept => write!(f, "accept"),
+            ClientCertificateMode::Ignore => write!(f, "ignore"),
+        }
+    }
+}
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+#[non_exhaustive]
+pub struct HttpScaleRuleBluePrint {
+    pub metadata: Option<BTreeMap<String, serde_yaml::Value>>,
+}
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+#[non_exhaustive]
+pub struct CustomScaleRuleBluePrint {
+    #[serde(rename = "type")]
+    pub rule_type: Option<String>,
+    pub metadata: Option<BTreeMap<String, serde_yaml::Value>>,
+}
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+#[non_exhaustive]
+pub struct ScaleRuleBluePrint {
+    pub name: Option<String>,
+    pub http: Option<HttpScaleRuleBluePrint>,
+    pub custom: Option<CustomScaleRuleBluePrint>,
+}
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+#[serde(rename_all = "camelCase")]
+#[non_exhaustive]
+pub struct ScaleBluePrint {
+    pub min_replicas: Option<u32>,
+    pub max_replicas: Option<u32>,
+    pub rules: Option<Vec<ScaleRuleBluePrint>>,
+}
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[non_exhaustive]
 pub struct ConfigurationBluePrint {
     pub ingress: Option<IngressBluePrint>,
     pub dapr: Option<DaprBluePrint>,
+    pub scale: Option<ScaleBluePrint>,
+    #[serde(rename = "maxInactiveRevisions")]
+    pub max_inactive_revisions: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub secrets: Option<Vec<SecretBluePrint>>,
 }
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+/// Secret of an app, its value is never written to the outputs
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+#[non_exhaustive]
+pub struct SecretBluePrint {
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value: Option<serde_yaml::Value>,
+    /// Key Vault secret the value is read from, it cannot be read locally
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub key_vault_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub identity: Option<String>,
+}
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[non_exhaustive]
 pub struct TemplateBluePrint {
     pub containers: Option<Vec<ContainerBluePrint>>,
+    /// Run once before the app containers, never restarted
+    #[serde(rename = "initContainers", skip_serializing_if = "Option::is_none")]
+    pub init_containers: Option<Vec<ContainerBluePrint>>,
+    /// Appended to the service names once its tokens are resolved
+    #[serde(rename = "revisionSuffix", skip_serializing_if = "Option::is_none")]
+    pub revision_suffix: Option<String>,
+    /// Moved to `configuration.scale` by the parsers
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scale: Option<ScaleBluePrint>,
+    #[serde(
+        rename = "terminationGracePeriodSeconds",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub termination_grace_period_seconds: Option<u32>,
+    /// Volumes the containers of the app mount
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub volumes: Option<Vec<VolumeBluePrint>>,
 }
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+#[non_exhaustive]
+pub struct VolumeBluePrint {
+    pub name: String,
+    /// EmptyDir when not set, AzureFile, NfsAzureFile or Secret
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub storage_type: Option<String>,
+    /// Storage of the managed environment holding the file share
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub storage_name: Option<String>,
+}
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+#[non_exhaustive]
+pub struct VolumeMountBluePrint {
+    pub volume_name: String,
+    pub mount_path: String,
+    /// Folder of the volume mounted instead of its root
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sub_path: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub read_only: Option<bool>,
+}
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+#[non_exhaustive]
+pub struct HttpGetProbeBluePrint {
+    pub path: Option<String>,
+    pub port: Option<u32>,
+}
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+#[non_exhaustive]
+pub struct TcpSocketProbeBluePrint {
+    pub port: Option<u32>,
+}
+/// Command run in the container, the probe fails on a non zero exit code
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+#[non_exhaustive]
+pub struct ExecProbeBluePrint {
+    pub command: Vec<String>,
+}
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+#[serde(rename_all = "camelCase")]
+#[non_exhaustive]
+pub struct ProbeBluePrint {
+    /// Liveness, Readiness or Startup
+    #[serde(rename = "type")]
+    pub probe_type: Option<String>,
+    pub http_get: Option<HttpGetProbeBluePrint>,
+    pub tcp_socket: Option<TcpSocketProbeBluePrint>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exec: Option<ExecProbeBluePrint>,
+    /// Timings are seconds, given as integers or durations (eg: 10s, 1m)
+    #[serde(default, deserialize_with = "deserialize_duration_seconds")]
+    pub initial_delay_seconds: Option<u32>,
+    #[serde(default, deserialize_with = "deserialize_duration_seconds")]
+    pub period_seconds: Option<u32>,
+    #[serde(default, deserialize_with = "deserialize_duration_seconds")]
+    pub timeout_seconds: Option<u32>,
+    pub failure_threshold: Option<u32>,
+}
+
+/***
+ * Seconds of a duration with a single unit (eg: 10s, 1m, 1h), plain numbers are seconds
+ */
+fn parse_duration_seconds(duration: &str) -> Option<u32> {
+    let duration = duration.trim();
+    let (value, multiplier) = match duration.char_indices().last()? {
+        (i, 's') => (&duration[..i], 1),
+        (i, 'm') => (&duration[..i], 60),
+        (i, 'h') => (&duration[..i], 3600),
+        _ => (duration, 1),
+    };
+
+    value.trim().parse::<u32>().ok()?.checked_mul(multiplier)
+}
+
+fn deserialize_duration_seconds<'de, D>(deserializer: D) -> Result<Option<u32>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Duration {
+        Seconds(u32),
+        Text(String),
+    }
+
+    match Option::<Duration>::deserialize(deserializer)? {
+        Some(Duration::Seconds(seconds)) => Ok(Some(seconds)),
+        Some(Duration::Text(text)) => parse_duration_seconds(&text)
+            .map(Some)
+            .ok_or_else(|| serde::de::Error::custom(format!("invalid duration: {}", text))),
+        None => Ok(None),
+    }
+}
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+#[non_exhaustive]
+pub struct ContainerResourcesBluePrint {
+    /// Number of cores (eg: 0.5)
+    pub cpu: Option<f64>,
+    /// Memory with its unit (eg: 1Gi)
+    pub memory: Option<String>,
+}
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[non_exhaustive]
 pub struct ContainerBluePrint {
     pub image: String,
     pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub probes: Option<Vec<ProbeBluePrint>>,
+    /// Run-as user (eg: 1000:1000)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user: Option<String>,
+    #[serde(rename = "workingDir", skip_serializing_if = "Option::is_none")]
+    pub working_dir: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub resources: Option<ContainerResourcesBluePrint>,
+    /// Configs the whole environment is pulled from
+    #[serde(rename = "envFrom", skip_serializing_if = "Option::is_none")]
+    pub env_from: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub env: Option<Vec<EnvVarBluePrint>>,
+    #[serde(rename = "volumeMounts", skip_serializing_if = "Option::is_none")]
+    pub volume_mounts: Option<Vec<VolumeMountBluePrint>>,
+}
+
+/// Environment variable of a container, set from its value or from a secret of the app
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+#[non_exhaustive]
+pub struct EnvVarBluePrint {
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value: Option<serde_yaml::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub secret_ref: Option<String>,
+}
+
+impl ContainerBluePrint {
+    pub fn new(name: impl Into<String>, image: impl Into<String>) -> Self {
+        ContainerBluePrint {
+            name: name.into(),
+            image: image.into(),
+            ..Default::default()
+        }
+    }
+}
+
+impl TemplateBluePrint {
+    pub fn new(containers: Vec<ContainerBluePrint>) -> Self {
+        TemplateBluePrint {
+            containers: Some(containers),
+            ..Default::default()
+        }
+    }
 }
+/// User assigned identities, either as a list of ids or as a map keyed by id
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(untagged)]
+pub enum UserAssignedIdentities {
+    List(Vec<String>),
+    Map(BTreeMap<String, serde_yaml::Value>),
+}
+
+impl UserAssignedIdentities {
+    pub fn ids(&self) -> Vec<String> {
+        match self {
+            UserAssignedIdentities::List(ids) => ids.clone(),
+            UserAssignedIdentities::Map(ids) => ids.keys().cloned().collect(),
+        }
+    }
+}
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+#[serde(rename_all = "camelCase")]
+#[non_exhaustive]
+pub struct Identity {
+    #[serde(rename = "type")]
+    pub identity_type: String,
+    pub user_assigned_identities: Option<UserAssignedIdentities>,
+}
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[non_exhaustive]
 pub struct ContainerAppBluePrint {
+    /// Name of the app resource, set by the parsers
+    #[serde(skip)]
+    pub name: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub configuration: Option<ConfigurationBluePrint>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub template: Option<TemplateBluePrint>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub identity: Option<Identity>,
+    #[serde(
+        rename = "workloadProfileName",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub workload_profile_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub location: Option<String>,
+    #[serde(rename = "resourceGroupName", skip_serializing_if = "Option::is_none")]
+    pub resource_group_name: Option<String>,
+    /// API version pinned by the resource type (eg: v20230501), set by the parsers
+    #[serde(skip)]
+    pub api_version: Option<String>,
+    /// Trigger of the job the app comes from, set by the parsers
+    #[serde(skip)]
+    pub job: Option<JobConfigurationBluePrint>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+impl ContainerAppBluePrint {
+    pub fn new(name: impl Into<String>) -> Self {
+        ContainerAppBluePrint {
+            name: Some(name.into()),
+            ..Default::default()
+        }
+    }
+
+    pub fn with_configuration(mut self, configuration: ConfigurationBluePrint) -> Self {
+        self.configuration = Some(configuration);
+        self
+    }
+
+    pub fn with_template(mut self, template: TemplateBluePrint) -> Self {
+        self.template = Some(template);
+        self
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+#[non_exhaustive]
+pub struct ScheduleTriggerConfigBluePrint {
+    pub cron_expression: String,
+    pub parallelism: Option<u32>,
+    pub replica_completion_count: Option<u32>,
+}
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+#[non_exhaustive]
+pub struct ManualTriggerConfigBluePrint {
+    pub parallelism: Option<u32>,
+    pub replica_completion_count: Option<u32>,
+}
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+#[non_exhaustive]
+pub struct JobConfigurationBluePrint {
+    /// Schedule, Manual or Event
+    pub trigger_type: String,
+    pub schedule_trigger_config: Option<ScheduleTriggerConfigBluePrint>,
+    pub manual_trigger_config: Option<ManualTriggerConfigBluePrint>,
+    pub replica_timeout: Option<u32>,
+    pub replica_retry_limit: Option<u32>,
+}
+/// Container Apps job (eg: azure-native:app:Job), built as an app which runs once
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[non_exhaustive]
+pub struct JobBluePrint {
+    /// Name of the job resource, set by the parsers
+    #[serde(skip)]
+    pub name: Option<String>,
+    pub configuration: Option<JobConfigurationBluePrint>,
+    pub template: Option<TemplateBluePrint>,
+    pub location: Option<String>,
+    #[serde(rename = "resourceGroupName")]
+    pub resource_group_name: Option<String>,
+    /// API version pinned by the resource type (eg: v20230501), set by the parsers
+    #[serde(skip)]
+    pub api_version: Option<String>,
+}
+
+impl From<JobBluePrint> for ContainerAppBluePrint {
+    fn from(job: JobBluePrint) -> Self {
+        ContainerAppBluePrint {
+            name: job.name,
+            template: job.template,
+            location: job.location,
+            resource_group_name: job.resource_group_name,
+            api_version: job.api_version,
+            job: Some(job.configuration.unwrap_or_default()),
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[non_exhaustive]
 pub struct BuildContextBluePrint {
     pub context: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dockerfile: Option<String>,
+    /// Build arguments as written, numbers included
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub args: Option<BTreeMap<String, serde_yaml::Value>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target: Option<String>,
+    /// Single platform of the image (eg: linux/amd64)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub platform: Option<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+impl BuildContextBluePrint {
+    pub fn new(context: impl Into<String>) -> Self {
+        BuildContextBluePrint {
+            context: context.into(),
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+#[non_exhaustive]
 pub struct ContainerImageBluePrint {
     pub name: Option<String>,
     pub build: BuildContextBluePrint,
     pub reference_name: Option<String>,
 }
 
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum DependencyCondition {
+    #[default]
+    ServiceStarted,
+    ServiceHealthy,
+    ServiceCompletedSuccessfully,
+}
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ServiceDependency {
+    #[serde(default)]
+    pub condition: DependencyCondition,
+}
+/// Short form waits for the services to be started, long form carries a condition
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(untagged)]
+pub enum DependsOn {
+    Services(Vec<String>),
+    Conditions(BTreeMap<String, ServiceDependency>),
+}
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct HealthCheck {
+    pub test: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub interval: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retries: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub start_period: Option<String>,
+}
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ResourceLimits {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cpus: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memory: Option<String>,
+}
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct DeployResources {
+    pub limits: ResourceLimits,
+}
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Deploy {
+    /// Instances of the service, from the scale `minReplicas`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub replicas: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub resources: Option<DeployResources>,
+}
+/// Volume or host folder mounted in a service, the volumes are declared in the top level `volumes`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ServiceVolume {
+    /// volume or bind
+    #[serde(rename = "type")]
+    pub volume_type: String,
+    pub source: String,
+    pub target: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub read_only: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub volume: Option<VolumeOptions>,
+    /// Azure Files share of a named volume, kept as a label of its declaration
+    #[serde(skip)]
+    pub share: Option<String>,
+}
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct VolumeOptions {
+    /// Folder of the volume mounted instead of its root
+    pub subpath: String,
+}
+/// Secret mounted in a service, its file is declared in the top level `secrets`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ServiceSecret {
+    pub source: String,
+    pub target: String,
+    #[serde(skip)]
+    pub file: String,
+}
+/// Config mounted in a service, its content is declared in the top level `configs`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ServiceConfig {
+    pub source: String,
+    pub target: String,
+    #[serde(skip)]
+    pub content: Option<String>,
+}
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[non_exhaustive]
 pub struct ContainerAppConfiguration {
     #[serde(skip_serializing)]
     pub name: String,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub depends_on: Option<Vec<String>>,
+    pub depends_on: Option<DependsOn>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub networks: Option<Vec<String>>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -80,42 +802,758 @@ pub struct ContainerAppConfiguration {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub command: Option<Vec<String>>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    pub healthcheck: Option<HealthCheck>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub restart: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub working_dir: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deploy: Option<Deploy>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stop_grace_period: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub configs: Option<Vec<ServiceConfig>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub secrets: Option<Vec<ServiceSecret>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub volumes: Option<Vec<ServiceVolume>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub network_mode: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub build: Option<BuildContext>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub labels: Option<BTreeMap<String, String>>,
+    /// Services of a profile only start when it is enabled (eg: docker compose --profile jobs up)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub profiles: Option<Vec<String>>,
+    /// Compose fields not modelled (eg: cap_add), merged last into the service
+    #[serde(skip)]
+    pub extra: Option<Mapping>,
+}
+
+impl ContainerAppConfiguration {
+    /// A service named `name` with every other field unset
+    pub fn new(name: impl Into<String>) -> Self {
+        ContainerAppConfiguration {
+            name: name.into(),
+            ..Default::default()
+        }
+    }
+
+    /// Invariants of a single service, whatever the other services
+    pub fn validate_self(&self) -> Result<(), Vec<String>> {
+        let mut errors: Vec<String> = vec![];
+
+        if self.name.trim().is_empty() {
+            errors.push("Service has no name".to_string());
+        }
+
+        match (&self.image, &self.build) {
+            (Some(_), Some(_)) => {
+                errors.push(format!("Service sets both image and build: {}", self.name))
+            }
+            (None, None) => errors.push(format!(
+                "Service sets neither image nor build: {}",
+                self.name
+            )),
+            _ => {}
+        }
+
+        if self.has_network_mode_conflict() {
+            errors.push(format!(
+                "Service sets both network_mode and networks: {}",
+                self.name
+            ));
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Compose rejects a service joining the network stack of another one and networks
+    pub(crate) fn has_network_mode_conflict(&self) -> bool {
+        self.network_mode.is_some()
+            && self
+                .networks
+                .as_ref()
+                .is_some_and(|networks| !networks.is_empty())
+    }
 }
+
+/***
+ * Whole content of a reader, without its byte order mark
+ */
+pub fn read_input<R: Read>(mut reader: R) -> Result<String, DeserializeError> {
+    let mut input = String::new();
+    reader
+        .read_to_string(&mut input)
+        .map_err(|e| DeserializeError::Parse(e.to_string()))?;
+
+    Ok(match input.strip_prefix('\u{feff}') {
+        Some(input) => input.to_string(),
+        None => input,
+    })
+}
+
 pub trait Serializer {
     type Output;
-    fn deserialize_value(&mut self, input: &str) -> Result<&Self::Output, String>;
+    fn deserialize_value(&mut self, input: &str) -> Result<&Self::Output, DeserializeError>;
+    /// Read the whole input then deserialize it as `deserialize_value` does
+    fn deserialize_reader<R: Read>(&mut self, reader: R) -> Result<&Self::Output, DeserializeError>
+    where
+        Self: Sized,
+    {
+        let input = read_input(reader)?;
+
+        self.deserialize_value(&input)
+    }
     fn serialize_value(
         &self,
-        services: &Vec<ContainerAppConfiguration>,
+        services: &[ContainerAppConfiguration],
     ) -> Result<Vec<u8>, serde_yaml::Error> {
-        let as_value = vec![services.clone(), vec![default_configuration()]]
-            .concat()
+        to_compose_yaml(services).map(String::into_bytes)
+    }
+}
+
+/***
+ * Serialize to a compose file
+ */
+pub fn to_compose_yaml(
+    services: &[ContainerAppConfiguration],
+) -> Result<String, serde_yaml::Error> {
+    to_compose_yaml_with_options(services, &SerializeOptions::default())
+}
+
+pub fn to_compose_yaml_with_options(
+    services: &[ContainerAppConfiguration],
+    options: &SerializeOptions,
+) -> Result<String, serde_yaml::Error> {
+    serde_yaml::to_string(
+        &build_compose_configuration(services).map_err(as_serialize_error::<serde_yaml::Error>)?,
+    )
+    .map(|compose| apply_command_style(&compose, options.command_style))
+}
+
+/***
+ * Serialize one app to its own compose file, without the placement service.
+ * The files are expected to be used along `to_shared_compose_yaml` (eg: docker compose -f)
+ */
+pub fn to_app_compose_yaml(
+    services: &[ContainerAppConfiguration],
+) -> Result<String, serde_yaml::Error> {
+    to_app_compose_yaml_with_options(services, &SerializeOptions::default())
+}
+
+pub fn to_app_compose_yaml_with_options(
+    services: &[ContainerAppConfiguration],
+    options: &SerializeOptions,
+) -> Result<String, serde_yaml::Error> {
+    serde_yaml::to_string(
+        &build_compose_file(services).map_err(as_serialize_error::<serde_yaml::Error>)?,
+    )
+    .map(|compose| apply_command_style(&compose, options.command_style))
+}
+
+/***
+ * Serialize what the apps share, the placement service and the Dapr network
+ */
+pub fn to_shared_compose_yaml() -> Result<String, serde_yaml::Error> {
+    to_shared_compose_yaml_with_options(&SerializeOptions::default())
+}
+
+pub fn to_shared_compose_yaml_with_options(
+    options: &SerializeOptions,
+) -> Result<String, serde_yaml::Error> {
+    serde_yaml::to_string(
+        &build_compose_file(&[default_configuration()])
+            .map_err(as_serialize_error::<serde_yaml::Error>)?,
+    )
+    .map(|compose| apply_command_style(&compose, options.command_style))
+}
+
+/***
+ * Render the `command` sequences of a compose file in the given style, serde_yaml
+ * only writes block sequences. Commands with multi-line arguments stay in block style
+ */
+fn apply_command_style(compose: &str, style: CommandStyle) -> String {
+    let lines: Vec<&str> = compose.lines().collect();
+    let mut output = String::new();
+    let mut index = 0;
+    // Top level key of the current line, only the commands of the services are rewritten
+    let mut section = "";
+
+    while index < lines.len() {
+        let line = lines[index];
+        let key = line.trim_start();
+        let indent = &line[..line.len() - key.len()];
+        index += 1;
+
+        if indent.is_empty() {
+            section = key;
+        }
+        // Other keys named `command` (eg: labels or extensions) are kept as is
+        let is_service_command =
+            section == "services:" && indent == SERVICE_KEY_INDENT && key == "command:";
+
+        let item_prefix = format!("{}- ", indent);
+        let items: Vec<&str> = lines[index..]
+            .iter()
+            .take_while(|item| is_service_command && item.starts_with(&item_prefix))
+            .map(|item| &item[item_prefix.len()..])
+            .collect();
+        // Arguments continued on the next lines (eg: block scalars)
+        let is_continued = lines
+            .get(index + items.len())
+            .is_some_and(|next| next.starts_with(&format!("{} ", indent)));
+        let arguments = items
             .iter()
-            .fold(Mapping::new(), |acc, x| cast_struct_as_value(acc, &x));
+            .map(|item| serde_yaml::from_str::<String>(item).ok())
+            .collect::<Option<Vec<String>>>()
+            .filter(|_| !is_continued)
+            .filter(|arguments| match style {
+                CommandStyle::Auto => arguments.len() <= FLOW_COMMAND_MAX_ARGUMENTS,
+                CommandStyle::Block => false,
+                CommandStyle::Flow => true,
+            });
 
-        let configuration = merge_configuration_with_networks(Mapping::new(), as_value);
+        match arguments.filter(|_| !items.is_empty()) {
+            Some(arguments) => {
+                let arguments: Vec<String> = arguments
+                    .iter()
+                    .map(|argument| serde_json::to_string(argument).unwrap())
+                    .collect();
+                output.push_str(&format!("{}command: [{}]\n", indent, arguments.join(", ")));
+                index += items.len();
+            }
+            None => {
+                output.push_str(line);
+                output.push('\n');
+            }
+        }
+    }
+
+    output
+}
+
+/***
+ * Read the services of a compose file back, the inverse of `to_compose_yaml`.
+ * Dapr sidecars (`*_dapr`) and the placement service are generated, they are skipped
+ */
+pub fn from_compose_yaml(input: &str) -> Result<Vec<ContainerAppConfiguration>, DeserializeError> {
+    let compose: serde_yaml::Value =
+        serde_yaml::from_str(input).map_err(|e| DeserializeError::Parse(e.to_string()))?;
+    let services = compose
+        .get("services")
+        .and_then(|services| services.as_mapping())
+        .ok_or(DeserializeError::NoContainer)?;
+    let placement = default_configuration().name;
+
+    services
+        .iter()
+        .filter_map(|(name, service)| {
+            let name = name.as_str()?;
+
+            (name != placement && !name.ends_with("_dapr")).then_some((name, service))
+        })
+        .map(|(name, service)| {
+            let mut service = service.clone();
+
+            // The name is the key of the service
+            if let Some(fields) = service.as_mapping_mut() {
+                fields.insert("name".into(), name.into());
+            }
 
-        match serde_yaml::to_string(&configuration) {
-            Ok(v) => Ok(v.as_bytes().to_vec()),
-            Err(e) => Err(e),
+            serde_yaml::from_value(service).map_err(|e| DeserializeError::Parse(e.to_string()))
+        })
+        .collect()
+}
+
+/***
+ * Serialize to compose with the environment values externalized,
+ * returns the compose file and the content of the matching `.env` file
+ */
+pub fn to_compose_with_env(
+    services: &[ContainerAppConfiguration],
+) -> Result<(String, String), serde_yaml::Error> {
+    let mut env_file = String::new();
+    // Different services may be formatted to the same key (eg: my-app and my_app)
+    let mut env_values: BTreeMap<String, String> = BTreeMap::new();
+    let mut externalized: Vec<ContainerAppConfiguration> = vec![];
+
+    for service in services {
+        let mut environment: Option<Vec<String>> = service.environment.as_ref().map(|_| vec![]);
+
+        for variable in service.environment.iter().flatten() {
+            let variable = match variable.split_once('=') {
+                Some((key, value)) => {
+                    let env_key = format_env_key(&service.name, key);
+
+                    match env_values.get(&env_key) {
+                        Some(other) if other != value => {
+                            return Err(as_serialize_error(
+                                DeserializeError::ConflictingDeclaration(env_key),
+                            ))
+                        }
+                        Some(_) => {}
+                        None => {
+                            env_file.push_str(&format!(
+                                "{}={}\n",
+                                env_key,
+                                format_env_value(value)
+                            ));
+                            env_values.insert(env_key.clone(), value.to_string());
+                        }
+                    }
+
+                    format!("{}=${{{}}}", key, env_key)
+                }
+                // Variables without value are taken from the host
+                None => variable.clone(),
+            };
+
+            environment.get_or_insert_with(Vec::new).push(variable);
         }
+
+        externalized.push(ContainerAppConfiguration {
+            environment,
+            ..service.clone()
+        });
     }
+
+    let compose = to_compose_yaml(&externalized)?;
+
+    Ok((compose, env_file))
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConfigDiff {
+    /// A service only present in the new configuration
+    Added(String),
+    /// A service only present in the old configuration
+    Removed(String),
+    /// A field of a service differs, absent fields are null
+    Changed {
+        service: String,
+        field: String,
+        old: serde_yaml::Value,
+        new: serde_yaml::Value,
+    },
+}
+
+/***
+ * Compare two configurations service by service and field by field,
+ * services are matched by name
+ */
+pub fn diff(
+    old: &[ContainerAppConfiguration],
+    new: &[ContainerAppConfiguration],
+) -> Vec<ConfigDiff> {
+    let as_fields = |service: &ContainerAppConfiguration| match serde_yaml::to_value(service) {
+        Ok(serde_yaml::Value::Mapping(fields)) => fields,
+        _ => Mapping::new(),
+    };
+    let mut diffs: Vec<ConfigDiff> = vec![];
+
+    for old_service in old {
+        let new_service = match new.iter().find(|service| service.name == old_service.name) {
+            Some(new_service) => new_service,
+            None => {
+                diffs.push(ConfigDiff::Removed(old_service.name.clone()));
+                continue;
+            }
+        };
+        let old_fields = as_fields(old_service);
+        let new_fields = as_fields(new_service);
+
+        let added_fields = new_fields
+            .keys()
+            .filter(|key| !old_fields.contains_key(*key));
+
+        for key in old_fields.keys().chain(added_fields) {
+            let old_value = old_fields.get(key).cloned().unwrap_or_default();
+            let new_value = new_fields.get(key).cloned().unwrap_or_default();
+
+            if old_value != new_value {
+                diffs.push(ConfigDiff::Changed {
+                    service: old_service.name.clone(),
+                    field: key.as_str().unwrap_or_default().to_string(),
+                    old: old_value,
+                    new: new_value,
+                });
+            }
+        }
+    }
+
+    for new_service in new {
+        if !old.iter().any(|service| service.name == new_service.name) {
+            diffs.push(ConfigDiff::Added(new_service.name.clone()));
+        }
+    }
+
+    diffs
+}
+
+/***
+ * The line of the input an error points to (1-based), logged along parse failures
+ */
+#[cfg(any(feature = "yaml", feature = "arm", feature = "state"))]
+pub(crate) fn snippet_at(input: &str, line: usize) -> &str {
+    input
+        .lines()
+        .nth(line.saturating_sub(1))
+        .unwrap_or_default()
+        .trim()
+}
+
+/***
+ * Serialize the compose configuration as JSON
+ */
+pub fn to_json(services: &[ContainerAppConfiguration]) -> Result<String, serde_json::Error> {
+    serde_json::to_string_pretty(
+        &build_compose_configuration(services).map_err(as_serialize_error)?,
+    )
+}
+
+/***
+ * Drop the null and empty list fields of a manifest
+ */
+fn without_empty_fields(value: serde_json::Value) -> serde_json::Value {
+    let is_empty = |value: &serde_json::Value| match value {
+        serde_json::Value::Null => true,
+        serde_json::Value::Array(array) => array.is_empty(),
+        _ => false,
+    };
+
+    match value {
+        serde_json::Value::Object(object) => object
+            .into_iter()
+            .filter(|(_, value)| !is_empty(value))
+            .map(|(key, value)| (key, without_empty_fields(value)))
+            .collect(),
+        serde_json::Value::Array(array) => array.into_iter().map(without_empty_fields).collect(),
+        value => value,
+    }
+}
+
+/***
+ * Serialize to Kubernetes manifests, a deployment per service and
+ * a service for the exposed ports. Labels are kept as annotations
+ */
+pub fn to_k8s(services: &[ContainerAppConfiguration]) -> Result<String, serde_yaml::Error> {
+    let mut manifests: Vec<String> = vec![];
+
+    for service in services {
+        // Ports are `host:container`
+        let ports: Vec<(&str, &str)> = service
+            .ports
+            .iter()
+            .flatten()
+            .map(|port| port.rsplit_once(':').unwrap_or((port, port)))
+            .collect();
+        let selector = serde_json::json!({ "app": service.name });
+        // Kubernetes does not build images, a built service needs a pushed image
+        let image = service.image.as_deref().ok_or_else(|| {
+            <serde_yaml::Error as serde::ser::Error>::custom(format!(
+                "Service {} has no image, its build context cannot be deployed to Kubernetes",
+                service.name
+            ))
+        })?;
+        // The compose command is the whole argv, Kubernetes splits the executable from its arguments
+        let (command, args) = match service.command.as_deref() {
+            Some([executable, args @ ..]) => (Some(vec![executable.clone()]), Some(args.to_vec())),
+            _ => (None, None),
+        };
+
+        let container = serde_json::json!({
+            "name": service.name,
+            "image": image,
+            "command": command,
+            "args": args,
+            "env": service.environment.iter().flatten().map(|variable| {
+                let (name, value) = variable.split_once('=').unwrap_or((variable, ""));
+
+                // `$$` only escapes the compose interpolation
+                serde_json::json!({ "name": name, "value": value.replace("$$", "$") })
+            }).collect::<Vec<_>>(),
+            "ports": ports.iter().map(|(_, target)| {
+                serde_json::json!({ "containerPort": target.parse::<u32>().ok() })
+            }).collect::<Vec<_>>(),
+            "workingDir": service.working_dir,
+        });
+        let deployment = serde_json::json!({
+            "apiVersion": "apps/v1",
+            "kind": "Deployment",
+            "metadata": { "name": service.name, "annotations": service.labels },
+            "spec": {
+                "selector": { "matchLabels": selector },
+                "template": {
+                    "metadata": { "labels": selector },
+                    "spec": { "containers": [container] },
+                },
+            },
+        });
+        manifests.push(serde_yaml::to_string(&without_empty_fields(deployment))?);
+
+        if !ports.is_empty() {
+            let service = serde_json::json!({
+                "apiVersion": "v1",
+                "kind": "Service",
+                "metadata": { "name": service.name },
+                "spec": {
+                    "selector": selector,
+                    "ports": ports.iter().map(|(host, target)| serde_json::json!({
+                        "port": host.parse::<u32>().ok(),
+                        "targetPort": target.parse::<u32>().ok(),
+                    })).collect::<Vec<_>>(),
+                },
+            });
+            manifests.push(serde_yaml::to_string(&without_empty_fields(service))?);
+        }
+    }
+
+    Ok(manifests.join("---\n"))
+}
+
+/***
+ * Env keys are prefixed by the service name to avoid collisions between services
+ */
+fn format_env_key(service_name: &str, key: &str) -> String {
+    format!("{}_{}", service_name, key)
+        .to_uppercase()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect()
+}
+
+/***
+ * Values of the `.env` file are double quoted, compose reads them as a single line and
+ * keeps interpolating them as it does in the compose file (eg: `$$` or `${SECRET}`)
+ */
+fn format_env_value(value: &str) -> String {
+    let escaped = value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n");
+
+    format!("\"{}\"", escaped)
+}
+
+/***
+ * Errors of the compose building reported as serialization ones
+ */
+fn as_serialize_error<E: serde::ser::Error>(error: DeserializeError) -> E {
+    E::custom(error)
+}
+
+fn build_compose_configuration(
+    services: &[ContainerAppConfiguration],
+) -> Result<Mapping, DeserializeError> {
+    build_compose_file(&[services.to_vec(), vec![default_configuration()]].concat())
+}
+
+fn build_compose_file(services: &[ContainerAppConfiguration]) -> Result<Mapping, DeserializeError> {
+    let as_value = services.iter().fold(Mapping::new(), cast_struct_as_value);
+    let networks = build_networks_declarations(services)?;
+    let mut configuration = merge_configuration_with_networks(Mapping::new(), as_value, networks);
+    let configs = build_configs_declarations(services);
+    let secrets = build_secrets_declarations(services);
+    let volumes = build_volumes_declarations(services)?;
+
+    if !configs.is_empty() {
+        configuration.insert(
+            serde_yaml::to_value("configs").unwrap(),
+            serde_yaml::to_value(configs).unwrap(),
+        );
+    }
+
+    if !secrets.is_empty() {
+        configuration.insert(
+            serde_yaml::to_value("secrets").unwrap(),
+            serde_yaml::to_value(secrets).unwrap(),
+        );
+    }
+
+    if !volumes.is_empty() {
+        configuration.insert(
+            serde_yaml::to_value("volumes").unwrap(),
+            serde_yaml::to_value(volumes).unwrap(),
+        );
+    }
+
+    Ok(configuration)
+}
+
+/***
+ * Merge network or volume declarations by name, identical duplicates are kept once
+ */
+pub fn normalize_declarations(
+    declarations: Vec<(String, serde_yaml::Value)>,
+) -> Result<Mapping, DeserializeError> {
+    let mut normalized = Mapping::new();
+
+    for (name, definition) in declarations {
+        let key = serde_yaml::Value::String(name.clone());
+
+        match normalized.get(&key) {
+            Some(existing) if existing != &definition => {
+                return Err(DeserializeError::ConflictingDeclaration(name));
+            }
+            Some(_) => (),
+            None => {
+                normalized.insert(key, definition);
+            }
+        }
+    }
+
+    Ok(normalized)
+}
+
+/***
+ * Check the top level `networks` and `volumes` the services declare can be merged
+ */
+pub fn validate_declarations(
+    services: &[ContainerAppConfiguration],
+) -> Result<(), DeserializeError> {
+    build_networks_declarations(services)?;
+    build_volumes_declarations(services)?;
+
+    Ok(())
+}
+
+/***
+ * Top level `networks` declaring the Dapr network and the networks the services join
+ */
+fn build_networks_declarations(
+    services: &[ContainerAppConfiguration],
+) -> Result<Mapping, DeserializeError> {
+    let networks = services
+        .iter()
+        .flat_map(|service| service.networks.iter().flatten())
+        .map(|network| (network.clone(), Mapping::new().into()));
+
+    normalize_declarations(
+        std::iter::once(("dapr-network".to_string(), Mapping::new().into()))
+            .chain(networks)
+            .collect(),
+    )
+}
+
+/***
+ * Top level `secrets` declaring the files of the secrets mounted in the services
+ */
+fn build_secrets_declarations(services: &[ContainerAppConfiguration]) -> Mapping {
+    let mut secrets = Mapping::new();
+
+    for secret in services
+        .iter()
+        .flat_map(|service| service.secrets.iter().flatten())
+    {
+        let mut declaration = Mapping::new();
+        declaration.insert("file".into(), secret.file.as_str().into());
+
+        secrets.insert(secret.source.as_str().into(), declaration.into());
+    }
+
+    secrets
+}
+
+/***
+ * Top level `volumes` declaring the named volumes mounted in the services,
+ * once whatever the number of services mounting them. The Azure Files shares
+ * are labelled with their share, a scratch volume cannot be named as one
+ */
+fn build_volumes_declarations(
+    services: &[ContainerAppConfiguration],
+) -> Result<Mapping, DeserializeError> {
+    normalize_declarations(
+        services
+            .iter()
+            .flat_map(|service| service.volumes.iter().flatten())
+            .filter(|volume| volume.volume_type == "volume")
+            .map(|volume| {
+                let mut declaration = Mapping::new();
+
+                if let Some(share) = &volume.share {
+                    let mut labels = Mapping::new();
+                    labels.insert("capp.share".into(), share.as_str().into());
+                    declaration.insert("labels".into(), labels.into());
+                }
+
+                (volume.source.clone(), declaration.into())
+            })
+            .collect(),
+    )
+}
+
+/***
+ * `.env.example` listing the host variables read by the environment of the services
+ * (eg: DB_PASSWORD= for DB_PASSWORD=${DB_PASSWORD}), empty when none is read
+ */
+pub fn to_env_example(services: &[ContainerAppConfiguration]) -> String {
+    let variable_regex =
+        Regex::new(r"\$\{([A-Z_][A-Z0-9_]*)\}").expect("Should match variable regex");
+    let mut variables: Vec<String> = vec![];
+
+    for value in services
+        .iter()
+        .flat_map(|service| service.environment.iter().flatten())
+        .filter_map(|variable| variable.split_once('=').map(|(_, value)| value))
+    {
+        for captures in variable_regex.captures_iter(value) {
+            if !variables.contains(&captures[1].to_string()) {
+                variables.push(captures[1].to_string());
+            }
+        }
+    }
+
+    variables
+        .iter()
+        .map(|variable| format!("{}=\n", variable))
+        .collect()
+}
+
+/***
+ * Top level `configs` declaring inline the content of the configs mounted in the services
+ */
+fn build_configs_declarations(services: &[ContainerAppConfiguration]) -> Mapping {
+    let mut configs = Mapping::new();
+
+    for config in services
+        .iter()
+        .flat_map(|service| service.configs.iter().flatten())
+    {
+        if let Some(content) = &config.content {
+            let mut declaration = Mapping::new();
+            declaration.insert("content".into(), content.as_str().into());
+
+            configs.insert(config.source.as_str().into(), declaration.into());
+        }
+    }
+
+    configs
 }
 
 fn cast_struct_as_value(mut acc: Mapping, service: &ContainerAppConfiguration) -> Mapping {
-    acc.insert(
-        serde_yaml::to_value(&service.name).unwrap(),
-        serde_yaml::to_value(&service).unwrap(),
-    );
+    let mut value = serde_yaml::to_value(service).unwrap();
+
+    // Extra fields win over the generated ones
+    if let (Some(fields), Some(extra)) = (value.as_mapping_mut(), &service.extra) {
+        for (key, extra_value) in extra {
+            fields.insert(key.clone(), extra_value.clone());
+        }
+    }
+
+    acc.insert(serde_yaml::to_value(&service.name).unwrap(), value);
     acc
 }
 
 fn default_configuration() -> ContainerAppConfiguration {
     ContainerAppConfiguration {
-        name: String::from("placement"),
         ports: Some(vec!["50006:50006".to_string()]),
         networks: Some(vec!["dapr-network".to_string()]),
         image: Some("daprio/dapr".to_string()),
@@ -124,14 +1562,15 @@ fn default_configuration() -> ContainerAppConfiguration {
             "-port".to_string(),
             "50006".to_string(),
         ]),
-        depends_on: None,
-        environment: None,
-        network_mode: None,
-        build: None,
+        ..ContainerAppConfiguration::new("placement")
     }
 }
 
-fn merge_configuration_with_networks(mut configuration: Mapping, services: Mapping) -> Mapping {
+fn merge_configuration_with_networks(
+    mut configuration: Mapping,
+    services: Mapping,
+    networks: Mapping,
+) -> Mapping {
     // Generate API version
     configuration.insert(
         serde_yaml::to_value("version").unwrap(),
@@ -143,15 +1582,6 @@ fn merge_configuration_with_networks(mut configuration: Mapping, services: Mappi
         serde_yaml::to_value(services).unwrap(),
     );
 
-    let dapr_network = Mapping::new();
-
-    let mut networks = Mapping::new();
-
-    networks.insert(
-        serde_yaml::to_value("dapr-network").unwrap(),
-        serde_yaml::to_value(dapr_network).unwrap(),
-    );
-
     configuration.insert(
         serde_yaml::to_value("networks").unwrap(),
         serde_yaml::to_value(networks).unwrap(),
@@ -168,7 +1598,7 @@ mod tests {
 
     impl Serializer for TestSerializer {
         type Output = TestSerializer;
-        fn deserialize_value(&mut self, _input: &str) -> Result<&Self, String> {
+        fn deserialize_value(&mut self, _input: &str) -> Result<&Self, DeserializeError> {
             Ok(self)
         }
     }
@@ -176,7 +1606,6 @@ mod tests {
     #[test]
     fn test_default_configuration() {
         let expected = ContainerAppConfiguration {
-            name: String::from("placement"),
             ports: Some(vec!["50006:50006".to_string()]),
             networks: Some(vec!["dapr-network".to_string()]),
             image: Some("daprio/dapr".to_string()),
@@ -185,10 +1614,7 @@ mod tests {
                 "-port".to_string(),
                 "50006".to_string(),
             ]),
-            depends_on: None,
-            environment: None,
-            network_mode: None,
-            build: None,
+            ..ContainerAppConfiguration::new(String::from("placement"))
         };
 
         let output = default_configuration();
@@ -222,7 +1648,11 @@ mod tests {
             serde_yaml::to_value("networks").unwrap(),
             serde_yaml::to_value(networks).unwrap(),
         );
-        let output = merge_configuration_with_networks(Mapping::new(), Mapping::new());
+        let output = merge_configuration_with_networks(
+            Mapping::new(),
+            Mapping::new(),
+            build_networks_declarations(&[]).unwrap(),
+        );
 
         assert_eq!(expected, output)
     }
@@ -233,27 +1663,18 @@ mod tests {
 
         let input = vec![
             ContainerAppConfiguration {
-                image: None,
                 build: Some(BuildContext {
                     context: "./node-app".to_string(),
+                    ..Default::default()
                 }),
-                name: "myapp".to_string(),
-                depends_on: Some(vec!["placement".to_string()]),
+                depends_on: Some(DependsOn::Services(vec!["placement".to_string()])),
                 networks: Some(vec![String::from("dapr-network")]),
-                network_mode: None,
-                environment: None,
-                ports: None,
-                command: None,
+                ..ContainerAppConfiguration::new("myapp")
             },
             ContainerAppConfiguration {
                 image: Some(String::from("daprio/daprd:edge")),
-                name: format!("myapp_dapr"),
-                depends_on: Some(vec![String::from("myapp")]),
+                depends_on: Some(DependsOn::Services(vec![String::from("myapp")])),
                 network_mode: Some(format!("service:{}", String::from("myapp"))),
-                environment: None,
-                ports: None,
-                networks: None,
-                build: None,
                 command: Some(vec![
                     "./daprd".to_string(),
                     "-app-id".to_string(),
@@ -264,6 +1685,7 @@ mod tests {
                     "placement:50006".to_string(),
                     "air".to_string(),
                 ]),
+                ..ContainerAppConfiguration::new("myapp_dapr")
             },
         ];
 
@@ -296,10 +1718,7 @@ services:
     image: daprio/dapr
     ports:
     - 50006:50006
-    command:
-    - ./placement
-    - -port
-    - '50006'
+    command: ["./placement", "-port", "50006"]
 networks:
   dapr-network: {}
 "#
@@ -310,4 +1729,411 @@ networks:
 
         assert_eq!(expected, output);
     }
+
+    #[test]
+    fn test_to_compose_with_env() {
+        let input = vec![ContainerAppConfiguration {
+            image: Some("node-12".to_string()),
+            environment: Some(vec![
+                "PORT=3000".to_string(),
+                "DATABASE_URL=postgres://db:5432/app?ssl=true".to_string(),
+                "HOME".to_string(),
+            ]),
+            ..ContainerAppConfiguration::new("my-app")
+        }];
+
+        let (compose, env_file) = to_compose_with_env(&input).unwrap();
+
+        assert!(compose.contains("    - PORT=${MY_APP_PORT}\n"));
+        assert!(compose.contains("    - DATABASE_URL=${MY_APP_DATABASE_URL}\n"));
+        assert!(compose.contains("    - HOME\n"));
+        assert_eq!(
+            "MY_APP_PORT=\"3000\"\nMY_APP_DATABASE_URL=\"postgres://db:5432/app?ssl=true\"\n",
+            env_file
+        );
+    }
+
+    #[test]
+    fn test_to_compose_with_env_quoting() {
+        let input = vec![ContainerAppConfiguration {
+            image: Some("node-12".to_string()),
+            environment: Some(vec![
+                "PRICE=cost$$5".to_string(),
+                "GREETING=hello \"capp\" world".to_string(),
+                "COMMENT=# not a comment".to_string(),
+                "MULTILINE=first\nsecond".to_string(),
+            ]),
+            ..ContainerAppConfiguration::new("my-app")
+        }];
+
+        let (_, env_file) = to_compose_with_env(&input).unwrap();
+
+        assert_eq!(
+            [
+                r#"MY_APP_PRICE="cost$$5""#,
+                r#"MY_APP_GREETING="hello \"capp\" world""#,
+                r##"MY_APP_COMMENT="# not a comment""##,
+                r#"MY_APP_MULTILINE="first\nsecond""#,
+                "",
+            ]
+            .join("\n"),
+            env_file
+        );
+    }
+
+    #[test]
+    fn test_to_compose_with_env_colliding_keys() {
+        let service = |name: &str, port: &str| ContainerAppConfiguration {
+            image: Some("node-12".to_string()),
+            environment: Some(vec![format!("PORT={}", port)]),
+            ..ContainerAppConfiguration::new(name)
+        };
+
+        // The same value is shared by both services
+        let (compose, env_file) =
+            to_compose_with_env(&[service("my-app", "3000"), service("my_app", "3000")]).unwrap();
+        assert_eq!("MY_APP_PORT=\"3000\"\n", env_file);
+        assert_eq!(2, compose.matches("    - PORT=${MY_APP_PORT}\n").count());
+
+        let output = to_compose_with_env(&[service("my-app", "3000"), service("my_app", "4000")]);
+        assert_eq!(
+            "Declaration has conflicting definitions: MY_APP_PORT",
+            output.unwrap_err().to_string()
+        );
+    }
+
+    #[test]
+    fn test_environment_order() {
+        let input = vec![ContainerAppConfiguration {
+            image: Some("node-12".to_string()),
+            environment: Some(vec![
+                "PORT=3000".to_string(),
+                "API_URL=http://api".to_string(),
+                "NODE_ENV=production".to_string(),
+            ]),
+            ..ContainerAppConfiguration::new("myapp")
+        }];
+
+        let output = build_compose_configuration(&input).unwrap();
+
+        let environment = output
+            .get("services")
+            .and_then(|services| services.get("myapp"))
+            .and_then(|service| service.get("environment"))
+            .unwrap();
+
+        // Variables keep the order of the container spec
+        assert_eq!(
+            &serde_yaml::to_value(vec![
+                "PORT=3000",
+                "API_URL=http://api",
+                "NODE_ENV=production",
+            ])
+            .unwrap(),
+            environment
+        );
+    }
+
+    #[test]
+    fn test_diff() {
+        let service = |name: &str, ports: Vec<&str>| ContainerAppConfiguration {
+            image: Some("node-12".to_string()),
+            ports: Some(ports.iter().map(|port| port.to_string()).collect()),
+            ..ContainerAppConfiguration::new(name.to_string())
+        };
+        let old = vec![
+            service("frontend", vec!["80:80"]),
+            service("backend", vec!["3000:3000"]),
+        ];
+        let new = vec![
+            service("frontend", vec!["8080:80"]),
+            service("backend", vec!["3000:3000"]),
+        ];
+
+        let output = diff(&old, &new);
+
+        let expected = vec![ConfigDiff::Changed {
+            service: "frontend".to_string(),
+            field: "ports".to_string(),
+            old: serde_yaml::to_value(vec!["80:80"]).unwrap(),
+            new: serde_yaml::to_value(vec!["8080:80"]).unwrap(),
+        }];
+        assert_eq!(expected, output);
+
+        let output = diff(&old[..1], &new[1..]);
+
+        assert_eq!(
+            vec![
+                ConfigDiff::Removed("frontend".to_string()),
+                ConfigDiff::Added("backend".to_string())
+            ],
+            output
+        );
+        assert!(diff(&old, &old).is_empty());
+    }
+
+    #[test]
+    fn test_constructors() {
+        let service = ContainerAppConfiguration::new("myapp");
+
+        assert_eq!("myapp", service.name);
+        assert_eq!(None, service.image);
+        assert_eq!(None, service.labels);
+        assert_eq!("{}\n", serde_yaml::to_string(&service).unwrap());
+
+        let app = ContainerAppBluePrint::new("myapp").with_template(TemplateBluePrint::new(vec![
+            ContainerBluePrint::new("myapp", "node-12"),
+        ]));
+
+        assert_eq!(Some("myapp".to_string()), app.name);
+        assert_eq!(None, app.configuration);
+
+        let template = app.template.unwrap();
+        assert_eq!(None, template.init_containers);
+
+        let container = &template.containers.unwrap()[0];
+        assert_eq!("node-12", container.image);
+        assert_eq!(None, container.probes);
+        assert_eq!(None, container.env_from);
+    }
+
+    #[test]
+    fn test_probe_durations() {
+        let parse = |period: &str| {
+            serde_yaml::from_str::<ProbeBluePrint>(&format!("periodSeconds: {}", period))
+                .map(|probe| probe.period_seconds)
+        };
+
+        assert_eq!(Some(10), parse("10").unwrap());
+        assert_eq!(Some(10), parse("\"10s\"").unwrap());
+        assert_eq!(Some(60), parse("\"1m\"").unwrap());
+        assert!(parse("\"soon\"")
+            .unwrap_err()
+            .to_string()
+            .contains("invalid duration: soon"));
+
+        let probe: ProbeBluePrint = serde_yaml::from_str("type: Liveness").unwrap();
+        assert_eq!(None, probe.period_seconds);
+    }
+
+    #[test]
+    fn test_validate_self() {
+        let service = ContainerAppConfiguration {
+            image: Some("node-12".to_string()),
+            ..ContainerAppConfiguration::new("myapp")
+        };
+        assert_eq!(Ok(()), service.validate_self());
+
+        let service = ContainerAppConfiguration {
+            network_mode: Some("service:myapp".to_string()),
+            networks: Some(vec!["dapr-network".to_string()]),
+            ..ContainerAppConfiguration::new("myapp_dapr")
+        };
+        assert_eq!(
+            Err(vec![
+                "Service sets neither image nor build: myapp_dapr".to_string(),
+                "Service sets both network_mode and networks: myapp_dapr".to_string(),
+            ]),
+            service.validate_self()
+        );
+
+        assert_eq!(
+            Err(vec![
+                "Service sets neither image nor build: myapp".to_string()
+            ]),
+            ContainerAppConfiguration::new("myapp").validate_self()
+        );
+    }
+
+    #[test]
+    fn test_extra_fields() {
+        let extra: Mapping = serde_yaml::from_str(
+            r#"
+            cap_add: ["NET_ADMIN"]
+            restart: always
+            "#,
+        )
+        .unwrap();
+        let service = ContainerAppConfiguration {
+            image: Some("node-12".to_string()),
+            restart: Some("unless-stopped".to_string()),
+            extra: Some(extra),
+            ..ContainerAppConfiguration::new("myapp")
+        };
+
+        let output = to_compose_yaml(&[service]).unwrap();
+        let compose: serde_yaml::Value = serde_yaml::from_str(&output).unwrap();
+
+        assert_eq!(
+            serde_yaml::from_str::<serde_yaml::Value>(r#"["NET_ADMIN"]"#).unwrap(),
+            compose["services"]["myapp"]["cap_add"]
+        );
+        assert_eq!(compose["services"]["myapp"]["restart"], "always");
+        assert_eq!(compose["services"]["myapp"]["image"], "node-12");
+    }
+
+    #[test]
+    fn test_normalize_declarations() {
+        let frontend = || serde_yaml::to_value(BTreeMap::from([("driver", "bridge")])).unwrap();
+
+        let output = normalize_declarations(vec![
+            ("frontend".to_string(), frontend()),
+            ("dapr-network".to_string(), serde_yaml::Value::Null),
+            ("frontend".to_string(), frontend()),
+        ])
+        .unwrap();
+
+        let mut expected = serde_yaml::Mapping::new();
+        expected.insert("frontend".into(), frontend());
+        expected.insert("dapr-network".into(), serde_yaml::Value::Null);
+        assert_eq!(expected, output);
+
+        let output = normalize_declarations(vec![
+            ("frontend".to_string(), frontend()),
+            (
+                "frontend".to_string(),
+                serde_yaml::to_value(BTreeMap::from([("driver", "overlay")])).unwrap(),
+            ),
+        ]);
+
+        assert_eq!(
+            Err(DeserializeError::ConflictingDeclaration(
+                "frontend".to_string()
+            )),
+            output
+        );
+    }
+
+    #[test]
+    fn test_to_compose_yaml_with_conflicting_volumes() {
+        let service = |name: &str, share: Option<&str>| ContainerAppConfiguration {
+            volumes: Some(vec![ServiceVolume {
+                volume_type: "volume".to_string(),
+                source: "data".to_string(),
+                target: "/data".to_string(),
+                read_only: None,
+                volume: None,
+                share: share.map(|share| share.to_string()),
+            }]),
+            ..ContainerAppConfiguration::new(name)
+        };
+
+        let output = to_compose_yaml(&[
+            service("api", Some("data")),
+            service("worker", Some("data")),
+        ])
+        .unwrap();
+        assert!(output.contains("volumes:\n  data:\n    labels:\n      capp.share: data\n"));
+
+        let output = to_compose_yaml(&[service("api", None), service("worker", Some("data"))]);
+        assert_eq!(
+            "Declaration has conflicting definitions: data",
+            output.unwrap_err().to_string()
+        );
+
+        let output = to_compose_with_env(&[service("api", None), service("worker", Some("data"))]);
+        assert_eq!(
+            "Declaration has conflicting definitions: data",
+            output.unwrap_err().to_string()
+        );
+    }
+
+    #[test]
+    fn test_command_style() {
+        let service = |command: &[&str]| ContainerAppConfiguration {
+            image: Some("node-12".to_string()),
+            command: Some(command.iter().map(|x| x.to_string()).collect()),
+            ..ContainerAppConfiguration::new("myapp")
+        };
+        let compose = |services: &[ContainerAppConfiguration], command_style: CommandStyle| {
+            to_app_compose_yaml_with_options(services, &SerializeOptions { command_style }).unwrap()
+        };
+        let short = service(&["npm", "run", "start"]);
+        let long = service(&["node", "--inspect", "server.js", "3000"]);
+
+        assert!(compose(std::slice::from_ref(&short), CommandStyle::Auto)
+            .contains("    command: [\"npm\", \"run\", \"start\"]\n"));
+        assert!(
+            compose(std::slice::from_ref(&long), CommandStyle::Auto).contains(
+                "    command:\n    - node\n    - --inspect\n    - server.js\n    - '3000'\n"
+            )
+        );
+        assert!(compose(&[short], CommandStyle::Block)
+            .contains("    command:\n    - npm\n    - run\n    - start\n"));
+        assert!(compose(&[long], CommandStyle::Flow)
+            .contains("    command: [\"node\", \"--inspect\", \"server.js\", \"3000\"]\n"));
+
+        // Quotes and multi-line arguments
+        assert!(
+            compose(&[service(&["echo", "a, \"b\""])], CommandStyle::Flow)
+                .contains(r#"    command: ["echo", "a, \"b\""]"#)
+        );
+        let output = compose(
+            &[service(&["sh", "-c", "echo a\necho b"])],
+            CommandStyle::Flow,
+        );
+        assert!(output.contains("    command:\n    - sh\n    - -c\n    - |-\n"));
+        assert_eq!(
+            Some(vec![
+                "sh".to_string(),
+                "-c".to_string(),
+                "echo a\necho b".to_string()
+            ]),
+            from_compose_yaml(&output).unwrap()[0].command
+        );
+
+        // Only the command of the service is rewritten, not the nested keys of the same name
+        let extra: Mapping = serde_yaml::from_str("x-tooling: { command: [lint, fix] }").unwrap();
+        let output = compose(
+            &[ContainerAppConfiguration {
+                extra: Some(extra),
+                ..service(&["npm", "start"])
+            }],
+            CommandStyle::Flow,
+        );
+        assert!(output.contains("    command: [\"npm\", \"start\"]\n"));
+        assert!(output.contains("    x-tooling:\n      command:\n      - lint\n      - fix\n"));
+    }
+
+    #[test]
+    fn test_to_k8s() {
+        let service = ContainerAppConfiguration {
+            image: Some("daprio/daprd".to_string()),
+            command: Some(vec![
+                "./daprd".to_string(),
+                "-app-id".to_string(),
+                "myapp".to_string(),
+            ]),
+            environment: Some(vec!["PRICE=cost$$5".to_string()]),
+            ..ContainerAppConfiguration::new("myapp_dapr")
+        };
+
+        let output = to_k8s(&[service]).unwrap();
+        let deployment: serde_yaml::Value = serde_yaml::from_str(&output).unwrap();
+        let container = &deployment["spec"]["template"]["spec"]["containers"][0];
+
+        assert_eq!(container["image"], "daprio/daprd");
+        assert_eq!(
+            serde_yaml::from_str::<serde_yaml::Value>(r#"["./daprd"]"#).unwrap(),
+            container["command"]
+        );
+        assert_eq!(
+            serde_yaml::from_str::<serde_yaml::Value>(r#"["-app-id", "myapp"]"#).unwrap(),
+            container["args"]
+        );
+        assert_eq!(container["env"][0]["value"], "cost$5");
+
+        // Built services have no image to deploy
+        let service = ContainerAppConfiguration {
+            build: Some(BuildContext {
+                context: "./node-app".to_string(),
+                ..BuildContext::default()
+            }),
+            ..ContainerAppConfiguration::new("myapp")
+        };
+        assert_eq!(
+            "Service myapp has no image, its build context cannot be deployed to Kubernetes",
+            to_k8s(&[service]).unwrap_err().to_string()
+        );
+    }
 }
@@ -0,0 +1,928 @@
+//! Terraform configurations, parsed by `hcl-rs`.
+//!
+//! The attributes are evaluated with the default values of the variables, the locals and the
+//! `tostring`, `tonumber`, `lower`, `upper`, `format` and `join` functions. References to the
+//! other resources are kept as `${...}` tokens (eg: ${azurerm_container_registry.acr.id}).
+//!
+//! The other expressions are not evaluated, their value is null or kept as written:
+//! - `for` expressions over references (eg: [for name in var.names : upper(name)])
+//! - template directives (eg: "%{ if var.debug }debug%{ endif }")
+//! - operations on references and the other function calls (eg: var.port + 1, try(...))
+use hcl::eval::{Context, Evaluate, FuncArgs, FuncDef, ParamType};
+use hcl::template::{Element, Template};
+use hcl::{Expression, ObjectKey, Structure, TemplateExpr, Value as HclValue};
+use serde_json::{Map, Value};
+
+use crate::error::DeserializeError;
+use crate::pulumi;
+use crate::serializer::{
+    BuildOptions, ContainerAppBluePrint, ContainerAppConfiguration, ContainerImageBluePrint,
+};
+
+const CONTAINER_APP_TYPE: &str = "azurerm_container_app";
+const ENVIRONMENT_TYPE: &str = "azurerm_container_app_environment";
+const IMAGE_TYPE: &str = "docker_image";
+
+// Blocks which can be repeated, by their plural name in the classic provider shape
+const REPEATED_BLOCKS: [(&str, &str); 11] = [
+    ("container", "containers"),
+    ("init_container", "init_containers"),
+    ("env", "envs"),
+    ("secret", "secrets"),
+    ("registry", "registries"),
+    ("liveness_probe", "liveness_probes"),
+    ("readiness_probe", "readiness_probes"),
+    ("startup_probe", "startup_probes"),
+    ("volume", "volumes"),
+    ("volume_mounts", "volume_mounts"),
+    ("traffic_weight", "traffic_weights"),
+];
+
+// Arguments of every resource, they are not properties of the app
+const META_ARGUMENTS: [&str; 7] = [
+    "name",
+    "resource_group_name",
+    "tags",
+    "depends_on",
+    "provider",
+    "count",
+    "for_each",
+];
+
+// Locals referencing each other are resolved by parsing the file again, at most this many times
+const MAX_PASSES: usize = 4;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+struct Body {
+    attributes: Map<String, Value>,
+    blocks: Vec<Block>,
+}
+
+/***
+ * Block with its type and labels (eg: resource "azurerm_container_app" "api" {...})
+ */
+#[derive(Debug, Clone, PartialEq)]
+struct Block {
+    kind: String,
+    labels: Vec<String>,
+    body: Body,
+}
+
+impl Block {
+    fn is(&self, kind: &str, resource_type: &str) -> bool {
+        self.kind == kind && self.labels.first().map(String::as_str) == Some(resource_type)
+    }
+
+    /// Address of a resource in the expressions (eg: azurerm_container_app.api)
+    fn address(&self) -> String {
+        self.labels.join(".")
+    }
+
+    fn label(&self) -> &str {
+        self.labels.last().map(String::as_str).unwrap_or_default()
+    }
+}
+
+/***
+ * HCL expressions as JSON values.
+ * Variables with a default value and locals are substituted, references to other resources
+ * are `${...}` tokens and expressions which cannot be evaluated statically (eg: timestamp())
+ * are null
+ */
+fn evaluate(expression: &Expression, context: &Context) -> Value {
+    if let Ok(value) = expression.evaluate(context) {
+        return serde_json::to_value(value).unwrap_or_default();
+    }
+
+    match expression {
+        Expression::Array(items) => {
+            Value::Array(items.iter().map(|item| evaluate(item, context)).collect())
+        }
+        Expression::Object(fields) => Value::Object(
+            fields
+                .iter()
+                .map(|(key, value)| (evaluate_key(key, context), evaluate(value, context)))
+                .collect(),
+        ),
+        Expression::TemplateExpr(template) => evaluate_template(template, context),
+        // Attributes of other resources (eg: azurerm_container_registry.acr.login_server)
+        Expression::Variable(_) | Expression::Traversal(_) => {
+            Value::String(format!("${{{}}}", expression))
+        }
+        Expression::Parenthesis(expression) => evaluate(expression, context),
+        Expression::Conditional(conditional) => match evaluate(&conditional.cond_expr, context) {
+            Value::Bool(true) => evaluate(&conditional.true_expr, context),
+            Value::Bool(false) => evaluate(&conditional.false_expr, context),
+            _ => Value::Null,
+        },
+        _ => Value::Null,
+    }
+}
+
+/// Parenthesized keys (eg: { (var.key) = 1 }) are evaluated like the values
+fn evaluate_key(key: &ObjectKey, context: &Context) -> String {
+    match key {
+        ObjectKey::Expression(expression) => match evaluate(expression, context) {
+            Value::String(key) => key,
+            key => key.to_string(),
+        },
+        key => key.to_string(),
+    }
+}
+
+/***
+ * Templates, interpolations (eg: "${var.prefix}-app") are substituted when known and kept as
+ * `${...}` tokens otherwise. Templates with directives are kept as written
+ */
+fn evaluate_template(template: &TemplateExpr, context: &Context) -> Value {
+    let Ok(elements) = Template::from_expr(template) else {
+        return Value::Null;
+    };
+    let mut output = String::new();
+
+    for element in elements.elements() {
+        match element {
+            Element::Literal(literal) => output.push_str(literal),
+            Element::Interpolation(interpolation) => match evaluate(&interpolation.expr, context) {
+                Value::String(value) => output.push_str(&value),
+                Value::Number(value) => output.push_str(&value.to_string()),
+                Value::Bool(value) => output.push_str(&value.to_string()),
+                _ => output.push_str(&format!("${{{}}}", interpolation.expr)),
+            },
+            Element::Directive(_) => return Value::String(template.to_string()),
+        }
+    }
+
+    Value::String(output)
+}
+
+/// Arguments of the functions as strings, references (eg: ${docker_image.api.name}) are unknown
+fn get_known_arguments(arguments: &[HclValue]) -> Result<Vec<String>, String> {
+    arguments
+        .iter()
+        .map(|argument| match argument {
+            HclValue::String(value) if !value.contains("${") => Ok(value.clone()),
+            HclValue::Number(value) => Ok(value.to_string()),
+            HclValue::Bool(value) => Ok(value.to_string()),
+            _ => Err("unknown argument".to_string()),
+        })
+        .collect()
+}
+
+fn to_string(arguments: FuncArgs) -> Result<HclValue, String> {
+    let mut arguments = get_known_arguments(&arguments)?;
+
+    Ok(HclValue::String(arguments.remove(0)))
+}
+
+fn to_number(arguments: FuncArgs) -> Result<HclValue, String> {
+    let arguments = get_known_arguments(&arguments)?;
+
+    serde_json::from_str::<HclValue>(arguments[0].trim())
+        .ok()
+        .filter(HclValue::is_number)
+        .ok_or_else(|| format!("invalid number `{}`", arguments[0]))
+}
+
+fn lower(arguments: FuncArgs) -> Result<HclValue, String> {
+    Ok(HclValue::String(
+        get_known_arguments(&arguments)?[0].to_lowercase(),
+    ))
+}
+
+fn upper(arguments: FuncArgs) -> Result<HclValue, String> {
+    Ok(HclValue::String(
+        get_known_arguments(&arguments)?[0].to_uppercase(),
+    ))
+}
+
+/// Verbs are replaced in order (eg: format("%s/app:%s", var.registry, var.tag))
+fn format(arguments: FuncArgs) -> Result<HclValue, String> {
+    let arguments = get_known_arguments(&arguments)?;
+
+    Ok(HclValue::String(arguments[1..].iter().fold(
+        arguments[0].clone(),
+        |output, value| match output.find(['%']).filter(|index| output.len() > index + 1) {
+            Some(index) => format!("{}{}{}", &output[..index], value, &output[index + 2..]),
+            None => output,
+        },
+    )))
+}
+
+fn join(arguments: FuncArgs) -> Result<HclValue, String> {
+    match &arguments[..] {
+        [HclValue::String(separator), HclValue::Array(items)] => Ok(HclValue::String(
+            get_known_arguments(items)?.join(separator),
+        )),
+        _ => Err("expected a separator and a list".to_string()),
+    }
+}
+
+/***
+ * Context of the evaluations, with the known values (eg: var.tag) and the functions of the
+ * Terraform language whose result is known from their arguments
+ */
+fn build_context(constants: &Map<String, Value>) -> Context<'static> {
+    let mut context = Context::new();
+
+    for (name, value) in constants {
+        if let Ok(value) = serde_json::from_value::<HclValue>(value.clone()) {
+            context.declare_var(name.as_str(), value);
+        }
+    }
+
+    let unary = |function| FuncDef::builder().param(ParamType::Any).build(function);
+    context.declare_func("tostring", unary(to_string));
+    context.declare_func("tonumber", unary(to_number));
+    context.declare_func("lower", unary(lower));
+    context.declare_func("upper", unary(upper));
+    context.declare_func(
+        "format",
+        FuncDef::builder()
+            .param(ParamType::String)
+            .variadic_param(ParamType::Any)
+            .build(format),
+    );
+    context.declare_func(
+        "join",
+        FuncDef::builder()
+            .params([ParamType::String, ParamType::Any])
+            .build(join),
+    );
+
+    context
+}
+
+fn as_body(body: &hcl::Body, context: &Context) -> Body {
+    let mut output = Body::default();
+
+    for structure in body.iter() {
+        match structure {
+            Structure::Attribute(attribute) => {
+                output.attributes.insert(
+                    attribute.key.to_string(),
+                    evaluate(&attribute.expr, context),
+                );
+            }
+            Structure::Block(block) => output.blocks.push(Block {
+                kind: block.identifier.to_string(),
+                labels: block
+                    .labels
+                    .iter()
+                    .map(|label| label.as_str().to_string())
+                    .collect(),
+                body: as_body(&block.body, context),
+            }),
+        }
+    }
+
+    output
+}
+
+/***
+ * Default values of the variables and locals, by their object in the expressions
+ * (eg: tag in var for var.tag)
+ */
+fn get_constants(blocks: &[Block]) -> Map<String, Value> {
+    let mut constants = Map::new();
+    let mut insert = |object: &str, name: &str, value: &Value| {
+        if let Value::Object(values) = constants
+            .entry(object.to_string())
+            .or_insert_with(|| Value::Object(Map::new()))
+        {
+            values.insert(name.to_string(), value.clone());
+        }
+    };
+
+    for block in blocks {
+        match block.kind.as_str() {
+            "variable" => {
+                if let Some(value) = block
+                    .body
+                    .attributes
+                    .get("default")
+                    .filter(|x| !x.is_null())
+                {
+                    insert("var", block.label(), value);
+                }
+            }
+            "locals" => {
+                for (name, value) in block.body.attributes.iter().filter(|(_, x)| !x.is_null()) {
+                    insert("local", name, value);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    constants
+}
+
+/***
+ * Blocks of the file, syntax errors are reported with their line and column
+ */
+fn parse_blocks(input: &str) -> Result<Vec<Block>, DeserializeError> {
+    let body = hcl::parse(input).map_err(|e| DeserializeError::Parse(e.to_string()))?;
+    let mut constants = Map::new();
+
+    for _ in 0..MAX_PASSES {
+        let blocks = as_body(&body, &build_context(&constants)).blocks;
+        let next = get_constants(&blocks);
+
+        if next == constants {
+            return Ok(blocks);
+        }
+        constants = next;
+    }
+
+    Ok(as_body(&body, &build_context(&constants)).blocks)
+}
+
+fn is_repeated(block: &Block) -> bool {
+    ["count", "for_each"]
+        .iter()
+        .any(|key| block.body.attributes.contains_key(*key))
+}
+
+/***
+ * Nested blocks as values, the repeated ones are lists under their plural name
+ * (eg: container blocks as containers) and the dynamic ones are skipped
+ */
+fn as_value(body: &Body) -> Value {
+    let mut fields = body.attributes.clone();
+
+    for block in body.blocks.iter().filter(|block| block.kind != "dynamic") {
+        let value = as_value(&block.body);
+
+        match REPEATED_BLOCKS.iter().find(|(kind, _)| *kind == block.kind) {
+            Some((_, plural)) => {
+                if let Value::Array(items) = fields
+                    .entry(plural.to_string())
+                    .or_insert_with(|| Value::Array(vec![]))
+                {
+                    items.push(value);
+                }
+            }
+            None => {
+                fields.insert(block.kind.clone(), value);
+            }
+        }
+    }
+
+    Value::Object(fields)
+}
+
+fn collect_dynamic_blocks(body: &Body, path: &str, paths: &mut Vec<String>) {
+    for block in &body.blocks {
+        // Dynamic blocks are named by their label (eg: dynamic "env" {...})
+        let (is_dynamic, kind) = match block.kind.as_str() {
+            "dynamic" => (true, block.label()),
+            kind => (false, kind),
+        };
+        let path = match path.is_empty() {
+            true => kind.to_string(),
+            false => format!("{}.{}", path, kind),
+        };
+
+        match is_dynamic {
+            true => paths.push(path),
+            false => collect_dynamic_blocks(&block.body, &path, paths),
+        }
+    }
+}
+
+// Block of the app and its properties in the azure-native shape
+type AppProperties<'a> = (&'a Block, serde_yaml::Value);
+
+/***
+ * Properties of the container apps, the azurerm schema is the one of the classic Pulumi provider
+ * in snake case and with singular blocks (eg: template { container {...} })
+ */
+fn get_app_properties(blocks: &[Block]) -> Result<Vec<AppProperties<'_>>, DeserializeError> {
+    blocks
+        .iter()
+        .filter(|block| block.is("resource", CONTAINER_APP_TYPE) && !is_repeated(block))
+        .map(|block| {
+            let mut properties = as_value(&block.body);
+            if let Some(properties) = properties.as_object_mut() {
+                for key in META_ARGUMENTS {
+                    properties.remove(key);
+                }
+            }
+
+            let properties = serde_yaml::to_value(properties)
+                .map_err(|e| DeserializeError::Parse(e.to_string()))?;
+
+            Ok((block, pulumi::normalize_classic_app(&properties)))
+        })
+        .collect()
+}
+
+fn literal(body: &Body, key: &str) -> Option<String> {
+    body.attributes
+        .get(key)
+        .and_then(Value::as_str)
+        .filter(|value| !value.contains("${"))
+        .map(str::to_string)
+}
+
+fn get_apps(blocks: &[Block]) -> Result<Vec<ContainerAppBluePrint>, DeserializeError> {
+    // The location of an app is the one of its environment
+    let locations: Vec<(String, String)> = blocks
+        .iter()
+        .filter(|block| block.is("resource", ENVIRONMENT_TYPE))
+        .filter_map(|block| {
+            let location = literal(&block.body, "location")?;
+            Some((format!("${{{}.id}}", block.address()), location))
+        })
+        .collect();
+
+    get_app_properties(blocks)?
+        .into_iter()
+        .map(|(block, properties)| {
+            let mut app: ContainerAppBluePrint = serde_yaml::from_value(properties)
+                .map_err(|e| DeserializeError::Parse(e.to_string()))?;
+            app.name = literal(&block.body, "name").or_else(|| Some(block.label().to_string()));
+            app.resource_group_name = literal(&block.body, "resource_group_name");
+            app.location = block
+                .body
+                .attributes
+                .get("container_app_environment_id")
+                .and_then(Value::as_str)
+                .and_then(|id| locations.iter().find(|(environment, _)| environment == id))
+                .map(|(_, location)| location.clone());
+            pulumi::normalize_scale(&mut app);
+
+            Ok(app)
+        })
+        .collect()
+}
+
+/***
+ * Images built from a local context (eg: docker_image with a build block),
+ * referenced by their address (eg: ${docker_image.api.name})
+ */
+fn get_images(blocks: &[Block]) -> Vec<ContainerImageBluePrint> {
+    blocks
+        .iter()
+        .filter(|block| block.is("resource", IMAGE_TYPE))
+        .filter_map(|block| {
+            let build = block.body.blocks.iter().find(|x| x.kind == "build")?;
+            // The context was named path by the versions 2 of the docker provider
+            let context =
+                literal(&build.body, "context").or_else(|| literal(&build.body, "path"))?;
+
+            Some(ContainerImageBluePrint {
+                name: literal(&block.body, "name"),
+                build: crate::serializer::BuildContextBluePrint::new(context),
+                reference_name: Some(block.address()),
+            })
+        })
+        .collect()
+}
+
+/***
+ * Blocks which are not translated (eg: modules), the input is expected to be valid
+ */
+pub fn collect_warnings(input: &str) -> Vec<String> {
+    let blocks = parse_blocks(input).unwrap_or_default();
+    let mut warnings = vec![];
+
+    for block in &blocks {
+        if block.kind == "module" {
+            warnings.push(format!(
+                "ignored module {} ({}), modules are not supported",
+                block.label(),
+                literal(&block.body, "source").unwrap_or_default()
+            ));
+        }
+
+        if block.is("resource", CONTAINER_APP_TYPE) && is_repeated(block) {
+            warnings.push(format!(
+                "ignored {}, resources with count or for_each are not supported",
+                block.address()
+            ));
+        }
+    }
+
+    for (block, properties) in get_app_properties(&blocks).unwrap_or_default() {
+        let mut dynamic_blocks = vec![];
+        collect_dynamic_blocks(&block.body, "", &mut dynamic_blocks);
+
+        warnings.extend(
+            dynamic_blocks
+                .into_iter()
+                .map(|path| format!("ignored dynamic block {} in {}", path, block.address())),
+        );
+        warnings.extend(
+            pulumi::collect_ignored_fields(&properties)
+                .unwrap_or_default()
+                .into_iter()
+                .map(|path| format!("ignored {} in {}", path, block.address())),
+        );
+    }
+
+    warnings
+}
+
+pub fn deserialize(
+    input: &str,
+    options: &BuildOptions,
+) -> Result<Vec<ContainerAppConfiguration>, DeserializeError> {
+    deserialize_per_app(input, options).map(pulumi::flatten_apps)
+}
+
+/***
+ * Services of each app, see `pulumi::build_per_app`
+ */
+pub fn deserialize_per_app(
+    input: &str,
+    options: &BuildOptions,
+) -> Result<Vec<(String, Vec<ContainerAppConfiguration>)>, DeserializeError> {
+    let blocks = parse_blocks(input)?;
+
+    if options.strict {
+        for (_, properties) in get_app_properties(&blocks)? {
+            pulumi::validate_known_fields(&properties)?;
+        }
+    }
+
+    let apps = get_apps(&blocks)?;
+    let images = get_images(&blocks);
+
+    pulumi::build_per_app(apps, images, options.include.as_deref(), options)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIGURATION: &str = r#"
+variable "tag" {
+  type    = string
+  default = "v1"
+}
+
+variable "registry_password" {
+  type      = string
+  sensitive = true
+}
+
+locals {
+  prefix   = "shop"
+  api_port = 3000
+  api_name = "${local.prefix}-api"
+}
+
+resource "azurerm_resource_group" "rg" {
+  name     = "rg-shop"
+  location = "westeurope"
+}
+
+resource "azurerm_container_registry" "acr" {
+  name                = "shopregistry"
+  resource_group_name = azurerm_resource_group.rg.name
+  location            = azurerm_resource_group.rg.location
+  sku                 = "Basic"
+}
+
+resource "azurerm_container_app_environment" "env" {
+  name                = "shop-env"
+  location            = "westeurope"
+  resource_group_name = azurerm_resource_group.rg.name
+}
+
+resource "docker_image" "api" {
+  name = "shop-api:${var.tag}"
+  build {
+    context = "./api"
+  }
+}
+
+# Dapr app built from the local context
+resource "azurerm_container_app" "api" {
+  name                         = local.api_name
+  container_app_environment_id = azurerm_container_app_environment.env.id
+  resource_group_name          = "rg-shop"
+  revision_mode                = "Single"
+
+  dapr {
+    app_id   = "api"
+    app_port = local.api_port
+  }
+
+  template {
+    min_replicas = 1
+    max_replicas = 3
+
+    container {
+      name   = "api"
+      image  = docker_image.api.name
+      cpu    = 0.5
+      memory = "1Gi"
+    }
+  }
+}
+
+resource "azurerm_container_app" "frontend" {
+  name                         = "frontend"
+  container_app_environment_id = azurerm_container_app_environment.env.id
+  resource_group_name          = azurerm_resource_group.rg.name
+  revision_mode                = "Single"
+
+  ingress {
+    external_enabled = true
+    target_port      = 80
+    traffic_weight {
+      percentage      = 100
+      latest_revision = true
+    }
+  }
+
+  template {
+    container {
+      name   = "frontend"
+      image  = "${azurerm_container_registry.acr.login_server}/frontend:${var.tag}"
+      cpu    = 0.25
+      memory = "0.5Gi"
+    }
+  }
+}
+
+resource "azurerm_container_app" "workers" {
+  count                        = 2
+  name                         = "worker-${count.index}"
+  container_app_environment_id = azurerm_container_app_environment.env.id
+  resource_group_name          = azurerm_resource_group.rg.name
+  revision_mode                = "Single"
+
+  template {
+    container {
+      name   = "worker"
+      image  = "worker:latest"
+      cpu    = 0.25
+      memory = "0.5Gi"
+    }
+  }
+}
+
+module "monitoring" {
+  source = "./modules/monitoring"
+}
+"#;
+
+    #[test]
+    fn test_parse_blocks() {
+        let input = r#"
+// Comments in every style
+/* with blocks */
+locals {
+  settings = { port = 8080, "log-level": "debug" }
+  names    = ["a", "b"]
+  upper    = [for name in local.names : upper(name)]
+  script   = <<-EOT
+    echo "${local.settings.port}"
+    exit 0
+  EOT
+  image    = format("%s/%s:%s", "registry", "app", "v1")
+  escaped  = "$${literal}"
+  enabled  = local.settings.port == 8080 ? "yes" : "no"
+}
+"#;
+
+        let output = parse_blocks(input).unwrap();
+
+        let expected = vec![Block {
+            kind: "locals".to_string(),
+            labels: vec![],
+            body: Body {
+                attributes: serde_json::from_value(serde_json::json!({
+                    "settings": { "port": 8080, "log-level": "debug" },
+                    "names": ["a", "b"],
+                    "upper": ["A", "B"],
+                    "script": "echo \"8080\"\nexit 0\n",
+                    "image": "registry/app:v1",
+                    "escaped": "${literal}",
+                    "enabled": "yes"
+                }))
+                .unwrap(),
+                blocks: vec![],
+            },
+        }];
+
+        assert_eq!(expected, output);
+    }
+
+    #[test]
+    fn test_parser_errors() {
+        let input = "resource \"azurerm_container_app\" \"api\" {\n  name = \"api\n}";
+
+        let output = parse_blocks(input);
+
+        assert!(
+            matches!(&output, Err(DeserializeError::Parse(message)) if message.contains("line 2, column 3")),
+            "{:?}",
+            output
+        );
+    }
+
+    #[test]
+    fn test_get_apps() {
+        let blocks = parse_blocks(CONFIGURATION).unwrap();
+
+        let output = get_apps(&blocks).unwrap();
+
+        assert_eq!(2, output.len());
+
+        let api = &output[0];
+        let dapr = api.configuration.as_ref().unwrap().dapr.as_ref().unwrap();
+        let scale = api.configuration.as_ref().unwrap().scale.as_ref().unwrap();
+        let container = &api.template.as_ref().unwrap().containers.as_ref().unwrap()[0];
+        assert_eq!(Some("shop-api".to_string()), api.name);
+        assert_eq!(Some("westeurope".to_string()), api.location);
+        assert_eq!(Some("rg-shop".to_string()), api.resource_group_name);
+        assert_eq!(
+            (Some(true), Some(3000), Some("api".to_string())),
+            (dapr.enabled, dapr.app_port, dapr.app_id.clone())
+        );
+        assert_eq!((Some(1), Some(3)), (scale.min_replicas, scale.max_replicas));
+        assert_eq!("${docker_image.api.name}", container.image);
+
+        // The resource group is a reference
+        let frontend = &output[1];
+        let ingress = frontend
+            .configuration
+            .as_ref()
+            .unwrap()
+            .ingress
+            .as_ref()
+            .unwrap();
+        assert_eq!(Some("frontend".to_string()), frontend.name);
+        assert_eq!(None, frontend.resource_group_name);
+        assert_eq!(
+            (Some(true), Some(80)),
+            (ingress.external, ingress.target_port)
+        );
+    }
+
+    #[test]
+    fn test_get_images() {
+        let blocks = parse_blocks(CONFIGURATION).unwrap();
+
+        let output = get_images(&blocks);
+
+        let expected = vec![ContainerImageBluePrint {
+            name: Some("shop-api:v1".to_string()),
+            build: crate::serializer::BuildContextBluePrint::new("./api"),
+            reference_name: Some("docker_image.api".to_string()),
+        }];
+
+        assert_eq!(expected, output);
+    }
+
+    #[test]
+    fn test_collect_warnings() {
+        let output = collect_warnings(CONFIGURATION);
+
+        let expected = vec![
+            "ignored azurerm_container_app.workers, resources with count or for_each are not supported",
+            "ignored module monitoring (./modules/monitoring), modules are not supported",
+            "ignored configuration.activeRevisionsMode in azurerm_container_app.api",
+            "ignored configuration.ingress.trafficWeights in azurerm_container_app.frontend",
+            "ignored configuration.activeRevisionsMode in azurerm_container_app.frontend",
+        ];
+
+        assert_eq!(expected, output);
+
+        let input = r#"
+resource "azurerm_container_app" "api" {
+  name = "api"
+  template {
+    container {
+      name  = "api"
+      image = "node:20"
+      dynamic "env" {
+        for_each = var.settings
+        content {
+          name  = env.key
+          value = env.value
+        }
+      }
+    }
+  }
+}
+"#;
+
+        assert_eq!(
+            vec!["ignored dynamic block template.container.env in azurerm_container_app.api"],
+            collect_warnings(input)
+        );
+    }
+
+    #[test]
+    fn test_deserialize() {
+        let options = BuildOptions {
+            tokens: [(
+                "azurerm_container_registry.acr.login_server".to_string(),
+                "shopregistry.azurecr.io".to_string(),
+            )]
+            .into(),
+            ..BuildOptions::default()
+        };
+
+        let output = deserialize(CONFIGURATION, &options).unwrap();
+
+        let services: Vec<(&str, Option<&str>, Option<&str>)> = output
+            .iter()
+            .map(|service| {
+                (
+                    service.name.as_str(),
+                    service.image.as_deref(),
+                    service.build.as_ref().map(|build| build.context.as_str()),
+                )
+            })
+            .collect();
+
+        assert_eq!(
+            vec![
+                ("api", None, Some("./api")),
+                ("api_dapr", Some("daprio/daprd:edge"), None),
+                (
+                    "frontend",
+                    Some("shopregistry.azurecr.io/frontend:v1"),
+                    None
+                ),
+            ],
+            services
+        );
+        assert_eq!(Some(vec!["80:80".to_string()]), output[2].ports);
+
+        // Unresolved registries are kept, like the Pulumi ones
+        assert_eq!(
+            Some("${azurerm_container_registry.acr.login_server}/frontend:v1".to_string()),
+            deserialize(CONFIGURATION, &BuildOptions::default()).unwrap()[2]
+                .image
+                .clone()
+        );
+    }
+
+    #[test]
+    fn test_deserialize_dapr_without_enabled() {
+        let input = r#"
+resource "azurerm_container_app" "api" {
+  name = "api"
+
+  dapr {
+    app_id   = "api"
+    app_port = 80
+  }
+
+  template {
+    container {
+      name  = "api"
+      image = "node:20"
+    }
+  }
+}
+"#;
+        let output = deserialize(input, &BuildOptions::default()).unwrap();
+
+        let names: Vec<&str> = output.iter().map(|service| service.name.as_str()).collect();
+        // The azurerm dapr block has no enabled argument, declaring it enables Dapr
+        assert_eq!(vec!["api", "api_dapr"], names);
+    }
+
+    #[test]
+    fn test_parse_blocks_unsupported_syntax() {
+        let parse = |expression: &str| {
+            parse_blocks(&format!(
+                "locals {{\n  key = {}\n  other = \"value\"\n}}\n",
+                expression
+            ))
+            .map(|blocks| blocks[0].body.attributes.get("key").cloned())
+        };
+
+        // Not evaluated
+        for expression in [
+            "[for name in var.names : upper(name)]",
+            "{ for key, value in var.settings : key => value }",
+            "var.port + 1",
+            r#"try(var.image, "node:20")"#,
+        ] {
+            assert_eq!(Ok(Some(Value::Null)), parse(expression), "{}", expression);
+        }
+        assert_eq!(
+            Ok(Some(Value::String(
+                "%{ if var.debug }debug%{ endif }".to_string()
+            ))),
+            parse(r#""%{ if var.debug }debug%{ endif }""#)
+        );
+
+        assert_eq!(Ok(Some(Value::Null)), parse("-var.offset"));
+        assert_eq!(Ok(Some(serde_json::json!(1500))), parse("1.5e3"));
+        assert_eq!(
+            Ok(Some(serde_json::json!({ "${var.key}": 1 }))),
+            parse("{ (var.key) = 1 }")
+        );
+    }
+}
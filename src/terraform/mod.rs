@@ -0,0 +1,68 @@
+#[cfg(feature = "terraform")]
+pub mod hcl;
+use crate::error::DeserializeError;
+use crate::serializer::{BuildOptions, ContainerAppConfiguration, Language, Serializer};
+use log::error;
+
+// Logs of the Terraform parsers
+pub(crate) const LOG_TARGET: &str = "capp_s::terraform";
+
+pub struct Terraform {
+    language: Language,
+    options: BuildOptions,
+    pub resources: Option<Vec<ContainerAppConfiguration>>,
+    apps: Vec<(String, Vec<ContainerAppConfiguration>)>,
+    warnings: Vec<String>,
+}
+
+impl Terraform {
+    pub fn new(language: Language) -> Option<Terraform> {
+        let is_supported = cfg!(feature = "terraform") && matches!(language, Language::Hcl);
+
+        is_supported.then(|| Terraform {
+            language,
+            options: BuildOptions::default(),
+            resources: None,
+            apps: vec![],
+            warnings: vec![],
+        })
+    }
+
+    pub fn with_options(mut self, options: BuildOptions) -> Terraform {
+        self.options = options;
+        self
+    }
+
+    /// Services of the last deserialized input by app, for one compose file per app
+    pub fn apps(&self) -> &[(String, Vec<ContainerAppConfiguration>)] {
+        &self.apps
+    }
+
+    /// Blocks of the last deserialized input which are not translated (eg: modules)
+    pub fn warnings(&self) -> &[String] {
+        &self.warnings
+    }
+}
+
+impl Serializer for Terraform {
+    type Output = Terraform;
+    #[cfg_attr(not(feature = "terraform"), allow(unused_variables))]
+    fn deserialize_value(&mut self, input: &str) -> Result<&Self, DeserializeError> {
+        match self.language {
+            #[cfg(feature = "terraform")]
+            Language::Hcl => match hcl::deserialize_per_app(input, &self.options) {
+                Ok(value) => {
+                    self.resources = Some(crate::pulumi::flatten_apps(value.clone()));
+                    self.apps = value;
+                    self.warnings = hcl::collect_warnings(input);
+                    Ok(self)
+                }
+                Err(err) => Err(err),
+            },
+            _ => {
+                error!(target: LOG_TARGET, "Language not supported");
+                Err(DeserializeError::UnsupportedLanguage)
+            }
+        }
+    }
+}
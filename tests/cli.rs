@@ -0,0 +1,292 @@
+// Fixtures are Pulumi YAML programs
+#![cfg(feature = "yaml")]
+
+use flate2::{write::GzEncoder, Compression};
+use std::{
+    fs,
+    io::{BufRead, BufReader, Write},
+    path::PathBuf,
+    process::{Command, Stdio},
+    sync::mpsc,
+    thread,
+    time::Duration,
+};
+
+const PROGRAM: &str = r#"
+resources:
+  myapp:
+    type: azure-native:app:ContainerApp
+    properties:
+      configuration:
+        ingress:
+          external: true
+          targetPort: 80
+      template:
+        containers:
+          - image: node-12
+            name: myapp
+"#;
+
+fn create_workspace(name: &str) -> PathBuf {
+    let workspace = std::env::temp_dir().join(format!("capp_s-{}-{}", name, std::process::id()));
+
+    fs::create_dir_all(&workspace).unwrap();
+    workspace
+}
+
+#[test]
+fn test_watch_reserializes_on_change() {
+    let workspace = create_workspace("watch");
+    let input = workspace.join("pulumi.yml");
+    fs::write(&input, PROGRAM).unwrap();
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_capp_s"))
+        .args(["pulumi", "--watch", "--poll-interval", "50", "-i"])
+        .arg(&input)
+        .arg("-o")
+        .arg(&workspace)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+
+    let (sender, receiver) = mpsc::channel();
+    let stdout = child.stdout.take().unwrap();
+    thread::spawn(move || {
+        for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+            sender.send(line).unwrap_or_default();
+        }
+    });
+
+    let wait_for_output = |expected: &str| loop {
+        match receiver.recv_timeout(Duration::from_secs(10)) {
+            Ok(line) if line.contains(expected) => return true,
+            Ok(_) => continue,
+            Err(_) => return false,
+        }
+    };
+
+    let first_output = wait_for_output("- 80:80");
+    fs::write(
+        &input,
+        PROGRAM.replace("targetPort: 80", "targetPort: 3000"),
+    )
+    .unwrap();
+    let second_output = wait_for_output("- 3000:3000");
+
+    child.kill().unwrap();
+    child.wait().unwrap();
+    fs::remove_dir_all(&workspace).unwrap();
+
+    assert!(first_output);
+    assert!(second_output);
+}
+
+fn convert(input: &PathBuf, output: &PathBuf) -> String {
+    fs::create_dir_all(output).unwrap();
+
+    let status = Command::new(env!("CARGO_BIN_EXE_capp_s"))
+        .args(["pulumi", "-i"])
+        .arg(input)
+        .arg("-o")
+        .arg(output)
+        .stderr(Stdio::null())
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    fs::read_to_string(output.join("docker-compose.yml")).unwrap()
+}
+
+#[test]
+fn test_gzipped_input() {
+    let workspace = create_workspace("gzip");
+    let plain_input = workspace.join("pulumi.yml");
+    let gzipped_input = workspace.join("pulumi.yml.gz");
+    fs::write(&plain_input, PROGRAM).unwrap();
+
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(PROGRAM.as_bytes()).unwrap();
+    fs::write(&gzipped_input, encoder.finish().unwrap()).unwrap();
+
+    let plain_output = convert(&plain_input, &workspace.join("plain"));
+    let gzipped_output = convert(&gzipped_input, &workspace.join("gzipped"));
+
+    fs::remove_dir_all(&workspace).unwrap();
+
+    assert!(plain_output.contains("- 80:80"));
+    assert_eq!(plain_output, gzipped_output);
+}
+
+fn convert_with_format(input: &PathBuf, output: &PathBuf, format: &str, filename: &str) -> String {
+    fs::create_dir_all(output).unwrap();
+
+    let status = Command::new(env!("CARGO_BIN_EXE_capp_s"))
+        .args(["pulumi", "--format", format, "-i"])
+        .arg(input)
+        .arg("-o")
+        .arg(output)
+        .stderr(Stdio::null())
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    fs::read_to_string(output.join(filename)).unwrap()
+}
+
+#[test]
+fn test_output_formats() {
+    let workspace = create_workspace("format");
+    let input = workspace.join("pulumi.yml");
+    fs::write(&input, PROGRAM).unwrap();
+
+    let compose = convert_with_format(&input, &workspace, "compose", "docker-compose.yml");
+    let json = convert_with_format(&input, &workspace, "json", "docker-compose.json");
+    let k8s = convert_with_format(&input, &workspace, "k8s", "k8s.yml");
+
+    let output = Command::new(env!("CARGO_BIN_EXE_capp_s"))
+        .args(["pulumi", "--format", "helm", "-i"])
+        .arg(&input)
+        .arg("-o")
+        .arg(&workspace)
+        .output()
+        .unwrap();
+
+    fs::remove_dir_all(&workspace).unwrap();
+
+    assert!(compose.contains("- 80:80"));
+    assert!(json.contains("\"80:80\""));
+    assert!(k8s.contains("kind: Deployment"));
+    assert!(k8s.contains("kind: Service"));
+    assert!(k8s.contains("containerPort: 80"));
+
+    assert!(!output.status.success());
+    assert!(
+        String::from_utf8_lossy(&output.stderr).contains("[possible values: compose, json, k8s]")
+    );
+}
+
+#[test]
+fn test_split_output() {
+    let workspace = create_workspace("split");
+    let input = workspace.join("pulumi.yml");
+    fs::write(
+        &input,
+        format!(
+            "{}{}",
+            PROGRAM,
+            PROGRAM
+                .replace("resources:\n", "")
+                .replace("myapp", "backend")
+                .replace("targetPort: 80", "targetPort: 3000")
+        ),
+    )
+    .unwrap();
+
+    let status = Command::new(env!("CARGO_BIN_EXE_capp_s"))
+        .args(["pulumi", "--split", "-i"])
+        .arg(&input)
+        .arg("-o")
+        .arg(&workspace)
+        .stderr(Stdio::null())
+        .status()
+        .unwrap();
+
+    let shared = fs::read_to_string(workspace.join("docker-compose.yml")).unwrap();
+    let myapp = fs::read_to_string(workspace.join("docker-compose.myapp.yml")).unwrap();
+    let backend = fs::read_to_string(workspace.join("docker-compose.backend.yml")).unwrap();
+
+    fs::remove_dir_all(&workspace).unwrap();
+
+    assert!(status.success());
+    assert!(shared.contains("placement:"));
+    assert!(myapp.contains("- 80:80"));
+    assert!(!myapp.contains("backend"));
+    assert!(!myapp.contains("placement:"));
+    assert!(backend.contains("- 3000:3000"));
+}
+
+#[test]
+fn test_project_folder() {
+    let workspace = create_workspace("project");
+    fs::write(workspace.join("Pulumi.yaml"), "name: shop\nruntime: yaml\n").unwrap();
+    fs::write(workspace.join("Main.yaml"), PROGRAM).unwrap();
+    let output = workspace.join("output");
+    fs::create_dir_all(&output).unwrap();
+
+    let status = Command::new(env!("CARGO_BIN_EXE_capp_s"))
+        .args(["pulumi", "--project"])
+        .arg(&workspace)
+        .arg("-o")
+        .arg(&output)
+        .stderr(Stdio::null())
+        .status()
+        .unwrap();
+    let compose = fs::read_to_string(output.join("docker-compose.yml")).unwrap();
+
+    fs::remove_dir_all(&workspace).unwrap();
+
+    assert!(status.success());
+    assert!(compose.contains("- 80:80"));
+}
+
+#[test]
+fn test_stdin_input() {
+    let workspace = create_workspace("stdin");
+    let input = workspace.join("pulumi.yml");
+    fs::write(&input, PROGRAM).unwrap();
+    let expected = convert(&input, &workspace.join("file"));
+    let output = workspace.join("stdin");
+    fs::create_dir_all(&output).unwrap();
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_capp_s"))
+        .args(["pulumi", "-l", "yaml", "-i", "-", "-o"])
+        .arg(&output)
+        .stdin(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(format!("\u{feff}{}", PROGRAM).as_bytes())
+        .unwrap();
+    let status = child.wait().unwrap();
+    let compose = fs::read_to_string(output.join("docker-compose.yml")).unwrap();
+
+    fs::remove_dir_all(&workspace).unwrap();
+
+    assert!(status.success());
+    assert_eq!(expected, compose);
+}
+
+#[test]
+fn test_stack_outputs_file() {
+    let workspace = create_workspace("stack-outputs");
+    let input = workspace.join("pulumi.yml");
+    let outputs = workspace.join("outputs.json");
+    fs::write(
+        &input,
+        PROGRAM.replace("node-12", "${infra.outputs[\"apiImage\"]}"),
+    )
+    .unwrap();
+    fs::write(&outputs, r#"{"apiImage": "myacr.azurecr.io/api:v1"}"#).unwrap();
+
+    let status = Command::new(env!("CARGO_BIN_EXE_capp_s"))
+        .args(["pulumi", "--stack-outputs"])
+        .arg(&outputs)
+        .arg("-i")
+        .arg(&input)
+        .arg("-o")
+        .arg(&workspace)
+        .stderr(Stdio::null())
+        .status()
+        .unwrap();
+    let compose = fs::read_to_string(workspace.join("docker-compose.yml")).unwrap();
+
+    fs::remove_dir_all(&workspace).unwrap();
+
+    assert!(status.success());
+    assert!(compose.contains("image: myacr.azurecr.io/api:v1"));
+}